@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate jvmti;
+
+use jvmti::bytecode::ClassReader;
+
+fuzz_target!(|data: &[u8]| {
+    // malformed input must be reported as an error, never as a panic
+    let _ = ClassReader::read_array(data);
+
+    if let Ok(lazy) = ClassReader::read_lazy(data) {
+        let _ = lazy.decode();
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate jvmti;
+
+use jvmti::bytecode::{ ClassReader, ClassWriter };
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(class) = ClassReader::read_array(data) {
+        let mut bytes: Vec<u8> = vec![];
+
+        if let Err(err) = ClassWriter::new(&mut bytes).write_class(&class) {
+            panic!("Could not write a class that was read successfully: {}", err);
+        }
+
+        match ClassReader::read_array(&bytes) {
+            Ok(reread) => assert_eq!(class, reread),
+            Err(err) => panic!("Could not read back a written class: {}", err)
+        }
+    }
+});
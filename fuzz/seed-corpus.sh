@@ -0,0 +1,15 @@
+#!/bin/bash
+# Compiles the classes under java/ and copies them into the corpus of every fuzz target
+
+FUZZDIR=$(cd "$(dirname "$0")" && pwd)
+CLASSDIR=$(mktemp -d)
+
+javac -g -d $CLASSDIR $FUZZDIR/../java/*.java
+javac -d $CLASSDIR/module $(find $FUZZDIR/../java/module -name "*.java")
+
+for TARGET in read roundtrip; do
+    mkdir -p $FUZZDIR/corpus/$TARGET
+    find $CLASSDIR -name "*.class" -exec cp {} $FUZZDIR/corpus/$TARGET/ \;
+done
+
+rm -rf $CLASSDIR
@@ -0,0 +1,15 @@
+import java.lang.invoke.MethodHandles;
+
+// javac can't load a dynamic constant from plain source, so condy/build.sh compiles this with a
+// plugin that turns every read of ANSWER into an ldc of a dynamic constant bootstrapped by answer
+public class Condy {
+    static int ANSWER;
+
+    static int answer(MethodHandles.Lookup lookup, String name, Class<?> type, int base) {
+        return base + 1;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(ANSWER);
+    }
+}
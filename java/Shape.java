@@ -0,0 +1,18 @@
+public sealed interface Shape permits Shape.Circle, Shape.Square {
+
+    double area();
+
+    record Circle(double radius) implements Shape {
+
+        public double area() {
+            return Math.PI * radius * radius;
+        }
+    }
+
+    record Square(double side, java.util.List<String> labels) implements Shape {
+
+        public double area() {
+            return side * side;
+        }
+    }
+}
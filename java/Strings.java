@@ -0,0 +1,9 @@
+public class Strings {
+    public static final String NUL = "a\u0000b";
+    public static final String EMOJI = "café 😀";
+    public static final String LONE = "\ud800";
+
+    public static void main(String[] args) {
+        System.out.println(NUL.length() + " " + EMOJI + " " + LONE.length());
+    }
+}
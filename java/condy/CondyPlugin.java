@@ -0,0 +1,35 @@
+import com.sun.source.util.*;
+import com.sun.tools.javac.api.BasicJavacTask;
+import com.sun.tools.javac.code.Symbol;
+import com.sun.tools.javac.code.Symbol.*;
+import com.sun.tools.javac.code.Symtab;
+import com.sun.tools.javac.jvm.PoolConstant.LoadableConstant;
+import com.sun.tools.javac.tree.JCTree;
+import com.sun.tools.javac.tree.JCTree.*;
+import com.sun.tools.javac.tree.TreeScanner;
+import com.sun.tools.javac.util.Names;
+
+/** Loads static fields named ANSWER through a dynamic constant bootstrapped by the class's answer method */
+public class CondyPlugin implements Plugin {
+    public String getName() { return "Condy"; }
+
+    public void init(JavacTask task, String... args) {
+        Symtab syms = Symtab.instance(((BasicJavacTask) task).getContext());
+        Names names = Names.instance(((BasicJavacTask) task).getContext());
+        task.addTaskListener(new TaskListener() {
+            public void finished(TaskEvent e) {
+                if (e.getKind() != TaskEvent.Kind.ANALYZE) return;
+                ((JCTree) e.getCompilationUnit()).accept(new TreeScanner() {
+                    public void visitIdent(JCIdent tree) {
+                        if (tree.sym instanceof VarSymbol && tree.name.toString().equals("ANSWER")) {
+                            ClassSymbol owner = (ClassSymbol) tree.sym.owner;
+                            MethodSymbol bsm = null;
+                            for (Symbol s : owner.members().getSymbolsByName(names.fromString("answer"))) bsm = (MethodSymbol) s;
+                            tree.sym = new DynamicVarSymbol(names.fromString("answer"), owner, new MethodHandleSymbol(bsm), syms.intType, new LoadableConstant[] { LoadableConstant.Int(41) });
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,22 @@
+#!/bin/bash
+# Compiles java/Condy.java into test-data/Condy.class with the compiler plugin next to this script.
+# Needs javac 17 or later
+
+set -e
+
+CONDY=$(cd "$(dirname "$0")" && pwd)
+ROOT="$CONDY/../.."
+WORK=$(mktemp -d)
+trap 'rm -rf "$WORK"' EXIT
+
+EXPORTS=""
+for PACKAGE in api code jvm tree util; do
+    EXPORTS="$EXPORTS --add-exports=jdk.compiler/com.sun.tools.javac.$PACKAGE=ALL-UNNAMED"
+done
+
+mkdir -p "$WORK/plugin" "$WORK/out"
+javac $EXPORTS -d "$WORK/plugin" "$CONDY/CondyPlugin.java"
+cp -r "$CONDY/META-INF" "$WORK/plugin/"
+
+javac $(echo $EXPORTS | sed 's/--add-exports/-J--add-exports/g') -processorpath "$WORK/plugin" -Xplugin:Condy -d "$WORK/out" "$ROOT/java/Condy.java"
+cp "$WORK/out/Condy.class" "$ROOT/test-data/Condy.class"
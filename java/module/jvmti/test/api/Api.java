@@ -0,0 +1,6 @@
+package jvmti.test.api;
+
+public interface Api {
+
+    String describe();
+}
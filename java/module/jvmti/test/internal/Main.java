@@ -0,0 +1,8 @@
+package jvmti.test.internal;
+
+public class Main {
+
+    public static void main(final String[] args) {
+        new Task().run();
+    }
+}
@@ -0,0 +1,8 @@
+package jvmti.test.internal;
+
+public class Task implements Runnable {
+
+    public void run() {
+        System.out.println("Running");
+    }
+}
@@ -0,0 +1,9 @@
+module jvmti.test {
+    requires java.logging;
+
+    exports jvmti.test.api;
+    opens jvmti.test.internal;
+
+    uses java.lang.Runnable;
+    provides java.lang.Runnable with jvmti.test.internal.Task;
+}
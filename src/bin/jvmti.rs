@@ -0,0 +1,383 @@
+extern crate jvmti;
+extern crate zip;
+
+use jvmti::bytecode::*;
+use jvmti::bytecode::printer::ClassfilePrinter;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+const USAGE: &'static str = "Usage: jvmti <command> <path> [<path>]
+
+Paths are class files, directories searched for class files, or JARs.
+
+Commands:
+    dump <path>             Print the classes
+    javap <path>            Print the classes the way `javap -v -p` does
+    disassemble <path>      Print the classes in assembly
+    assemble <source>       Assemble a source into a class file next to it, named after it
+    roundtrip <path>        Read and rewrite the classes, reporting those whose bytes change
+    verify <path>           Type-check the bytecode of the classes
+    stats <path>            Count the classes, members, constants, attributes and instructions
+    diff <path> <path>      Compare the printouts of classes of the same name
+    write <class>           Read and rewrite a class into <class>.out.class";
+
+/// A class file loaded from disk along with the name it's reported under
+struct Input {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match (args.get(0).map(|arg| arg.as_str()), args.len()) {
+        (Some("dump"), 2) => load(&args[1]).map(|inputs| dump(&inputs)),
+        (Some("javap"), 2) => load(&args[1]).map(|inputs| javap(&inputs)),
+        (Some("disassemble"), 2) => load(&args[1]).map(|inputs| disassemble(&inputs)),
+        (Some("assemble"), 2) => assemble(&args[1]),
+        (Some("roundtrip"), 2) => load(&args[1]).map(|inputs| roundtrip(&inputs)),
+        (Some("verify"), 2) => load(&args[1]).map(|inputs| check(&inputs)),
+        (Some("stats"), 2) => load(&args[1]).map(|inputs| stats(&inputs)),
+        (Some("diff"), 3) => load(&args[1]).and_then(|left| load(&args[2]).map(|right| diff(&left, &right))),
+        (Some("write"), 2) => write(&args[1]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    }
+}
+
+/// Load the classes a path refers to, which is either a class file, a directory or a JAR
+fn load(path: &str) -> io::Result<Vec<Input>> {
+    let path = Path::new(path);
+    let mut inputs = vec![];
+
+    if path.is_dir() {
+        load_dir(path, path, &mut inputs)?;
+    } else if path.extension().map(|ext| ext == "jar" || ext == "zip").unwrap_or(false) {
+        load_jar(path, &mut inputs)?;
+    } else {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or(path.display().to_string());
+        inputs.push(Input { name: name, bytes: read_file(path)? });
+    }
+
+    Ok(inputs)
+}
+
+fn load_dir(root: &Path, dir: &Path, inputs: &mut Vec<Input>) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+
+        if path.is_dir() {
+            load_dir(root, &path, inputs)?;
+        } else if path.extension().map(|ext| ext == "class").unwrap_or(false) {
+            let name = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into_owned();
+            inputs.push(Input { name: name, bytes: read_file(&path)? });
+        }
+    }
+
+    Ok(())
+}
+
+fn load_jar(path: &Path, inputs: &mut Vec<Input>) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+
+    for idx in 0..archive.len() {
+        let mut entry = archive.by_index(idx).map_err(zip_error)?;
+
+        if entry.name().ends_with(".class") {
+            let mut bytes = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut bytes)?;
+
+            inputs.push(Input { name: entry.name().to_string(), bytes: bytes });
+        }
+    }
+
+    Ok(())
+}
+
+fn zip_error(err: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_class(class: &Classfile) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    ClassWriter::new(&mut bytes).write_class(class)?;
+    Ok(bytes)
+}
+
+/// Parse every input, reporting the ones that can't be read. Returns whether all of them could.
+fn each_class<F>(inputs: &[Input], mut f: F) -> bool where F: FnMut(&Input, &Classfile) -> bool {
+    let mut ok = true;
+
+    for input in inputs {
+        ok = match ClassReader::read_array(&input.bytes) {
+            Ok(class) => f(input, &class),
+            Err(err) => {
+                println!("{}: {}", input.name, err);
+                false
+            }
+        } && ok;
+    }
+
+    ok
+}
+
+fn dump(inputs: &[Input]) -> bool {
+    each_class(inputs, |_, class| {
+        for line in ClassfilePrinter::render_lines(class) {
+            println!("{}", line);
+        }
+
+        println!();
+        true
+    })
+}
+
+fn javap(inputs: &[Input]) -> bool {
+    each_class(inputs, |_, class| {
+        for line in ClassfilePrinter::render_javap(class, true) {
+            println!("{}", line);
+        }
+
+        true
+    })
+}
+
+fn disassemble(inputs: &[Input]) -> bool {
+    each_class(inputs, |input, class| {
+        match asm::disassemble(class) {
+            Ok(text) => { println!("{}", text); true },
+            Err(err) => { println!("{}: {}", input.name, err); false }
+        }
+    })
+}
+
+fn assemble(path: &str) -> io::Result<bool> {
+    let source = fs::read_to_string(path)?;
+    let class = asm::assemble(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, err)))?;
+
+    fs::write(Path::new(path).with_extension("class"), write_class(&class)?)?;
+    Ok(true)
+}
+
+fn roundtrip(inputs: &[Input]) -> bool {
+    let mut identical = 0;
+
+    let ok = each_class(inputs, |input, class| {
+        match write_class(class) {
+            Ok(ref bytes) if *bytes == input.bytes => { identical += 1; true },
+            Ok(bytes) => {
+                let offset = bytes.iter().zip(input.bytes.iter()).position(|(a, b)| a != b).unwrap_or(bytes.len().min(input.bytes.len()));
+                println!("{}: differs from offset {} ({} bytes read, {} written)", input.name, offset, input.bytes.len(), bytes.len());
+                false
+            },
+            Err(err) => {
+                println!("{}: {}", input.name, err);
+                false
+            }
+        }
+    });
+
+    println!("{} of {} classes are identical after rewriting", identical, inputs.len());
+    ok
+}
+
+fn check(inputs: &[Input]) -> bool {
+    let mut verified = 0;
+
+    let ok = each_class(inputs, |input, class| {
+        match verify(class, &EmptyHierarchy) {
+            Ok(_) => { verified += 1; true },
+            Err(err) => {
+                println!("{}: {}", input.name, err);
+                false
+            }
+        }
+    });
+
+    println!("{} of {} classes verified", verified, inputs.len());
+    ok
+}
+
+fn stats(inputs: &[Input]) -> bool {
+    let (mut classes, mut fields, mut methods, mut instructions) = (0, 0, 0, 0);
+    let mut versions: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut constants: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut attributes: BTreeMap<String, usize> = BTreeMap::new();
+
+    let ok = each_class(inputs, |_, class| {
+        classes += 1;
+        fields += class.fields.len();
+        methods += class.methods.len();
+        *versions.entry(class.version.major_version).or_insert(0) += 1;
+
+        for constant in class.constant_pool.constants.iter() {
+            if let Some(kind) = constant_kind(constant) {
+                *constants.entry(kind).or_insert(0) += 1;
+            }
+        }
+
+        let members = class.fields.iter().map(|field| &field.attributes).chain(class.methods.iter().map(|method| &method.attributes));
+
+        for attribute in class.attributes.iter().chain(members.flat_map(|attributes| attributes.iter())) {
+            count_attribute(attribute, &class.constant_pool, &mut attributes, &mut instructions);
+        }
+
+        true
+    });
+
+    println!("classes: {}", classes);
+    println!("fields: {}", fields);
+    println!("methods: {}", methods);
+    println!("instructions: {}", instructions);
+    println!("major versions:");
+
+    for (version, count) in versions.iter() {
+        println!("    {:<28} {}", version, count);
+    }
+
+    println!("constants:");
+
+    for (kind, count) in constants.iter() {
+        println!("    {:<28} {}", kind, count);
+    }
+
+    println!("attributes:");
+
+    for (name, count) in attributes.iter() {
+        println!("    {:<28} {}", name, count);
+    }
+
+    ok
+}
+
+/// Count an attribute and the attributes nested in it by name, along with the instructions of code
+fn count_attribute(attribute: &Attribute, cp: &ConstantPool, attributes: &mut BTreeMap<String, usize>, instructions: &mut usize) {
+    let name = match attribute.name() {
+        Some(name) => name.to_string(),
+        None => match attribute {
+            &Attribute::RawAttribute { ref name_index, .. } => cp.get_utf8_string(name_index.idx as u16).unwrap_or(String::from("<unknown>")),
+            _ => String::from("<unknown>")
+        }
+    };
+
+    *attributes.entry(name).or_insert(0) += 1;
+
+    if let &Attribute::Code { ref code, attributes: ref nested, .. } = attribute {
+        *instructions += code.len();
+
+        for attribute in nested.iter() {
+            count_attribute(attribute, cp, attributes, instructions);
+        }
+    }
+}
+
+fn constant_kind(constant: &Constant) -> Option<&'static str> {
+    Some(match constant {
+        &Constant::Utf8(_) => "Utf8",
+        &Constant::Integer(_) => "Integer",
+        &Constant::Float(_) => "Float",
+        &Constant::Long(_) => "Long",
+        &Constant::Double(_) => "Double",
+        &Constant::Class(_) => "Class",
+        &Constant::FieldRef { .. } => "Fieldref",
+        &Constant::MethodRef { .. } => "Methodref",
+        &Constant::InterfaceMethodRef { .. } => "InterfaceMethodref",
+        &Constant::String(_) => "String",
+        &Constant::NameAndType { .. } => "NameAndType",
+        &Constant::MethodHandle { .. } => "MethodHandle",
+        &Constant::MethodType(_) => "MethodType",
+        &Constant::Dynamic { .. } => "Dynamic",
+        &Constant::InvokeDynamic { .. } => "InvokeDynamic",
+        &Constant::Module(_) => "Module",
+        &Constant::Package(_) => "Package",
+        &Constant::Unknown(_) => "Unknown",
+        &Constant::Placeholder => return None
+    })
+}
+
+/// Compare the classes of the same name on both sides, printing the lines their printouts differ in
+fn diff(left: &[Input], right: &[Input]) -> bool {
+    // Comparing two single classes matches them up regardless of their file names
+    let single = left.len() == 1 && right.len() == 1;
+    let key = |input: &Input| if single { String::new() } else { input.name.clone() };
+
+    let lefts: BTreeMap<String, &Input> = left.iter().map(|input| (key(input), input)).collect();
+    let rights: BTreeMap<String, &Input> = right.iter().map(|input| (key(input), input)).collect();
+    let mut same = true;
+
+    for (name, input) in lefts.iter() {
+        match rights.get(name) {
+            Some(other) => same = diff_class(input, other) && same,
+            None => { println!("only in left: {}", input.name); same = false }
+        }
+    }
+
+    for (name, input) in rights.iter() {
+        if !lefts.contains_key(name) {
+            println!("only in right: {}", input.name);
+            same = false;
+        }
+    }
+
+    same
+}
+
+fn diff_class(left: &Input, right: &Input) -> bool {
+    let render = |input: &Input| ClassReader::read_array(&input.bytes).map(|class| ClassfilePrinter::render_lines(&class));
+
+    let (left_lines, right_lines) = match (render(left), render(right)) {
+        (Ok(left_lines), Ok(right_lines)) => (left_lines, right_lines),
+        (Err(err), _) => { println!("{}: {}", left.name, err); return false },
+        (_, Err(err)) => { println!("{}: {}", right.name, err); return false }
+    };
+
+    if left_lines == right_lines {
+        return true;
+    }
+
+    println!("--- {}", left.name);
+    println!("+++ {}", right.name);
+
+    for line in diff::diff_lines(&left_lines, &right_lines) {
+        match line {
+            diff::DiffLine::Removed(line) => println!("-{}", line),
+            diff::DiffLine::Added(line) => println!("+{}", line),
+            diff::DiffLine::Same(_) => ()
+        }
+    }
+
+    false
+}
+
+/// Rewrite a class next to itself, which is what `regression-test.sh` compares against the original
+fn write(path: &str) -> io::Result<bool> {
+    let bytes = read_file(Path::new(path))?;
+    let class = ClassReader::read_array(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+    fs::write(format!("{}.out.class", path), write_class(&class)?)?;
+    Ok(true)
+}
@@ -0,0 +1,526 @@
+use std::collections::{ BTreeMap, VecDeque };
+use super::cfg::Offsets;
+use super::classfile::*;
+use super::descriptor::*;
+use super::frames::{ FrameError, FrameErrorKind };
+use super::hierarchy::*;
+
+///
+/// Computes the types of the local variables and of the operand stack before every instruction of a
+/// method, in the spirit of ASM's `Analyzer`. Values that reach an instruction along different paths
+/// are merged, asking the hierarchy for the common superclass wherever two class types meet, so it
+/// should know the class being analyzed as well, eg. by wrapping it in `WithClass`. Subroutines
+/// can't be analyzed.
+pub struct Analyzer<'a, H: 'a> {
+    cp: &'a ConstantPool<'a>,
+    class: &'a str,
+    hierarchy: &'a H,
+}
+
+impl<'a, H: ClassHierarchy> Analyzer<'a, H> {
+    /// An analyzer for the methods of `class`, given by its internal name and its constant pool
+    pub fn new(cp: &'a ConstantPool<'a>, class: &'a str, hierarchy: &'a H) -> Analyzer<'a, H> {
+        Analyzer { cp: cp, class: class, hierarchy: hierarchy }
+    }
+
+    /// Analyze a method of the class, returning `None` if it has no code
+    pub fn analyze_method(&self, method: &Method) -> Result<Option<BTreeMap<usize, Frame>>, FrameError> {
+        let name = self.cp.get_utf8_string(method.name_index.idx as u16).unwrap_or_default();
+        let descriptor = self.cp.get_utf8_string(method.descriptor_index.idx as u16).unwrap_or_default();
+        let is_static = method.access_flags.has_flag(MethodAccessFlags::Static as u16);
+
+        for attribute in &method.attributes {
+            if let &Attribute::Code { max_locals, ref code, ref exception_table, .. } = attribute {
+                return self.analyze(&name, &descriptor, is_static, max_locals, code, exception_table).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    ///
+    /// Analyze the code of a method of the class, returning the frame before every instruction by
+    /// offset. Instructions that can't be reached have no frame.
+    pub fn analyze(&self, name: &str, descriptor: &str, is_static: bool, max_locals: u16, code: &[Instruction], exception_table: &[ExceptionHandler]) -> Result<BTreeMap<usize, Frame>, FrameError> {
+        let method = Signature { class: self.class, name: name, descriptor: descriptor, is_static: is_static };
+
+        self.run(&method, max_locals, code, exception_table).map_err(|(offset, kind)| FrameError {
+            method: format!("{}{}", name, descriptor),
+            offset: offset,
+            kind: kind,
+        })
+    }
+
+    /// Run the instructions until the frame before each of them no longer changes
+    fn run(&self, method: &Signature, max_locals: u16, code: &[Instruction], exception_table: &[ExceptionHandler]) -> Result<BTreeMap<usize, Frame>, Failure> {
+        let cp = self.cp;
+        let initial = Frame::initial(method.class, method.name, method.descriptor, method.is_static, max_locals).map_err(|kind| (0, kind))?;
+
+        if code.is_empty() {
+            return Err((0, FrameErrorKind::FallsOffEnd));
+        }
+
+        let offsets = Offsets::new(code);
+        let handlers = exception_table.iter().map(|handler| {
+            let catch_type = match handler.catch_type.idx {
+                0 => String::from("java/lang/Throwable"),
+                idx => class_name(cp, idx as u16).map_err(|kind| (handler.handler_pc as usize, kind))?
+            };
+
+            let position = offsets.instruction_at(handler.handler_pc as i64).map_err(|target| (handler.handler_pc as usize, FrameErrorKind::InvalidTarget(target)))?;
+            Ok((handler.start_pc as usize, handler.end_pc as usize, position, Value::Reference(catch_type)))
+        }).collect::<Result<Vec<(usize, usize, usize, Value)>, Failure>>()?;
+
+        let mut frames: Vec<Option<Frame>> = vec![None; code.len()];
+        let mut queued = vec![false; code.len()];
+        let mut queue = VecDeque::new();
+
+        frames[0] = Some(initial.clone());
+        queued[0] = true;
+        queue.push_back(0);
+
+        while let Some(position) = queue.pop_front() {
+            queued[position] = false;
+
+            let offset = offsets.offset(position);
+            let before = frames[position].clone().unwrap_or(initial.clone());
+            let mut after = before.clone();
+            let mut successors = vec![];
+
+            for &(start, end, handler, ref catch_type) in &handlers {
+                if start <= offset && offset < end {
+                    successors.push((handler, Frame { locals: before.locals.clone(), stack: vec![ catch_type.clone() ] }));
+                }
+            }
+
+            execute(cp, method, code, &offsets, position, &mut after).map_err(|kind| (offset, kind))?;
+            let flow = offsets.flow(code, position).map_err(|target| (offset, FrameErrorKind::InvalidTarget(target)))?;
+
+            for target in flow.targets() {
+                successors.push((target, after.clone()));
+            }
+
+            if flow.continues() {
+                if position + 1 == code.len() {
+                    return Err((offset, FrameErrorKind::FallsOffEnd));
+                }
+
+                successors.push((position + 1, after));
+            }
+
+            for (target, frame) in successors {
+                let merged = match frames[target] {
+                    Some(ref existing) => existing.merge(&frame, self.hierarchy).map_err(|kind| (offsets.offset(target), kind))?,
+                    None => frame
+                };
+
+                if frames[target].as_ref() != Some(&merged) {
+                    frames[target] = Some(merged);
+
+                    if !queued[target] {
+                        queued[target] = true;
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        Ok(frames.into_iter().enumerate().filter_map(|(position, frame)| frame.map(|frame| (offsets.offset(position), frame))).collect())
+    }
+}
+
+/// The type of a local variable or an operand stack entry. Longs and doubles take a single operand
+/// stack entry, but two local variable slots, the second of which holds `Top`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A local variable that can't be used, or the second slot of a long or double
+    Top,
+    /// An `int`, or a `boolean`, `byte`, `char` or `short` widened to one
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// An object created by the `NEW` at this offset whose constructor hasn't been called yet
+    Uninitialized(usize),
+    /// A class by internal name, or an array by descriptor
+    Reference(String),
+}
+
+impl Value {
+    /// Whether the value takes two words, ie. is a long or a double
+    pub fn is_wide(&self) -> bool {
+        matches!(*self, Value::Long | Value::Double)
+    }
+
+    /// The type of a value of the given field type, arrays being referred to by their descriptor
+    pub fn from_field_type(field_type: &FieldType) -> Value {
+        match *field_type {
+            FieldType::Byte | FieldType::Char | FieldType::Int | FieldType::Short | FieldType::Boolean => Value::Integer,
+            FieldType::Float => Value::Float,
+            FieldType::Long => Value::Long,
+            FieldType::Double => Value::Double,
+            FieldType::Object(ref class) => Value::Reference(class.clone()),
+            FieldType::Array(_) => Value::Reference(field_type.descriptor())
+        }
+    }
+
+    /// The type of a value of the given field descriptor, eg. `I` or `[Ljava/lang/String;`
+    pub fn from_descriptor(descriptor: &str) -> Option<Value> {
+        FieldType::parse(descriptor).ok().map(|field_type| Value::from_field_type(&field_type))
+    }
+
+    /// Merge the types two paths bring to the same place, yielding `Top` if they have nothing in common
+    pub fn merge<H: ClassHierarchy>(&self, other: &Value, hierarchy: &H) -> Value {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (&Value::Null, &Value::Reference(_)) => other.clone(),
+            (&Value::Reference(_), &Value::Null) => self.clone(),
+            (&Value::Reference(ref a), &Value::Reference(ref b)) => Value::Reference(hierarchy.common_super_class(a, b)),
+            _ => Value::Top
+        }
+    }
+}
+
+/// Split a method descriptor into the types of its parameters and its return type, `None` for void
+fn parse_method_descriptor(descriptor: &str) -> Option<(Vec<Value>, Option<Value>)> {
+    MethodDescriptor::parse(descriptor).ok().map(|method| (method.params.iter().map(Value::from_field_type).collect(), method.ret.as_ref().map(Value::from_field_type)))
+}
+
+/// The method being analyzed
+struct Signature<'s> {
+    class: &'s str,
+    name: &'s str,
+    descriptor: &'s str,
+    is_static: bool,
+}
+
+type Failure = (usize, FrameErrorKind);
+
+/// The types of the local variables and of the operand stack before an instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// One entry per local variable slot, up to max_locals
+    pub locals: Vec<Value>,
+    /// The operand stack from the bottom up, one entry per value
+    pub stack: Vec<Value>,
+}
+
+impl Frame {
+    ///
+    /// The frame on entry to a method, as implied by its descriptor. Constructors of classes other
+    /// than `java/lang/Object` start with an uninitialized `this`.
+    pub fn initial(class: &str, name: &str, descriptor: &str, is_static: bool, max_locals: u16) -> Result<Frame, FrameErrorKind> {
+        let (params, _) = method_type(descriptor.to_string())?;
+        let mut locals = vec![];
+
+        if !is_static {
+            if name == "<init>" && class != "java/lang/Object" {
+                locals.push(Value::UninitializedThis);
+            } else {
+                locals.push(Value::Reference(class.to_string()));
+            }
+        }
+
+        for param in params {
+            let wide = param.is_wide();
+            locals.push(param);
+
+            if wide {
+                locals.push(Value::Top);
+            }
+        }
+
+        if locals.len() > max_locals as usize {
+            return Err(FrameErrorKind::InvalidLocal(locals.len() as u16 - 1));
+        }
+
+        locals.resize(max_locals as usize, Value::Top);
+        Ok(Frame { locals: locals, stack: vec![] })
+    }
+
+    fn pop(&mut self) -> Result<Value, FrameErrorKind> {
+        self.stack.pop().ok_or(FrameErrorKind::StackUnderflow)
+    }
+
+    fn pop_n(&mut self, count: usize) -> Result<(), FrameErrorKind> {
+        for _ in 0..count {
+            self.pop()?;
+        }
+
+        Ok(())
+    }
+
+    /// Pop values covering exactly `words` stack words, as the untyped stack instructions do,
+    /// returning them bottom first
+    fn take_words(&mut self, words: usize) -> Result<Vec<Value>, FrameErrorKind> {
+        let mut taken = vec![];
+        let mut count = 0;
+
+        while count < words {
+            let value = self.pop()?;
+            count += if value.is_wide() { 2 } else { 1 };
+            taken.insert(0, value);
+        }
+
+        if count == words { Ok(taken) } else { Err(FrameErrorKind::StackUnderflow) }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn push_all(&mut self, values: &[Value]) {
+        self.stack.extend(values.iter().cloned());
+    }
+
+    fn load(&mut self, idx: u16) -> Result<(), FrameErrorKind> {
+        match self.locals.get(idx as usize) {
+            Some(&Value::Top) | None => Err(FrameErrorKind::InvalidLocal(idx)),
+            Some(value) => { self.stack.push(value.clone()); Ok(()) }
+        }
+    }
+
+    fn store(&mut self, idx: u16) -> Result<(), FrameErrorKind> {
+        let value = self.pop()?;
+        let slot = idx as usize;
+
+        if slot + if value.is_wide() { 2 } else { 1 } > self.locals.len() {
+            return Err(FrameErrorKind::InvalidLocal(idx));
+        }
+
+        if slot > 0 && self.locals[slot - 1].is_wide() {
+            self.locals[slot - 1] = Value::Top;
+        }
+
+        if value.is_wide() {
+            self.locals[slot + 1] = Value::Top;
+        }
+
+        self.locals[slot] = value;
+        Ok(())
+    }
+
+    /// Replace every occurrence of an uninitialized object once its constructor has been called
+    fn initialize(&mut self, uninitialized: &Value, initialized: Value) {
+        for value in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if *value == *uninitialized {
+                *value = initialized.clone();
+            }
+        }
+    }
+
+    /// Merge the frames two paths bring to the same instruction. Operand stacks have to agree, while
+    /// locals that don't become unusable.
+    pub fn merge<H: ClassHierarchy>(&self, other: &Frame, hierarchy: &H) -> Result<Frame, FrameErrorKind> {
+        if self.stack.len() != other.stack.len() {
+            return Err(FrameErrorKind::InconsistentStack);
+        }
+
+        let stack = self.stack.iter().zip(other.stack.iter()).map(|(a, b)| match a.merge(b, hierarchy) {
+            Value::Top => Err(FrameErrorKind::InconsistentStack),
+            merged => Ok(merged)
+        }).collect::<Result<Vec<Value>, FrameErrorKind>>()?;
+
+        Ok(Frame {
+            locals: self.locals.iter().zip(other.locals.iter()).map(|(a, b)| a.merge(b, hierarchy)).collect(),
+            stack: stack,
+        })
+    }
+}
+
+fn class_name(cp: &ConstantPool, idx: u16) -> Result<String, FrameErrorKind> {
+    cp.get_class_name(idx).ok_or(FrameErrorKind::BadConstant(idx))
+}
+
+fn name_and_type(cp: &ConstantPool, idx: u16) -> Result<(String, String), FrameErrorKind> {
+    cp.get_name_and_type(idx).ok_or(FrameErrorKind::BadConstant(idx))
+}
+
+/// The class, name and descriptor of a field or method reference
+fn member(cp: &ConstantPool, idx: u16) -> Result<(String, String, String), FrameErrorKind> {
+    cp.get_member_ref(idx).ok_or(FrameErrorKind::BadConstant(idx))
+}
+
+fn field_type(descriptor: String) -> Result<Value, FrameErrorKind> {
+    Value::from_descriptor(&descriptor).ok_or(FrameErrorKind::BadDescriptor(descriptor))
+}
+
+fn method_type(descriptor: String) -> Result<(Vec<Value>, Option<Value>), FrameErrorKind> {
+    parse_method_descriptor(&descriptor).ok_or(FrameErrorKind::BadDescriptor(descriptor))
+}
+
+fn constant_type(cp: &ConstantPool, idx: u16) -> Result<Value, FrameErrorKind> {
+    match cp.resolve_index(&ConstantPoolIndex::new(idx as usize)) {
+        Some(&Constant::Integer(_)) => Ok(Value::Integer),
+        Some(&Constant::Float(_)) => Ok(Value::Float),
+        Some(&Constant::Long(_)) => Ok(Value::Long),
+        Some(&Constant::Double(_)) => Ok(Value::Double),
+        Some(&Constant::String(_)) => Ok(Value::Reference(String::from("java/lang/String"))),
+        Some(&Constant::Class(_)) => Ok(Value::Reference(String::from("java/lang/Class"))),
+        Some(&Constant::MethodType(_)) => Ok(Value::Reference(String::from("java/lang/invoke/MethodType"))),
+        Some(&Constant::MethodHandle { .. }) => Ok(Value::Reference(String::from("java/lang/invoke/MethodHandle"))),
+        Some(&Constant::Dynamic { ref name_and_type_index, .. }) => field_type(name_and_type(cp, name_and_type_index.idx as u16)?.1),
+        _ => Err(FrameErrorKind::BadConstant(idx))
+    }
+}
+
+/// The type of an array with elements of the given class or array type
+fn array_of(class: &str) -> Value {
+    if class.starts_with('[') {
+        Value::Reference(format!("[{}", class))
+    } else {
+        Value::Reference(format!("[L{};", class))
+    }
+}
+
+/// Apply the effect of a single instruction to the state before it
+fn execute(cp: &ConstantPool, method: &Signature, code: &[Instruction], offsets: &Offsets, position: usize, state: &mut Frame) -> Result<(), FrameErrorKind> {
+    let offset = offsets.offset(position);
+
+    let int = Value::Integer;
+    let long = Value::Long;
+    let float = Value::Float;
+    let double = Value::Double;
+
+    match code[position] {
+        Instruction::NOP => (),
+        Instruction::ACONST_NULL => state.push(Value::Null),
+        Instruction::ICONST_M1 | Instruction::ICONST_0 | Instruction::ICONST_1 | Instruction::ICONST_2 |
+        Instruction::ICONST_3 | Instruction::ICONST_4 | Instruction::ICONST_5 |
+        Instruction::BIPUSH(_) | Instruction::SIPUSH(_) => state.push(int),
+        Instruction::LCONST_0 | Instruction::LCONST_1 => state.push(long),
+        Instruction::FCONST_0 | Instruction::FCONST_1 | Instruction::FCONST_2 => state.push(float),
+        Instruction::DCONST_0 | Instruction::DCONST_1 => state.push(double),
+        Instruction::LDC(idx) => state.push(constant_type(cp, idx as u16)?),
+        Instruction::LDC_W(idx) | Instruction::LDC2_W(idx) => state.push(constant_type(cp, idx)?),
+
+        Instruction::ILOAD(idx) | Instruction::FLOAD(idx) | Instruction::LLOAD(idx) | Instruction::DLOAD(idx) | Instruction::ALOAD(idx) => state.load(idx as u16)?,
+        Instruction::ILOAD_W(idx) | Instruction::FLOAD_W(idx) | Instruction::LLOAD_W(idx) | Instruction::DLOAD_W(idx) | Instruction::ALOAD_W(idx) => state.load(idx)?,
+        Instruction::ILOAD_0 | Instruction::FLOAD_0 | Instruction::LLOAD_0 | Instruction::DLOAD_0 | Instruction::ALOAD_0 => state.load(0)?,
+        Instruction::ILOAD_1 | Instruction::FLOAD_1 | Instruction::LLOAD_1 | Instruction::DLOAD_1 | Instruction::ALOAD_1 => state.load(1)?,
+        Instruction::ILOAD_2 | Instruction::FLOAD_2 | Instruction::LLOAD_2 | Instruction::DLOAD_2 | Instruction::ALOAD_2 => state.load(2)?,
+        Instruction::ILOAD_3 | Instruction::FLOAD_3 | Instruction::LLOAD_3 | Instruction::DLOAD_3 | Instruction::ALOAD_3 => state.load(3)?,
+
+        Instruction::ISTORE(idx) | Instruction::FSTORE(idx) | Instruction::LSTORE(idx) | Instruction::DSTORE(idx) | Instruction::ASTORE(idx) => state.store(idx as u16)?,
+        Instruction::ISTORE_W(idx) | Instruction::FSTORE_W(idx) | Instruction::LSTORE_W(idx) | Instruction::DSTORE_W(idx) | Instruction::ASTORE_W(idx) => state.store(idx)?,
+        Instruction::ISTORE_0 | Instruction::FSTORE_0 | Instruction::LSTORE_0 | Instruction::DSTORE_0 | Instruction::ASTORE_0 => state.store(0)?,
+        Instruction::ISTORE_1 | Instruction::FSTORE_1 | Instruction::LSTORE_1 | Instruction::DSTORE_1 | Instruction::ASTORE_1 => state.store(1)?,
+        Instruction::ISTORE_2 | Instruction::FSTORE_2 | Instruction::LSTORE_2 | Instruction::DSTORE_2 | Instruction::ASTORE_2 => state.store(2)?,
+        Instruction::ISTORE_3 | Instruction::FSTORE_3 | Instruction::LSTORE_3 | Instruction::DSTORE_3 | Instruction::ASTORE_3 => state.store(3)?,
+
+        Instruction::IALOAD | Instruction::BALOAD | Instruction::CALOAD | Instruction::SALOAD => { state.pop_n(2)?; state.push(int) },
+        Instruction::LALOAD => { state.pop_n(2)?; state.push(long) },
+        Instruction::FALOAD => { state.pop_n(2)?; state.push(float) },
+        Instruction::DALOAD => { state.pop_n(2)?; state.push(double) },
+        Instruction::AALOAD => {
+            state.pop()?;
+
+            let element = match state.pop()? {
+                Value::Reference(ref array) if array.starts_with('[') => Value::from_descriptor(&array[1..]).unwrap_or(Value::Reference(String::from("java/lang/Object"))),
+                Value::Null => Value::Null,
+                _ => Value::Reference(String::from("java/lang/Object"))
+            };
+
+            state.push(element);
+        },
+        Instruction::IASTORE | Instruction::LASTORE | Instruction::FASTORE | Instruction::DASTORE |
+        Instruction::AASTORE | Instruction::BASTORE | Instruction::CASTORE | Instruction::SASTORE => state.pop_n(3)?,
+
+        Instruction::POP => { state.take_words(1)?; },
+        Instruction::POP2 => { state.take_words(2)?; },
+        Instruction::DUP => { let a = state.take_words(1)?; state.push_all(&a); state.push_all(&a) },
+        Instruction::DUP_X1 => { let a = state.take_words(1)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP_X2 => { let a = state.take_words(1)?; let b = state.take_words(2)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP2 => { let a = state.take_words(2)?; state.push_all(&a); state.push_all(&a) },
+        Instruction::DUP2_X1 => { let a = state.take_words(2)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP2_X2 => { let a = state.take_words(2)?; let b = state.take_words(2)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::SWAP => { let a = state.take_words(1)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b) },
+
+        Instruction::IADD | Instruction::ISUB | Instruction::IMUL | Instruction::IDIV | Instruction::IREM |
+        Instruction::IAND | Instruction::IOR | Instruction::IXOR | Instruction::ISHL | Instruction::ISHR | Instruction::IUSHR |
+        Instruction::LCMP | Instruction::FCMPL | Instruction::FCMPG | Instruction::DCMPL | Instruction::DCMPG => { state.pop_n(2)?; state.push(int) },
+        Instruction::LADD | Instruction::LSUB | Instruction::LMUL | Instruction::LDIV | Instruction::LREM |
+        Instruction::LAND | Instruction::LOR | Instruction::LXOR | Instruction::LSHL | Instruction::LSHR | Instruction::LUSHR => { state.pop_n(2)?; state.push(long) },
+        Instruction::FADD | Instruction::FSUB | Instruction::FMUL | Instruction::FDIV | Instruction::FREM => { state.pop_n(2)?; state.push(float) },
+        Instruction::DADD | Instruction::DSUB | Instruction::DMUL | Instruction::DDIV | Instruction::DREM => { state.pop_n(2)?; state.push(double) },
+
+        Instruction::INEG | Instruction::L2I | Instruction::F2I | Instruction::D2I | Instruction::I2B | Instruction::I2C | Instruction::I2S => { state.pop()?; state.push(int) },
+        Instruction::LNEG | Instruction::I2L | Instruction::F2L | Instruction::D2L => { state.pop()?; state.push(long) },
+        Instruction::FNEG | Instruction::I2F | Instruction::L2F | Instruction::D2F => { state.pop()?; state.push(float) },
+        Instruction::DNEG | Instruction::I2D | Instruction::L2D | Instruction::F2D => { state.pop()?; state.push(double) },
+
+        Instruction::IINC(idx, _) => { state.load(idx as u16)?; state.pop()?; },
+        Instruction::IINC_W(idx, _) => { state.load(idx)?; state.pop()?; },
+
+        Instruction::IFEQ(_) | Instruction::IFNE(_) | Instruction::IFLT(_) | Instruction::IFGE(_) |
+        Instruction::IFGT(_) | Instruction::IFLE(_) | Instruction::IFNULL(_) | Instruction::IFNONNULL(_) => { state.pop()?; },
+        Instruction::IF_ICMPEQ(_) | Instruction::IF_ICMPNE(_) | Instruction::IF_ICMPLT(_) | Instruction::IF_ICMPGE(_) |
+        Instruction::IF_ICMPGT(_) | Instruction::IF_ICMPLE(_) | Instruction::IF_ACMPEQ(_) | Instruction::IF_ACMPNE(_) => state.pop_n(2)?,
+        Instruction::GOTO(_) | Instruction::GOTO_W(_) | Instruction::RETURN => (),
+        Instruction::JSR(_) | Instruction::JSR_W(_) | Instruction::RET(_) | Instruction::RET_W(_) => return Err(FrameErrorKind::Subroutine),
+        Instruction::TABLESWITCH(..) | Instruction::LOOKUPSWITCH(..) |
+        Instruction::IRETURN | Instruction::LRETURN | Instruction::FRETURN | Instruction::DRETURN | Instruction::ARETURN | Instruction::ATHROW => { state.pop()?; },
+
+        Instruction::GETSTATIC(idx) => { let (_, _, descriptor) = member(cp, idx)?; state.push(field_type(descriptor)?) },
+        Instruction::PUTSTATIC(_) => { state.pop()?; },
+        Instruction::GETFIELD(idx) => { let (_, _, descriptor) = member(cp, idx)?; state.pop()?; state.push(field_type(descriptor)?) },
+        Instruction::PUTFIELD(_) => state.pop_n(2)?,
+        Instruction::INVOKEVIRTUAL(idx) | Instruction::INVOKESPECIAL(idx) | Instruction::INVOKESTATIC(idx) | Instruction::INVOKEINTERFACE(idx, _) => {
+            let (_, name, descriptor) = member(cp, idx)?;
+            let (params, ret) = method_type(descriptor)?;
+            state.pop_n(params.len())?;
+
+            match code[position] {
+                Instruction::INVOKESTATIC(_) => (),
+                Instruction::INVOKESPECIAL(_) if name == "<init>" => {
+                    let receiver = state.pop()?;
+                    let initialized = match receiver {
+                        Value::UninitializedThis => Value::Reference(method.class.to_string()),
+                        Value::Uninitialized(created) => match offsets.instruction_at(created as i64).ok().map(|idx| &code[idx]) {
+                            Some(&Instruction::NEW(class)) => Value::Reference(class_name(cp, class)?),
+                            _ => return Err(FrameErrorKind::InvalidTarget(created as i64))
+                        },
+                        _ => receiver.clone()
+                    };
+
+                    state.initialize(&receiver, initialized);
+                },
+                _ => { state.pop()?; }
+            }
+
+            if let Some(ret) = ret {
+                state.push(ret);
+            }
+        },
+        Instruction::INVOKEDYNAMIC(idx) => {
+            let (params, ret) = match cp.resolve_index(&ConstantPoolIndex::new(idx as usize)) {
+                Some(&Constant::InvokeDynamic { ref name_and_type_index, .. }) => method_type(name_and_type(cp, name_and_type_index.idx as u16)?.1)?,
+                _ => return Err(FrameErrorKind::BadConstant(idx))
+            };
+
+            state.pop_n(params.len())?;
+
+            if let Some(ret) = ret {
+                state.push(ret);
+            }
+        },
+        Instruction::NEW(_) => state.push(Value::Uninitialized(offset)),
+        Instruction::NEWARRAY(atype) => {
+            state.pop()?;
+            state.push(Value::Reference(match atype {
+                4 => "[Z", 5 => "[C", 6 => "[F", 7 => "[D", 8 => "[B", 9 => "[S", 10 => "[I", 11 => "[J",
+                _ => return Err(FrameErrorKind::UnknownInstruction)
+            }.to_string()));
+        },
+        Instruction::ANEWARRAY(idx) => { state.pop()?; state.push(array_of(&class_name(cp, idx)?)) },
+        Instruction::MULTIANEWARRAY(idx, dimensions) => { state.pop_n(dimensions as usize)?; state.push(Value::Reference(class_name(cp, idx)?)) },
+        Instruction::ARRAYLENGTH | Instruction::INSTANCEOF(_) => { state.pop()?; state.push(int) },
+        Instruction::CHECKCAST(idx) => { state.pop()?; state.push(Value::Reference(class_name(cp, idx)?)) },
+        Instruction::MONITORENTER | Instruction::MONITOREXIT => { state.pop()?; },
+        Instruction::PADDED_INSTRUCTION(_) | Instruction::WTF(_) => return Err(FrameErrorKind::UnknownInstruction),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,2202 @@
+//!
+//! A textual assembly language for classes in the spirit of Jasmin. `disassemble` renders a class
+//! in it and `assemble` parses it back into a class, building the constant pool along the way.
+//!
+//! The source is made of whitespace separated tokens, one directive, instruction or table entry
+//! per line. Directives start with a dot, and the ones that open a block, such as `.method` or
+//! `.code`, are closed by a matching `.end`. Every attribute is a directive named after the
+//! attribute in lowercase. A semicolon at the start of a token comments out the rest of the line,
+//! and tokens that would be ambiguous otherwise are written as quoted strings with Java escapes,
+//! along with `\xHH` for bytes that aren't valid modified UTF-8.
+//!
+//! Constants are written by value. Where the class file expects a constant of a given kind, eg. a
+//! class name after `new`, the value is enough, elsewhere it's preceded by the kind of constant,
+//! eg. `ldc string "Hello"` or `invokevirtual method java/io/PrintStream println (I)V`. A constant
+//! of an unexpected kind is written in parentheses, and `-` stands for index 0. Offsets in code are
+//! written as labels, `L` followed by the offset.
+//!
+//! ```text
+//! .version 52 0
+//! .class public super Hello
+//! .super java/lang/Object
+//!
+//! .method public static main ([Ljava/lang/String;)V
+//!     .code stack 2 locals 1
+//!         getstatic field java/lang/System out Ljava/io/PrintStream;
+//!         ldc string "Hello"
+//!         invokevirtual method java/io/PrintStream println (Ljava/lang/String;)V
+//!         return
+//!     .end code
+//! .end method
+//! ```
+//!
+//! Reassembling a disassembled class gives back the same class up to the layout of its constant
+//! pool, which holds the constants in the order they're first used.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use super::classfile::*;
+use super::code::Branch;
+use super::mutf8;
+
+#[derive(Debug, PartialEq)]
+pub enum AsmError {
+    /// The source doesn't parse, the error being on the given line
+    Syntax { line: usize, message: String },
+    /// The class holds something the assembly language can't express, such as a reference to an
+    /// index that holds no constant or a branch into the middle of an instruction
+    Unrepresentable(String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &AsmError::Syntax { line, ref message } => write!(f, "line {}: {}", line, message),
+            &AsmError::Unrepresentable(ref message) => write!(f, "can't disassemble {}", message),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// Render a class in assembly
+pub fn disassemble(classfile: &Classfile) -> Result<String, AsmError> {
+    let mut disassembler = Disassembler::new(&classfile.constant_pool, false);
+
+    disassembler.class(classfile)?;
+    Ok(disassembler.out)
+}
+
+///
+/// Render a single attribute of a class in assembly, one line per item. Labels are numbered in
+/// order instead of being named after their offset, so that code inserted into a method only renames
+/// the labels following it when it brings labels of its own, which keeps diffs of code small.
+pub fn disassemble_attribute(cp: &ConstantPool, attribute: &Attribute) -> Result<Vec<String>, AsmError> {
+    let mut disassembler = Disassembler::new(cp, true);
+
+    disassembler.attribute(attribute)?;
+    Ok(disassembler.out.lines().map(String::from).collect())
+}
+
+/// Render a constant in assembly, preceded by its kind, eg. `string "Hello"`
+pub fn disassemble_constant(cp: &ConstantPool, idx: &ConstantPoolIndex) -> Result<String, AsmError> {
+    Disassembler::new(cp, false).reference(Kind::Any, idx)
+}
+
+/// Render the access flags of a class, a field or a method as the keywords the assembler reads
+pub fn disassemble_flags(flags: &AccessFlags, owner: FlagsOwner) -> String {
+    let table = match owner {
+        FlagsOwner::Class => CLASS_FLAGS,
+        FlagsOwner::Field => FIELD_FLAGS,
+        FlagsOwner::Method => METHOD_FLAGS,
+    };
+
+    self::flags(flags, table).join(" ")
+}
+
+/// What access flags belong to, which decides their names
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlagsOwner {
+    Class,
+    Field,
+    Method,
+}
+
+/// Parse a class from assembly
+pub fn assemble(source: &str) -> Result<Classfile<'static>, AsmError> {
+    let mut assembler = Assembler { tokens: tokenize(source)?, pos: 0, cp: ConstantPool::default() };
+
+    assembler.reserve_ldc_constants()?;
+    assembler.class()
+}
+
+const CLASS_FLAGS: &[(&str, u16)] = &[
+    ("public", 0x0001), ("final", 0x0010), ("super", 0x0020), ("interface", 0x0200), ("abstract", 0x0400),
+    ("synthetic", 0x1000), ("annotation", 0x2000), ("enum", 0x4000), ("module", 0x8000),
+];
+
+const FIELD_FLAGS: &[(&str, u16)] = &[
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("volatile", 0x0040), ("transient", 0x0080), ("synthetic", 0x1000), ("enum", 0x4000),
+];
+
+const METHOD_FLAGS: &[(&str, u16)] = &[
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("synchronized", 0x0020), ("bridge", 0x0040), ("varargs", 0x0080), ("native", 0x0100), ("abstract", 0x0400),
+    ("strict", 0x0800), ("synthetic", 0x1000),
+];
+
+const INNER_CLASS_FLAGS: &[(&str, u16)] = &[
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("interface", 0x0200), ("abstract", 0x0400), ("synthetic", 0x1000), ("annotation", 0x2000), ("enum", 0x4000),
+];
+
+const PARAMETER_FLAGS: &[(&str, u16)] = &[ ("final", 0x0010), ("synthetic", 0x1000), ("mandated", 0x8000) ];
+
+const MODULE_FLAGS: &[(&str, u16)] = &[ ("open", 0x0020), ("synthetic", 0x1000), ("mandated", 0x8000) ];
+
+const REQUIRES_FLAGS: &[(&str, u16)] = &[ ("transitive", 0x0020), ("static_phase", 0x0040), ("synthetic", 0x1000), ("mandated", 0x8000) ];
+
+const EXPORTS_FLAGS: &[(&str, u16)] = &[ ("synthetic", 0x1000), ("mandated", 0x8000) ];
+
+const REFERENCE_KINDS: &[(&str, ReferenceKind)] = &[
+    ("getfield", ReferenceKind::GetField), ("getstatic", ReferenceKind::GetStatic), ("putfield", ReferenceKind::PutField),
+    ("putstatic", ReferenceKind::PutStatic), ("invokevirtual", ReferenceKind::InvokeVirtual), ("invokestatic", ReferenceKind::InvokeStatic),
+    ("invokespecial", ReferenceKind::InvokeSpecial), ("newinvokespecial", ReferenceKind::NewInvokeSpecial),
+    ("invokeinterface", ReferenceKind::InvokeInterface), ("unknown", ReferenceKind::Unknown),
+];
+
+const ARRAY_TYPES: &[(&str, u8)] = &[
+    ("boolean", 4), ("char", 5), ("float", 6), ("double", 7), ("byte", 8), ("short", 9), ("int", 10), ("long", 11),
+];
+
+const TYPE_PATH_KINDS: &[(&str, u8)] = &[ ("array", 0), ("nested", 1), ("wildcard", 2), ("type_argument", 3) ];
+
+const BRANCHES: &[Branch] = &[
+    Branch::IFEQ, Branch::IFNE, Branch::IFLT, Branch::IFGE, Branch::IFGT, Branch::IFLE, Branch::IF_ICMPEQ, Branch::IF_ICMPNE,
+    Branch::IF_ICMPLT, Branch::IF_ICMPGE, Branch::IF_ICMPGT, Branch::IF_ICMPLE, Branch::IF_ACMPEQ, Branch::IF_ACMPNE,
+    Branch::IFNULL, Branch::IFNONNULL, Branch::GOTO, Branch::GOTO_W, Branch::JSR, Branch::JSR_W,
+];
+
+/// Words that have a meaning of their own where a name may appear, so names spelled like them are quoted
+const RESERVED: &[&str] = &[ "-", "(", ")", "{", "}", "[", "]", "=", "any", "default", "to", "with" ];
+
+/// Constants refer to each other at most this deep, deeper references are cycles
+const MAX_DEPTH: usize = 8;
+
+/// The kinds of constants that can be written by value alone where they're expected
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Utf8,
+    Class,
+    NameAndType,
+    Module,
+    Package,
+    /// Any kind of constant, written with its kind
+    Any,
+}
+
+/// Write the bytes of a `Utf8` constant as a token, quoting it if it wouldn't read back as is
+fn quote(bytes: &[u8]) -> String {
+    match mutf8::decode(bytes) {
+        Ok(ref value) if mutf8::encode(value)[..] == bytes[..] => {
+            let bare = !value.is_empty() && !RESERVED.contains(&&value[..]) && !value.starts_with(&[ ';', '.', '"' ][..]) &&
+                !value.ends_with(':') && !value.contains(|c: char| c.is_whitespace() || c.is_control() || c == '"' || c == '\\');
+
+            if bare {
+                return value.to_string();
+            }
+
+            let mut quoted = String::from("\"");
+
+            for c in value.chars() {
+                match c {
+                    '"' => quoted.push_str("\\\""),
+                    '\\' => quoted.push_str("\\\\"),
+                    '\n' => quoted.push_str("\\n"),
+                    '\r' => quoted.push_str("\\r"),
+                    '\t' => quoted.push_str("\\t"),
+                    c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => quoted.push(c)
+                }
+            }
+
+            quoted.push('"');
+            quoted
+        },
+        _ => {
+            let mut quoted = String::from("\"");
+
+            for &b in bytes {
+                match b {
+                    b'"' => quoted.push_str("\\\""),
+                    b'\\' => quoted.push_str("\\\\"),
+                    0x20..=0x7E => quoted.push(b as char),
+                    _ => quoted.push_str(&format!("\\x{:02x}", b))
+                }
+            }
+
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+fn flags(flags: &AccessFlags, table: &[(&str, u16)]) -> Vec<String> {
+    let mut names: Vec<String> = table.iter().filter(|&&(_, bit)| flags.has_flag(bit)).map(|&(name, _)| name.to_string()).collect();
+    let rest = table.iter().fold(flags.flags, |rest, &(_, bit)| rest & !bit);
+
+    if rest != 0 {
+        names.push(format!("0x{:04x}", rest));
+    }
+
+    names
+}
+
+/// A list of tokens in brackets
+fn bracketed(items: Vec<String>) -> String {
+    if items.is_empty() { String::from("[ ]") } else { format!("[ {} ]", items.join(" ")) }
+}
+
+fn float(bits: u32) -> String {
+    let value = f32::from_bits(bits);
+
+    if value.is_nan() { format!("0x{:08x}", bits) } else { format!("{:?}", value) }
+}
+
+fn double(bits: u64) -> String {
+    let value = f64::from_bits(bits);
+
+    if value.is_nan() { format!("0x{:016x}", bits) } else { format!("{:?}", value) }
+}
+
+/// The offset delta of a frame, or `None` for the frames of reserved types, which have none
+fn frame_delta(frame: &StackMapFrame) -> Option<usize> {
+    match frame {
+        &StackMapFrame::SameFrame { tag } => Some(tag as usize),
+        &StackMapFrame::SameLocals1StackItemFrame { tag, .. } => Some(tag.saturating_sub(64) as usize),
+        &StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. } | &StackMapFrame::ChopFrame { offset_delta, .. } |
+        &StackMapFrame::SameFrameExtended { offset_delta } | &StackMapFrame::AppendFrame { offset_delta, .. } |
+        &StackMapFrame::FullFrame { offset_delta, .. } => Some(offset_delta as usize),
+        &StackMapFrame::FutureUse { .. } => None
+    }
+}
+
+/// The offsets of the frames of a stack map table, the first one at its delta and the others one
+/// past the previous offset plus their delta
+fn frame_offsets(frames: &[StackMapFrame]) -> Vec<Option<usize>> {
+    let mut previous: Option<usize> = None;
+
+    frames.iter().map(|frame| frame_delta(frame).map(|delta| {
+        let offset = previous.map(|previous| previous + 1 + delta).unwrap_or(delta);
+        previous = Some(offset);
+        offset
+    })).collect()
+}
+
+/// Every offset the code and the attributes nested in it refer to, which all get a label
+fn code_labels(code: &[Instruction], exception_table: &[ExceptionHandler], attributes: &[Attribute]) -> BTreeSet<i64> {
+    let mut labels = BTreeSet::new();
+    let mut offset = 0;
+
+    for instruction in code {
+        let at = offset as i64;
+
+        match instruction {
+            &Instruction::TABLESWITCH(default, _, _, ref targets) => {
+                labels.insert(at + default as i64);
+                labels.extend(targets.iter().map(|&target| at + target as i64));
+            },
+            &Instruction::LOOKUPSWITCH(default, ref pairs) => {
+                labels.insert(at + default as i64);
+                labels.extend(pairs.iter().map(|&(_, target)| at + target as i64));
+            },
+            _ => labels.extend(Branch::from_instruction(instruction).map(|(_, delta)| at + delta as i64))
+        }
+
+        offset += instruction.len_at(offset);
+    }
+
+    for handler in exception_table {
+        labels.extend(&[ handler.start_pc as i64, handler.end_pc as i64, handler.handler_pc as i64 ]);
+    }
+
+    for attribute in attributes {
+        match attribute {
+            &Attribute::LineNumberTable(ref table) => labels.extend(table.iter().map(|entry| entry.start_pc as i64)),
+            &Attribute::LocalVariableTable(ref table) => for entry in table {
+                labels.extend(&[ entry.start_pc as i64, entry.start_pc as i64 + entry.length as i64 ]);
+            },
+            &Attribute::LocalVariableTypeTable(ref table) => for entry in table {
+                labels.extend(&[ entry.start_pc as i64, entry.start_pc as i64 + entry.length as i64 ]);
+            },
+            &Attribute::StackMapTable(ref frames) => {
+                labels.extend(frame_offsets(frames).into_iter().filter_map(|offset| offset.map(|offset| offset as i64)));
+
+                for frame in frames {
+                    let types: Vec<&VerificationType> = match frame {
+                        &StackMapFrame::SameLocals1StackItemFrame { ref stack, .. } | &StackMapFrame::SameLocals1StackItemFrameExtended { ref stack, .. } => vec![ stack ],
+                        &StackMapFrame::AppendFrame { ref locals, .. } => locals.iter().collect(),
+                        &StackMapFrame::FullFrame { ref locals, ref stack, .. } => locals.iter().chain(stack.iter()).collect(),
+                        _ => vec![]
+                    };
+
+                    labels.extend(types.into_iter().filter_map(|verification_type| match verification_type {
+                        &VerificationType::Uninitialized { offset } => Some(offset as i64),
+                        _ => None
+                    }));
+                }
+            },
+            _ => ()
+        }
+    }
+
+    labels
+}
+
+struct Disassembler<'c> {
+    cp: &'c ConstantPool<'c>,
+    out: String,
+    indent: usize,
+    /// The names of the labels of the code being rendered, by offset. Offsets are written as
+    /// plain numbers outside code.
+    labels: Option<BTreeMap<i64, String>>,
+    /// Whether labels are numbered in order of offset rather than named after their offset
+    numbered_labels: bool,
+}
+
+impl<'c> Disassembler<'c> {
+    fn new(cp: &'c ConstantPool<'c>, numbered_labels: bool) -> Disassembler<'c> {
+        Disassembler { cp: cp, out: String::new(), indent: 0, labels: None, numbered_labels: numbered_labels }
+    }
+
+    fn line<S: AsRef<str>>(&mut self, text: S) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+
+        self.out.push_str(text.as_ref());
+        self.out.push('\n');
+    }
+
+    fn pc(&self, offset: i64) -> String {
+        match self.labels.as_ref().and_then(|labels| labels.get(&offset)) {
+            Some(label) => label.clone(),
+            None => offset.to_string()
+        }
+    }
+
+    /// Open a block, render its contents one level deeper and close it
+    fn block<F>(&mut self, header: String, end: &str, contents: F) -> Result<(), AsmError> where F: FnOnce(&mut Self) -> Result<(), AsmError> {
+        self.line(header);
+        self.indent += 1;
+        contents(self)?;
+        self.indent -= 1;
+        self.line(format!(".end {}", end));
+        Ok(())
+    }
+
+    fn reference(&self, kind: Kind, idx: &ConstantPoolIndex) -> Result<String, AsmError> {
+        self.render(kind, idx, 0)
+    }
+
+    fn render(&self, kind: Kind, idx: &ConstantPoolIndex, depth: usize) -> Result<String, AsmError> {
+        if idx.idx == 0 {
+            return Ok(String::from("-"));
+        } else if depth > MAX_DEPTH {
+            return Err(AsmError::Unrepresentable(format!("constant #{}, which refers to itself", idx.idx)));
+        }
+
+        let constant = self.cp.get(idx).map_err(|_| AsmError::Unrepresentable(format!("reference to invalid constant pool index #{}", idx.idx)))?;
+
+        match (kind, constant) {
+            (Kind::Utf8, &Constant::Utf8(ref bytes)) => Ok(quote(bytes)),
+            (Kind::Class, &Constant::Class(ref name)) | (Kind::Module, &Constant::Module(ref name)) | (Kind::Package, &Constant::Package(ref name)) => self.render(Kind::Utf8, name, depth + 1),
+            (Kind::NameAndType, &Constant::NameAndType { ref name_index, ref descriptor_index }) => Ok(format!("{} {}", self.render(Kind::Utf8, name_index, depth + 1)?, self.render(Kind::Utf8, descriptor_index, depth + 1)?)),
+            (Kind::Any, _) => self.constant(idx, constant, depth),
+            _ => Ok(format!("( {} )", self.constant(idx, constant, depth)?))
+        }
+    }
+
+    /// A constant preceded by its kind
+    fn constant(&self, idx: &ConstantPoolIndex, constant: &Constant, depth: usize) -> Result<String, AsmError> {
+        let depth = depth + 1;
+        let member = |keyword: &str, class_index: &ConstantPoolIndex, name_and_type_index: &ConstantPoolIndex| -> Result<String, AsmError> {
+            Ok(format!("{} {} {}", keyword, self.render(Kind::Class, class_index, depth)?, self.render(Kind::NameAndType, name_and_type_index, depth)?))
+        };
+
+        match constant {
+            &Constant::Utf8(ref bytes) => Ok(format!("utf8 {}", quote(bytes))),
+            &Constant::Integer(value) => Ok(format!("int {}", value as i32)),
+            &Constant::Float(bits) => Ok(format!("float {}", float(bits))),
+            &Constant::Long(value) => Ok(format!("long {}", value as i64)),
+            &Constant::Double(bits) => Ok(format!("double {}", double(bits))),
+            &Constant::Class(ref name) => Ok(format!("class {}", self.render(Kind::Utf8, name, depth)?)),
+            &Constant::String(ref value) => Ok(format!("string {}", self.render(Kind::Utf8, value, depth)?)),
+            &Constant::FieldRef { ref class_index, ref name_and_type_index } => member("field", class_index, name_and_type_index),
+            &Constant::MethodRef { ref class_index, ref name_and_type_index } => member("method", class_index, name_and_type_index),
+            &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } => member("interfacemethod", class_index, name_and_type_index),
+            &Constant::NameAndType { ref name_index, ref descriptor_index } => Ok(format!("nameandtype {} {}", self.render(Kind::Utf8, name_index, depth)?, self.render(Kind::Utf8, descriptor_index, depth)?)),
+            &Constant::MethodHandle { ref reference_kind, ref reference_index } => {
+                let kind = REFERENCE_KINDS.iter().find(|&&(_, ref kind)| kind == reference_kind).map(|&(name, _)| name).unwrap_or("unknown");
+                Ok(format!("methodhandle {} {}", kind, self.render(Kind::Any, reference_index, depth)?))
+            },
+            &Constant::MethodType(ref descriptor) => Ok(format!("methodtype {}", self.render(Kind::Utf8, descriptor, depth)?)),
+            &Constant::Dynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => Ok(format!("dynamic {} {}", bootstrap_method_attr_index.idx, self.render(Kind::NameAndType, name_and_type_index, depth)?)),
+            &Constant::InvokeDynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => Ok(format!("invokedynamic {} {}", bootstrap_method_attr_index.idx, self.render(Kind::NameAndType, name_and_type_index, depth)?)),
+            &Constant::Module(ref name) => Ok(format!("module {}", self.render(Kind::Utf8, name, depth)?)),
+            &Constant::Package(ref name) => Ok(format!("package {}", self.render(Kind::Utf8, name, depth)?)),
+            &Constant::Unknown(_) | &Constant::Placeholder => Err(AsmError::Unrepresentable(format!("reference to invalid constant pool index #{}", idx.idx)))
+        }
+    }
+
+    fn references(&self, kind: Kind, indices: &[ConstantPoolIndex]) -> Result<Vec<String>, AsmError> {
+        indices.iter().map(|idx| self.reference(kind, idx)).collect()
+    }
+
+    fn class(&mut self, classfile: &Classfile) -> Result<(), AsmError> {
+        let mut header = vec![ String::from(".class") ];
+        header.extend(flags(&classfile.access_flags, CLASS_FLAGS));
+        header.push(self.reference(Kind::Class, &classfile.this_class)?);
+
+        self.line(format!(".version {} {}", classfile.version.major_version, classfile.version.minor_version));
+        self.line(header.join(" "));
+
+        if classfile.super_class.idx != 0 {
+            let super_class = self.reference(Kind::Class, &classfile.super_class)?;
+            self.line(format!(".super {}", super_class));
+        }
+
+        for interface in &classfile.interfaces {
+            let interface = self.reference(Kind::Class, interface)?;
+            self.line(format!(".implements {}", interface));
+        }
+
+        for field in &classfile.fields {
+            let mut header = vec![ String::from(".field") ];
+            header.extend(flags(&field.access_flags, FIELD_FLAGS));
+            header.push(self.reference(Kind::Utf8, &field.name_index)?);
+            header.push(self.reference(Kind::Utf8, &field.descriptor_index)?);
+
+            self.line("");
+            self.block(header.join(" "), "field", |this| this.attributes(&field.attributes))?;
+        }
+
+        for method in &classfile.methods {
+            let mut header = vec![ String::from(".method") ];
+            header.extend(flags(&method.access_flags, METHOD_FLAGS));
+            header.push(self.reference(Kind::Utf8, &method.name_index)?);
+            header.push(self.reference(Kind::Utf8, &method.descriptor_index)?);
+
+            self.line("");
+            self.block(header.join(" "), "method", |this| this.attributes(&method.attributes))?;
+        }
+
+        if !classfile.attributes.is_empty() {
+            self.line("");
+        }
+
+        self.attributes(&classfile.attributes)
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) -> Result<(), AsmError> {
+        attributes.iter().try_for_each(|attribute| self.attribute(attribute))
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) -> Result<(), AsmError> {
+        let name = attribute.name().unwrap_or("attribute").to_lowercase();
+        let directive = format!(".{}", name);
+
+        match attribute {
+            &Attribute::ConstantValue(ref idx) => {
+                let value = self.reference(Kind::Any, idx)?;
+                self.line(format!("{} {}", directive, value));
+            },
+            &Attribute::Code { max_stack, max_locals, ref code, ref exception_table, ref attributes } => self.code(max_stack, max_locals, code, exception_table, attributes)?,
+            &Attribute::StackMapTable(ref frames) => self.block(directive, &name, |this| this.frames(frames))?,
+            &Attribute::Exceptions(ref table) | &Attribute::NestMembers(ref table) | &Attribute::PermittedSubclasses(ref table) => {
+                let classes = self.references(Kind::Class, table)?;
+                self.line(format!("{} {}", directive, classes.join(" ")).trim_end());
+            },
+            &Attribute::ModulePackages(ref table) => {
+                let packages = self.references(Kind::Package, table)?;
+                self.line(format!("{} {}", directive, packages.join(" ")).trim_end());
+            },
+            &Attribute::InnerClasses(ref table) => self.block(directive, &name, |this| {
+                for entry in table {
+                    let mut line = vec![ this.reference(Kind::Class, &entry.inner_class_info_index)?, this.reference(Kind::Class, &entry.outer_class_info_index)?, this.reference(Kind::Utf8, &entry.inner_name_index)? ];
+                    line.extend(flags(&entry.access_flags, INNER_CLASS_FLAGS));
+                    this.line(line.join(" "));
+                }
+
+                Ok(())
+            })?,
+            &Attribute::EnclosingMethod { ref class_index, ref method_index } => {
+                let (class, method) = (self.reference(Kind::Class, class_index)?, self.reference(Kind::NameAndType, method_index)?);
+                self.line(format!("{} {} {}", directive, class, method));
+            },
+            &Attribute::Synthetic | &Attribute::Deprecated => self.line(directive),
+            &Attribute::Signature(ref idx) | &Attribute::SourceFile(ref idx) => {
+                let value = self.reference(Kind::Utf8, idx)?;
+                self.line(format!("{} {}", directive, value));
+            },
+            &Attribute::ModuleMainClass(ref idx) | &Attribute::NestHost(ref idx) => {
+                let class = self.reference(Kind::Class, idx)?;
+                self.line(format!("{} {}", directive, class));
+            },
+            &Attribute::SourceDebugExtension(ref bytes) => self.line(format!("{} {}", directive, quote(bytes))),
+            &Attribute::LineNumberTable(ref table) => self.block(directive, &name, |this| {
+                for entry in table {
+                    let line = format!("{} {}", this.pc(entry.start_pc as i64), entry.line_number);
+                    this.line(line);
+                }
+
+                Ok(())
+            })?,
+            &Attribute::LocalVariableTable(ref table) => self.block(directive, &name, |this| {
+                for entry in table {
+                    let line = this.local_variable(entry.start_pc, entry.length, entry.index, &entry.name_index, &entry.descriptor_index)?;
+                    this.line(line);
+                }
+
+                Ok(())
+            })?,
+            &Attribute::LocalVariableTypeTable(ref table) => self.block(directive, &name, |this| {
+                for entry in table {
+                    let line = this.local_variable(entry.start_pc, entry.length, entry.index, &entry.name_index, &entry.signature_index)?;
+                    this.line(line);
+                }
+
+                Ok(())
+            })?,
+            &Attribute::RuntimeVisibleAnnotations(ref annotations) | &Attribute::RuntimeInvisibleAnnotations(ref annotations) => self.block(directive, &name, |this| {
+                annotations.iter().try_for_each(|annotation| this.annotation(String::new(), &annotation.type_index, &annotation.element_value_pairs))
+            })?,
+            &Attribute::RuntimeVisibleParameterAnnotations(ref parameters) | &Attribute::RuntimeInvisibleParameterAnnotations(ref parameters) => self.block(directive, &name, |this| {
+                for annotations in parameters {
+                    if annotations.is_empty() {
+                        this.line("[ ]");
+                    } else {
+                        this.line("[");
+                        this.indent += 1;
+
+                        for annotation in annotations {
+                            this.annotation(String::new(), &annotation.type_index, &annotation.element_value_pairs)?;
+                        }
+
+                        this.indent -= 1;
+                        this.line("]");
+                    }
+                }
+
+                Ok(())
+            })?,
+            &Attribute::RuntimeVisibleTypeAnnotations(ref annotations) | &Attribute::RuntimeInvisibleTypeAnnotations(ref annotations) => self.block(directive, &name, |this| {
+                annotations.iter().try_for_each(|annotation| {
+                    let path = annotation.target_path.path.iter().map(|&(ref kind, argument)| format!("{} {}", TYPE_PATH_KINDS[kind.value() as usize].0, argument)).collect();
+                    let prefix = format!("{} {} ", type_annotation_target(&annotation.target_info), bracketed(path));
+                    this.annotation(prefix, &annotation.type_index, &annotation.element_value_pairs)
+                })
+            })?,
+            &Attribute::AnnotationDefault(ref value) => {
+                let value = self.element_value(value)?;
+                self.line(format!("{} {}", directive, value));
+            },
+            &Attribute::BootstrapMethods(ref methods) => self.block(directive, &name, |this| {
+                for method in methods {
+                    let line = format!("{} {}", this.reference(Kind::Any, &method.bootstrap_method_ref)?, bracketed(this.references(Kind::Any, &method.bootstrap_arguments)?));
+                    this.line(line);
+                }
+
+                Ok(())
+            })?,
+            &Attribute::MethodParameters(ref parameters) => self.block(directive, &name, |this| {
+                for parameter in parameters {
+                    let mut line = vec![ this.reference(Kind::Utf8, &parameter.name_index)? ];
+                    line.extend(flags(&parameter.access_flags, PARAMETER_FLAGS));
+                    this.line(line.join(" "));
+                }
+
+                Ok(())
+            })?,
+            &Attribute::Module { ref name_index, ref flags, ref version_index, ref requires, ref exports, ref opens, ref uses, ref provides } => {
+                let mut header = vec![ directive.clone(), self.reference(Kind::Module, name_index)?, self.reference(Kind::Utf8, version_index)? ];
+                header.extend(self::flags(flags, MODULE_FLAGS));
+
+                self.block(header.join(" "), &name, |this| {
+                    for entry in requires {
+                        let mut line = vec![ String::from("requires"), this.reference(Kind::Module, &entry.requires_index)?, this.reference(Kind::Utf8, &entry.requires_version_index)? ];
+                        line.extend(self::flags(&entry.requires_flags, REQUIRES_FLAGS));
+                        this.line(line.join(" "));
+                    }
+
+                    let exports = exports.iter().map(|entry| ("exports", &entry.exports_index, &entry.exports_flags, &entry.exports_to));
+                    let opens = opens.iter().map(|entry| ("opens", &entry.opens_index, &entry.opens_flags, &entry.opens_to));
+
+                    for (keyword, package, flags, to) in exports.chain(opens) {
+                        let mut line = vec![ String::from(keyword), this.reference(Kind::Package, package)? ];
+                        line.extend(self::flags(flags, EXPORTS_FLAGS));
+
+                        if !to.is_empty() {
+                            line.push(String::from("to"));
+                            line.extend(this.references(Kind::Module, to)?);
+                        }
+
+                        this.line(line.join(" "));
+                    }
+
+                    for class in uses {
+                        let line = format!("uses {}", this.reference(Kind::Class, class)?);
+                        this.line(line);
+                    }
+
+                    for entry in provides {
+                        let line = format!("provides {} with {}", this.reference(Kind::Class, &entry.provides_index)?, this.references(Kind::Class, &entry.provides_with)?.join(" "));
+                        this.line(line.trim_end());
+                    }
+
+                    Ok(())
+                })?;
+            },
+            &Attribute::Record(ref components) => self.block(directive, &name, |this| {
+                for component in components {
+                    let header = format!(".component {} {}", this.reference(Kind::Utf8, &component.name_index)?, this.reference(Kind::Utf8, &component.descriptor_index)?);
+                    this.block(header, "component", |this| this.attributes(&component.attributes))?;
+                }
+
+                Ok(())
+            })?,
+            &Attribute::RawAttribute { ref name_index, ref info } => {
+                let name = self.reference(Kind::Utf8, name_index)?;
+                self.line(format!(".attribute {} {}", name, quote(info)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn code(&mut self, max_stack: u16, max_locals: u16, code: &[Instruction], exception_table: &[ExceptionHandler], attributes: &[Attribute]) -> Result<(), AsmError> {
+        let mut starts = BTreeSet::new();
+        let mut length = 0;
+
+        for instruction in code {
+            starts.insert(length as i64);
+            length += instruction.len_at(length);
+        }
+
+        let labels = code_labels(code, exception_table, attributes);
+
+        if let Some(label) = labels.iter().find(|&&label| label != length as i64 && !starts.contains(&label)) {
+            return Err(AsmError::Unrepresentable(format!("reference to offset {}, which isn't the start of an instruction", label)));
+        }
+
+        let numbered = self.numbered_labels;
+        self.labels = Some(labels.iter().enumerate().map(|(idx, &offset)| (offset, format!("L{}", if numbered { idx as i64 } else { offset }))).collect());
+        self.line(format!(".code stack {} locals {}", max_stack, max_locals));
+
+        let mut offset = 0;
+
+        for instruction in code {
+            if labels.contains(&(offset as i64)) {
+                let label = self.pc(offset as i64);
+                self.line(format!("{}:", label));
+            }
+
+            self.indent += 1;
+            self.instruction(instruction, offset)?;
+            self.indent -= 1;
+
+            offset += instruction.len_at(offset);
+        }
+
+        if labels.contains(&(length as i64)) {
+            let label = self.pc(length as i64);
+            self.line(format!("{}:", label));
+        }
+
+        self.indent += 1;
+
+        for handler in exception_table {
+            let catch_type = if handler.catch_type.idx == 0 { String::from("any") } else { self.reference(Kind::Class, &handler.catch_type)? };
+            let (start, end, handler) = (self.pc(handler.start_pc as i64), self.pc(handler.end_pc as i64), self.pc(handler.handler_pc as i64));
+            self.line(format!(".catch {} {} {} {}", catch_type, start, end, handler));
+        }
+
+        self.attributes(attributes)?;
+        self.indent -= 1;
+        self.labels = None;
+        self.line(".end code");
+        Ok(())
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, offset: usize) -> Result<(), AsmError> {
+        let name = instruction.mnemonic();
+        let at = offset as i64;
+
+        if let Some((_, delta)) = Branch::from_instruction(instruction) {
+            let target = self.pc(at + delta as i64);
+            self.line(format!("{} {}", name, target));
+            return Ok(());
+        }
+
+        let line = match instruction {
+            &Instruction::ALOAD(idx) | &Instruction::ASTORE(idx) | &Instruction::DLOAD(idx) | &Instruction::DSTORE(idx) |
+            &Instruction::FLOAD(idx) | &Instruction::FSTORE(idx) | &Instruction::ILOAD(idx) | &Instruction::ISTORE(idx) |
+            &Instruction::LLOAD(idx) | &Instruction::LSTORE(idx) | &Instruction::RET(idx) => format!("{} {}", name, idx),
+            &Instruction::ALOAD_W(idx) | &Instruction::ASTORE_W(idx) | &Instruction::DLOAD_W(idx) | &Instruction::DSTORE_W(idx) |
+            &Instruction::FLOAD_W(idx) | &Instruction::FSTORE_W(idx) | &Instruction::ILOAD_W(idx) | &Instruction::ISTORE_W(idx) |
+            &Instruction::LLOAD_W(idx) | &Instruction::LSTORE_W(idx) | &Instruction::RET_W(idx) => format!("{} {}", name, idx),
+            &Instruction::BIPUSH(value) => format!("{} {}", name, value as i8),
+            &Instruction::SIPUSH(value) => format!("{} {}", name, value as i16),
+            &Instruction::IINC(idx, delta) => format!("{} {} {}", name, idx, delta),
+            &Instruction::IINC_W(idx, delta) => format!("{} {} {}", name, idx, delta),
+            &Instruction::NEWARRAY(atype) => match ARRAY_TYPES.iter().find(|&&(_, value)| value == atype) {
+                Some(&(type_name, _)) => format!("{} {}", name, type_name),
+                None => format!("{} {}", name, atype)
+            },
+            &Instruction::ANEWARRAY(idx) | &Instruction::CHECKCAST(idx) | &Instruction::INSTANCEOF(idx) | &Instruction::NEW(idx) => {
+                format!("{} {}", name, self.reference(Kind::Class, &ConstantPoolIndex::new(idx as usize))?)
+            },
+            &Instruction::MULTIANEWARRAY(idx, dimensions) => format!("{} {} {}", name, self.reference(Kind::Class, &ConstantPoolIndex::new(idx as usize))?, dimensions),
+            &Instruction::LDC(idx) => format!("{} {}", name, self.reference(Kind::Any, &ConstantPoolIndex::new(idx as usize))?),
+            &Instruction::LDC_W(idx) | &Instruction::LDC2_W(idx) | &Instruction::GETFIELD(idx) | &Instruction::GETSTATIC(idx) |
+            &Instruction::PUTFIELD(idx) | &Instruction::PUTSTATIC(idx) | &Instruction::INVOKEVIRTUAL(idx) | &Instruction::INVOKESPECIAL(idx) |
+            &Instruction::INVOKESTATIC(idx) | &Instruction::INVOKEDYNAMIC(idx) => format!("{} {}", name, self.reference(Kind::Any, &ConstantPoolIndex::new(idx as usize))?),
+            &Instruction::INVOKEINTERFACE(idx, count) => format!("{} {} {}", name, self.reference(Kind::Any, &ConstantPoolIndex::new(idx as usize))?, count),
+            &Instruction::TABLESWITCH(default, low, _, ref targets) => {
+                self.line(format!("{} {}", name, low));
+                self.indent += 1;
+
+                for &target in targets {
+                    let target = self.pc(at + target as i64);
+                    self.line(target);
+                }
+
+                let default = self.pc(at + default as i64);
+                self.line(format!("default {}", default));
+                self.indent -= 1;
+                return Ok(());
+            },
+            &Instruction::LOOKUPSWITCH(default, ref pairs) => {
+                self.line(name);
+                self.indent += 1;
+
+                for &(key, target) in pairs {
+                    let target = self.pc(at + target as i64);
+                    self.line(format!("{} {}", key, target));
+                }
+
+                let default = self.pc(at + default as i64);
+                self.line(format!("default {}", default));
+                self.indent -= 1;
+                return Ok(());
+            },
+            &Instruction::PADDED_INSTRUCTION(_) | &Instruction::WTF(_) => return Err(AsmError::Unrepresentable(format!("the {} at offset {}", name, offset))),
+            _ => String::from(name)
+        };
+
+        self.line(line);
+        Ok(())
+    }
+
+    fn local_variable(&self, start_pc: u16, length: u16, index: u16, name_index: &ConstantPoolIndex, descriptor_index: &ConstantPoolIndex) -> Result<String, AsmError> {
+        Ok(format!("{} {} {} {} {}", index, self.reference(Kind::Utf8, name_index)?, self.reference(Kind::Utf8, descriptor_index)?, self.pc(start_pc as i64), self.pc(start_pc as i64 + length as i64)))
+    }
+
+    fn frames(&mut self, frames: &[StackMapFrame]) -> Result<(), AsmError> {
+        for (frame, offset) in frames.iter().zip(frame_offsets(frames)) {
+            let at = offset.map(|offset| self.pc(offset as i64)).unwrap_or_default();
+
+            let line = match frame {
+                &StackMapFrame::SameFrame { .. } => format!("same {}", at),
+                &StackMapFrame::SameLocals1StackItemFrame { ref stack, .. } => format!("same_locals_1_stack_item {} {}", at, self.verification_type(stack)?),
+                &StackMapFrame::SameLocals1StackItemFrameExtended { ref stack, .. } => format!("same_locals_1_stack_item_extended {} {}", at, self.verification_type(stack)?),
+                &StackMapFrame::ChopFrame { tag, .. } => format!("chop {} {}", at, 251 - tag as i32),
+                &StackMapFrame::SameFrameExtended { .. } => format!("same_extended {}", at),
+                &StackMapFrame::AppendFrame { ref locals, .. } => format!("append {} {}", at, self.verification_types(locals)?.join(" ")),
+                &StackMapFrame::FullFrame { ref locals, ref stack, .. } => {
+                    format!("full {} {} {}", at, bracketed(self.verification_types(locals)?), bracketed(self.verification_types(stack)?))
+                },
+                &StackMapFrame::FutureUse { tag } => format!("reserved {}", tag)
+            };
+
+            self.line(line);
+        }
+
+        Ok(())
+    }
+
+    fn verification_types(&self, types: &[VerificationType]) -> Result<Vec<String>, AsmError> {
+        types.iter().map(|verification_type| self.verification_type(verification_type)).collect()
+    }
+
+    fn verification_type(&self, verification_type: &VerificationType) -> Result<String, AsmError> {
+        Ok(match verification_type {
+            &VerificationType::Top => String::from("top"),
+            &VerificationType::Integer => String::from("int"),
+            &VerificationType::Float => String::from("float"),
+            &VerificationType::Long => String::from("long"),
+            &VerificationType::Double => String::from("double"),
+            &VerificationType::Null => String::from("null"),
+            &VerificationType::UninitializedThis => String::from("uninitialized_this"),
+            &VerificationType::Object { ref cpool_index } => format!("class {}", self.reference(Kind::Class, cpool_index)?),
+            &VerificationType::Uninitialized { offset } => format!("uninitialized {}", self.pc(offset as i64))
+        })
+    }
+
+    /// An annotation, on a line of its own if it has no elements and with a line per element otherwise
+    fn annotation(&mut self, prefix: String, type_index: &ConstantPoolIndex, pairs: &[ElementValuePair]) -> Result<(), AsmError> {
+        let header = format!("{}{} {{", prefix, self.reference(Kind::Utf8, type_index)?);
+
+        if pairs.is_empty() {
+            self.line(format!("{} }}", header));
+            return Ok(());
+        }
+
+        self.line(header);
+        self.indent += 1;
+
+        for pair in pairs {
+            let line = format!("{} = {}", self.reference(Kind::Utf8, &pair.element_name_index)?, self.element_value(&pair.value)?);
+            self.line(line);
+        }
+
+        self.indent -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    fn element_value(&self, value: &ElementValue) -> Result<String, AsmError> {
+        match value {
+            &ElementValue::ConstantValue(tag, ref idx) => Ok(format!("{} {}", tag as char, self.reference(Kind::Any, idx)?)),
+            &ElementValue::Enum { ref type_name_index, ref const_name_index } => Ok(format!("e {} {}", self.reference(Kind::Utf8, type_name_index)?, self.reference(Kind::Utf8, const_name_index)?)),
+            &ElementValue::ClassInfo(ref idx) => Ok(format!("c {}", self.reference(Kind::Utf8, idx)?)),
+            &ElementValue::Annotation(ref annotation) => {
+                let mut parts = vec![ String::from("@"), self.reference(Kind::Utf8, &annotation.type_index)?, String::from("{") ];
+
+                for pair in &annotation.element_value_pairs {
+                    parts.push(format!("{} = {}", self.reference(Kind::Utf8, &pair.element_name_index)?, self.element_value(&pair.value)?));
+                }
+
+                parts.push(String::from("}"));
+                Ok(parts.join(" "))
+            },
+            &ElementValue::Array(ref values) => Ok(bracketed(values.iter().map(|value| self.element_value(value)).collect::<Result<_, AsmError>>()?))
+        }
+    }
+}
+
+/// The target of a type annotation, named after the `target_info` item, with offsets in code left as numbers
+fn type_annotation_target(target: &TargetInfo) -> String {
+    match target {
+        &TargetInfo::TypeParameter { subtype, idx } => format!("type_parameter 0x{:02x} {}", subtype, idx),
+        &TargetInfo::SuperType { idx } => format!("supertype {}", idx),
+        &TargetInfo::TypeParameterBound { subtype, param_idx, bound_index } => format!("type_parameter_bound 0x{:02x} {} {}", subtype, param_idx, bound_index),
+        &TargetInfo::Empty { subtype } => format!("empty 0x{:02x}", subtype),
+        &TargetInfo::MethodFormalParameter { idx } => format!("formal_parameter {}", idx),
+        &TargetInfo::Throws { idx } => format!("throws {}", idx),
+        &TargetInfo::LocalVar { subtype, ref target } => {
+            let entries = target.iter().map(|&(start, length, index)| format!("{} {} {}", start, length, index)).collect();
+            format!("localvar 0x{:02x} {}", subtype, bracketed(entries))
+        },
+        &TargetInfo::Catch { idx } => format!("catch {}", idx),
+        &TargetInfo::Offset { subtype, idx } => format!("offset 0x{:02x} {}", subtype, idx),
+        &TargetInfo::TypeArgument { subtype, offset, type_arg_idx } => format!("type_argument 0x{:02x} {} {}", subtype, offset, type_arg_idx)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Bare,
+    Quoted,
+    Newline,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    /// The token as written for bare tokens, and the decoded string for quoted ones
+    text: String,
+    /// The modified UTF-8 bytes the token stands for
+    bytes: Vec<u8>,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, AsmError> {
+    let mut tokens = vec![];
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut chars = text.chars().peekable();
+
+        loop {
+            while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+                chars.next();
+            }
+
+            match chars.peek().cloned() {
+                None | Some(';') => break,
+                Some('"') => {
+                    chars.next();
+                    let bytes = quoted(&mut chars).map_err(|message| AsmError::Syntax { line: line, message: message })?;
+                    tokens.push(Token { kind: TokenKind::Quoted, text: mutf8::decode_lossy(&bytes).into_owned(), bytes: bytes, line: line });
+                },
+                Some(_) => {
+                    let mut word = String::new();
+
+                    while let Some(c) = chars.peek().cloned() {
+                        if c.is_whitespace() {
+                            break;
+                        }
+
+                        word.push(c);
+                        chars.next();
+                    }
+
+                    let bytes = mutf8::encode(&word).into_owned();
+                    tokens.push(Token { kind: TokenKind::Bare, text: word, bytes: bytes, line: line });
+                }
+            }
+        }
+
+        tokens.push(Token { kind: TokenKind::Newline, text: String::new(), bytes: vec![], line: line });
+    }
+
+    Ok(tokens)
+}
+
+/// The bytes of a quoted string, from after the opening quote up to and including the closing one
+fn quoted<I: Iterator<Item = char>>(chars: &mut I) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+
+    let hex = |chars: &mut I, digits: usize| -> Result<u32, String> {
+        let digits: String = chars.take(digits).collect();
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape `{}`", digits))
+    };
+
+    loop {
+        match chars.next() {
+            None => return Err(String::from("unterminated string")),
+            Some('"') => return Ok(bytes),
+            Some('\\') => match chars.next() {
+                Some('"') => bytes.push(b'"'),
+                Some('\\') => bytes.push(b'\\'),
+                Some('n') => bytes.push(b'\n'),
+                Some('r') => bytes.push(b'\r'),
+                Some('t') => bytes.push(b'\t'),
+                Some('x') => bytes.push(hex(chars, 2)? as u8),
+                Some('u') => {
+                    let unit = hex(chars, 4)?;
+
+                    match ::std::char::from_u32(unit) {
+                        Some(c) => bytes.extend_from_slice(&mutf8::encode(&c.to_string())),
+                        // Surrogates are kept as is, paired or not, which is what modified UTF-8 does anyway
+                        None => bytes.extend_from_slice(&[ 0xE0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8 ])
+                    }
+                },
+                Some(c) => return Err(format!("invalid escape `\\{}`", c)),
+                None => return Err(String::from("unterminated string"))
+            },
+            Some(c) => bytes.extend_from_slice(&mutf8::encode(&c.to_string()))
+        }
+    }
+}
+
+fn parse_integer(word: &str) -> Option<i64> {
+    match word.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => word.parse().ok()
+    }
+}
+
+/// A reference to an offset in code, by label or by number, resolved once the code is laid out
+struct Pc {
+    name: String,
+    line: usize,
+}
+
+impl Pc {
+    fn resolve(&self, labels: Option<&HashMap<String, usize>>) -> Result<usize, AsmError> {
+        labels.and_then(|labels| labels.get(&self.name).cloned()).or_else(|| self.name.parse().ok())
+            .ok_or_else(|| AsmError::Syntax { line: self.line, message: format!("undefined label `{}`", self.name) })
+    }
+
+    fn resolve_u16(&self, labels: Option<&HashMap<String, usize>>) -> Result<u16, AsmError> {
+        let offset = self.resolve(labels)?;
+        u16::try_from(offset).map_err(|_| AsmError::Syntax { line: self.line, message: format!("offset {} out of range", offset) })
+    }
+}
+
+enum PendingType {
+    Ready(VerificationType),
+    Uninitialized(Pc),
+}
+
+struct PendingFrame {
+    kind: String,
+    at: Option<Pc>,
+    /// The number of locals a `chop` frame removes, or the tag of a reserved frame
+    argument: u8,
+    locals: Vec<PendingType>,
+    stack: Vec<PendingType>,
+    line: usize,
+}
+
+/// An attribute as parsed, with the offsets it refers to still unresolved
+enum PendingAttribute {
+    Ready(Attribute<'static>),
+    LineNumberTable(Vec<(Pc, u16)>),
+    LocalVariableTable(Vec<(u16, ConstantPoolIndex, ConstantPoolIndex, Pc, Pc)>),
+    LocalVariableTypeTable(Vec<(u16, ConstantPoolIndex, ConstantPoolIndex, Pc, Pc)>),
+    StackMapTable(Vec<PendingFrame>),
+}
+
+struct Assembler {
+    tokens: Vec<Token>,
+    pos: usize,
+    cp: ConstantPool<'static>,
+}
+
+impl Assembler {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is(&self, text: &str) -> bool {
+        self.peek().map(|token| token.kind == TokenKind::Bare && token.text == text).unwrap_or(false)
+    }
+
+    fn at_end_of_line(&self) -> bool {
+        self.peek().map(|token| token.kind == TokenKind::Newline).unwrap_or(true)
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().map(|token| token.kind == TokenKind::Newline).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    /// The line of the token being parsed, which is the previous one unless a new line just started
+    fn line(&self) -> usize {
+        match (self.pos.checked_sub(1).and_then(|idx| self.tokens.get(idx)), self.peek()) {
+            (Some(previous), _) if previous.kind != TokenKind::Newline => previous.line,
+            (_, Some(current)) => current.line,
+            (Some(previous), None) => previous.line,
+            (None, None) => 1
+        }
+    }
+
+    fn error<T, S: Into<String>>(&self, message: S) -> Result<T, AsmError> {
+        Err(AsmError::Syntax { line: self.line(), message: message.into() })
+    }
+
+    /// Add a constant to the pool unless it's there already, failing on the current line when it's full
+    fn ensure(&mut self, constant: Constant<'static>) -> Result<ConstantPoolIndex, AsmError> {
+        match self.cp.ensure(constant) {
+            Ok(idx) => Ok(idx),
+            Err(err) => self.error(err.to_string())
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.peek().cloned() {
+            None => self.error("unexpected end of input"),
+            Some(ref token) if token.kind == TokenKind::Newline => self.error("unexpected end of line"),
+            Some(token) => {
+                self.pos += 1;
+                Ok(token)
+            }
+        }
+    }
+
+    fn word(&mut self) -> Result<String, AsmError> {
+        let token = self.next()?;
+
+        match token.kind {
+            TokenKind::Bare => Ok(token.text),
+            _ => self.error(format!("unexpected string \"{}\"", token.text))
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        if self.is(text) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) if token.kind != TokenKind::Newline => self.error(format!("expected `{}`, found `{}`", text, token.text)),
+                _ => self.error(format!("expected `{}`", text))
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), AsmError> {
+        match self.peek().cloned() {
+            None => Ok(()),
+            Some(ref token) if token.kind == TokenKind::Newline => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(token) => self.error(format!("unexpected `{}`", token.text))
+        }
+    }
+
+    /// Whether the block being parsed ends here, in which case its `.end` is consumed
+    fn block_end(&mut self, name: &str) -> Result<bool, AsmError> {
+        self.skip_newlines();
+
+        if self.peek().is_none() {
+            return self.error(format!("missing `.end {}`", name));
+        } else if !self.is(".end") {
+            return Ok(false);
+        }
+
+        self.pos += 1;
+        self.expect(name)?;
+        Ok(true)
+    }
+
+    fn integer<T: TryFrom<i64>>(&mut self) -> Result<T, AsmError> {
+        let word = self.word()?;
+
+        match parse_integer(&word).and_then(|value| T::try_from(value).ok()) {
+            Some(value) => Ok(value),
+            None => self.error(format!("invalid number `{}`", word))
+        }
+    }
+
+    fn keyword<T: Clone>(&mut self, table: &[(&str, T)], what: &str) -> Result<T, AsmError> {
+        let word = self.word()?;
+
+        match table.iter().find(|&&(name, _)| name == word) {
+            Some(&(_, ref value)) => Ok(value.clone()),
+            None => self.error(format!("unknown {} `{}`", what, word))
+        }
+    }
+
+    /// The flags in the next `count` tokens
+    fn flags(&mut self, table: &[(&str, u16)], count: usize) -> Result<AccessFlags, AsmError> {
+        let mut flags = AccessFlags::new();
+
+        for _ in 0..count {
+            let word = self.word()?;
+
+            match table.iter().find(|&&(name, _)| name == word).map(|&(_, bit)| bit).or_else(|| parse_integer(&word).and_then(|value| u16::try_from(value).ok())) {
+                Some(bit) => flags.set_flag(bit),
+                None => return self.error(format!("unknown flag `{}`", word))
+            }
+        }
+
+        Ok(flags)
+    }
+
+    /// The flags up to the end of the line or the given word
+    fn trailing_flags(&mut self, table: &[(&str, u16)], until: &str) -> Result<AccessFlags, AsmError> {
+        let count = self.tokens[self.pos..].iter().take_while(|token| token.kind != TokenKind::Newline && !(token.kind == TokenKind::Bare && token.text == until)).count();
+        self.flags(table, count)
+    }
+
+    /// The number of flags preceding the given number of references that end the line, each of
+    /// them a single token or a constant in parentheses
+    fn leading_flag_count(&self, references: usize) -> usize {
+        let bare = |idx: usize, text: &str| self.tokens[idx].kind == TokenKind::Bare && self.tokens[idx].text == text;
+        let end = self.tokens[self.pos..].iter().position(|token| token.kind == TokenKind::Newline).map(|len| self.pos + len).unwrap_or(self.tokens.len());
+        let mut idx = end;
+
+        for _ in 0..references {
+            if idx == self.pos {
+                break;
+            }
+
+            idx -= 1;
+
+            if bare(idx, ")") {
+                let mut depth = 1;
+
+                while idx > self.pos && depth > 0 {
+                    idx -= 1;
+                    depth += if bare(idx, ")") { 1 } else if bare(idx, "(") { -1 } else { 0 };
+                }
+            }
+        }
+
+        idx - self.pos
+    }
+
+    fn pc(&mut self) -> Result<Pc, AsmError> {
+        let word = self.word()?;
+        Ok(Pc { name: word, line: self.line() })
+    }
+
+    /// A reference to a constant of the given kind, adding the constant to the pool if needed
+    fn reference(&mut self, kind: Kind) -> Result<ConstantPoolIndex, AsmError> {
+        if self.is("-") {
+            self.pos += 1;
+            return Ok(ConstantPoolIndex::new(0));
+        } else if self.is("(") {
+            self.pos += 1;
+            let keyword = self.word()?;
+            let idx = self.constant(&keyword)?;
+            self.expect(")")?;
+            return Ok(idx);
+        }
+
+        let constant = match kind {
+            Kind::Utf8 => Constant::Utf8(Cow::Owned(self.next()?.bytes)),
+            Kind::Class => Constant::Class(self.reference(Kind::Utf8)?),
+            Kind::NameAndType => Constant::NameAndType { name_index: self.reference(Kind::Utf8)?, descriptor_index: self.reference(Kind::Utf8)? },
+            Kind::Module => Constant::Module(self.reference(Kind::Utf8)?),
+            Kind::Package => Constant::Package(self.reference(Kind::Utf8)?),
+            Kind::Any => {
+                let keyword = self.word()?;
+                return self.constant(&keyword);
+            }
+        };
+
+        self.ensure(constant)
+    }
+
+    /// A constant following the keyword of its kind
+    fn constant(&mut self, keyword: &str) -> Result<ConstantPoolIndex, AsmError> {
+        let constant = match keyword {
+            "utf8" => Constant::Utf8(Cow::Owned(self.next()?.bytes)),
+            "int" => Constant::Integer(self.integer::<i32>()? as u32),
+            "float" => {
+                let word = self.word()?;
+
+                match word.strip_prefix("0x").map(|hex| u32::from_str_radix(hex, 16).ok()).unwrap_or_else(|| word.parse::<f32>().ok().map(f32::to_bits)) {
+                    Some(bits) => Constant::Float(bits),
+                    None => return self.error(format!("invalid float `{}`", word))
+                }
+            },
+            "long" => Constant::Long(self.integer::<i64>()? as u64),
+            "double" => {
+                let word = self.word()?;
+
+                match word.strip_prefix("0x").map(|hex| u64::from_str_radix(hex, 16).ok()).unwrap_or_else(|| word.parse::<f64>().ok().map(f64::to_bits)) {
+                    Some(bits) => Constant::Double(bits),
+                    None => return self.error(format!("invalid double `{}`", word))
+                }
+            },
+            "class" => Constant::Class(self.reference(Kind::Utf8)?),
+            "string" => Constant::String(self.reference(Kind::Utf8)?),
+            "field" => Constant::FieldRef { class_index: self.reference(Kind::Class)?, name_and_type_index: self.reference(Kind::NameAndType)? },
+            "method" => Constant::MethodRef { class_index: self.reference(Kind::Class)?, name_and_type_index: self.reference(Kind::NameAndType)? },
+            "interfacemethod" => Constant::InterfaceMethodRef { class_index: self.reference(Kind::Class)?, name_and_type_index: self.reference(Kind::NameAndType)? },
+            "nameandtype" => Constant::NameAndType { name_index: self.reference(Kind::Utf8)?, descriptor_index: self.reference(Kind::Utf8)? },
+            "methodhandle" => Constant::MethodHandle { reference_kind: self.keyword(REFERENCE_KINDS, "reference kind")?, reference_index: self.reference(Kind::Any)? },
+            "methodtype" => Constant::MethodType(self.reference(Kind::Utf8)?),
+            "dynamic" => Constant::Dynamic { bootstrap_method_attr_index: ConstantPoolIndex::new(self.integer::<u16>()? as usize), name_and_type_index: self.reference(Kind::NameAndType)? },
+            "invokedynamic" => Constant::InvokeDynamic { bootstrap_method_attr_index: ConstantPoolIndex::new(self.integer::<u16>()? as usize), name_and_type_index: self.reference(Kind::NameAndType)? },
+            "module" => Constant::Module(self.reference(Kind::Utf8)?),
+            "package" => Constant::Package(self.reference(Kind::Utf8)?),
+            _ => return self.error(format!("unknown kind of constant `{}`", keyword))
+        };
+
+        self.ensure(constant)
+    }
+
+    fn references(&mut self, kind: Kind) -> Result<Vec<ConstantPoolIndex>, AsmError> {
+        let mut indices = vec![];
+
+        while !self.at_end_of_line() {
+            indices.push(self.reference(kind)?);
+        }
+
+        Ok(indices)
+    }
+
+    /// Add the constants `ldc` loads to the pool before any other, so that they get the low
+    /// indices the instruction can refer to
+    fn reserve_ldc_constants(&mut self) -> Result<(), AsmError> {
+        for idx in 0..self.tokens.len() {
+            let starts_line = idx == 0 || match self.tokens[idx - 1].kind {
+                TokenKind::Newline => true,
+                TokenKind::Bare => self.tokens[idx - 1].text.ends_with(':'),
+                TokenKind::Quoted => false
+            };
+
+            if starts_line && self.tokens[idx].kind == TokenKind::Bare && self.tokens[idx].text == "ldc" {
+                self.pos = idx + 1;
+                self.reference(Kind::Any)?;
+            }
+        }
+
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn class(&mut self) -> Result<Classfile<'static>, AsmError> {
+        let mut class = Classfile::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.peek().is_none() {
+                break;
+            }
+
+            let directive = self.word()?;
+
+            match &directive[..] {
+                ".version" => {
+                    let major_version = self.integer()?;
+                    class.version = ClassfileVersion::new(major_version, self.integer()?);
+                },
+                ".class" => {
+                    let count = self.leading_flag_count(1);
+                    class.access_flags = self.flags(CLASS_FLAGS, count)?;
+                    class.this_class = self.reference(Kind::Class)?;
+                },
+                ".super" => class.super_class = self.reference(Kind::Class)?,
+                ".implements" => {
+                    let interfaces = self.references(Kind::Class)?;
+                    class.interfaces.extend(interfaces);
+                },
+                ".field" => {
+                    let count = self.leading_flag_count(2);
+                    let access_flags = self.flags(FIELD_FLAGS, count)?;
+                    let (name_index, descriptor_index) = (self.reference(Kind::Utf8)?, self.reference(Kind::Utf8)?);
+                    self.end_of_line()?;
+
+                    let attributes = self.attributes("field")?;
+                    class.fields.push(Field { access_flags: access_flags, name_index: name_index, descriptor_index: descriptor_index, attributes: attributes });
+                },
+                ".method" => {
+                    let count = self.leading_flag_count(2);
+                    let access_flags = self.flags(METHOD_FLAGS, count)?;
+                    let (name_index, descriptor_index) = (self.reference(Kind::Utf8)?, self.reference(Kind::Utf8)?);
+                    self.end_of_line()?;
+
+                    let attributes = self.attributes("method")?;
+                    class.methods.push(Method { access_flags: access_flags, name_index: name_index, descriptor_index: descriptor_index, attributes: attributes });
+                },
+                _ => {
+                    let attribute = self.attribute(&directive)?;
+                    let attribute = self.resolve(attribute, None)?;
+                    class.attributes.push(attribute);
+                }
+            }
+
+            self.end_of_line()?;
+        }
+
+        class.constant_pool = mem::take(&mut self.cp);
+        Ok(class)
+    }
+
+    /// The attributes of the block being parsed, up to its `.end`
+    fn attributes(&mut self, end: &str) -> Result<Vec<Attribute<'static>>, AsmError> {
+        let mut attributes = vec![];
+
+        while !self.block_end(end)? {
+            let directive = self.word()?;
+            let attribute = self.attribute(&directive)?;
+
+            attributes.push(self.resolve(attribute, None)?);
+            self.end_of_line()?;
+        }
+
+        Ok(attributes)
+    }
+
+    /// The attribute a directive starts, up to the end of its last line
+    fn attribute(&mut self, directive: &str) -> Result<PendingAttribute, AsmError> {
+        let name = &directive[1.min(directive.len())..];
+
+        let attribute = match directive {
+            ".constantvalue" => Attribute::ConstantValue(self.reference(Kind::Any)?),
+            ".code" => self.code()?,
+            ".stackmaptable" => {
+                self.end_of_line()?;
+                let mut frames = vec![];
+
+                while !self.block_end(name)? {
+                    frames.push(self.frame()?);
+                    self.end_of_line()?;
+                }
+
+                return Ok(PendingAttribute::StackMapTable(frames));
+            },
+            ".exceptions" => Attribute::Exceptions(self.references(Kind::Class)?),
+            ".innerclasses" => {
+                self.end_of_line()?;
+                let mut table = vec![];
+
+                while !self.block_end(name)? {
+                    let (inner_class_info_index, outer_class_info_index, inner_name_index) = (self.reference(Kind::Class)?, self.reference(Kind::Class)?, self.reference(Kind::Utf8)?);
+                    let access_flags = self.trailing_flags(INNER_CLASS_FLAGS, "")?;
+
+                    table.push(InnerClass { inner_class_info_index: inner_class_info_index, outer_class_info_index: outer_class_info_index, inner_name_index: inner_name_index, access_flags: access_flags });
+                    self.end_of_line()?;
+                }
+
+                Attribute::InnerClasses(table)
+            },
+            ".enclosingmethod" => Attribute::EnclosingMethod { class_index: self.reference(Kind::Class)?, method_index: self.reference(Kind::NameAndType)? },
+            ".synthetic" => Attribute::Synthetic,
+            ".deprecated" => Attribute::Deprecated,
+            ".signature" => Attribute::Signature(self.reference(Kind::Utf8)?),
+            ".sourcefile" => Attribute::SourceFile(self.reference(Kind::Utf8)?),
+            ".sourcedebugextension" => Attribute::SourceDebugExtension(self.next()?.bytes),
+            ".linenumbertable" => {
+                self.end_of_line()?;
+                let mut table = vec![];
+
+                while !self.block_end(name)? {
+                    let start = self.pc()?;
+                    table.push((start, self.integer()?));
+                    self.end_of_line()?;
+                }
+
+                return Ok(PendingAttribute::LineNumberTable(table));
+            },
+            ".localvariabletable" | ".localvariabletypetable" => {
+                self.end_of_line()?;
+                let mut table = vec![];
+
+                while !self.block_end(name)? {
+                    let index = self.integer()?;
+                    let (name_index, descriptor_index) = (self.reference(Kind::Utf8)?, self.reference(Kind::Utf8)?);
+                    let (start, end) = (self.pc()?, self.pc()?);
+
+                    table.push((index, name_index, descriptor_index, start, end));
+                    self.end_of_line()?;
+                }
+
+                return Ok(if directive == ".localvariabletable" { PendingAttribute::LocalVariableTable(table) } else { PendingAttribute::LocalVariableTypeTable(table) });
+            },
+            ".runtimevisibleannotations" | ".runtimeinvisibleannotations" => {
+                self.end_of_line()?;
+                let mut annotations = vec![];
+
+                while !self.block_end(name)? {
+                    annotations.push(self.annotation()?);
+                }
+
+                if directive == ".runtimevisibleannotations" { Attribute::RuntimeVisibleAnnotations(annotations) } else { Attribute::RuntimeInvisibleAnnotations(annotations) }
+            },
+            ".runtimevisibleparameterannotations" | ".runtimeinvisibleparameterannotations" => {
+                self.end_of_line()?;
+                let mut parameters = vec![];
+
+                while !self.block_end(name)? {
+                    self.expect("[")?;
+                    let mut annotations = vec![];
+
+                    loop {
+                        self.skip_newlines();
+
+                        if self.is("]") {
+                            self.pos += 1;
+                            break;
+                        }
+
+                        annotations.push(self.annotation()?);
+                    }
+
+                    parameters.push(annotations);
+                }
+
+                if directive == ".runtimevisibleparameterannotations" { Attribute::RuntimeVisibleParameterAnnotations(parameters) } else { Attribute::RuntimeInvisibleParameterAnnotations(parameters) }
+            },
+            ".runtimevisibletypeannotations" | ".runtimeinvisibletypeannotations" => {
+                self.end_of_line()?;
+                let mut annotations = vec![];
+
+                while !self.block_end(name)? {
+                    annotations.push(self.type_annotation()?);
+                }
+
+                if directive == ".runtimevisibletypeannotations" { Attribute::RuntimeVisibleTypeAnnotations(annotations) } else { Attribute::RuntimeInvisibleTypeAnnotations(annotations) }
+            },
+            ".annotationdefault" => Attribute::AnnotationDefault(self.element_value()?),
+            ".bootstrapmethods" => {
+                self.end_of_line()?;
+                let mut methods = vec![];
+
+                while !self.block_end(name)? {
+                    let bootstrap_method_ref = self.reference(Kind::Any)?;
+                    let mut bootstrap_arguments = vec![];
+                    self.expect("[")?;
+
+                    while !self.is("]") {
+                        bootstrap_arguments.push(self.reference(Kind::Any)?);
+                    }
+
+                    self.pos += 1;
+                    methods.push(BootstrapMethod { bootstrap_method_ref: bootstrap_method_ref, bootstrap_arguments: bootstrap_arguments });
+                    self.end_of_line()?;
+                }
+
+                Attribute::BootstrapMethods(methods)
+            },
+            ".methodparameters" => {
+                self.end_of_line()?;
+                let mut parameters = vec![];
+
+                while !self.block_end(name)? {
+                    let name_index = self.reference(Kind::Utf8)?;
+                    let access_flags = self.trailing_flags(PARAMETER_FLAGS, "")?;
+
+                    parameters.push(MethodParameter { name_index: name_index, access_flags: access_flags });
+                    self.end_of_line()?;
+                }
+
+                Attribute::MethodParameters(parameters)
+            },
+            ".module" => self.module()?,
+            ".modulepackages" => Attribute::ModulePackages(self.references(Kind::Package)?),
+            ".modulemainclass" => Attribute::ModuleMainClass(self.reference(Kind::Class)?),
+            ".nesthost" => Attribute::NestHost(self.reference(Kind::Class)?),
+            ".nestmembers" => Attribute::NestMembers(self.references(Kind::Class)?),
+            ".permittedsubclasses" => Attribute::PermittedSubclasses(self.references(Kind::Class)?),
+            ".record" => {
+                self.end_of_line()?;
+                let mut components = vec![];
+
+                while !self.block_end(name)? {
+                    self.expect(".component")?;
+                    let (name_index, descriptor_index) = (self.reference(Kind::Utf8)?, self.reference(Kind::Utf8)?);
+                    self.end_of_line()?;
+
+                    let attributes = self.attributes("component")?;
+                    components.push(RecordComponent { name_index: name_index, descriptor_index: descriptor_index, attributes: attributes });
+                    self.end_of_line()?;
+                }
+
+                Attribute::Record(components)
+            },
+            ".attribute" => Attribute::RawAttribute { name_index: self.reference(Kind::Utf8)?, info: Cow::Owned(self.next()?.bytes) },
+            _ => return self.error(format!("unknown directive `{}`", directive))
+        };
+
+        Ok(PendingAttribute::Ready(attribute))
+    }
+
+    /// Resolve the offsets an attribute refers to and add its name to the constant pool
+    fn resolve(&mut self, attribute: PendingAttribute, labels: Option<&HashMap<String, usize>>) -> Result<Attribute<'static>, AsmError> {
+        let attribute = match attribute {
+            PendingAttribute::Ready(attribute) => attribute,
+            PendingAttribute::LineNumberTable(table) => Attribute::LineNumberTable(table.into_iter().map(|(start, line_number)| {
+                Ok(LineNumberTable { start_pc: start.resolve_u16(labels)?, line_number: line_number })
+            }).collect::<Result<_, AsmError>>()?),
+            PendingAttribute::LocalVariableTable(table) => Attribute::LocalVariableTable(table.into_iter().map(|(index, name_index, descriptor_index, start, end)| {
+                let (start_pc, length) = range(&start, &end, labels)?;
+                Ok(LocalVariableTable { start_pc: start_pc, length: length, name_index: name_index, descriptor_index: descriptor_index, index: index })
+            }).collect::<Result<_, AsmError>>()?),
+            PendingAttribute::LocalVariableTypeTable(table) => Attribute::LocalVariableTypeTable(table.into_iter().map(|(index, name_index, signature_index, start, end)| {
+                let (start_pc, length) = range(&start, &end, labels)?;
+                Ok(LocalVariableTypeTable { start_pc: start_pc, length: length, name_index: name_index, signature_index: signature_index, index: index })
+            }).collect::<Result<_, AsmError>>()?),
+            PendingAttribute::StackMapTable(frames) => Attribute::StackMapTable(resolve_frames(frames, labels)?)
+        };
+
+        if let Some(name) = attribute.name() {
+            self.ensure(Constant::Utf8(Cow::Owned(mutf8::encode(name).into_owned())))?;
+        }
+
+        Ok(attribute)
+    }
+
+    fn code(&mut self) -> Result<Attribute<'static>, AsmError> {
+        self.expect("stack")?;
+        let max_stack = self.integer()?;
+        self.expect("locals")?;
+        let max_locals = self.integer()?;
+        self.end_of_line()?;
+
+        let mut labels = HashMap::new();
+        let mut instructions = vec![];
+        let mut handlers = vec![];
+        let mut attributes = vec![];
+        let mut length = 0;
+
+        while !self.block_end("code")? {
+            let word = self.word()?;
+
+            if word.len() > 1 && word.ends_with(':') {
+                if labels.insert(word[..word.len() - 1].to_string(), length).is_some() {
+                    return self.error(format!("duplicate label `{}`", &word[..word.len() - 1]));
+                }
+
+                continue;
+            }
+
+            if word == ".catch" {
+                let catch_type = if self.is("any") { self.pos += 1; ConstantPoolIndex::new(0) } else { self.reference(Kind::Class)? };
+                handlers.push((catch_type, self.pc()?, self.pc()?, self.pc()?));
+            } else if word.starts_with('.') {
+                attributes.push(self.attribute(&word)?);
+            } else {
+                let line = self.line();
+                let (instruction, targets) = self.instruction(&word)?;
+                let len = instruction.len_at(length);
+
+                instructions.push((instruction, targets, length, line));
+                length += len;
+            }
+
+            self.end_of_line()?;
+        }
+
+        let mut code = Vec::with_capacity(instructions.len());
+
+        for (instruction, targets, offset, line) in instructions {
+            let deltas = targets.iter().map(|target| target.resolve(Some(&labels)).map(|target| target as i64 - offset as i64)).collect::<Result<Vec<i64>, AsmError>>()?;
+            code.push(patch(instruction, &deltas).map_err(|message| AsmError::Syntax { line: line, message: message })?);
+        }
+
+        let exception_table = handlers.into_iter().map(|(catch_type, start, end, handler)| {
+            Ok(ExceptionHandler { start_pc: start.resolve_u16(Some(&labels))?, end_pc: end.resolve_u16(Some(&labels))?, handler_pc: handler.resolve_u16(Some(&labels))?, catch_type: catch_type })
+        }).collect::<Result<_, AsmError>>()?;
+
+        let attributes = attributes.into_iter().map(|attribute| self.resolve(attribute, Some(&labels))).collect::<Result<_, AsmError>>()?;
+
+        Ok(Attribute::Code { max_stack: max_stack, max_locals: max_locals, code: code, exception_table: exception_table, attributes: attributes })
+    }
+
+    /// An instruction along with the offsets it branches to, which are patched in once known
+    fn instruction(&mut self, name: &str) -> Result<(Instruction, Vec<Pc>), AsmError> {
+        if let Some(instruction) = simple_instruction(name) {
+            return Ok((instruction, vec![]));
+        } else if let Some(branch) = BRANCHES.iter().find(|branch| branch.to_instruction(0).mnemonic() == name) {
+            return Ok((branch.to_instruction(0), vec![ self.pc()? ]));
+        }
+
+        let instruction = match name {
+            "aload" => Instruction::ALOAD(self.integer()?),
+            "astore" => Instruction::ASTORE(self.integer()?),
+            "dload" => Instruction::DLOAD(self.integer()?),
+            "dstore" => Instruction::DSTORE(self.integer()?),
+            "fload" => Instruction::FLOAD(self.integer()?),
+            "fstore" => Instruction::FSTORE(self.integer()?),
+            "iload" => Instruction::ILOAD(self.integer()?),
+            "istore" => Instruction::ISTORE(self.integer()?),
+            "lload" => Instruction::LLOAD(self.integer()?),
+            "lstore" => Instruction::LSTORE(self.integer()?),
+            "ret" => Instruction::RET(self.integer()?),
+            "aload_w" => Instruction::ALOAD_W(self.integer()?),
+            "astore_w" => Instruction::ASTORE_W(self.integer()?),
+            "dload_w" => Instruction::DLOAD_W(self.integer()?),
+            "dstore_w" => Instruction::DSTORE_W(self.integer()?),
+            "fload_w" => Instruction::FLOAD_W(self.integer()?),
+            "fstore_w" => Instruction::FSTORE_W(self.integer()?),
+            "iload_w" => Instruction::ILOAD_W(self.integer()?),
+            "istore_w" => Instruction::ISTORE_W(self.integer()?),
+            "lload_w" => Instruction::LLOAD_W(self.integer()?),
+            "lstore_w" => Instruction::LSTORE_W(self.integer()?),
+            "ret_w" => Instruction::RET_W(self.integer()?),
+            "bipush" => Instruction::BIPUSH(self.integer::<i8>()? as u8),
+            "sipush" => Instruction::SIPUSH(self.integer::<i16>()? as u16),
+            "iinc" => Instruction::IINC(self.integer()?, self.integer()?),
+            "iinc_w" => Instruction::IINC_W(self.integer()?, self.integer()?),
+            "newarray" => {
+                let word = self.word()?;
+
+                match ARRAY_TYPES.iter().find(|&&(type_name, _)| type_name == word).map(|&(_, atype)| atype).or_else(|| parse_integer(&word).and_then(|value| u8::try_from(value).ok())) {
+                    Some(atype) => Instruction::NEWARRAY(atype),
+                    None => return self.error(format!("unknown array type `{}`", word))
+                }
+            },
+            "anewarray" => Instruction::ANEWARRAY(self.index(Kind::Class)?),
+            "checkcast" => Instruction::CHECKCAST(self.index(Kind::Class)?),
+            "instanceof" => Instruction::INSTANCEOF(self.index(Kind::Class)?),
+            "new" => Instruction::NEW(self.index(Kind::Class)?),
+            "multianewarray" => Instruction::MULTIANEWARRAY(self.index(Kind::Class)?, self.integer()?),
+            "ldc" => {
+                let idx = self.index(Kind::Any)?;
+
+                match u8::try_from(idx) {
+                    Ok(idx) => Instruction::LDC(idx),
+                    Err(_) => return self.error(format!("constant #{} is out of reach of ldc", idx))
+                }
+            },
+            "ldc_w" => Instruction::LDC_W(self.index(Kind::Any)?),
+            "ldc2_w" => Instruction::LDC2_W(self.index(Kind::Any)?),
+            "getfield" => Instruction::GETFIELD(self.index(Kind::Any)?),
+            "getstatic" => Instruction::GETSTATIC(self.index(Kind::Any)?),
+            "putfield" => Instruction::PUTFIELD(self.index(Kind::Any)?),
+            "putstatic" => Instruction::PUTSTATIC(self.index(Kind::Any)?),
+            "invokevirtual" => Instruction::INVOKEVIRTUAL(self.index(Kind::Any)?),
+            "invokespecial" => Instruction::INVOKESPECIAL(self.index(Kind::Any)?),
+            "invokestatic" => Instruction::INVOKESTATIC(self.index(Kind::Any)?),
+            "invokedynamic" => Instruction::INVOKEDYNAMIC(self.index(Kind::Any)?),
+            "invokeinterface" => Instruction::INVOKEINTERFACE(self.index(Kind::Any)?, self.integer()?),
+            "tableswitch" => {
+                let low: i32 = self.integer()?;
+                let mut targets = vec![];
+                self.end_of_line()?;
+
+                loop {
+                    self.skip_newlines();
+
+                    if self.is("default") {
+                        self.pos += 1;
+                        targets.insert(0, self.pc()?);
+                        break;
+                    }
+
+                    targets.push(self.pc()?);
+                    self.end_of_line()?;
+                }
+
+                let count = targets.len() - 1;
+                return Ok((Instruction::TABLESWITCH(0, low, low.wrapping_add(count as i32).wrapping_sub(1), vec![ 0; count ]), targets));
+            },
+            "lookupswitch" => {
+                let mut pairs = vec![];
+                let mut targets = vec![];
+                self.end_of_line()?;
+
+                loop {
+                    self.skip_newlines();
+
+                    if self.is("default") {
+                        self.pos += 1;
+                        targets.insert(0, self.pc()?);
+                        break;
+                    }
+
+                    pairs.push((self.integer()?, 0));
+                    targets.push(self.pc()?);
+                    self.end_of_line()?;
+                }
+
+                return Ok((Instruction::LOOKUPSWITCH(0, pairs), targets));
+            },
+            _ => return self.error(format!("unknown instruction `{}`", name))
+        };
+
+        Ok((instruction, vec![]))
+    }
+
+    /// A reference as the 16 bit operand of an instruction
+    fn index(&mut self, kind: Kind) -> Result<u16, AsmError> {
+        let idx = self.reference(kind)?;
+        Ok(idx.idx as u16)
+    }
+
+    fn frame(&mut self) -> Result<PendingFrame, AsmError> {
+        let kind = self.word()?;
+        let mut frame = PendingFrame { kind: kind.clone(), at: None, argument: 0, locals: vec![], stack: vec![], line: self.line() };
+
+        if kind == "reserved" {
+            frame.argument = self.integer()?;
+            return Ok(frame);
+        }
+
+        frame.at = Some(self.pc()?);
+
+        match &kind[..] {
+            "same" | "same_extended" => (),
+            "same_locals_1_stack_item" | "same_locals_1_stack_item_extended" => frame.stack.push(self.verification_type()?),
+            "chop" => frame.argument = self.integer()?,
+            "append" => while !self.at_end_of_line() {
+                frame.locals.push(self.verification_type()?);
+            },
+            "full" => {
+                frame.locals = self.verification_types()?;
+                frame.stack = self.verification_types()?;
+            },
+            _ => return self.error(format!("unknown frame type `{}`", kind))
+        }
+
+        Ok(frame)
+    }
+
+    fn verification_types(&mut self) -> Result<Vec<PendingType>, AsmError> {
+        let mut types = vec![];
+        self.expect("[")?;
+
+        while !self.is("]") {
+            types.push(self.verification_type()?);
+        }
+
+        self.pos += 1;
+        Ok(types)
+    }
+
+    fn verification_type(&mut self) -> Result<PendingType, AsmError> {
+        let word = self.word()?;
+
+        Ok(PendingType::Ready(match &word[..] {
+            "top" => VerificationType::Top,
+            "int" => VerificationType::Integer,
+            "float" => VerificationType::Float,
+            "long" => VerificationType::Long,
+            "double" => VerificationType::Double,
+            "null" => VerificationType::Null,
+            "uninitialized_this" => VerificationType::UninitializedThis,
+            "class" => VerificationType::Object { cpool_index: self.reference(Kind::Class)? },
+            "uninitialized" => return Ok(PendingType::Uninitialized(self.pc()?)),
+            _ => return self.error(format!("unknown verification type `{}`", word))
+        }))
+    }
+
+    /// An annotation, which may span several lines
+    fn annotation(&mut self) -> Result<Annotation, AsmError> {
+        let (type_index, element_value_pairs) = self.annotation_body()?;
+        Ok(Annotation { type_index: type_index, element_value_pairs: element_value_pairs })
+    }
+
+    fn annotation_body(&mut self) -> Result<(ConstantPoolIndex, Vec<ElementValuePair>), AsmError> {
+        self.skip_newlines();
+        let type_index = self.reference(Kind::Utf8)?;
+        let mut pairs = vec![];
+        self.expect("{")?;
+
+        loop {
+            self.skip_newlines();
+
+            if self.is("}") {
+                self.pos += 1;
+                return Ok((type_index, pairs));
+            }
+
+            let element_name_index = self.reference(Kind::Utf8)?;
+            self.expect("=")?;
+            pairs.push(ElementValuePair { element_name_index: element_name_index, value: self.element_value()? });
+        }
+    }
+
+    fn element_value(&mut self) -> Result<ElementValue, AsmError> {
+        let tag = self.word()?;
+
+        Ok(match &tag[..] {
+            "e" => ElementValue::Enum { type_name_index: self.reference(Kind::Utf8)?, const_name_index: self.reference(Kind::Utf8)? },
+            "c" => ElementValue::ClassInfo(self.reference(Kind::Utf8)?),
+            "@" => ElementValue::Annotation(self.annotation()?),
+            "[" => {
+                let mut values = vec![];
+
+                while !self.is("]") {
+                    values.push(self.element_value()?);
+                }
+
+                self.pos += 1;
+                ElementValue::Array(values)
+            },
+            _ if tag.len() == 1 => ElementValue::ConstantValue(tag.as_bytes()[0], self.reference(Kind::Any)?),
+            _ => return self.error(format!("unknown element value tag `{}`", tag))
+        })
+    }
+
+    fn type_annotation(&mut self) -> Result<TypeAnnotation, AsmError> {
+        self.skip_newlines();
+        let target = self.word()?;
+
+        let target_info = match &target[..] {
+            "type_parameter" => TargetInfo::TypeParameter { subtype: self.integer()?, idx: self.integer()? },
+            "supertype" => TargetInfo::SuperType { idx: self.integer()? },
+            "type_parameter_bound" => TargetInfo::TypeParameterBound { subtype: self.integer()?, param_idx: self.integer()?, bound_index: self.integer()? },
+            "empty" => TargetInfo::Empty { subtype: self.integer()? },
+            "formal_parameter" => TargetInfo::MethodFormalParameter { idx: self.integer()? },
+            "throws" => TargetInfo::Throws { idx: self.integer()? },
+            "localvar" => {
+                let subtype = self.integer()?;
+                let mut entries = vec![];
+                self.expect("[")?;
+
+                while !self.is("]") {
+                    entries.push((self.integer()?, self.integer()?, self.integer()?));
+                }
+
+                self.pos += 1;
+                TargetInfo::LocalVar { subtype: subtype, target: entries }
+            },
+            "catch" => TargetInfo::Catch { idx: self.integer()? },
+            "offset" => TargetInfo::Offset { subtype: self.integer()?, idx: self.integer()? },
+            "type_argument" => TargetInfo::TypeArgument { subtype: self.integer()?, offset: self.integer()?, type_arg_idx: self.integer()? },
+            _ => return self.error(format!("unknown type annotation target `{}`", target))
+        };
+
+        let mut path = vec![];
+        self.expect("[")?;
+
+        while !self.is("]") {
+            let kind = match self.keyword(TYPE_PATH_KINDS, "type path kind")? {
+                0 => TypePathKind::Array,
+                1 => TypePathKind::Nested,
+                2 => TypePathKind::Wildcard,
+                _ => TypePathKind::TypeArgument
+            };
+
+            path.push((kind, self.integer()?));
+        }
+
+        self.pos += 1;
+        let (type_index, element_value_pairs) = self.annotation_body()?;
+
+        Ok(TypeAnnotation { target_info: target_info, target_path: TypePath { path: path }, type_index: type_index, element_value_pairs: element_value_pairs })
+    }
+
+    fn module(&mut self) -> Result<Attribute<'static>, AsmError> {
+        let (name_index, version_index) = (self.reference(Kind::Module)?, self.reference(Kind::Utf8)?);
+        let flags = self.trailing_flags(MODULE_FLAGS, "")?;
+        let (mut requires, mut exports, mut opens, mut uses, mut provides) = (vec![], vec![], vec![], vec![], vec![]);
+        self.end_of_line()?;
+
+        while !self.block_end("module")? {
+            let keyword = self.word()?;
+
+            match &keyword[..] {
+                "requires" => {
+                    let (requires_index, requires_version_index) = (self.reference(Kind::Module)?, self.reference(Kind::Utf8)?);
+                    let requires_flags = self.trailing_flags(REQUIRES_FLAGS, "")?;
+                    requires.push(ModuleRequires { requires_index: requires_index, requires_flags: requires_flags, requires_version_index: requires_version_index });
+                },
+                "exports" | "opens" => {
+                    let package = self.reference(Kind::Package)?;
+                    let flags = self.trailing_flags(EXPORTS_FLAGS, "to")?;
+                    let mut to = vec![];
+
+                    if self.is("to") {
+                        self.pos += 1;
+                        to = self.references(Kind::Module)?;
+                    }
+
+                    if keyword == "exports" {
+                        exports.push(ModuleExports { exports_index: package, exports_flags: flags, exports_to: to });
+                    } else {
+                        opens.push(ModuleOpens { opens_index: package, opens_flags: flags, opens_to: to });
+                    }
+                },
+                "uses" => uses.push(self.reference(Kind::Class)?),
+                "provides" => {
+                    let provides_index = self.reference(Kind::Class)?;
+                    self.expect("with")?;
+                    provides.push(ModuleProvides { provides_index: provides_index, provides_with: self.references(Kind::Class)? });
+                },
+                _ => return self.error(format!("unknown module directive `{}`", keyword))
+            }
+
+            self.end_of_line()?;
+        }
+
+        Ok(Attribute::Module { name_index: name_index, flags: flags, version_index: version_index, requires: requires, exports: exports, opens: opens, uses: uses, provides: provides })
+    }
+}
+
+/// The start and the length of a range given by its start and its end
+fn range(start: &Pc, end: &Pc, labels: Option<&HashMap<String, usize>>) -> Result<(u16, u16), AsmError> {
+    let start_pc = start.resolve_u16(labels)?;
+    let end_pc = end.resolve_u16(labels)?;
+
+    match end_pc.checked_sub(start_pc) {
+        Some(length) => Ok((start_pc, length)),
+        None => Err(AsmError::Syntax { line: end.line, message: String::from("range ends before it starts") })
+    }
+}
+
+fn resolve_frames(frames: Vec<PendingFrame>, labels: Option<&HashMap<String, usize>>) -> Result<Vec<StackMapFrame>, AsmError> {
+    let resolve_types = |types: Vec<PendingType>| types.into_iter().map(|pending| match pending {
+        PendingType::Ready(verification_type) => Ok(verification_type),
+        PendingType::Uninitialized(pc) => Ok(VerificationType::Uninitialized { offset: pc.resolve_u16(labels)? })
+    }).collect::<Result<Vec<VerificationType>, AsmError>>();
+
+    let mut previous: Option<usize> = None;
+    let mut resolved = Vec::with_capacity(frames.len());
+
+    for frame in frames {
+        let line = frame.line;
+        let error = |message: &str| Err(AsmError::Syntax { line: line, message: message.to_string() });
+
+        let at = match frame.at {
+            None => {
+                resolved.push(StackMapFrame::FutureUse { tag: frame.argument });
+                continue;
+            },
+            Some(ref at) => at.resolve(labels)?
+        };
+
+        let delta = match previous {
+            None => at,
+            Some(previous) if at > previous => at - previous - 1,
+            Some(_) => return error("frames must be in increasing order of offset")
+        };
+
+        let offset_delta = match u16::try_from(delta) {
+            Ok(offset_delta) => offset_delta,
+            Err(_) => return error("frame offset out of range")
+        };
+
+        previous = Some(at);
+
+        let mut locals = resolve_types(frame.locals)?;
+        let mut stack = resolve_types(frame.stack)?;
+
+        resolved.push(match &frame.kind[..] {
+            "same" if delta <= 63 => StackMapFrame::SameFrame { tag: delta as u8 },
+            "same_locals_1_stack_item" if delta <= 63 => StackMapFrame::SameLocals1StackItemFrame { tag: 64 + delta as u8, stack: stack.remove(0) },
+            "same" | "same_locals_1_stack_item" => return error("frame offset out of range, use the extended form"),
+            "same_locals_1_stack_item_extended" => StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta: offset_delta, stack: stack.remove(0) },
+            "chop" if (1..=3).contains(&frame.argument) => StackMapFrame::ChopFrame { tag: 251 - frame.argument, offset_delta: offset_delta },
+            "chop" => return error("chop frames remove 1 to 3 locals"),
+            "same_extended" => StackMapFrame::SameFrameExtended { offset_delta: offset_delta },
+            "append" if (1..=3).contains(&locals.len()) => StackMapFrame::AppendFrame { tag: 251 + locals.len() as u8, offset_delta: offset_delta, locals: mem::take(&mut locals) },
+            "append" => return error("append frames add 1 to 3 locals"),
+            _ => StackMapFrame::FullFrame { offset_delta: offset_delta, locals: locals, stack: stack }
+        });
+    }
+
+    Ok(resolved)
+}
+
+/// Fill in the offsets of a branch or a switch, the default one first for switches
+fn patch(instruction: Instruction, deltas: &[i64]) -> Result<Instruction, String> {
+    if let Some((branch, _)) = Branch::from_instruction(&instruction) {
+        let delta = deltas[0];
+        let wide = branch == Branch::GOTO_W || branch == Branch::JSR_W;
+
+        if !wide && (delta < i16::MIN as i64 || delta > i16::MAX as i64) {
+            return Err(format!("branch target out of reach of {}", instruction.mnemonic()));
+        }
+
+        return Ok(branch.to_instruction(delta as i32));
+    }
+
+    match instruction {
+        Instruction::TABLESWITCH(_, low, high, _) => Ok(Instruction::TABLESWITCH(deltas[0] as i32, low, high, deltas[1..].iter().map(|&delta| delta as i32).collect())),
+        Instruction::LOOKUPSWITCH(_, pairs) => Ok(Instruction::LOOKUPSWITCH(deltas[0] as i32, pairs.into_iter().zip(&deltas[1..]).map(|((key, _), &delta)| (key, delta as i32)).collect())),
+        instruction => Ok(instruction)
+    }
+}
+
+/// The instructions without operands, by mnemonic
+fn simple_instruction(name: &str) -> Option<Instruction> {
+    Some(match name {
+            "aaload" => Instruction::AALOAD,
+            "aastore" => Instruction::AASTORE,
+            "aconst_null" => Instruction::ACONST_NULL,
+            "aload_0" => Instruction::ALOAD_0,
+            "aload_1" => Instruction::ALOAD_1,
+            "aload_2" => Instruction::ALOAD_2,
+            "aload_3" => Instruction::ALOAD_3,
+            "areturn" => Instruction::ARETURN,
+            "arraylength" => Instruction::ARRAYLENGTH,
+            "astore_0" => Instruction::ASTORE_0,
+            "astore_1" => Instruction::ASTORE_1,
+            "astore_2" => Instruction::ASTORE_2,
+            "astore_3" => Instruction::ASTORE_3,
+            "athrow" => Instruction::ATHROW,
+            "baload" => Instruction::BALOAD,
+            "bastore" => Instruction::BASTORE,
+            "caload" => Instruction::CALOAD,
+            "castore" => Instruction::CASTORE,
+            "d2f" => Instruction::D2F,
+            "d2i" => Instruction::D2I,
+            "d2l" => Instruction::D2L,
+            "dadd" => Instruction::DADD,
+            "daload" => Instruction::DALOAD,
+            "dastore" => Instruction::DASTORE,
+            "dcmpl" => Instruction::DCMPL,
+            "dcmpg" => Instruction::DCMPG,
+            "dconst_0" => Instruction::DCONST_0,
+            "dconst_1" => Instruction::DCONST_1,
+            "ddiv" => Instruction::DDIV,
+            "dload_0" => Instruction::DLOAD_0,
+            "dload_1" => Instruction::DLOAD_1,
+            "dload_2" => Instruction::DLOAD_2,
+            "dload_3" => Instruction::DLOAD_3,
+            "dmul" => Instruction::DMUL,
+            "dneg" => Instruction::DNEG,
+            "drem" => Instruction::DREM,
+            "dreturn" => Instruction::DRETURN,
+            "dstore_0" => Instruction::DSTORE_0,
+            "dstore_1" => Instruction::DSTORE_1,
+            "dstore_2" => Instruction::DSTORE_2,
+            "dstore_3" => Instruction::DSTORE_3,
+            "dsub" => Instruction::DSUB,
+            "dup" => Instruction::DUP,
+            "dup_x1" => Instruction::DUP_X1,
+            "dup_x2" => Instruction::DUP_X2,
+            "dup2" => Instruction::DUP2,
+            "dup2_x1" => Instruction::DUP2_X1,
+            "dup2_x2" => Instruction::DUP2_X2,
+            "f2d" => Instruction::F2D,
+            "f2i" => Instruction::F2I,
+            "f2l" => Instruction::F2L,
+            "fadd" => Instruction::FADD,
+            "faload" => Instruction::FALOAD,
+            "fastore" => Instruction::FASTORE,
+            "fcmpl" => Instruction::FCMPL,
+            "fcmpg" => Instruction::FCMPG,
+            "fconst_0" => Instruction::FCONST_0,
+            "fconst_1" => Instruction::FCONST_1,
+            "fconst_2" => Instruction::FCONST_2,
+            "fdiv" => Instruction::FDIV,
+            "fload_0" => Instruction::FLOAD_0,
+            "fload_1" => Instruction::FLOAD_1,
+            "fload_2" => Instruction::FLOAD_2,
+            "fload_3" => Instruction::FLOAD_3,
+            "fmul" => Instruction::FMUL,
+            "fneg" => Instruction::FNEG,
+            "frem" => Instruction::FREM,
+            "freturn" => Instruction::FRETURN,
+            "fstore_0" => Instruction::FSTORE_0,
+            "fstore_1" => Instruction::FSTORE_1,
+            "fstore_2" => Instruction::FSTORE_2,
+            "fstore_3" => Instruction::FSTORE_3,
+            "fsub" => Instruction::FSUB,
+            "i2b" => Instruction::I2B,
+            "i2c" => Instruction::I2C,
+            "i2d" => Instruction::I2D,
+            "i2f" => Instruction::I2F,
+            "i2l" => Instruction::I2L,
+            "i2s" => Instruction::I2S,
+            "iadd" => Instruction::IADD,
+            "iaload" => Instruction::IALOAD,
+            "iand" => Instruction::IAND,
+            "iastore" => Instruction::IASTORE,
+            "iconst_m1" => Instruction::ICONST_M1,
+            "iconst_0" => Instruction::ICONST_0,
+            "iconst_1" => Instruction::ICONST_1,
+            "iconst_2" => Instruction::ICONST_2,
+            "iconst_3" => Instruction::ICONST_3,
+            "iconst_4" => Instruction::ICONST_4,
+            "iconst_5" => Instruction::ICONST_5,
+            "idiv" => Instruction::IDIV,
+            "iload_0" => Instruction::ILOAD_0,
+            "iload_1" => Instruction::ILOAD_1,
+            "iload_2" => Instruction::ILOAD_2,
+            "iload_3" => Instruction::ILOAD_3,
+            "imul" => Instruction::IMUL,
+            "ineg" => Instruction::INEG,
+            "ior" => Instruction::IOR,
+            "irem" => Instruction::IREM,
+            "ireturn" => Instruction::IRETURN,
+            "ishl" => Instruction::ISHL,
+            "ishr" => Instruction::ISHR,
+            "istore_0" => Instruction::ISTORE_0,
+            "istore_1" => Instruction::ISTORE_1,
+            "istore_2" => Instruction::ISTORE_2,
+            "istore_3" => Instruction::ISTORE_3,
+            "isub" => Instruction::ISUB,
+            "iushr" => Instruction::IUSHR,
+            "ixor" => Instruction::IXOR,
+            "l2d" => Instruction::L2D,
+            "l2f" => Instruction::L2F,
+            "l2i" => Instruction::L2I,
+            "ladd" => Instruction::LADD,
+            "laload" => Instruction::LALOAD,
+            "land" => Instruction::LAND,
+            "lastore" => Instruction::LASTORE,
+            "lcmp" => Instruction::LCMP,
+            "lconst_0" => Instruction::LCONST_0,
+            "lconst_1" => Instruction::LCONST_1,
+            "ldiv" => Instruction::LDIV,
+            "lload_0" => Instruction::LLOAD_0,
+            "lload_1" => Instruction::LLOAD_1,
+            "lload_2" => Instruction::LLOAD_2,
+            "lload_3" => Instruction::LLOAD_3,
+            "lmul" => Instruction::LMUL,
+            "lneg" => Instruction::LNEG,
+            "lor" => Instruction::LOR,
+            "lrem" => Instruction::LREM,
+            "lreturn" => Instruction::LRETURN,
+            "lshl" => Instruction::LSHL,
+            "lshr" => Instruction::LSHR,
+            "lstore_0" => Instruction::LSTORE_0,
+            "lstore_1" => Instruction::LSTORE_1,
+            "lstore_2" => Instruction::LSTORE_2,
+            "lstore_3" => Instruction::LSTORE_3,
+            "lsub" => Instruction::LSUB,
+            "lushr" => Instruction::LUSHR,
+            "lxor" => Instruction::LXOR,
+            "monitorenter" => Instruction::MONITORENTER,
+            "monitorexit" => Instruction::MONITOREXIT,
+            "nop" => Instruction::NOP,
+            "pop" => Instruction::POP,
+            "pop2" => Instruction::POP2,
+            "return" => Instruction::RETURN,
+            "saload" => Instruction::SALOAD,
+            "sastore" => Instruction::SASTORE,
+            "swap" => Instruction::SWAP,
+        _ => return None
+    })
+}
@@ -0,0 +1,573 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::ops::Range;
+use super::classfile::*;
+use super::code::{ Branch, CodeError };
+
+///
+/// The control flow graph of a method body. Blocks are ordered by offset, the first one is the
+/// entry of the method and the last one is an empty block at the end of the code that every
+/// `return` and `athrow` leads to, so that exits show up as edges like everything else.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// A run of instructions that's only ever entered at the top and left at the bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction
+    pub start: usize,
+    /// Offset just past the last instruction
+    pub end: usize,
+    /// Positions of the instructions within the code
+    pub instructions: Range<usize>,
+    pub successors: Vec<Edge>,
+    /// The blocks with an edge to this one, each listed once
+    pub predecessors: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    /// Index of the block the edge leads to
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction
+    Fallthrough,
+    /// A conditional branch is taken
+    Branch,
+    /// `goto` or `goto_w`
+    Jump,
+    /// A case or the default of a `tableswitch` or `lookupswitch`
+    Switch,
+    /// An exception handler catching the given class, or anything if the index is 0
+    Exception(ConstantPoolIndex),
+    /// `jsr` or `jsr_w` calling a subroutine
+    Subroutine,
+    /// `ret` returning from a subroutine to the instruction after one of its calls
+    SubroutineReturn,
+    /// A return instruction, leading to the exit block
+    Return,
+    /// `athrow`, leading to the exit block for exceptions that aren't caught within the method
+    Throw,
+}
+
+///
+/// The dominator tree of a control flow graph. A block dominates another if every path from the
+/// entry to the other block passes through it, and every block dominates itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dominators {
+    /// The immediate dominator of every reachable block, with the entry as its own
+    idoms: Vec<Option<usize>>,
+}
+
+/// A natural loop, made of the blocks that can reach a back edge without passing through its header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Loop {
+    /// The block every iteration starts with, which dominates the whole loop
+    pub header: usize,
+    /// The blocks with a back edge to the header
+    pub latches: Vec<usize>,
+    /// Every block of the loop, including the header and the latches
+    pub blocks: BTreeSet<usize>,
+}
+
+///
+/// Where control can go after an instruction, besides the exception handlers covering it. Targets
+/// are positions of instructions within the code, as resolved by `Offsets::flow`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Flow {
+    /// Execution continues with the next instruction
+    Next,
+    /// A conditional branch, either taken or continuing with the next instruction
+    Branch(usize),
+    /// `goto` or `goto_w`
+    Jump(usize),
+    /// The default and the cases of a `tableswitch` or `lookupswitch`
+    Switch(Vec<usize>),
+    /// `jsr` or `jsr_w` calling a subroutine, which returns to the next instruction
+    Subroutine(usize),
+    /// `ret` returning from a subroutine
+    Ret,
+    /// A return instruction
+    Return,
+    /// `athrow`
+    Throw,
+}
+
+impl Flow {
+    /// Whether execution may go on with the next instruction
+    pub fn continues(&self) -> bool {
+        matches!(*self, Flow::Next | Flow::Branch(_) | Flow::Subroutine(_))
+    }
+
+    /// The positions the instruction branches, jumps or switches to
+    pub fn targets(&self) -> Vec<usize> {
+        match *self {
+            Flow::Branch(target) | Flow::Jump(target) | Flow::Subroutine(target) => vec![ target ],
+            Flow::Switch(ref targets) => targets.clone(),
+            Flow::Next | Flow::Ret | Flow::Return | Flow::Throw => vec![]
+        }
+    }
+}
+
+///
+/// The offset of every instruction of some code, translating between positions within the code
+/// and offsets. Lookups fail with the offset that doesn't start an instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offsets {
+    /// The offset of each instruction followed by the length of the code
+    offsets: Vec<usize>,
+}
+
+impl Offsets {
+    /// Lay out the instructions of some code
+    pub fn new(code: &[Instruction]) -> Offsets {
+        let mut offsets = Vec::with_capacity(code.len() + 1);
+        let mut length = 0;
+
+        for instruction in code {
+            offsets.push(length);
+            length += instruction.len_at(length);
+        }
+
+        offsets.push(length);
+        Offsets { offsets: offsets }
+    }
+
+    /// The offset of the instruction at a position, or the length of the code past the last one
+    pub fn offset(&self, position: usize) -> usize {
+        self.offsets[position]
+    }
+
+    /// The position of the instruction at an offset, or the number of instructions for the length
+    /// of the code, which is where ranges end
+    pub fn position(&self, offset: i64) -> Result<usize, i64> {
+        if offset < 0 {
+            return Err(offset);
+        }
+
+        self.offsets.binary_search(&(offset as usize)).map_err(|_| offset)
+    }
+
+    /// The position of the instruction at an offset, which can't be the end of the code
+    pub fn instruction_at(&self, offset: i64) -> Result<usize, i64> {
+        match self.position(offset)? {
+            position if position + 1 == self.offsets.len() => Err(offset),
+            position => Ok(position)
+        }
+    }
+
+    /// The position of the instruction a branch at `position` leads to
+    pub fn target(&self, position: usize, delta: i32) -> Result<usize, i64> {
+        self.instruction_at(self.offsets[position] as i64 + delta as i64)
+    }
+
+    /// Where control can go after the instruction at a position of `code`, which has to be the
+    /// code these are the offsets of
+    pub fn flow(&self, code: &[Instruction], position: usize) -> Result<Flow, i64> {
+        let switch = |deltas: Vec<i32>| deltas.into_iter().map(|delta| self.target(position, delta)).collect::<Result<Vec<usize>, i64>>().map(Flow::Switch);
+
+        Ok(match code[position] {
+            Instruction::TABLESWITCH(default, _, _, ref targets) => switch(Some(default).into_iter().chain(targets.iter().cloned()).collect())?,
+            Instruction::LOOKUPSWITCH(default, ref pairs) => switch(Some(default).into_iter().chain(pairs.iter().map(|&(_, delta)| delta)).collect())?,
+            Instruction::IRETURN | Instruction::LRETURN | Instruction::FRETURN | Instruction::DRETURN | Instruction::ARETURN | Instruction::RETURN => Flow::Return,
+            Instruction::ATHROW => Flow::Throw,
+            Instruction::RET(_) | Instruction::RET_W(_) => Flow::Ret,
+            ref instruction => match Branch::from_instruction(instruction) {
+                Some((Branch::GOTO, delta)) | Some((Branch::GOTO_W, delta)) => Flow::Jump(self.target(position, delta)?),
+                Some((Branch::JSR, delta)) | Some((Branch::JSR_W, delta)) => Flow::Subroutine(self.target(position, delta)?),
+                Some((_, delta)) => Flow::Branch(self.target(position, delta)?),
+                None => Flow::Next
+            }
+        })
+    }
+}
+
+impl ControlFlowGraph {
+    /// Build the graph of a `Code` attribute
+    pub fn from_code(attribute: &Attribute) -> Result<ControlFlowGraph, CodeError> {
+        match attribute {
+            &Attribute::Code { ref code, ref exception_table, .. } => ControlFlowGraph::new(code, exception_table),
+            _ => Err(CodeError::NotCode)
+        }
+    }
+
+    ///
+    /// Build the graph of some code. Blocks end at every instruction that doesn't simply fall through
+    /// and start at every target, and at every boundary of an exception handler range so that each
+    /// block is either entirely covered by a handler or not at all.
+    pub fn new(code: &[Instruction], exception_table: &[ExceptionHandler]) -> Result<ControlFlowGraph, CodeError> {
+        if code.is_empty() {
+            return Err(CodeError::InvalidOffset(0));
+        }
+
+        let offsets = Offsets::new(code);
+        let mut flows = Vec::with_capacity(code.len());
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+
+        for position in 0..code.len() {
+            let flow = offsets.flow(code, position).map_err(CodeError::InvalidOffset)?;
+            leaders.extend(flow.targets());
+
+            match flow {
+                ref flow if flow.continues() && position + 1 == code.len() => return Err(CodeError::InvalidOffset(offsets.offset(code.len()) as i64)),
+                Flow::Next => (),
+                _ => { leaders.insert(position + 1); }
+            }
+
+            flows.push(flow);
+        }
+
+        let handlers = exception_table.iter().map(|handler| {
+            let range = (offsets.position(handler.start_pc as i64).map_err(CodeError::InvalidOffset)?, offsets.position(handler.end_pc as i64).map_err(CodeError::InvalidOffset)?);
+            Ok((range, offsets.instruction_at(handler.handler_pc as i64).map_err(CodeError::InvalidOffset)?, handler.catch_type.clone()))
+        }).collect::<Result<Vec<((usize, usize), usize, ConstantPoolIndex)>, CodeError>>()?;
+
+        for &((start, end), handler, _) in &handlers {
+            leaders.insert(start);
+            leaders.insert(end);
+            leaders.insert(handler);
+        }
+
+        leaders.insert(code.len());
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+        let mut block_of = vec![0; code.len() + 1];
+
+        for (idx, window) in leaders.windows(2).enumerate() {
+            for block in &mut block_of[window[0]..window[1]] {
+                *block = idx;
+            }
+        }
+
+        block_of[code.len()] = leaders.len() - 1;
+
+        let mut blocks: Vec<BasicBlock> = leaders.iter().enumerate().map(|(idx, &first)| {
+            let last = leaders.get(idx + 1).cloned().unwrap_or(first);
+            BasicBlock { start: offsets.offset(first), end: offsets.offset(last), instructions: first..last, successors: vec![], predecessors: vec![] }
+        }).collect();
+
+        let exit = blocks.len() - 1;
+
+        for block in blocks.iter_mut().take(exit) {
+            let last = block.instructions.end - 1;
+            let mut successors = vec![];
+
+            match flows[last] {
+                Flow::Next => successors.push(Edge { target: block_of[last + 1], kind: EdgeKind::Fallthrough }),
+                Flow::Branch(target) => {
+                    successors.push(Edge { target: block_of[target], kind: EdgeKind::Branch });
+                    successors.push(Edge { target: block_of[last + 1], kind: EdgeKind::Fallthrough });
+                },
+                Flow::Jump(target) => successors.push(Edge { target: block_of[target], kind: EdgeKind::Jump }),
+                Flow::Switch(ref targets) => successors.extend(targets.iter().map(|&target| Edge { target: block_of[target], kind: EdgeKind::Switch })),
+                Flow::Subroutine(target) => successors.push(Edge { target: block_of[target], kind: EdgeKind::Subroutine }),
+                Flow::Return => successors.push(Edge { target: exit, kind: EdgeKind::Return }),
+                Flow::Throw => successors.push(Edge { target: exit, kind: EdgeKind::Throw }),
+                Flow::Ret => ()
+            }
+
+            let first = block.instructions.start;
+
+            for &((start, end), handler, ref catch_type) in &handlers {
+                if start <= first && first < end {
+                    successors.push(Edge { target: block_of[handler], kind: EdgeKind::Exception(catch_type.clone()) });
+                }
+            }
+
+            for edge in successors {
+                if !block.successors.contains(&edge) {
+                    block.successors.push(edge);
+                }
+            }
+        }
+
+        link_subroutines(&mut blocks, &flows, &block_of);
+
+        for idx in 0..blocks.len() {
+            for target in blocks[idx].successors.iter().map(|edge| edge.target).collect::<Vec<usize>>() {
+                if !blocks[target].predecessors.contains(&idx) {
+                    blocks[target].predecessors.push(idx);
+                }
+            }
+        }
+
+        Ok(ControlFlowGraph { blocks: blocks })
+    }
+
+    /// Index of the block the method starts with
+    pub fn entry(&self) -> usize {
+        0
+    }
+
+    /// Index of the empty block that returns and uncaught exceptions lead to
+    pub fn exit(&self) -> usize {
+        self.blocks.len() - 1
+    }
+
+    /// Index of the block containing the instruction at `offset`
+    pub fn block_at(&self, offset: usize) -> Option<usize> {
+        match self.blocks.binary_search_by_key(&offset, |block| block.start) {
+            Ok(idx) if idx == self.exit() => None,
+            Ok(idx) => Some(idx),
+            Err(0) => None,
+            Err(idx) if offset < self.blocks[idx - 1].end => Some(idx - 1),
+            Err(_) => None
+        }
+    }
+
+    /// The blocks reachable from the entry, in reverse postorder
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = vec![];
+        let mut stack = vec![(self.entry(), 0)];
+        visited[self.entry()] = true;
+
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(edge) => {
+                    stack.push((block, next + 1));
+
+                    if !visited[edge.target] {
+                        visited[edge.target] = true;
+                        stack.push((edge.target, 0));
+                    }
+                },
+                None => order.push(block)
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    ///
+    /// Compute the dominator tree, following the "simple, fast dominance algorithm" of Cooper, Harvey
+    /// and Kennedy. Exception edges count like any other, so a handler is dominated by the blocks
+    /// common to everything its range covers.
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_postorder();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+
+        for (idx, &block) in order.iter().enumerate() {
+            rank[block] = idx;
+        }
+
+        let mut idoms: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        idoms[self.entry()] = Some(self.entry());
+
+        let intersect = |idoms: &Vec<Option<usize>>, mut a: usize, mut b: usize| {
+            while a != b {
+                while rank[a] > rank[b] {
+                    a = idoms[a].unwrap_or(a);
+                }
+
+                while rank[b] > rank[a] {
+                    b = idoms[b].unwrap_or(b);
+                }
+            }
+
+            a
+        };
+
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for &block in order.iter().skip(1) {
+                let mut idom = None;
+
+                for &predecessor in &self.blocks[block].predecessors {
+                    if idoms[predecessor].is_some() {
+                        idom = Some(match idom {
+                            None => predecessor,
+                            Some(idom) => intersect(&idoms, predecessor, idom)
+                        });
+                    }
+                }
+
+                if idom.is_some() && idoms[block] != idom {
+                    idoms[block] = idom;
+                    changed = true;
+                }
+            }
+        }
+
+        Dominators { idoms: idoms }
+    }
+
+    ///
+    /// Find the natural loops, one per header, ordered by the offset of their headers. Back edges are
+    /// edges to a block that dominates their source. Irreducible loops, which can be entered in more
+    /// than one place, have no such edge and aren't reported.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let mut loops: Vec<Loop> = vec![];
+
+        for (latch, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                if !dominators.dominates(edge.target, latch) {
+                    continue;
+                }
+
+                let header = edge.target;
+                let mut body = BTreeSet::new();
+                let mut pending = vec![latch];
+                body.insert(header);
+
+                while let Some(block) = pending.pop() {
+                    if body.insert(block) {
+                        pending.extend(self.blocks[block].predecessors.iter().cloned().filter(|&block| dominators.is_reachable(block)));
+                    }
+                }
+
+                match loops.iter().position(|found| found.header == header) {
+                    Some(idx) => {
+                        if !loops[idx].latches.contains(&latch) {
+                            loops[idx].latches.push(latch);
+                        }
+
+                        loops[idx].blocks.extend(body);
+                    },
+                    None => loops.push(Loop { header: header, latches: vec![ latch ], blocks: body })
+                }
+            }
+        }
+
+        loops.sort_by_key(|found| found.header);
+        loops
+    }
+
+    ///
+    /// Render the graph in the DOT language of Graphviz, with the instructions of every block. `code`
+    /// has to be the code the graph was built from.
+    pub fn to_dot(&self, code: &[Instruction]) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (idx, block) in self.blocks.iter().enumerate() {
+            if idx == self.exit() {
+                let _ = writeln!(dot, "    B{} [label=\"exit\", shape=ellipse];", idx);
+                continue;
+            }
+
+            let _ = write!(dot, "    B{} [label=\"B{}\\l", idx, idx);
+            let mut offset = block.start;
+
+            for instruction in &code[block.instructions.clone()] {
+                let _ = write!(dot, "{}: {}\\l", offset, instruction.mnemonic());
+                offset += instruction.len_at(offset);
+            }
+
+            dot.push_str("\"];\n");
+        }
+
+        for (idx, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                let attributes = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Branch => " [label=\"branch\"]",
+                    EdgeKind::Jump => " [label=\"goto\"]",
+                    EdgeKind::Switch => " [label=\"switch\"]",
+                    EdgeKind::Exception(_) => " [label=\"catch\", style=dashed]",
+                    EdgeKind::Subroutine => " [label=\"jsr\"]",
+                    EdgeKind::SubroutineReturn => " [label=\"ret\", style=dotted]",
+                    EdgeKind::Return => " [label=\"return\"]",
+                    EdgeKind::Throw => " [label=\"athrow\", style=dashed]",
+                };
+
+                let _ = writeln!(dot, "    B{} -> B{}{};", idx, edge.target, attributes);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+///
+/// Add an edge from every `ret` to the instruction after each call of the subroutine it returns
+/// from. The blocks of a subroutine are those reachable from its entry, stepping over the calls of
+/// nested subroutines rather than into them.
+fn link_subroutines(blocks: &mut [BasicBlock], flows: &[Flow], block_of: &[usize]) {
+    // The block calling a subroutine along with the block it returns to
+    let calls: Vec<(usize, usize)> = (0..blocks.len() - 1).filter_map(|idx| match flows[blocks[idx].instructions.end - 1] {
+        Flow::Subroutine(_) => Some((idx, block_of[blocks[idx].instructions.end])),
+        _ => None
+    }).collect();
+
+    let entries: BTreeSet<usize> = calls.iter().flat_map(|&(call, _)| blocks[call].successors.iter().filter(|edge| edge.kind == EdgeKind::Subroutine).map(|edge| edge.target).collect::<Vec<usize>>()).collect();
+
+    for entry in entries {
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![entry];
+        let mut rets = vec![];
+
+        while let Some(block) = pending.pop() {
+            if block == blocks.len() - 1 || !visited.insert(block) {
+                continue;
+            }
+
+            match flows[blocks[block].instructions.end - 1] {
+                Flow::Ret => rets.push(block),
+                Flow::Subroutine(_) => pending.extend(calls.iter().filter(|&&(call, _)| call == block).map(|&(_, site)| site)),
+                _ => ()
+            }
+
+            pending.extend(blocks[block].successors.iter().filter(|edge| edge.kind != EdgeKind::Subroutine).map(|edge| edge.target));
+        }
+
+        let sites: Vec<usize> = calls.iter().filter(|&&(call, _)| blocks[call].successors.iter().any(|edge| edge.kind == EdgeKind::Subroutine && edge.target == entry)).map(|&(_, site)| site).collect();
+
+        for ret in rets {
+            for &site in &sites {
+                let edge = Edge { target: site, kind: EdgeKind::SubroutineReturn };
+
+                if !blocks[ret].successors.contains(&edge) {
+                    blocks[ret].successors.push(edge);
+                }
+            }
+        }
+    }
+}
+
+impl Dominators {
+    /// The closest strict dominator of a block, or `None` for the entry and unreachable blocks
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        match self.idoms[block] {
+            Some(idom) if idom != block => Some(idom),
+            _ => None
+        }
+    }
+
+    /// Whether the block can be reached from the entry at all
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.idoms[block].is_some()
+    }
+
+    /// Whether every path from the entry to `block` passes through `dominator`
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        if !self.is_reachable(block) {
+            return false;
+        }
+
+        let mut current = block;
+
+        loop {
+            if current == dominator {
+                return true;
+            }
+
+            match self.immediate_dominator(current) {
+                Some(idom) => current = idom,
+                None => return false
+            }
+        }
+    }
+}
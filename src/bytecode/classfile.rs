@@ -1,29 +1,55 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use super::mutf8;
+
 ///
 /// A `Classfile` represents a definition of a single JVM class or interface. Unlike the bytecode
 /// itself, it doesn't represent every byte in the class definition, though, many information are
 /// encoded in the type system instead. This approach may seem restrictive but it helps achieving
 /// bytecode safety.
-#[derive(Debug)]
-pub struct Classfile {
+///
+/// Byte strings, ie. `Utf8` constants and the bodies of unrecognised attributes, are borrowed from
+/// the buffer the class was read from when possible, hence the lifetime parameter.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Classfile<'a> {
     pub version: ClassfileVersion,
-    pub constant_pool: ConstantPool,
+    pub constant_pool: ConstantPool<'a>,
     pub access_flags: AccessFlags,
     pub this_class: ConstantPoolIndex,
     pub super_class: ConstantPoolIndex,
     pub interfaces: Vec<ConstantPoolIndex>,
-    pub fields: Vec<Field>,
-    pub methods: Vec<Method>,
-    pub attributes: Vec<Attribute>,
+    pub fields: Vec<Field<'a>>,
+    pub methods: Vec<Method<'a>>,
+    pub attributes: Vec<Attribute<'a>>,
 }
 
-impl Classfile {
+impl<'a> Classfile<'a> {
     /// Create a new classfile, initialised with sensible default values
-    pub fn new() -> Classfile {
+    pub fn new() -> Classfile<'a> {
         Classfile::default()
     }
+
+    /// Copy every borrowed byte string into memory owned by the class, detaching it from the
+    /// buffer it was read from
+    pub fn into_owned(self) -> Classfile<'static> {
+        Classfile {
+            version: self.version,
+            constant_pool: self.constant_pool.into_owned(),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields.into_iter().map(|field| field.into_owned()).collect(),
+            methods: self.methods.into_iter().map(|method| method.into_owned()).collect(),
+            attributes: Attribute::all_into_owned(self.attributes),
+        }
+    }
 }
 
-impl Default for Classfile {
+impl<'a> Default for Classfile<'a> {
     fn default() -> Self {
         Classfile {
             version: ClassfileVersion::default(),
@@ -41,7 +67,7 @@ impl Default for Classfile {
 
 ///
 /// Describe a classfile version number.
-#[derive(Ord, PartialOrd, Eq, PartialEq, Debug)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ClassfileVersion {
     pub minor_version: u16,
     pub major_version: u16,
@@ -62,35 +88,93 @@ impl Default for ClassfileVersion {
     }
 }
 
+/// The largest `constant_pool_count` a classfile can declare
+const MAX_CP_LEN: usize = 0xFFFF;
+
 ///
 /// A `ConstantPool` is a table of various string and number literal constants that are referred
 /// within the substructures of the `Classfile`.
-#[derive(Debug)]
-pub struct ConstantPool {
-    pub constants: Vec<Constant>
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConstantPool<'a> {
+    pub constants: Vec<Constant<'a>>
 }
 
-impl ConstantPool {
-    pub fn new(constants: Vec<Constant>) -> ConstantPool {
+impl<'a> ConstantPool<'a> {
+    pub fn new(constants: Vec<Constant<'a>>) -> ConstantPool<'a> {
         ConstantPool {
             constants: constants
         }
     }
 
-    pub fn get_utf8(&self, idx: u16) -> Option<&Vec<u8>> {
-        match self.constants.get(idx as usize) {
-            Some(constant) => match constant {
-                &Constant::Utf8(ref bytes) => Some(bytes),
+    pub fn into_owned(self) -> ConstantPool<'static> {
+        ConstantPool::new(self.constants.into_iter().map(|constant| constant.into_owned()).collect())
+    }
+
+    ///
+    /// Look up the constant at an index. Index 0 and the slots following longs and doubles don't
+    /// hold constants and are rejected along with indices beyond the end of the pool.
+    pub fn get(&self, idx: &ConstantPoolIndex) -> Result<&Constant<'a>, ConstantPoolError> {
+        match self.constants.get(idx.idx) {
+            None | Some(&Constant::Placeholder) => Err(ConstantPoolError::InvalidIndex(idx.idx)),
+            Some(constant) => Ok(constant)
+        }
+    }
+
+    pub fn resolve_utf8(&self, idx: Utf8Index) -> Result<&[u8], ConstantPoolError> {
+        match self.get(&idx.into())? {
+            &Constant::Utf8(ref bytes) => Ok(bytes),
+            _ => Err(ConstantPoolError::UnexpectedConstant { idx: idx.0 as usize, expected: "Utf8" })
+        }
+    }
+
+    /// Resolve a `Class` constant to the index of its name
+    pub fn resolve_class(&self, idx: ClassIndex) -> Result<Utf8Index, ConstantPoolError> {
+        match self.get(&idx.into())? {
+            &Constant::Class(ref name_index) => Ok(Utf8Index::try_from(name_index)?),
+            _ => Err(ConstantPoolError::UnexpectedConstant { idx: idx.0 as usize, expected: "Class" })
+        }
+    }
+
+    /// Resolve a `NameAndType` constant to the indices of its name and descriptor
+    pub fn resolve_name_and_type(&self, idx: NameAndTypeIndex) -> Result<(Utf8Index, Utf8Index), ConstantPoolError> {
+        match self.get(&idx.into())? {
+            &Constant::NameAndType { ref name_index, ref descriptor_index } => Ok((Utf8Index::try_from(name_index)?, Utf8Index::try_from(descriptor_index)?)),
+            _ => Err(ConstantPoolError::UnexpectedConstant { idx: idx.0 as usize, expected: "NameAndType" })
+        }
+    }
+
+    pub fn get_utf8(&self, idx: u16) -> Option<&[u8]> {
+        self.resolve_utf8(Utf8Index(idx)).ok()
+    }
+
+    /// Decode the modified UTF-8 of a `Utf8` constant, failing on strings a Rust string can't hold
+    pub fn get_utf8_string(&self, idx: u16) -> Option<String> {
+        self.get_utf8(idx).and_then(|bytes| mutf8::decode(bytes).ok()).map(|string| string.into_owned())
+    }
+
+    /// Resolve a `Class` constant to the internal name of the class, eg. `java/lang/String`
+    pub fn get_class_name(&self, idx: u16) -> Option<String> {
+        self.resolve_class(ClassIndex(idx)).ok().and_then(|name_index| self.get_utf8_string(name_index.0))
+    }
+
+    /// Resolve a `NameAndType` constant to its name and descriptor
+    pub fn get_name_and_type(&self, idx: u16) -> Option<(String, String)> {
+        match self.resolve_name_and_type(NameAndTypeIndex(idx)) {
+            Ok((name_index, descriptor_index)) => match (self.get_utf8_string(name_index.0), self.get_utf8_string(descriptor_index.0)) {
+                (Some(name), Some(descriptor)) => Some((name, descriptor)),
                 _ => None
             },
             _ => None
         }
     }
 
-    pub fn get_utf8_string(&self, idx: u16) -> Option<String> {
-        match self.get_utf8(idx) {
-            Some(bytes) => match String::from_utf8(bytes.clone()) {
-                Ok(string) => Some(string),
+    /// Resolve a field, method or interface method reference to its class, name and descriptor
+    pub fn get_member_ref(&self, idx: u16) -> Option<(String, String, String)> {
+        match self.get(&ConstantPoolIndex::new(idx as usize)) {
+            Ok(&Constant::FieldRef { ref class_index, ref name_and_type_index }) |
+            Ok(&Constant::MethodRef { ref class_index, ref name_and_type_index }) |
+            Ok(&Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index }) => match (self.get_class_name(class_index.idx as u16), self.get_name_and_type(name_and_type_index.idx as u16)) {
+                (Some(class), Some((name, descriptor))) => Some((class, name, descriptor)),
                 _ => None
             },
             _ => None
@@ -98,10 +182,12 @@ impl ConstantPool {
     }
 
     pub fn find_ut8_index(&self, utf8: &'static str) -> Option<usize> {
+        let encoded = mutf8::encode(utf8);
+
         for i in 0..self.constants.len() {
             match self.constants[i] {
                 Constant::Utf8(ref bytes) => {
-                    if bytes.as_slice() == utf8.as_bytes() {
+                    if &bytes[..] == &encoded[..] {
                         return Some(i);
                     }
                 }
@@ -115,34 +201,162 @@ impl ConstantPool {
         self.find_ut8_index(utf8).unwrap_or(0)
     }
 
-    pub fn resolve_index(&self, idx: &ConstantPoolIndex) -> Option<&Constant> {
-        self.constants.get(idx.idx)
+    pub fn resolve_index(&self, idx: &ConstantPoolIndex) -> Option<&Constant<'a>> {
+        self.get(idx).ok()
     }
 
-    pub fn has_constant(&self, constant: &Constant) -> bool {
-        self.constants.iter().any(|item| *constant == *item)
+    pub fn has_constant(&self, constant: &Constant<'a>) -> bool {
+        self.get_constant_index(constant).is_some()
     }
 
-    pub fn add_constant(&mut self, constant: Constant) -> ConstantPoolIndex {
+    ///
+    /// Append a constant to the pool and return its index. Longs and doubles are followed by the
+    /// placeholder of the slot they claim, and an empty pool gets the placeholder of index 0 first,
+    /// so positions in `constants` are always constant pool indices. Fails without touching the pool
+    /// when the constant doesn't fit in the 65535 indices a classfile can address.
+    pub fn add_constant(&mut self, constant: Constant<'a>) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let idx = cmp::max(self.constants.len(), 1);
+        let size = constant.cp_size();
+
+        if idx + size > MAX_CP_LEN {
+            return Err(ConstantPoolError::PoolFull);
+        }
+
+        if self.constants.is_empty() {
+            self.constants.push(Constant::Placeholder);
+        }
+
         self.constants.push(constant);
-        ConstantPoolIndex::new(self.cp_len())
+
+        for _ in 1..size {
+            self.constants.push(Constant::Placeholder);
+        }
+
+        Ok(ConstantPoolIndex::new(idx))
     }
 
-    pub fn get_constant_index(&self, constant: &Constant) -> Option<ConstantPoolIndex> {
-        if self.has_constant(constant) {
-            Some(ConstantPoolIndex::new(self.constants.iter().take_while(|item| **item != *constant).map(|item| item.cp_size()).fold(1, |acc, x| acc + x)))
-        } else {
-            None
+    /// The index of the first constant equal to the given one
+    pub fn get_constant_index(&self, constant: &Constant<'a>) -> Option<ConstantPoolIndex> {
+        match *constant {
+            Constant::Placeholder => None,
+            _ => self.constants.iter().position(|item| *item == *constant).map(ConstantPoolIndex::new)
         }
     }
 
+    /// The number of indices in the pool including index 0, ie. the `constant_pool_count` of the classfile
     pub fn cp_len(&self) -> usize {
-        //self.constants.iter().fold(0, |acc, x| acc + x.cp_size())
         self.constants.len()
     }
+
+    ///
+    /// Return the index of a constant equal to the given one, adding it to the pool if there's none
+    /// yet. The helpers below build on this so that constants are never duplicated.
+    pub fn ensure(&mut self, constant: Constant<'a>) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        match self.get_constant_index(&constant) {
+            Some(idx) => Ok(idx),
+            None => self.add_constant(constant)
+        }
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Result<Utf8Index, ConstantPoolError> {
+        Utf8Index::try_from(&self.ensure(Constant::Utf8(Cow::Owned(mutf8::encode(value).into_owned())))?)
+    }
+
+    /// A `Class` constant of an internal class name such as `java/lang/System`
+    pub fn class(&mut self, name: &str) -> Result<ClassIndex, ConstantPoolError> {
+        let encoded = mutf8::encode(name);
+        let existing = self.constants.iter().position(|constant| match constant {
+            &Constant::Class(ref name_index) => Utf8Index::try_from(name_index).and_then(|name_index| self.resolve_utf8(name_index)).map(|bytes| bytes == &encoded[..]).unwrap_or(false),
+            _ => false
+        });
+
+        match existing {
+            Some(idx) => ClassIndex::try_from(&ConstantPoolIndex::new(idx)),
+            None => {
+                let name_index = self.utf8(name)?;
+                ClassIndex::try_from(&self.add_constant(Constant::Class(name_index.into()))?)
+            }
+        }
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<NameAndTypeIndex, ConstantPoolError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+
+        NameAndTypeIndex::try_from(&self.ensure(Constant::NameAndType { name_index: name_index.into(), descriptor_index: descriptor_index.into() })?)
+    }
+
+    pub fn field_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let class_index = self.class(owner)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.ensure(Constant::FieldRef { class_index: class_index.into(), name_and_type_index: name_and_type_index.into() })
+    }
+
+    pub fn method_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let class_index = self.class(owner)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.ensure(Constant::MethodRef { class_index: class_index.into(), name_and_type_index: name_and_type_index.into() })
+    }
+
+    pub fn interface_method_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let class_index = self.class(owner)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.ensure(Constant::InterfaceMethodRef { class_index: class_index.into(), name_and_type_index: name_and_type_index.into() })
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let string_index = self.utf8(value)?;
+
+        self.ensure(Constant::String(string_index.into()))
+    }
+
+    pub fn int(&mut self, value: i32) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        self.ensure(Constant::Integer(value as u32))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        self.ensure(Constant::Float(value.to_bits()))
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        self.ensure(Constant::Long(value as u64))
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        self.ensure(Constant::Double(value.to_bits()))
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let descriptor_index = self.utf8(descriptor)?;
+
+        self.ensure(Constant::MethodType(descriptor_index.into()))
+    }
+
+    /// A `MethodHandle` constant referring to a field or method reference added by `field_ref`,
+    /// `method_ref` or `interface_method_ref`
+    pub fn method_handle(&mut self, reference_kind: ReferenceKind, reference_index: ConstantPoolIndex) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        self.ensure(Constant::MethodHandle { reference_kind: reference_kind, reference_index: reference_index })
+    }
+
+    /// An `InvokeDynamic` constant using the bootstrap method at the given index of the `BootstrapMethods` attribute
+    pub fn invoke_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.ensure(Constant::InvokeDynamic { bootstrap_method_attr_index: ConstantPoolIndex::new(bootstrap_method_attr_index as usize), name_and_type_index: name_and_type_index.into() })
+    }
+
+    /// A `Dynamic` constant using the bootstrap method at the given index of the `BootstrapMethods` attribute
+    pub fn dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<ConstantPoolIndex, ConstantPoolError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.ensure(Constant::Dynamic { bootstrap_method_attr_index: ConstantPoolIndex::new(bootstrap_method_attr_index as usize), name_and_type_index: name_and_type_index.into() })
+    }
 }
 
-impl Default for ConstantPool {
+impl<'a> Default for ConstantPool<'a> {
     fn default() -> Self {
         ConstantPool {
             constants: vec![]
@@ -150,7 +364,7 @@ impl Default for ConstantPool {
     }
 }
 
-#[derive(Default, Debug, PartialEq)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
 pub struct ConstantPoolIndex {
     pub idx: usize
 }
@@ -161,9 +375,85 @@ impl ConstantPoolIndex {
     }
 }
 
+/// An index expected to point to a `Utf8` constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Utf8Index(pub u16);
+
+/// An index expected to point to a `Class` constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClassIndex(pub u16);
+
+/// An index expected to point to a `NameAndType` constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NameAndTypeIndex(pub u16);
+
+impl<'a> TryFrom<&'a ConstantPoolIndex> for Utf8Index {
+    type Error = ConstantPoolError;
+
+    fn try_from(idx: &'a ConstantPoolIndex) -> Result<Self, Self::Error> {
+        u16::try_from(idx.idx).map(Utf8Index).map_err(|_| ConstantPoolError::InvalidIndex(idx.idx))
+    }
+}
+
+impl<'a> TryFrom<&'a ConstantPoolIndex> for ClassIndex {
+    type Error = ConstantPoolError;
+
+    fn try_from(idx: &'a ConstantPoolIndex) -> Result<Self, Self::Error> {
+        u16::try_from(idx.idx).map(ClassIndex).map_err(|_| ConstantPoolError::InvalidIndex(idx.idx))
+    }
+}
+
+impl<'a> TryFrom<&'a ConstantPoolIndex> for NameAndTypeIndex {
+    type Error = ConstantPoolError;
+
+    fn try_from(idx: &'a ConstantPoolIndex) -> Result<Self, Self::Error> {
+        u16::try_from(idx.idx).map(NameAndTypeIndex).map_err(|_| ConstantPoolError::InvalidIndex(idx.idx))
+    }
+}
+
+impl From<Utf8Index> for ConstantPoolIndex {
+    fn from(idx: Utf8Index) -> Self {
+        ConstantPoolIndex::new(idx.0 as usize)
+    }
+}
+
+impl From<ClassIndex> for ConstantPoolIndex {
+    fn from(idx: ClassIndex) -> Self {
+        ConstantPoolIndex::new(idx.0 as usize)
+    }
+}
+
+impl From<NameAndTypeIndex> for ConstantPoolIndex {
+    fn from(idx: NameAndTypeIndex) -> Self {
+        ConstantPoolIndex::new(idx.0 as usize)
+    }
+}
+
 #[derive(Debug, PartialEq)]
-pub enum Constant {
-    Utf8(Vec<u8>),
+pub enum ConstantPoolError {
+    /// The index is 0, lies beyond the end of the pool or points to the slot following a long or double
+    InvalidIndex(usize),
+    /// The index points to a constant of another kind than expected
+    UnexpectedConstant { idx: usize, expected: &'static str },
+    /// The constant doesn't fit in the pool, whose indices must stay below 65535
+    PoolFull,
+}
+
+impl fmt::Display for ConstantPoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ConstantPoolError::InvalidIndex(idx) => write!(f, "invalid constant pool index #{}", idx),
+            &ConstantPoolError::UnexpectedConstant { idx, expected } => write!(f, "constant pool index #{} is not a {} constant", idx, expected),
+            &ConstantPoolError::PoolFull => write!(f, "the constant pool is full"),
+        }
+    }
+}
+
+impl Error for ConstantPoolError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Constant<'a> {
+    Utf8(#[serde(with = "::bytecode::json::utf8")] Cow<'a, [u8]>),
     Integer(u32),
     Float(u32),
     Long(u64),
@@ -176,12 +466,18 @@ pub enum Constant {
     NameAndType { name_index: ConstantPoolIndex, descriptor_index: ConstantPoolIndex },
     MethodHandle { reference_kind: ReferenceKind, reference_index: ConstantPoolIndex },
     MethodType(ConstantPoolIndex),
-    InvokeDynamic { bootstrap_method_attr_index: ConstantPoolIndex, name_and_type_index: ConstantPoolIndex },
+    /// `bootstrap_method_attr_index` is an index into the `BootstrapMethods` attribute rather than the constant pool
+    Dynamic { #[serde(serialize_with = "::bytecode::json::serialize_unresolved")] bootstrap_method_attr_index: ConstantPoolIndex, name_and_type_index: ConstantPoolIndex },
+    InvokeDynamic { #[serde(serialize_with = "::bytecode::json::serialize_unresolved")] bootstrap_method_attr_index: ConstantPoolIndex, name_and_type_index: ConstantPoolIndex },
+    Module(ConstantPoolIndex),
+    Package(ConstantPoolIndex),
     Unknown(u8),
     Placeholder,
 }
 
-impl Constant {
+impl<'a> Constant<'a> {
+    /// The number of indices the constant claims. Longs and doubles claim two, the second of which
+    /// is held by the placeholder that follows them, which thus claims none of its own.
     pub fn cp_size(&self) -> usize {
         match self {
             &Constant::Long(_) => 2,
@@ -190,9 +486,33 @@ impl Constant {
             _ => 1
         }
     }
+
+    pub fn into_owned(self) -> Constant<'static> {
+        match self {
+            Constant::Utf8(bytes) => Constant::Utf8(Cow::Owned(bytes.into_owned())),
+            Constant::Integer(value) => Constant::Integer(value),
+            Constant::Float(value) => Constant::Float(value),
+            Constant::Long(value) => Constant::Long(value),
+            Constant::Double(value) => Constant::Double(value),
+            Constant::Class(idx) => Constant::Class(idx),
+            Constant::FieldRef { class_index, name_and_type_index } => Constant::FieldRef { class_index: class_index, name_and_type_index: name_and_type_index },
+            Constant::MethodRef { class_index, name_and_type_index } => Constant::MethodRef { class_index: class_index, name_and_type_index: name_and_type_index },
+            Constant::InterfaceMethodRef { class_index, name_and_type_index } => Constant::InterfaceMethodRef { class_index: class_index, name_and_type_index: name_and_type_index },
+            Constant::String(idx) => Constant::String(idx),
+            Constant::NameAndType { name_index, descriptor_index } => Constant::NameAndType { name_index: name_index, descriptor_index: descriptor_index },
+            Constant::MethodHandle { reference_kind, reference_index } => Constant::MethodHandle { reference_kind: reference_kind, reference_index: reference_index },
+            Constant::MethodType(idx) => Constant::MethodType(idx),
+            Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => Constant::Dynamic { bootstrap_method_attr_index: bootstrap_method_attr_index, name_and_type_index: name_and_type_index },
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => Constant::InvokeDynamic { bootstrap_method_attr_index: bootstrap_method_attr_index, name_and_type_index: name_and_type_index },
+            Constant::Module(idx) => Constant::Module(idx),
+            Constant::Package(idx) => Constant::Package(idx),
+            Constant::Unknown(tag) => Constant::Unknown(tag),
+            Constant::Placeholder => Constant::Placeholder,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ReferenceKind {
     GetField = 1,
     GetStatic = 2,
@@ -238,7 +558,7 @@ impl ReferenceKind {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AccessFlags {
     pub flags: u16
 }
@@ -280,7 +600,9 @@ pub enum ClassAccessFlags {
     // Declared synthetic; not present in the source code.
     Annotation = 0x2000,
     // Declared as an annotation type.
-    Enum = 0x4000, // Declared as an enum type.
+    Enum = 0x4000,
+    // Declared as an enum type.
+    Module = 0x8000, // Is a module, not a class or interface.
 }
 
 pub enum FieldAccessFlags {
@@ -357,26 +679,66 @@ pub enum ParameterAccessFlags {
     Mandated = 0x8000,
 }
 
-#[derive(Default, Debug)]
-pub struct Field {
+pub enum ModuleAccessFlags {
+    Open = 0x0020,
+    //	Indicates that this module is open.
+    Synthetic = 0x1000,
+    //	Indicates that this module was not explicitly or implicitly declared.
+    Mandated = 0x8000, //	Indicates that this module was implicitly declared.
+}
+
+pub enum RequiresAccessFlags {
+    Transitive = 0x0020,
+    //	Indicates that any module which depends on the current module, implicitly declares a dependence on the module indicated by this entry.
+    StaticPhase = 0x0040,
+    //	Indicates that this dependence is mandatory in the static phase, i.e., at compile time, but is optional in the dynamic phase, i.e., at run time.
+    Synthetic = 0x1000,
+    //	Indicates that this dependence was not explicitly or implicitly declared in the source of the module declaration.
+    Mandated = 0x8000, //	Indicates that this dependence was implicitly declared in the source of the module declaration.
+}
+
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Field<'a> {
     pub access_flags: AccessFlags,
     pub name_index: ConstantPoolIndex,
     pub descriptor_index: ConstantPoolIndex,
-    pub attributes: Vec<Attribute>,
+    pub attributes: Vec<Attribute<'a>>,
 }
 
-#[derive(Default, Debug)]
-pub struct Method {
+impl<'a> Field<'a> {
+    pub fn into_owned(self) -> Field<'static> {
+        Field {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: Attribute::all_into_owned(self.attributes),
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Method<'a> {
     pub access_flags: AccessFlags,
     pub name_index: ConstantPoolIndex,
     pub descriptor_index: ConstantPoolIndex,
-    pub attributes: Vec<Attribute>,
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> Method<'a> {
+    pub fn into_owned(self) -> Method<'static> {
+        Method {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: Attribute::all_into_owned(self.attributes),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub enum Attribute {
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Attribute<'a> {
     ConstantValue(ConstantPoolIndex),
-    Code { max_stack: u16, max_locals: u16, code: Vec<Instruction>, exception_table: Vec<ExceptionHandler>, attributes: Vec<Attribute> },
+    Code { max_stack: u16, max_locals: u16, code: Vec<Instruction>, exception_table: Vec<ExceptionHandler>, attributes: Vec<Attribute<'a>> },
     StackMapTable(Vec<StackMapFrame>),
     Exceptions(Vec<ConstantPoolIndex>),
     InnerClasses(Vec<InnerClass>),
@@ -398,10 +760,112 @@ pub enum Attribute {
     AnnotationDefault(ElementValue),
     BootstrapMethods(Vec<BootstrapMethod>),
     MethodParameters(Vec<MethodParameter>),
-    RawAttribute { name_index: ConstantPoolIndex, info: Vec<u8> },
+    Module { name_index: ConstantPoolIndex, flags: AccessFlags, version_index: ConstantPoolIndex, requires: Vec<ModuleRequires>, exports: Vec<ModuleExports>, opens: Vec<ModuleOpens>, uses: Vec<ConstantPoolIndex>, provides: Vec<ModuleProvides> },
+    ModulePackages(Vec<ConstantPoolIndex>),
+    ModuleMainClass(ConstantPoolIndex),
+    NestHost(ConstantPoolIndex),
+    NestMembers(Vec<ConstantPoolIndex>),
+    Record(Vec<RecordComponent<'a>>),
+    PermittedSubclasses(Vec<ConstantPoolIndex>),
+    RawAttribute { name_index: ConstantPoolIndex, info: Cow<'a, [u8]> },
 }
 
-#[derive(Debug)]
+impl<'a> Attribute<'a> {
+    pub fn into_owned(self) -> Attribute<'static> {
+        match self {
+            Attribute::ConstantValue(idx) => Attribute::ConstantValue(idx),
+            Attribute::Code { max_stack, max_locals, code, exception_table, attributes } => Attribute::Code {
+                max_stack: max_stack,
+                max_locals: max_locals,
+                code: code,
+                exception_table: exception_table,
+                attributes: Attribute::all_into_owned(attributes),
+            },
+            Attribute::StackMapTable(table) => Attribute::StackMapTable(table),
+            Attribute::Exceptions(table) => Attribute::Exceptions(table),
+            Attribute::InnerClasses(table) => Attribute::InnerClasses(table),
+            Attribute::EnclosingMethod { class_index, method_index } => Attribute::EnclosingMethod { class_index: class_index, method_index: method_index },
+            Attribute::Synthetic => Attribute::Synthetic,
+            Attribute::Signature(idx) => Attribute::Signature(idx),
+            Attribute::SourceFile(idx) => Attribute::SourceFile(idx),
+            Attribute::SourceDebugExtension(value) => Attribute::SourceDebugExtension(value),
+            Attribute::LineNumberTable(table) => Attribute::LineNumberTable(table),
+            Attribute::LocalVariableTable(table) => Attribute::LocalVariableTable(table),
+            Attribute::LocalVariableTypeTable(table) => Attribute::LocalVariableTypeTable(table),
+            Attribute::Deprecated => Attribute::Deprecated,
+            Attribute::RuntimeVisibleAnnotations(table) => Attribute::RuntimeVisibleAnnotations(table),
+            Attribute::RuntimeInvisibleAnnotations(table) => Attribute::RuntimeInvisibleAnnotations(table),
+            Attribute::RuntimeVisibleParameterAnnotations(table) => Attribute::RuntimeVisibleParameterAnnotations(table),
+            Attribute::RuntimeInvisibleParameterAnnotations(table) => Attribute::RuntimeInvisibleParameterAnnotations(table),
+            Attribute::RuntimeVisibleTypeAnnotations(table) => Attribute::RuntimeVisibleTypeAnnotations(table),
+            Attribute::RuntimeInvisibleTypeAnnotations(table) => Attribute::RuntimeInvisibleTypeAnnotations(table),
+            Attribute::AnnotationDefault(value) => Attribute::AnnotationDefault(value),
+            Attribute::BootstrapMethods(table) => Attribute::BootstrapMethods(table),
+            Attribute::MethodParameters(table) => Attribute::MethodParameters(table),
+            Attribute::Module { name_index, flags, version_index, requires, exports, opens, uses, provides } => Attribute::Module {
+                name_index: name_index,
+                flags: flags,
+                version_index: version_index,
+                requires: requires,
+                exports: exports,
+                opens: opens,
+                uses: uses,
+                provides: provides,
+            },
+            Attribute::ModulePackages(table) => Attribute::ModulePackages(table),
+            Attribute::ModuleMainClass(idx) => Attribute::ModuleMainClass(idx),
+            Attribute::NestHost(idx) => Attribute::NestHost(idx),
+            Attribute::NestMembers(table) => Attribute::NestMembers(table),
+            Attribute::Record(components) => Attribute::Record(components.into_iter().map(|component| component.into_owned()).collect()),
+            Attribute::PermittedSubclasses(table) => Attribute::PermittedSubclasses(table),
+            Attribute::RawAttribute { name_index, info } => Attribute::RawAttribute { name_index: name_index, info: Cow::Owned(info.into_owned()) },
+        }
+    }
+
+    /// The name the attribute is written with, or `None` for raw attributes, which refer to their
+    /// name by index
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            &Attribute::ConstantValue(_) => Some("ConstantValue"),
+            &Attribute::Code { .. } => Some("Code"),
+            &Attribute::StackMapTable(_) => Some("StackMapTable"),
+            &Attribute::Exceptions(_) => Some("Exceptions"),
+            &Attribute::InnerClasses(_) => Some("InnerClasses"),
+            &Attribute::EnclosingMethod { .. } => Some("EnclosingMethod"),
+            &Attribute::Synthetic => Some("Synthetic"),
+            &Attribute::Signature(_) => Some("Signature"),
+            &Attribute::SourceFile(_) => Some("SourceFile"),
+            &Attribute::SourceDebugExtension(_) => Some("SourceDebugExtension"),
+            &Attribute::LineNumberTable(_) => Some("LineNumberTable"),
+            &Attribute::LocalVariableTable(_) => Some("LocalVariableTable"),
+            &Attribute::LocalVariableTypeTable(_) => Some("LocalVariableTypeTable"),
+            &Attribute::Deprecated => Some("Deprecated"),
+            &Attribute::RuntimeVisibleAnnotations(_) => Some("RuntimeVisibleAnnotations"),
+            &Attribute::RuntimeInvisibleAnnotations(_) => Some("RuntimeInvisibleAnnotations"),
+            &Attribute::RuntimeVisibleParameterAnnotations(_) => Some("RuntimeVisibleParameterAnnotations"),
+            &Attribute::RuntimeInvisibleParameterAnnotations(_) => Some("RuntimeInvisibleParameterAnnotations"),
+            &Attribute::RuntimeVisibleTypeAnnotations(_) => Some("RuntimeVisibleTypeAnnotations"),
+            &Attribute::RuntimeInvisibleTypeAnnotations(_) => Some("RuntimeInvisibleTypeAnnotations"),
+            &Attribute::AnnotationDefault(_) => Some("AnnotationDefault"),
+            &Attribute::BootstrapMethods(_) => Some("BootstrapMethods"),
+            &Attribute::MethodParameters(_) => Some("MethodParameters"),
+            &Attribute::Module { .. } => Some("Module"),
+            &Attribute::ModulePackages(_) => Some("ModulePackages"),
+            &Attribute::ModuleMainClass(_) => Some("ModuleMainClass"),
+            &Attribute::NestHost(_) => Some("NestHost"),
+            &Attribute::NestMembers(_) => Some("NestMembers"),
+            &Attribute::Record(_) => Some("Record"),
+            &Attribute::PermittedSubclasses(_) => Some("PermittedSubclasses"),
+            &Attribute::RawAttribute { .. } => None,
+        }
+    }
+
+    pub fn all_into_owned(attributes: Vec<Attribute<'a>>) -> Vec<Attribute<'static>> {
+        attributes.into_iter().map(|attribute| attribute.into_owned()).collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum StackMapFrame {
     SameFrame { tag: u8 },
     SameLocals1StackItemFrame { tag: u8, stack: VerificationType },
@@ -428,7 +892,7 @@ impl StackMapFrame {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum VerificationType {
     Top,
     Integer,
@@ -451,7 +915,7 @@ impl VerificationType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ExceptionHandler {
     pub start_pc: u16,
     pub end_pc: u16,
@@ -459,7 +923,7 @@ pub struct ExceptionHandler {
     pub catch_type: ConstantPoolIndex,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct InnerClass {
     pub inner_class_info_index: ConstantPoolIndex,
     pub outer_class_info_index: ConstantPoolIndex,
@@ -467,13 +931,13 @@ pub struct InnerClass {
     pub access_flags: AccessFlags,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LineNumberTable {
     pub start_pc: u16,
     pub line_number: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LocalVariableTable {
     pub start_pc: u16,
     pub length: u16,
@@ -482,7 +946,7 @@ pub struct LocalVariableTable {
     pub index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LocalVariableTypeTable {
     pub start_pc: u16,
     pub length: u16,
@@ -491,7 +955,7 @@ pub struct LocalVariableTypeTable {
     pub index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Annotation {
     pub type_index: ConstantPoolIndex,
     pub element_value_pairs: Vec<ElementValuePair>,
@@ -504,7 +968,7 @@ impl Annotation {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ElementValuePair {
     pub element_name_index: ConstantPoolIndex,
     pub value: ElementValue,
@@ -516,7 +980,7 @@ impl ElementValuePair {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum ElementValue {
     ConstantValue(u8, ConstantPoolIndex),
     Enum { type_name_index: ConstantPoolIndex, const_name_index: ConstantPoolIndex },
@@ -537,7 +1001,7 @@ impl ElementValue {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct TypeAnnotation {
     pub target_info: TargetInfo,
     pub target_path: TypePath,
@@ -551,7 +1015,7 @@ impl TypeAnnotation {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum TargetInfo {
     TypeParameter { subtype: u8, idx: u8 },
     SuperType { idx: u16 },
@@ -597,7 +1061,7 @@ impl TargetInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct TypePath {
     pub path: Vec<(TypePathKind, u8)>
 }
@@ -608,7 +1072,7 @@ impl TypePath {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum TypePathKind {
     Array,
     // Annotation is deeper in an array type
@@ -630,7 +1094,7 @@ impl TypePathKind {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct BootstrapMethod {
     pub bootstrap_method_ref: ConstantPoolIndex,
     pub bootstrap_arguments: Vec<ConstantPoolIndex>,
@@ -638,7 +1102,7 @@ pub struct BootstrapMethod {
 
 impl BootstrapMethod {}
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct MethodParameter {
     pub name_index: ConstantPoolIndex,
     pub access_flags: AccessFlags,
@@ -648,8 +1112,74 @@ impl MethodParameter {
     pub fn len(&self) -> usize { 4 }
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModuleRequires {
+    pub requires_index: ConstantPoolIndex,
+    pub requires_flags: AccessFlags,
+    pub requires_version_index: ConstantPoolIndex,
+}
+
+impl ModuleRequires {
+    pub fn len(&self) -> usize { 6 }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModuleExports {
+    pub exports_index: ConstantPoolIndex,
+    pub exports_flags: AccessFlags,
+    pub exports_to: Vec<ConstantPoolIndex>,
+}
+
+impl ModuleExports {
+    pub fn len(&self) -> usize {
+        6 + self.exports_to.len() * 2
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModuleOpens {
+    pub opens_index: ConstantPoolIndex,
+    pub opens_flags: AccessFlags,
+    pub opens_to: Vec<ConstantPoolIndex>,
+}
+
+impl ModuleOpens {
+    pub fn len(&self) -> usize {
+        6 + self.opens_to.len() * 2
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModuleProvides {
+    pub provides_index: ConstantPoolIndex,
+    pub provides_with: Vec<ConstantPoolIndex>,
+}
+
+impl ModuleProvides {
+    pub fn len(&self) -> usize {
+        4 + self.provides_with.len() * 2
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordComponent<'a> {
+    pub name_index: ConstantPoolIndex,
+    pub descriptor_index: ConstantPoolIndex,
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> RecordComponent<'a> {
+    pub fn into_owned(self) -> RecordComponent<'static> {
+        RecordComponent {
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: Attribute::all_into_owned(self.attributes),
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Instruction {
     AALOAD,
     AASTORE,
@@ -869,6 +1399,8 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// The encoded size of the instruction in bytes, not counting the alignment padding of
+    /// `TABLESWITCH` and `LOOKUPSWITCH`, which depends on where the instruction is placed
     pub fn len(&self) -> usize {
         match self {
             &Instruction::ALOAD(_) => 2,
@@ -915,7 +1447,7 @@ impl Instruction {
             &Instruction::LDC_W(_) => 3,
             &Instruction::LDC2_W(_) => 3,
             &Instruction::LLOAD(_) => 2,
-            &Instruction::LOOKUPSWITCH(_, ref pairs) => { 5 + pairs.len() * 4 }
+            &Instruction::LOOKUPSWITCH(_, ref pairs) => { 9 + pairs.len() * 8 }
             &Instruction::LSTORE(_) => 2,
             &Instruction::MULTIANEWARRAY(_, _) => 4,
             &Instruction::NEW(_) => 3,
@@ -925,20 +1457,250 @@ impl Instruction {
             &Instruction::RET(_) => 2,
             &Instruction::SIPUSH(_) => 3,
             &Instruction::TABLESWITCH(_, _, _, ref indices) => { 13 + (indices.len() * 4) }
-            &Instruction::IINC_W(_, _) => 9,
-            &Instruction::ILOAD_W(_) => 5,
-            &Instruction::FLOAD_W(_) => 5,
-            &Instruction::ALOAD_W(_) => 5,
-            &Instruction::LLOAD_W(_) => 5,
-            &Instruction::DLOAD_W(_) => 5,
-            &Instruction::ISTORE_W(_) => 5,
-            &Instruction::FSTORE_W(_) => 5,
-            &Instruction::ASTORE_W(_) => 5,
-            &Instruction::LSTORE_W(_) => 5,
-            &Instruction::DSTORE_W(_) => 5,
-            &Instruction::RET_W(_) => 5,
+            &Instruction::IINC_W(_, _) => 6,
+            &Instruction::ILOAD_W(_) => 4,
+            &Instruction::FLOAD_W(_) => 4,
+            &Instruction::ALOAD_W(_) => 4,
+            &Instruction::LLOAD_W(_) => 4,
+            &Instruction::DLOAD_W(_) => 4,
+            &Instruction::ISTORE_W(_) => 4,
+            &Instruction::FSTORE_W(_) => 4,
+            &Instruction::ASTORE_W(_) => 4,
+            &Instruction::LSTORE_W(_) => 4,
+            &Instruction::DSTORE_W(_) => 4,
+            &Instruction::RET_W(_) => 4,
             &Instruction::PADDED_INSTRUCTION(padding) => padding,
             _ => 1
         }
     }
+
+    /// The encoded size of the instruction in bytes when it starts at `offset` within the code
+    pub fn len_at(&self, offset: usize) -> usize {
+        match self {
+            &Instruction::LOOKUPSWITCH(_, _) | &Instruction::TABLESWITCH(_, _, _, _) => self.len() + (4 - ((offset + 1) % 4)) % 4,
+            _ => self.len()
+        }
+    }
+
+    /// The name of the instruction as written in assembly and by `javap`. The wide forms of
+    /// local variable instructions are named like `iload_w`, as `javap` does.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            &Instruction::AALOAD => "aaload",
+            &Instruction::AASTORE => "aastore",
+            &Instruction::ACONST_NULL => "aconst_null",
+            &Instruction::ALOAD(_) => "aload",
+            &Instruction::ALOAD_0 => "aload_0",
+            &Instruction::ALOAD_1 => "aload_1",
+            &Instruction::ALOAD_2 => "aload_2",
+            &Instruction::ALOAD_3 => "aload_3",
+            &Instruction::ANEWARRAY(_) => "anewarray",
+            &Instruction::ARETURN => "areturn",
+            &Instruction::ARRAYLENGTH => "arraylength",
+            &Instruction::ASTORE(_) => "astore",
+            &Instruction::ASTORE_0 => "astore_0",
+            &Instruction::ASTORE_1 => "astore_1",
+            &Instruction::ASTORE_2 => "astore_2",
+            &Instruction::ASTORE_3 => "astore_3",
+            &Instruction::ATHROW => "athrow",
+            &Instruction::BALOAD => "baload",
+            &Instruction::BASTORE => "bastore",
+            &Instruction::BIPUSH(_) => "bipush",
+            &Instruction::CALOAD => "caload",
+            &Instruction::CASTORE => "castore",
+            &Instruction::CHECKCAST(_) => "checkcast",
+            &Instruction::D2F => "d2f",
+            &Instruction::D2I => "d2i",
+            &Instruction::D2L => "d2l",
+            &Instruction::DADD => "dadd",
+            &Instruction::DALOAD => "daload",
+            &Instruction::DASTORE => "dastore",
+            &Instruction::DCMPL => "dcmpl",
+            &Instruction::DCMPG => "dcmpg",
+            &Instruction::DCONST_0 => "dconst_0",
+            &Instruction::DCONST_1 => "dconst_1",
+            &Instruction::DDIV => "ddiv",
+            &Instruction::DLOAD(_) => "dload",
+            &Instruction::DLOAD_0 => "dload_0",
+            &Instruction::DLOAD_1 => "dload_1",
+            &Instruction::DLOAD_2 => "dload_2",
+            &Instruction::DLOAD_3 => "dload_3",
+            &Instruction::DMUL => "dmul",
+            &Instruction::DNEG => "dneg",
+            &Instruction::DREM => "drem",
+            &Instruction::DRETURN => "dreturn",
+            &Instruction::DSTORE(_) => "dstore",
+            &Instruction::DSTORE_0 => "dstore_0",
+            &Instruction::DSTORE_1 => "dstore_1",
+            &Instruction::DSTORE_2 => "dstore_2",
+            &Instruction::DSTORE_3 => "dstore_3",
+            &Instruction::DSUB => "dsub",
+            &Instruction::DUP => "dup",
+            &Instruction::DUP_X1 => "dup_x1",
+            &Instruction::DUP_X2 => "dup_x2",
+            &Instruction::DUP2 => "dup2",
+            &Instruction::DUP2_X1 => "dup2_x1",
+            &Instruction::DUP2_X2 => "dup2_x2",
+            &Instruction::F2D => "f2d",
+            &Instruction::F2I => "f2i",
+            &Instruction::F2L => "f2l",
+            &Instruction::FADD => "fadd",
+            &Instruction::FALOAD => "faload",
+            &Instruction::FASTORE => "fastore",
+            &Instruction::FCMPL => "fcmpl",
+            &Instruction::FCMPG => "fcmpg",
+            &Instruction::FCONST_0 => "fconst_0",
+            &Instruction::FCONST_1 => "fconst_1",
+            &Instruction::FCONST_2 => "fconst_2",
+            &Instruction::FDIV => "fdiv",
+            &Instruction::FLOAD(_) => "fload",
+            &Instruction::FLOAD_0 => "fload_0",
+            &Instruction::FLOAD_1 => "fload_1",
+            &Instruction::FLOAD_2 => "fload_2",
+            &Instruction::FLOAD_3 => "fload_3",
+            &Instruction::FMUL => "fmul",
+            &Instruction::FNEG => "fneg",
+            &Instruction::FREM => "frem",
+            &Instruction::FRETURN => "freturn",
+            &Instruction::FSTORE(_) => "fstore",
+            &Instruction::FSTORE_0 => "fstore_0",
+            &Instruction::FSTORE_1 => "fstore_1",
+            &Instruction::FSTORE_2 => "fstore_2",
+            &Instruction::FSTORE_3 => "fstore_3",
+            &Instruction::FSUB => "fsub",
+            &Instruction::GETFIELD(_) => "getfield",
+            &Instruction::GETSTATIC(_) => "getstatic",
+            &Instruction::GOTO(_) => "goto",
+            &Instruction::GOTO_W(_) => "goto_w",
+            &Instruction::I2B => "i2b",
+            &Instruction::I2C => "i2c",
+            &Instruction::I2D => "i2d",
+            &Instruction::I2F => "i2f",
+            &Instruction::I2L => "i2l",
+            &Instruction::I2S => "i2s",
+            &Instruction::IADD => "iadd",
+            &Instruction::IALOAD => "iaload",
+            &Instruction::IAND => "iand",
+            &Instruction::IASTORE => "iastore",
+            &Instruction::ICONST_M1 => "iconst_m1",
+            &Instruction::ICONST_0 => "iconst_0",
+            &Instruction::ICONST_1 => "iconst_1",
+            &Instruction::ICONST_2 => "iconst_2",
+            &Instruction::ICONST_3 => "iconst_3",
+            &Instruction::ICONST_4 => "iconst_4",
+            &Instruction::ICONST_5 => "iconst_5",
+            &Instruction::IDIV => "idiv",
+            &Instruction::IF_ACMPEQ(_) => "if_acmpeq",
+            &Instruction::IF_ACMPNE(_) => "if_acmpne",
+            &Instruction::IF_ICMPEQ(_) => "if_icmpeq",
+            &Instruction::IF_ICMPNE(_) => "if_icmpne",
+            &Instruction::IF_ICMPLT(_) => "if_icmplt",
+            &Instruction::IF_ICMPGE(_) => "if_icmpge",
+            &Instruction::IF_ICMPGT(_) => "if_icmpgt",
+            &Instruction::IF_ICMPLE(_) => "if_icmple",
+            &Instruction::IFEQ(_) => "ifeq",
+            &Instruction::IFNE(_) => "ifne",
+            &Instruction::IFLT(_) => "iflt",
+            &Instruction::IFGE(_) => "ifge",
+            &Instruction::IFGT(_) => "ifgt",
+            &Instruction::IFLE(_) => "ifle",
+            &Instruction::IFNONNULL(_) => "ifnonnull",
+            &Instruction::IFNULL(_) => "ifnull",
+            &Instruction::IINC(_, _) => "iinc",
+            &Instruction::ILOAD(_) => "iload",
+            &Instruction::ILOAD_0 => "iload_0",
+            &Instruction::ILOAD_1 => "iload_1",
+            &Instruction::ILOAD_2 => "iload_2",
+            &Instruction::ILOAD_3 => "iload_3",
+            &Instruction::IMUL => "imul",
+            &Instruction::INEG => "ineg",
+            &Instruction::INSTANCEOF(_) => "instanceof",
+            &Instruction::INVOKEDYNAMIC(_) => "invokedynamic",
+            &Instruction::INVOKEINTERFACE(_, _) => "invokeinterface",
+            &Instruction::INVOKESPECIAL(_) => "invokespecial",
+            &Instruction::INVOKESTATIC(_) => "invokestatic",
+            &Instruction::INVOKEVIRTUAL(_) => "invokevirtual",
+            &Instruction::IOR => "ior",
+            &Instruction::IREM => "irem",
+            &Instruction::IRETURN => "ireturn",
+            &Instruction::ISHL => "ishl",
+            &Instruction::ISHR => "ishr",
+            &Instruction::ISTORE(_) => "istore",
+            &Instruction::ISTORE_0 => "istore_0",
+            &Instruction::ISTORE_1 => "istore_1",
+            &Instruction::ISTORE_2 => "istore_2",
+            &Instruction::ISTORE_3 => "istore_3",
+            &Instruction::ISUB => "isub",
+            &Instruction::IUSHR => "iushr",
+            &Instruction::IXOR => "ixor",
+            &Instruction::JSR(_) => "jsr",
+            &Instruction::JSR_W(_) => "jsr_w",
+            &Instruction::L2D => "l2d",
+            &Instruction::L2F => "l2f",
+            &Instruction::L2I => "l2i",
+            &Instruction::LADD => "ladd",
+            &Instruction::LALOAD => "laload",
+            &Instruction::LAND => "land",
+            &Instruction::LASTORE => "lastore",
+            &Instruction::LCMP => "lcmp",
+            &Instruction::LCONST_0 => "lconst_0",
+            &Instruction::LCONST_1 => "lconst_1",
+            &Instruction::LDC(_) => "ldc",
+            &Instruction::LDC_W(_) => "ldc_w",
+            &Instruction::LDC2_W(_) => "ldc2_w",
+            &Instruction::LDIV => "ldiv",
+            &Instruction::LLOAD(_) => "lload",
+            &Instruction::LLOAD_0 => "lload_0",
+            &Instruction::LLOAD_1 => "lload_1",
+            &Instruction::LLOAD_2 => "lload_2",
+            &Instruction::LLOAD_3 => "lload_3",
+            &Instruction::LMUL => "lmul",
+            &Instruction::LNEG => "lneg",
+            &Instruction::LOOKUPSWITCH(_, _) => "lookupswitch",
+            &Instruction::LOR => "lor",
+            &Instruction::LREM => "lrem",
+            &Instruction::LRETURN => "lreturn",
+            &Instruction::LSHL => "lshl",
+            &Instruction::LSHR => "lshr",
+            &Instruction::LSTORE(_) => "lstore",
+            &Instruction::LSTORE_0 => "lstore_0",
+            &Instruction::LSTORE_1 => "lstore_1",
+            &Instruction::LSTORE_2 => "lstore_2",
+            &Instruction::LSTORE_3 => "lstore_3",
+            &Instruction::LSUB => "lsub",
+            &Instruction::LUSHR => "lushr",
+            &Instruction::LXOR => "lxor",
+            &Instruction::MONITORENTER => "monitorenter",
+            &Instruction::MONITOREXIT => "monitorexit",
+            &Instruction::MULTIANEWARRAY(_, _) => "multianewarray",
+            &Instruction::NEW(_) => "new",
+            &Instruction::NEWARRAY(_) => "newarray",
+            &Instruction::NOP => "nop",
+            &Instruction::POP => "pop",
+            &Instruction::POP2 => "pop2",
+            &Instruction::PUTFIELD(_) => "putfield",
+            &Instruction::PUTSTATIC(_) => "putstatic",
+            &Instruction::RET(_) => "ret",
+            &Instruction::RETURN => "return",
+            &Instruction::SALOAD => "saload",
+            &Instruction::SASTORE => "sastore",
+            &Instruction::SIPUSH(_) => "sipush",
+            &Instruction::SWAP => "swap",
+            &Instruction::TABLESWITCH(_, _, _, _) => "tableswitch",
+            &Instruction::IINC_W(_, _) => "iinc_w",
+            &Instruction::ILOAD_W(_) => "iload_w",
+            &Instruction::FLOAD_W(_) => "fload_w",
+            &Instruction::ALOAD_W(_) => "aload_w",
+            &Instruction::LLOAD_W(_) => "lload_w",
+            &Instruction::DLOAD_W(_) => "dload_w",
+            &Instruction::ISTORE_W(_) => "istore_w",
+            &Instruction::FSTORE_W(_) => "fstore_w",
+            &Instruction::ASTORE_W(_) => "astore_w",
+            &Instruction::LSTORE_W(_) => "lstore_w",
+            &Instruction::DSTORE_W(_) => "dstore_w",
+            &Instruction::RET_W(_) => "ret_w",
+            &Instruction::PADDED_INSTRUCTION(_) => "padded_instruction",
+            &Instruction::WTF(_) => "wtf",
+        }
+    }
 }
@@ -0,0 +1,552 @@
+use std::collections::{ BTreeSet, HashMap };
+use std::error::Error;
+use std::fmt;
+use super::classfile::*;
+
+///
+/// A position in the code of a method. Branches, switches, exception handlers and debug tables
+/// refer to labels instead of byte offsets, so instructions can be inserted or removed without
+/// breaking them. Labels only get an offset again when the code is lowered into an `Attribute::Code`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub u32);
+
+/// The branch instructions that jump to a single target
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    IFEQ,
+    IFNE,
+    IFLT,
+    IFGE,
+    IFGT,
+    IFLE,
+    IF_ICMPEQ,
+    IF_ICMPNE,
+    IF_ICMPLT,
+    IF_ICMPGE,
+    IF_ICMPGT,
+    IF_ICMPLE,
+    IF_ACMPEQ,
+    IF_ACMPNE,
+    IFNULL,
+    IFNONNULL,
+    /// Lowered to `GOTO_W` if the target is out of reach of a 16 bit offset
+    GOTO,
+    /// Always lowered to `GOTO_W`, regardless of the distance to the target
+    GOTO_W,
+    /// Lowered to `JSR_W` if the target is out of reach of a 16 bit offset
+    JSR,
+    /// Always lowered to `JSR_W`, regardless of the distance to the target
+    JSR_W,
+}
+
+impl Branch {
+    /// The branch taken exactly when this one isn't, or `None` for the unconditional branches
+    pub fn negate(&self) -> Option<Branch> {
+        match *self {
+            Branch::IFEQ => Some(Branch::IFNE),
+            Branch::IFNE => Some(Branch::IFEQ),
+            Branch::IFLT => Some(Branch::IFGE),
+            Branch::IFGE => Some(Branch::IFLT),
+            Branch::IFGT => Some(Branch::IFLE),
+            Branch::IFLE => Some(Branch::IFGT),
+            Branch::IF_ICMPEQ => Some(Branch::IF_ICMPNE),
+            Branch::IF_ICMPNE => Some(Branch::IF_ICMPEQ),
+            Branch::IF_ICMPLT => Some(Branch::IF_ICMPGE),
+            Branch::IF_ICMPGE => Some(Branch::IF_ICMPLT),
+            Branch::IF_ICMPGT => Some(Branch::IF_ICMPLE),
+            Branch::IF_ICMPLE => Some(Branch::IF_ICMPGT),
+            Branch::IF_ACMPEQ => Some(Branch::IF_ACMPNE),
+            Branch::IF_ACMPNE => Some(Branch::IF_ACMPEQ),
+            Branch::IFNULL => Some(Branch::IFNONNULL),
+            Branch::IFNONNULL => Some(Branch::IFNULL),
+            Branch::GOTO | Branch::GOTO_W | Branch::JSR | Branch::JSR_W => None
+        }
+    }
+
+    /// Split a branch instruction into its kind and its relative offset, or `None` if the
+    /// instruction isn't a single-target branch
+    pub fn from_instruction(instruction: &Instruction) -> Option<(Branch, i32)> {
+        match instruction {
+            &Instruction::IFEQ(offset) => Some((Branch::IFEQ, offset as i32)),
+            &Instruction::IFNE(offset) => Some((Branch::IFNE, offset as i32)),
+            &Instruction::IFLT(offset) => Some((Branch::IFLT, offset as i32)),
+            &Instruction::IFGE(offset) => Some((Branch::IFGE, offset as i32)),
+            &Instruction::IFGT(offset) => Some((Branch::IFGT, offset as i32)),
+            &Instruction::IFLE(offset) => Some((Branch::IFLE, offset as i32)),
+            &Instruction::IF_ICMPEQ(offset) => Some((Branch::IF_ICMPEQ, offset as i32)),
+            &Instruction::IF_ICMPNE(offset) => Some((Branch::IF_ICMPNE, offset as i32)),
+            &Instruction::IF_ICMPLT(offset) => Some((Branch::IF_ICMPLT, offset as i32)),
+            &Instruction::IF_ICMPGE(offset) => Some((Branch::IF_ICMPGE, offset as i32)),
+            &Instruction::IF_ICMPGT(offset) => Some((Branch::IF_ICMPGT, offset as i32)),
+            &Instruction::IF_ICMPLE(offset) => Some((Branch::IF_ICMPLE, offset as i32)),
+            &Instruction::IF_ACMPEQ(offset) => Some((Branch::IF_ACMPEQ, offset as i32)),
+            &Instruction::IF_ACMPNE(offset) => Some((Branch::IF_ACMPNE, offset as i32)),
+            &Instruction::IFNULL(offset) => Some((Branch::IFNULL, offset as i32)),
+            &Instruction::IFNONNULL(offset) => Some((Branch::IFNONNULL, offset as i32)),
+            &Instruction::GOTO(offset) => Some((Branch::GOTO, offset as i32)),
+            &Instruction::GOTO_W(offset) => Some((Branch::GOTO_W, offset)),
+            &Instruction::JSR(offset) => Some((Branch::JSR, offset as i32)),
+            &Instruction::JSR_W(offset) => Some((Branch::JSR_W, offset)),
+            _ => None
+        }
+    }
+
+    /// Build the branch instruction jumping `offset` bytes. Offsets that don't fit the instruction
+    /// are truncated, callers are expected to have picked the right form already.
+    pub fn to_instruction(&self, offset: i32) -> Instruction {
+        match *self {
+            Branch::IFEQ => Instruction::IFEQ(offset as i16),
+            Branch::IFNE => Instruction::IFNE(offset as i16),
+            Branch::IFLT => Instruction::IFLT(offset as i16),
+            Branch::IFGE => Instruction::IFGE(offset as i16),
+            Branch::IFGT => Instruction::IFGT(offset as i16),
+            Branch::IFLE => Instruction::IFLE(offset as i16),
+            Branch::IF_ICMPEQ => Instruction::IF_ICMPEQ(offset as i16),
+            Branch::IF_ICMPNE => Instruction::IF_ICMPNE(offset as i16),
+            Branch::IF_ICMPLT => Instruction::IF_ICMPLT(offset as i16),
+            Branch::IF_ICMPGE => Instruction::IF_ICMPGE(offset as i16),
+            Branch::IF_ICMPGT => Instruction::IF_ICMPGT(offset as i16),
+            Branch::IF_ICMPLE => Instruction::IF_ICMPLE(offset as i16),
+            Branch::IF_ACMPEQ => Instruction::IF_ACMPEQ(offset as i16),
+            Branch::IF_ACMPNE => Instruction::IF_ACMPNE(offset as i16),
+            Branch::IFNULL => Instruction::IFNULL(offset as i16),
+            Branch::IFNONNULL => Instruction::IFNONNULL(offset as i16),
+            Branch::GOTO => Instruction::GOTO(offset as i16),
+            Branch::GOTO_W => Instruction::GOTO_W(offset),
+            Branch::JSR => Instruction::JSR(offset as i16),
+            Branch::JSR_W => Instruction::JSR_W(offset),
+        }
+    }
+
+    /// The encoded size of the branch, given whether its target is out of reach of a 16 bit offset.
+    /// Conditional branches that can't reach are lowered to the negated branch skipping over a `GOTO_W`.
+    fn len(&self, far: bool) -> usize {
+        match *self {
+            Branch::GOTO_W | Branch::JSR_W => 5,
+            Branch::GOTO | Branch::JSR if far => 5,
+            _ if far => 8,
+            _ => 3
+        }
+    }
+}
+
+/// A single element of labeled code
+#[derive(Debug, PartialEq)]
+pub enum CodeItem {
+    /// Marks the position of the item that follows it
+    Label(Label),
+    /// Any instruction that doesn't refer to other instructions. Branches and switches must be
+    /// expressed by the other variants, as their raw offsets aren't adjusted during lowering.
+    Instruction(Instruction),
+    Branch(Branch, Label),
+    TableSwitch { default: Label, low: i32, high: i32, targets: Vec<Label> },
+    LookupSwitch { default: Label, pairs: Vec<(i32, Label)> },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LabeledExceptionHandler {
+    pub start: Label,
+    /// Exclusive end of the protected range
+    pub end: Label,
+    pub handler: Label,
+    pub catch_type: ConstantPoolIndex,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LabeledLineNumber {
+    pub start: Label,
+    pub line_number: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LabeledLocalVariable {
+    pub start: Label,
+    /// Exclusive end of the range the variable is live in
+    pub end: Label,
+    pub name_index: ConstantPoolIndex,
+    pub descriptor_index: ConstantPoolIndex,
+    pub index: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LabeledLocalVariableType {
+    pub start: Label,
+    /// Exclusive end of the range the variable is live in
+    pub end: Label,
+    pub name_index: ConstantPoolIndex,
+    pub signature_index: ConstantPoolIndex,
+    pub index: u16,
+}
+
+/// An attribute of labeled code
+#[derive(Debug, PartialEq)]
+pub enum CodeAttribute<'a> {
+    LineNumberTable(Vec<LabeledLineNumber>),
+    LocalVariableTable(Vec<LabeledLocalVariable>),
+    LocalVariableTypeTable(Vec<LabeledLocalVariableType>),
+    /// Any other attribute, carried over unchanged. Offsets in a `StackMapTable` or in type
+    /// annotations still refer to the original code.
+    Other(Attribute<'a>),
+}
+
+///
+/// The body of a method as a sequence of instructions and labels. It's lifted from, and lowered
+/// back into, an `Attribute::Code`. Lowering resolves every label to an offset, recomputes the
+/// padding of switches and widens branches that no longer reach their targets.
+#[derive(Debug, PartialEq)]
+pub struct LabeledCode<'a> {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub items: Vec<CodeItem>,
+    pub exception_table: Vec<LabeledExceptionHandler>,
+    pub attributes: Vec<CodeAttribute<'a>>,
+    next_label: u32,
+}
+
+/// An error raised while lifting or lowering labeled code, or while building its control flow graph
+#[derive(Debug, PartialEq)]
+pub enum CodeError {
+    /// The attribute to lift isn't a `Code` attribute
+    NotCode,
+    /// A branch, switch, exception handler or debug table points at this offset, which isn't the
+    /// start of an instruction
+    InvalidOffset(i64),
+    /// A label is referred to but never placed
+    UndefinedLabel(Label),
+    /// A label is placed more than once
+    DuplicateLabel(Label),
+    /// A range ends before it starts
+    InvalidRange(Label, Label),
+    /// The lowered code is longer than the 65535 bytes a method can hold
+    CodeTooLarge(usize),
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CodeError::NotCode => write!(f, "not a Code attribute"),
+            &CodeError::InvalidOffset(offset) => write!(f, "offset {} is not the start of an instruction", offset),
+            &CodeError::UndefinedLabel(label) => write!(f, "label {} is never placed", label.0),
+            &CodeError::DuplicateLabel(label) => write!(f, "label {} is placed more than once", label.0),
+            &CodeError::InvalidRange(start, end) => write!(f, "range from label {} to label {} ends before it starts", start.0, end.0),
+            &CodeError::CodeTooLarge(len) => write!(f, "code is {} bytes long, at most 65535 are allowed", len),
+        }
+    }
+}
+
+impl Error for CodeError {}
+
+/// Hands out a label for every offset referred to while lifting code
+struct Labeler {
+    instructions: BTreeSet<usize>,
+    code_length: usize,
+    labels: HashMap<usize, Label>,
+}
+
+impl Labeler {
+    /// The label for `offset`, which has to be the start of an instruction or the end of the code
+    fn at(&mut self, offset: i64) -> Result<Label, CodeError> {
+        if offset < 0 || (offset as usize != self.code_length && !self.instructions.contains(&(offset as usize))) {
+            return Err(CodeError::InvalidOffset(offset));
+        }
+
+        let next = Label(self.labels.len() as u32);
+        Ok(*self.labels.entry(offset as usize).or_insert(next))
+    }
+
+    fn relative(&mut self, offset: usize, delta: i32) -> Result<Label, CodeError> {
+        self.at(offset as i64 + delta as i64)
+    }
+}
+
+impl<'a> LabeledCode<'a> {
+    pub fn new(max_stack: u16, max_locals: u16) -> LabeledCode<'a> {
+        LabeledCode {
+            max_stack: max_stack,
+            max_locals: max_locals,
+            items: vec![],
+            exception_table: vec![],
+            attributes: vec![],
+            next_label: 0,
+        }
+    }
+
+    /// Create a label that isn't used anywhere in this code yet
+    pub fn new_label(&mut self) -> Label {
+        let label = Label(self.next_label);
+        self.next_label += 1;
+        label
+    }
+
+    /// Replace every offset in a `Code` attribute with a label
+    pub fn from_code(attribute: Attribute<'a>) -> Result<LabeledCode<'a>, CodeError> {
+        let (max_stack, max_locals, code, exception_table, attributes) = match attribute {
+            Attribute::Code { max_stack, max_locals, code, exception_table, attributes } => (max_stack, max_locals, code, exception_table, attributes),
+            _ => return Err(CodeError::NotCode)
+        };
+
+        let mut offsets = Vec::with_capacity(code.len());
+        let mut code_length = 0;
+
+        for instruction in &code {
+            offsets.push(code_length);
+            code_length += instruction.len_at(code_length);
+        }
+
+        let mut labeler = Labeler { instructions: offsets.iter().cloned().collect(), code_length: code_length, labels: HashMap::new() };
+
+        let lifted = code.into_iter().zip(offsets.iter()).map(|(instruction, &offset)| {
+            if let Some((branch, delta)) = Branch::from_instruction(&instruction) {
+                return Ok(CodeItem::Branch(branch, labeler.relative(offset, delta)?));
+            }
+
+            match instruction {
+                Instruction::TABLESWITCH(default, low, high, targets) => Ok(CodeItem::TableSwitch {
+                    default: labeler.relative(offset, default)?,
+                    low: low,
+                    high: high,
+                    targets: targets.into_iter().map(|delta| labeler.relative(offset, delta)).collect::<Result<Vec<Label>, CodeError>>()?,
+                }),
+                Instruction::LOOKUPSWITCH(default, pairs) => Ok(CodeItem::LookupSwitch {
+                    default: labeler.relative(offset, default)?,
+                    pairs: pairs.into_iter().map(|(key, delta)| Ok((key, labeler.relative(offset, delta)?))).collect::<Result<Vec<(i32, Label)>, CodeError>>()?,
+                }),
+                instruction => Ok(CodeItem::Instruction(instruction))
+            }
+        }).collect::<Result<Vec<CodeItem>, CodeError>>()?;
+
+        let exception_table = exception_table.into_iter().map(|handler| Ok(LabeledExceptionHandler {
+            start: labeler.at(handler.start_pc as i64)?,
+            end: labeler.at(handler.end_pc as i64)?,
+            handler: labeler.at(handler.handler_pc as i64)?,
+            catch_type: handler.catch_type,
+        })).collect::<Result<Vec<LabeledExceptionHandler>, CodeError>>()?;
+
+        let attributes = attributes.into_iter().map(|attribute| Ok(match attribute {
+            Attribute::LineNumberTable(table) => CodeAttribute::LineNumberTable(table.into_iter().map(|entry| Ok(LabeledLineNumber {
+                start: labeler.at(entry.start_pc as i64)?,
+                line_number: entry.line_number,
+            })).collect::<Result<Vec<LabeledLineNumber>, CodeError>>()?),
+            Attribute::LocalVariableTable(table) => CodeAttribute::LocalVariableTable(table.into_iter().map(|entry| Ok(LabeledLocalVariable {
+                start: labeler.at(entry.start_pc as i64)?,
+                end: labeler.at(entry.start_pc as i64 + entry.length as i64)?,
+                name_index: entry.name_index,
+                descriptor_index: entry.descriptor_index,
+                index: entry.index,
+            })).collect::<Result<Vec<LabeledLocalVariable>, CodeError>>()?),
+            Attribute::LocalVariableTypeTable(table) => CodeAttribute::LocalVariableTypeTable(table.into_iter().map(|entry| Ok(LabeledLocalVariableType {
+                start: labeler.at(entry.start_pc as i64)?,
+                end: labeler.at(entry.start_pc as i64 + entry.length as i64)?,
+                name_index: entry.name_index,
+                signature_index: entry.signature_index,
+                index: entry.index,
+            })).collect::<Result<Vec<LabeledLocalVariableType>, CodeError>>()?),
+            other => CodeAttribute::Other(other)
+        })).collect::<Result<Vec<CodeAttribute<'a>>, CodeError>>()?;
+
+        let labels = labeler.labels;
+        let mut items = Vec::with_capacity(lifted.len() + labels.len());
+
+        for (item, offset) in lifted.into_iter().zip(offsets.into_iter()) {
+            if let Some(&label) = labels.get(&offset) {
+                items.push(CodeItem::Label(label));
+            }
+
+            items.push(item);
+        }
+
+        if let Some(&label) = labels.get(&code_length) {
+            items.push(CodeItem::Label(label));
+        }
+
+        Ok(LabeledCode {
+            max_stack: max_stack,
+            max_locals: max_locals,
+            items: items,
+            exception_table: exception_table,
+            attributes: attributes,
+            next_label: labels.len() as u32,
+        })
+    }
+
+    /// Resolve every label to an offset and turn the code back into a `Code` attribute
+    pub fn into_code(self) -> Result<Attribute<'a>, CodeError> {
+        let layout = Layout::new(&self.items)?;
+        let mut code = Vec::with_capacity(self.items.len());
+
+        for (idx, item) in self.items.into_iter().enumerate() {
+            let offset = layout.offsets[idx];
+
+            match item {
+                CodeItem::Label(_) => (),
+                CodeItem::Instruction(instruction) => code.push(instruction),
+                CodeItem::Branch(branch, target) => {
+                    let delta = layout.relative(offset, target)?;
+
+                    match (layout.far[idx], branch.negate()) {
+                        (true, Some(negated)) => {
+                            code.push(negated.to_instruction(8));
+                            code.push(Instruction::GOTO_W(delta - 3));
+                        },
+                        (true, None) if branch == Branch::GOTO => code.push(Instruction::GOTO_W(delta)),
+                        (true, None) if branch == Branch::JSR => code.push(Instruction::JSR_W(delta)),
+                        _ => code.push(branch.to_instruction(delta))
+                    }
+                },
+                CodeItem::TableSwitch { default, low, high, targets } => code.push(Instruction::TABLESWITCH(
+                    layout.relative(offset, default)?,
+                    low,
+                    high,
+                    targets.into_iter().map(|target| layout.relative(offset, target)).collect::<Result<Vec<i32>, CodeError>>()?
+                )),
+                CodeItem::LookupSwitch { default, pairs } => code.push(Instruction::LOOKUPSWITCH(
+                    layout.relative(offset, default)?,
+                    pairs.into_iter().map(|(key, target)| Ok((key, layout.relative(offset, target)?))).collect::<Result<Vec<(i32, i32)>, CodeError>>()?
+                ))
+            }
+        }
+
+        let exception_table = self.exception_table.into_iter().map(|handler| {
+            let (start_pc, end_pc) = layout.range(handler.start, handler.end)?;
+
+            Ok(ExceptionHandler {
+                start_pc: start_pc,
+                end_pc: end_pc,
+                handler_pc: layout.offset(handler.handler)?,
+                catch_type: handler.catch_type,
+            })
+        }).collect::<Result<Vec<ExceptionHandler>, CodeError>>()?;
+
+        let attributes = self.attributes.into_iter().map(|attribute| Ok(match attribute {
+            CodeAttribute::LineNumberTable(table) => Attribute::LineNumberTable(table.into_iter().map(|entry| Ok(LineNumberTable {
+                start_pc: layout.offset(entry.start)?,
+                line_number: entry.line_number,
+            })).collect::<Result<Vec<LineNumberTable>, CodeError>>()?),
+            CodeAttribute::LocalVariableTable(table) => Attribute::LocalVariableTable(table.into_iter().map(|entry| {
+                let (start_pc, end_pc) = layout.range(entry.start, entry.end)?;
+
+                Ok(LocalVariableTable {
+                    start_pc: start_pc,
+                    length: end_pc - start_pc,
+                    name_index: entry.name_index,
+                    descriptor_index: entry.descriptor_index,
+                    index: entry.index,
+                })
+            }).collect::<Result<Vec<LocalVariableTable>, CodeError>>()?),
+            CodeAttribute::LocalVariableTypeTable(table) => Attribute::LocalVariableTypeTable(table.into_iter().map(|entry| {
+                let (start_pc, end_pc) = layout.range(entry.start, entry.end)?;
+
+                Ok(LocalVariableTypeTable {
+                    start_pc: start_pc,
+                    length: end_pc - start_pc,
+                    name_index: entry.name_index,
+                    signature_index: entry.signature_index,
+                    index: entry.index,
+                })
+            }).collect::<Result<Vec<LocalVariableTypeTable>, CodeError>>()?),
+            CodeAttribute::Other(attribute) => attribute
+        })).collect::<Result<Vec<Attribute<'a>>, CodeError>>()?;
+
+        Ok(Attribute::Code {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: code,
+            exception_table: exception_table,
+            attributes: attributes,
+        })
+    }
+}
+
+/// The offsets labeled code is placed at once lowered
+struct Layout {
+    /// The offset of every item, followed by the length of the code
+    offsets: Vec<usize>,
+    /// Whether each item is a branch whose target is out of reach of a 16 bit offset
+    far: Vec<bool>,
+    /// The item each label is placed before
+    labels: HashMap<Label, usize>,
+}
+
+impl Layout {
+    /// Place every item, starting with all branches in their short form and widening the ones
+    /// that can't reach their target until nothing changes anymore. Branches only ever grow, so
+    /// this terminates after at most one round per branch.
+    fn new(items: &Vec<CodeItem>) -> Result<Layout, CodeError> {
+        let mut labels = HashMap::new();
+
+        for (idx, item) in items.iter().enumerate() {
+            if let &CodeItem::Label(label) = item {
+                if labels.insert(label, idx).is_some() {
+                    return Err(CodeError::DuplicateLabel(label));
+                }
+            }
+        }
+
+        let mut layout = Layout { offsets: vec![], far: vec![false; items.len()], labels: labels };
+
+        loop {
+            layout.place(items);
+
+            let mut widened = false;
+
+            for (idx, item) in items.iter().enumerate() {
+                if let &CodeItem::Branch(_, target) = item {
+                    let delta = layout.relative(layout.offsets[idx], target)?;
+
+                    if !layout.far[idx] && (delta < i16::min_value() as i32 || delta > i16::max_value() as i32) {
+                        layout.far[idx] = true;
+                        widened = true;
+                    }
+                }
+            }
+
+            if !widened {
+                break;
+            }
+        }
+
+        match layout.offsets.last() {
+            Some(&length) if length > 0xFFFF => Err(CodeError::CodeTooLarge(length)),
+            _ => Ok(layout)
+        }
+    }
+
+    fn place(&mut self, items: &Vec<CodeItem>) {
+        let mut offset = 0;
+        self.offsets.clear();
+
+        for (idx, item) in items.iter().enumerate() {
+            self.offsets.push(offset);
+
+            offset += match item {
+                &CodeItem::Label(_) => 0,
+                &CodeItem::Instruction(ref instruction) => instruction.len_at(offset),
+                &CodeItem::Branch(branch, _) => branch.len(self.far[idx]),
+                &CodeItem::TableSwitch { ref targets, .. } => Instruction::TABLESWITCH(0, 0, 0, vec![]).len_at(offset) + targets.len() * 4,
+                &CodeItem::LookupSwitch { ref pairs, .. } => Instruction::LOOKUPSWITCH(0, vec![]).len_at(offset) + pairs.len() * 8,
+            };
+        }
+
+        self.offsets.push(offset);
+    }
+
+    fn position(&self, label: Label) -> Result<usize, CodeError> {
+        self.labels.get(&label).map(|&idx| self.offsets[idx]).ok_or(CodeError::UndefinedLabel(label))
+    }
+
+    fn offset(&self, label: Label) -> Result<u16, CodeError> {
+        self.position(label).map(|offset| offset as u16)
+    }
+
+    fn relative(&self, from: usize, label: Label) -> Result<i32, CodeError> {
+        self.position(label).map(|offset| offset as i32 - from as i32)
+    }
+
+    fn range(&self, start: Label, end: Label) -> Result<(u16, u16), CodeError> {
+        match (self.offset(start)?, self.offset(end)?) {
+            (start_pc, end_pc) if end_pc < start_pc => Err(CodeError::InvalidRange(start, end)),
+            range => Ok(range)
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use super::classfile::*;
+use super::mutf8;
+
+#[derive(Debug, PartialEq)]
+pub enum CompactError {
+    /// Something in the class refers to a constant pool entry that doesn't exist
+    InvalidIndex(usize),
+    /// The class holds an attribute of the given name only as raw bytes, which may refer to
+    /// constants that couldn't be remapped
+    RawAttribute(String),
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CompactError::InvalidIndex(idx) => write!(f, "reference to invalid constant pool index #{}", idx),
+            &CompactError::RawAttribute(ref name) => write!(f, "unrecognised attribute {} may refer to constants", name),
+        }
+    }
+}
+
+impl Error for CompactError {}
+
+///
+/// Rebuild the constant pool so that it only holds the constants the class actually refers to,
+/// each of them once. Constants are kept in their original order, so indices only ever shrink and
+/// `LDC` instructions remain valid. Every reference from fields, methods, attributes, instructions,
+/// frames, annotations and other constants is rewritten to the new indices. The names of attributes
+/// are kept as well, since the writer looks them up by value.
+///
+/// Classes holding attributes that weren't recognised, and are kept as raw bytes, are refused and
+/// left as they are. There's no telling whether those bytes refer to constants, as vendor and
+/// compiler specific attributes often do, and such references would point at the wrong constants
+/// once the pool is rebuilt.
+pub fn compact_constant_pool(class: &mut Classfile) -> Result<(), CompactError> {
+    let raw = raw_attribute(&class.attributes)
+        .or_else(|| class.fields.iter().filter_map(|field| raw_attribute(&field.attributes)).next())
+        .or_else(|| class.methods.iter().filter_map(|method| raw_attribute(&method.attributes)).next());
+
+    if let Some(name_index) = raw {
+        let name = class.constant_pool.get_utf8(name_index.idx as u16).map(|name| mutf8::decode_lossy(name).into_owned());
+
+        return Err(CompactError::RawAttribute(name.unwrap_or_else(|| format!("#{}", name_index.idx))));
+    }
+
+    let count = class.constant_pool.constants.len();
+    let mut used = vec![false; count];
+    let mut pending = vec![];
+
+    visit_class(class, &mut |idx: &mut usize| pending.push(*idx));
+
+    let mut names = vec![];
+    attribute_names(&class.attributes, &mut names);
+
+    for field in &class.fields {
+        attribute_names(&field.attributes, &mut names);
+    }
+
+    for method in &class.methods {
+        attribute_names(&method.attributes, &mut names);
+    }
+
+    pending.extend(names.into_iter().filter_map(|name| class.constant_pool.find_ut8_index(name)));
+
+    while let Some(idx) = pending.pop() {
+        if idx == 0 || used.get(idx) == Some(&true) {
+            continue;
+        }
+
+        match class.constant_pool.constants.get_mut(idx) {
+            None | Some(&mut Constant::Placeholder) => return Err(CompactError::InvalidIndex(idx)),
+            Some(constant) => {
+                used[idx] = true;
+                visit_constant(constant, &mut |child: &mut usize| pending.push(*child));
+            }
+        }
+    }
+
+    // Constants referring to equal constants only turn out to be equal themselves once those
+    // have been merged, so keep merging until nothing changes
+    let mut canonical: Vec<usize> = (0..count).collect();
+
+    loop {
+        let mut seen = HashMap::new();
+        let mut changed = false;
+
+        for idx in (0..count).filter(|&idx| used[idx]) {
+            let mut key = class.constant_pool.constants[idx].clone();
+            visit_constant(&mut key, &mut |child: &mut usize| *child = canonical[*child]);
+
+            match seen.entry(key) {
+                Entry::Occupied(entry) => if canonical[idx] != *entry.get() {
+                    canonical[idx] = *entry.get();
+                    changed = true;
+                },
+                Entry::Vacant(entry) => { entry.insert(idx); }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut new_index = vec![0; count];
+    let mut next = 1;
+
+    for idx in (0..count).filter(|&idx| used[idx] && canonical[idx] == idx) {
+        new_index[idx] = next;
+        next += class.constant_pool.constants[idx].cp_size();
+    }
+
+    for idx in (0..count).filter(|&idx| used[idx]) {
+        new_index[idx] = new_index[canonical[idx]];
+    }
+
+    let mut constants = vec![ Constant::Placeholder ];
+
+    for (idx, mut constant) in mem::replace(&mut class.constant_pool.constants, vec![]).into_iter().enumerate() {
+        if used[idx] && canonical[idx] == idx {
+            visit_constant(&mut constant, &mut |child: &mut usize| *child = new_index[*child]);
+
+            let wide = constant.cp_size() == 2;
+            constants.push(constant);
+
+            if wide {
+                constants.push(Constant::Placeholder);
+            }
+        }
+    }
+
+    class.constant_pool.constants = constants;
+    visit_class(class, &mut |idx: &mut usize| *idx = new_index[*idx]);
+
+    Ok(())
+}
+
+/// The name of the first attribute that is only held as raw bytes, including nested attributes
+fn raw_attribute(attributes: &Vec<Attribute>) -> Option<ConstantPoolIndex> {
+    attributes.iter().filter_map(|attribute| match attribute {
+        &Attribute::RawAttribute { ref name_index, .. } => Some(name_index.clone()),
+        &Attribute::Code { ref attributes, .. } => raw_attribute(attributes),
+        &Attribute::Record(ref components) => components.iter().filter_map(|component| raw_attribute(&component.attributes)).next(),
+        _ => None
+    }).next()
+}
+
+/// Collect the names of the attributes the writer looks up by value
+fn attribute_names(attributes: &Vec<Attribute>, names: &mut Vec<&'static str>) {
+    for attribute in attributes {
+        names.extend(attribute.name());
+
+        match attribute {
+            &Attribute::Code { ref attributes, .. } => attribute_names(attributes, names),
+            &Attribute::Record(ref components) => for component in components {
+                attribute_names(&component.attributes, names);
+            },
+            _ => ()
+        }
+    }
+}
+
+/// Call `f` with the index of every constant a constant refers to
+fn visit_constant<F: FnMut(&mut usize)>(constant: &mut Constant, f: &mut F) {
+    match *constant {
+        Constant::Class(ref mut idx) | Constant::String(ref mut idx) | Constant::MethodType(ref mut idx) |
+        Constant::Module(ref mut idx) | Constant::Package(ref mut idx) => f(&mut idx.idx),
+        Constant::FieldRef { ref mut class_index, ref mut name_and_type_index } |
+        Constant::MethodRef { ref mut class_index, ref mut name_and_type_index } |
+        Constant::InterfaceMethodRef { ref mut class_index, ref mut name_and_type_index } => {
+            f(&mut class_index.idx);
+            f(&mut name_and_type_index.idx);
+        },
+        Constant::NameAndType { ref mut name_index, ref mut descriptor_index } => {
+            f(&mut name_index.idx);
+            f(&mut descriptor_index.idx);
+        },
+        Constant::MethodHandle { ref mut reference_index, .. } => f(&mut reference_index.idx),
+        // The bootstrap method index points into the BootstrapMethods attribute, not the pool
+        Constant::Dynamic { ref mut name_and_type_index, .. } | Constant::InvokeDynamic { ref mut name_and_type_index, .. } => f(&mut name_and_type_index.idx),
+        Constant::Utf8(_) | Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_) |
+        Constant::Unknown(_) | Constant::Placeholder => ()
+    }
+}
+
+/// Call `f` with every constant pool index the class refers to outside of the pool itself. Optional
+/// references are passed as well, holding 0.
+fn visit_class<F: FnMut(&mut usize)>(class: &mut Classfile, f: &mut F) {
+    f(&mut class.this_class.idx);
+    f(&mut class.super_class.idx);
+
+    for idx in class.interfaces.iter_mut() {
+        f(&mut idx.idx);
+    }
+
+    for field in class.fields.iter_mut() {
+        f(&mut field.name_index.idx);
+        f(&mut field.descriptor_index.idx);
+        visit_attributes(&mut field.attributes, f);
+    }
+
+    for method in class.methods.iter_mut() {
+        f(&mut method.name_index.idx);
+        f(&mut method.descriptor_index.idx);
+        visit_attributes(&mut method.attributes, f);
+    }
+
+    visit_attributes(&mut class.attributes, f);
+}
+
+fn visit_attributes<F: FnMut(&mut usize)>(attributes: &mut Vec<Attribute>, f: &mut F) {
+    for attribute in attributes.iter_mut() {
+        match *attribute {
+            Attribute::ConstantValue(ref mut idx) | Attribute::Signature(ref mut idx) | Attribute::SourceFile(ref mut idx) |
+            Attribute::ModuleMainClass(ref mut idx) | Attribute::NestHost(ref mut idx) => f(&mut idx.idx),
+            Attribute::Code { ref mut code, ref mut exception_table, ref mut attributes, .. } => {
+                for instruction in code.iter_mut() {
+                    visit_instruction(instruction, f);
+                }
+
+                for handler in exception_table.iter_mut() {
+                    f(&mut handler.catch_type.idx);
+                }
+
+                visit_attributes(attributes, f);
+            },
+            Attribute::StackMapTable(ref mut table) => for frame in table.iter_mut() {
+                match *frame {
+                    StackMapFrame::SameLocals1StackItemFrame { ref mut stack, .. } |
+                    StackMapFrame::SameLocals1StackItemFrameExtended { ref mut stack, .. } => visit_verification_type(stack, f),
+                    StackMapFrame::AppendFrame { ref mut locals, .. } => for value in locals.iter_mut() {
+                        visit_verification_type(value, f);
+                    },
+                    StackMapFrame::FullFrame { ref mut locals, ref mut stack, .. } => for value in locals.iter_mut().chain(stack.iter_mut()) {
+                        visit_verification_type(value, f);
+                    },
+                    _ => ()
+                }
+            },
+            Attribute::Exceptions(ref mut table) | Attribute::ModulePackages(ref mut table) |
+            Attribute::NestMembers(ref mut table) | Attribute::PermittedSubclasses(ref mut table) => for idx in table.iter_mut() {
+                f(&mut idx.idx);
+            },
+            Attribute::InnerClasses(ref mut table) => for class in table.iter_mut() {
+                f(&mut class.inner_class_info_index.idx);
+                f(&mut class.outer_class_info_index.idx);
+                f(&mut class.inner_name_index.idx);
+            },
+            Attribute::EnclosingMethod { ref mut class_index, ref mut method_index } => {
+                f(&mut class_index.idx);
+                f(&mut method_index.idx);
+            },
+            Attribute::LocalVariableTable(ref mut table) => for variable in table.iter_mut() {
+                f(&mut variable.name_index.idx);
+                f(&mut variable.descriptor_index.idx);
+            },
+            Attribute::LocalVariableTypeTable(ref mut table) => for variable in table.iter_mut() {
+                f(&mut variable.name_index.idx);
+                f(&mut variable.signature_index.idx);
+            },
+            Attribute::RuntimeVisibleAnnotations(ref mut table) | Attribute::RuntimeInvisibleAnnotations(ref mut table) => for annotation in table.iter_mut() {
+                visit_annotation(annotation, f);
+            },
+            Attribute::RuntimeVisibleParameterAnnotations(ref mut table) | Attribute::RuntimeInvisibleParameterAnnotations(ref mut table) => for annotation in table.iter_mut().flat_map(|parameter| parameter.iter_mut()) {
+                visit_annotation(annotation, f);
+            },
+            Attribute::RuntimeVisibleTypeAnnotations(ref mut table) | Attribute::RuntimeInvisibleTypeAnnotations(ref mut table) => for annotation in table.iter_mut() {
+                f(&mut annotation.type_index.idx);
+
+                for pair in annotation.element_value_pairs.iter_mut() {
+                    f(&mut pair.element_name_index.idx);
+                    visit_element_value(&mut pair.value, f);
+                }
+            },
+            Attribute::AnnotationDefault(ref mut value) => visit_element_value(value, f),
+            Attribute::BootstrapMethods(ref mut table) => for method in table.iter_mut() {
+                f(&mut method.bootstrap_method_ref.idx);
+
+                for argument in method.bootstrap_arguments.iter_mut() {
+                    f(&mut argument.idx);
+                }
+            },
+            Attribute::MethodParameters(ref mut table) => for parameter in table.iter_mut() {
+                f(&mut parameter.name_index.idx);
+            },
+            Attribute::Module { ref mut name_index, ref mut version_index, ref mut requires, ref mut exports, ref mut opens, ref mut uses, ref mut provides, .. } => {
+                f(&mut name_index.idx);
+                f(&mut version_index.idx);
+
+                for require in requires.iter_mut() {
+                    f(&mut require.requires_index.idx);
+                    f(&mut require.requires_version_index.idx);
+                }
+
+                for export in exports.iter_mut() {
+                    f(&mut export.exports_index.idx);
+
+                    for idx in export.exports_to.iter_mut() {
+                        f(&mut idx.idx);
+                    }
+                }
+
+                for open in opens.iter_mut() {
+                    f(&mut open.opens_index.idx);
+
+                    for idx in open.opens_to.iter_mut() {
+                        f(&mut idx.idx);
+                    }
+                }
+
+                for idx in uses.iter_mut() {
+                    f(&mut idx.idx);
+                }
+
+                for provide in provides.iter_mut() {
+                    f(&mut provide.provides_index.idx);
+
+                    for idx in provide.provides_with.iter_mut() {
+                        f(&mut idx.idx);
+                    }
+                }
+            },
+            Attribute::Record(ref mut components) => for component in components.iter_mut() {
+                f(&mut component.name_index.idx);
+                f(&mut component.descriptor_index.idx);
+                visit_attributes(&mut component.attributes, f);
+            },
+            // Refused before getting here, the contents can't be remapped
+            Attribute::RawAttribute { ref mut name_index, .. } => f(&mut name_index.idx),
+            Attribute::Synthetic | Attribute::Deprecated | Attribute::SourceDebugExtension(_) | Attribute::LineNumberTable(_) => ()
+        }
+    }
+}
+
+fn visit_instruction<F: FnMut(&mut usize)>(instruction: &mut Instruction, f: &mut F) {
+    match *instruction {
+        Instruction::LDC(ref mut idx) => {
+            let mut wide = *idx as usize;
+            f(&mut wide);
+            *idx = wide as u8;
+        },
+        Instruction::LDC_W(ref mut idx) | Instruction::LDC2_W(ref mut idx) |
+        Instruction::GETSTATIC(ref mut idx) | Instruction::PUTSTATIC(ref mut idx) | Instruction::GETFIELD(ref mut idx) | Instruction::PUTFIELD(ref mut idx) |
+        Instruction::INVOKEVIRTUAL(ref mut idx) | Instruction::INVOKESPECIAL(ref mut idx) | Instruction::INVOKESTATIC(ref mut idx) |
+        Instruction::INVOKEINTERFACE(ref mut idx, _) | Instruction::INVOKEDYNAMIC(ref mut idx) |
+        Instruction::NEW(ref mut idx) | Instruction::ANEWARRAY(ref mut idx) | Instruction::CHECKCAST(ref mut idx) |
+        Instruction::INSTANCEOF(ref mut idx) | Instruction::MULTIANEWARRAY(ref mut idx, _) => {
+            let mut wide = *idx as usize;
+            f(&mut wide);
+            *idx = wide as u16;
+        },
+        _ => ()
+    }
+}
+
+fn visit_verification_type<F: FnMut(&mut usize)>(value: &mut VerificationType, f: &mut F) {
+    if let VerificationType::Object { ref mut cpool_index } = *value {
+        f(&mut cpool_index.idx);
+    }
+}
+
+fn visit_annotation<F: FnMut(&mut usize)>(annotation: &mut Annotation, f: &mut F) {
+    f(&mut annotation.type_index.idx);
+
+    for pair in annotation.element_value_pairs.iter_mut() {
+        f(&mut pair.element_name_index.idx);
+        visit_element_value(&mut pair.value, f);
+    }
+}
+
+fn visit_element_value<F: FnMut(&mut usize)>(value: &mut ElementValue, f: &mut F) {
+    match *value {
+        ElementValue::ConstantValue(_, ref mut idx) | ElementValue::ClassInfo(ref mut idx) => f(&mut idx.idx),
+        ElementValue::Enum { ref mut type_name_index, ref mut const_name_index } => {
+            f(&mut type_name_index.idx);
+            f(&mut const_name_index.idx);
+        },
+        ElementValue::Annotation(ref mut annotation) => visit_annotation(annotation, f),
+        ElementValue::Array(ref mut values) => for value in values.iter_mut() {
+            visit_element_value(value, f);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::fmt;
+
+///
+/// The type of a field, parameter or return value as written in a descriptor
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// A class by internal name, eg. `java/lang/String`
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    /// Parse a field descriptor such as `I` or `[Ljava/lang/String;`
+    pub fn parse(descriptor: &str) -> Result<FieldType, DescriptorError> {
+        let mut parser = Parser { descriptor: descriptor, offset: 0 };
+        let field_type = parser.field_type()?;
+
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    /// The number of local variable slots and operand stack words taken by a value of this type
+    pub fn slots(&self) -> usize {
+        match *self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1
+        }
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        match *self {
+            FieldType::Object(_) | FieldType::Array(_) => false,
+            _ => true
+        }
+    }
+
+    /// The descriptor of the type, which is what the type renders to as well
+    pub fn descriptor(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &FieldType::Byte => write!(f, "B"),
+            &FieldType::Char => write!(f, "C"),
+            &FieldType::Double => write!(f, "D"),
+            &FieldType::Float => write!(f, "F"),
+            &FieldType::Int => write!(f, "I"),
+            &FieldType::Long => write!(f, "J"),
+            &FieldType::Short => write!(f, "S"),
+            &FieldType::Boolean => write!(f, "Z"),
+            &FieldType::Object(ref class) => write!(f, "L{};", class),
+            &FieldType::Array(ref component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+///
+/// The parameter types and return type of a method, the latter being `None` for `void`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub params: Vec<FieldType>,
+    pub ret: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    /// Parse a method descriptor such as `(ILjava/lang/String;)V`
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor, DescriptorError> {
+        let mut parser = Parser { descriptor: descriptor, offset: 0 };
+
+        parser.expect('(')?;
+
+        let mut params = vec![];
+
+        while parser.peek() != Some(')') {
+            params.push(parser.field_type()?);
+        }
+
+        parser.expect(')')?;
+
+        let ret = match parser.peek() {
+            Some('V') => { parser.offset += 1; None },
+            _ => Some(parser.field_type()?)
+        };
+
+        parser.end()?;
+        Ok(MethodDescriptor { params: params, ret: ret })
+    }
+
+    /// The number of local variable slots taken by the parameters, not counting `this`
+    pub fn param_slots(&self) -> usize {
+        self.params.iter().map(|param| param.slots()).sum()
+    }
+
+    /// The number of operand stack words taken by the return value, zero for `void`
+    pub fn return_slots(&self) -> usize {
+        self.ret.as_ref().map(|ret| ret.slots()).unwrap_or(0)
+    }
+
+    ///
+    /// The number of local variable slots taken by the arguments of a method with this descriptor,
+    /// including `this` unless the method is static. This is what JVMTI's `GetArgumentsSize` reports.
+    pub fn argument_size(&self, is_static: bool) -> usize {
+        self.param_slots() + if is_static { 0 } else { 1 }
+    }
+
+    /// The descriptor of the method, which is what the method descriptor renders to as well
+    pub fn descriptor(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+
+        for param in self.params.iter() {
+            write!(f, "{}", param)?;
+        }
+
+        match self.ret {
+            Some(ref ret) => write!(f, "){}", ret),
+            None => write!(f, ")V")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DescriptorError {
+    pub descriptor: String,
+    /// Offset of the first character that couldn't be parsed
+    pub offset: usize,
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid descriptor {} at offset {}", self.descriptor, self.offset)
+    }
+}
+
+impl Error for DescriptorError {}
+
+/// The JVM doesn't allow arrays of more than 255 dimensions
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+struct Parser<'d> {
+    descriptor: &'d str,
+    offset: usize,
+}
+
+impl<'d> Parser<'d> {
+    fn peek(&self) -> Option<char> {
+        self.descriptor[self.offset..].chars().next()
+    }
+
+    fn error(&self) -> DescriptorError {
+        DescriptorError { descriptor: self.descriptor.to_string(), offset: self.offset }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DescriptorError> {
+        match self.peek() {
+            Some(c) if c == expected => { self.offset += c.len_utf8(); Ok(()) },
+            _ => Err(self.error())
+        }
+    }
+
+    fn end(&self) -> Result<(), DescriptorError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error())
+        }
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, DescriptorError> {
+        let start = self.offset;
+
+        let field_type = match self.peek() {
+            Some('B') => FieldType::Byte,
+            Some('C') => FieldType::Char,
+            Some('D') => FieldType::Double,
+            Some('F') => FieldType::Float,
+            Some('I') => FieldType::Int,
+            Some('J') => FieldType::Long,
+            Some('S') => FieldType::Short,
+            Some('Z') => FieldType::Boolean,
+            Some('L') => {
+                let name = &self.descriptor[start + 1..];
+
+                return match name.find(|c| c == ';' || c == '.' || c == '[') {
+                    Some(end) if end > 0 && name[end..].starts_with(';') && !name[..end].split('/').any(|part| part.is_empty()) => {
+                        self.offset = start + end + 2;
+                        Ok(FieldType::Object(name[..end].to_string()))
+                    },
+                    Some(end) => { self.offset = start + 1 + end; Err(self.error()) },
+                    None => { self.offset = self.descriptor.len(); Err(self.error()) }
+                };
+            },
+            Some('[') => {
+                let dimensions = self.descriptor[start..].len() - self.descriptor[start..].trim_start_matches('[').len();
+
+                if dimensions > MAX_ARRAY_DIMENSIONS {
+                    self.offset = start + MAX_ARRAY_DIMENSIONS;
+                    return Err(self.error());
+                }
+
+                self.offset = start + dimensions;
+
+                let mut field_type = self.field_type()?;
+
+                for _ in 0..dimensions {
+                    field_type = FieldType::Array(Box::new(field_type));
+                }
+
+                return Ok(field_type);
+            },
+            _ => return Err(self.error())
+        };
+
+        self.offset += 1;
+        Ok(field_type)
+    }
+}
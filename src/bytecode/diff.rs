@@ -0,0 +1,327 @@
+//!
+//! Semantic comparison of two classes, eg. of a class before and after instrumentation. Constants
+//! are compared by value rather than by index, so differently laid out constant pools don't show up
+//! as differences. Fields and methods are matched up by name and descriptor, attributes by name, and
+//! attributes are compared line by line in their assembly rendering, which for code means
+//! instruction by instruction.
+//!
+//! The result is a tree of differences, which renders as unified text:
+//!
+//! ```text
+//! --- Counter
+//! +++ Counter
+//! @@ method count (I)I @@
+//! -flags public static
+//! +flags public static synchronized
+//!  .code stack 2 locals 2
+//! -    iconst_0
+//! +    iconst_1
+//!      istore_1
+//!  L0:
+//!      iload_0
+//!  ...
+//! +method reset ()V
+//! ```
+
+use std::collections::BTreeMap;
+use super::asm::{self, FlagsOwner};
+use super::classfile::*;
+use super::mutf8;
+
+/// The number of unchanged lines shown around changed ones
+const CONTEXT: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub struct ClassDiff {
+    pub old_name: String,
+    pub new_name: String,
+    /// The differences of the class itself, that is of its version, flags, names and attributes
+    pub changes: Vec<Change>,
+    pub fields: Vec<MemberDiff>,
+    pub methods: Vec<MemberDiff>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MemberDiff {
+    /// A member only the new class has, by name and descriptor
+    Added(String),
+    /// A member only the old class has, by name and descriptor
+    Removed(String),
+    /// A member both classes have that differs between them
+    Changed { member: String, changes: Vec<Change> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// A property with a single value, such as the flags or the super class, differs
+    Property { name: &'static str, old: String, new: String },
+    /// An attribute only the new side has, along with its rendering
+    AttributeAdded { name: String, lines: Vec<String> },
+    /// An attribute only the old side has, along with its rendering
+    AttributeRemoved { name: String, lines: Vec<String> },
+    /// An attribute both sides have, with the differences between their renderings
+    AttributeChanged { name: String, lines: Vec<DiffLine> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl ClassDiff {
+    /// Whether the classes are the same
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.fields.is_empty() && self.methods.is_empty()
+    }
+
+    /// Render the differences as unified text, or nothing if there are none
+    pub fn render_unified(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        let mut lines = vec![ format!("--- {}", self.old_name), format!("+++ {}", self.new_name) ];
+
+        if !self.changes.is_empty() {
+            lines.push(format!("@@ class {} @@", self.new_name));
+            render_changes(&self.changes, &mut lines);
+        }
+
+        for (kind, members) in &[ ("field", &self.fields), ("method", &self.methods) ] {
+            for member in members.iter() {
+                match member {
+                    &MemberDiff::Added(ref member) => lines.push(format!("+{} {}", kind, member)),
+                    &MemberDiff::Removed(ref member) => lines.push(format!("-{} {}", kind, member)),
+                    &MemberDiff::Changed { ref member, ref changes } => {
+                        lines.push(format!("@@ {} {} @@", kind, member));
+                        render_changes(changes, &mut lines);
+                    }
+                }
+            }
+        }
+
+        lines
+    }
+}
+
+fn render_changes(changes: &[Change], lines: &mut Vec<String>) {
+    for change in changes {
+        match change {
+            &Change::Property { name, ref old, ref new } => {
+                lines.push(format!("-{} {}", name, old));
+                lines.push(format!("+{} {}", name, new));
+            },
+            &Change::AttributeAdded { lines: ref added, .. } => lines.extend(added.iter().map(|line| format!("+{}", line))),
+            &Change::AttributeRemoved { lines: ref removed, .. } => lines.extend(removed.iter().map(|line| format!("-{}", line))),
+            &Change::AttributeChanged { lines: ref changed, .. } => {
+                let near_change = |idx: usize| {
+                    let from = idx.saturating_sub(CONTEXT);
+                    changed[from..(idx + CONTEXT + 1).min(changed.len())].iter().any(|line| !is_same(line))
+                };
+
+                let mut elided = false;
+
+                for (idx, line) in changed.iter().enumerate() {
+                    match line {
+                        &DiffLine::Removed(ref line) => lines.push(format!("-{}", line)),
+                        &DiffLine::Added(ref line) => lines.push(format!("+{}", line)),
+                        &DiffLine::Same(ref line) if near_change(idx) => lines.push(format!(" {}", line)),
+                        &DiffLine::Same(_) => {
+                            if !elided {
+                                lines.push(String::from(" ..."));
+                            }
+
+                            elided = true;
+                            continue;
+                        }
+                    }
+
+                    elided = false;
+                }
+            }
+        }
+    }
+}
+
+fn is_same(line: &DiffLine) -> bool {
+    matches!(line, &DiffLine::Same(_))
+}
+
+/// Compare two classes
+pub fn diff(old: &Classfile, new: &Classfile) -> ClassDiff {
+    let (old_cp, new_cp) = (&old.constant_pool, &new.constant_pool);
+    let mut changes = vec![];
+
+    let version = |class: &Classfile| format!("{}.{}", class.version.major_version, class.version.minor_version);
+    let interfaces = |class: &Classfile| class.interfaces.iter().map(|idx| class_name(&class.constant_pool, idx)).collect::<Vec<String>>().join(" ");
+
+    property(&mut changes, "version", version(old), version(new));
+    property(&mut changes, "flags", asm::disassemble_flags(&old.access_flags, FlagsOwner::Class), asm::disassemble_flags(&new.access_flags, FlagsOwner::Class));
+    property(&mut changes, "this_class", class_name(old_cp, &old.this_class), class_name(new_cp, &new.this_class));
+    property(&mut changes, "super_class", class_name(old_cp, &old.super_class), class_name(new_cp, &new.super_class));
+    property(&mut changes, "interfaces", interfaces(old), interfaces(new));
+    diff_attributes(old_cp, &old.attributes, new_cp, &new.attributes, &mut changes);
+
+    let fields = diff_members(
+        old.fields.iter().map(|field| (&field.access_flags, &field.name_index, &field.descriptor_index, &field.attributes)).collect(), old_cp,
+        new.fields.iter().map(|field| (&field.access_flags, &field.name_index, &field.descriptor_index, &field.attributes)).collect(), new_cp,
+        FlagsOwner::Field);
+
+    let methods = diff_members(
+        old.methods.iter().map(|method| (&method.access_flags, &method.name_index, &method.descriptor_index, &method.attributes)).collect(), old_cp,
+        new.methods.iter().map(|method| (&method.access_flags, &method.name_index, &method.descriptor_index, &method.attributes)).collect(), new_cp,
+        FlagsOwner::Method);
+
+    ClassDiff { old_name: class_name(old_cp, &old.this_class), new_name: class_name(new_cp, &new.this_class), changes: changes, fields: fields, methods: methods }
+}
+
+/// The flags, name, descriptor and attributes of a field or a method
+type Member<'m, 'a> = (&'m AccessFlags, &'m ConstantPoolIndex, &'m ConstantPoolIndex, &'m Vec<Attribute<'a>>);
+
+fn diff_members(old: Vec<Member>, old_cp: &ConstantPool, new: Vec<Member>, new_cp: &ConstantPool, owner: FlagsOwner) -> Vec<MemberDiff> {
+    let key = |cp: &ConstantPool, member: &Member| format!("{} {}", utf8(cp, member.1), utf8(cp, member.2));
+    let old_keys: Vec<String> = old.iter().map(|member| key(old_cp, member)).collect();
+    let new_keys: Vec<String> = new.iter().map(|member| key(new_cp, member)).collect();
+    let mut diffs = vec![];
+
+    for (old_member, old_key) in old.iter().zip(old_keys.iter()) {
+        match new_keys.iter().position(|new_key| new_key == old_key) {
+            None => diffs.push(MemberDiff::Removed(old_key.clone())),
+            Some(idx) => {
+                let new_member = &new[idx];
+                let mut changes = vec![];
+
+                property(&mut changes, "flags", asm::disassemble_flags(old_member.0, owner), asm::disassemble_flags(new_member.0, owner));
+                diff_attributes(old_cp, old_member.3, new_cp, new_member.3, &mut changes);
+
+                if !changes.is_empty() {
+                    diffs.push(MemberDiff::Changed { member: old_key.clone(), changes: changes });
+                }
+            }
+        }
+    }
+
+    diffs.extend(new_keys.iter().filter(|new_key| !old_keys.contains(new_key)).map(|new_key| MemberDiff::Added(new_key.clone())));
+    diffs
+}
+
+/// Compare attributes by name, pairing up attributes of the same name in order
+fn diff_attributes(old_cp: &ConstantPool, old: &[Attribute], new_cp: &ConstantPool, new: &[Attribute], changes: &mut Vec<Change>) {
+    let mut names: Vec<String> = vec![];
+    let mut old_by_name: BTreeMap<String, Vec<&Attribute>> = BTreeMap::new();
+    let mut new_by_name: BTreeMap<String, Vec<&Attribute>> = BTreeMap::new();
+
+    for attribute in old {
+        let name = attribute_name(old_cp, attribute);
+
+        if !names.contains(&name) {
+            names.push(name.clone());
+        }
+
+        old_by_name.entry(name).or_default().push(attribute);
+    }
+
+    for attribute in new {
+        let name = attribute_name(new_cp, attribute);
+
+        if !names.contains(&name) {
+            names.push(name.clone());
+        }
+
+        new_by_name.entry(name).or_default().push(attribute);
+    }
+
+    for name in names {
+        let (olds, news) = (old_by_name.remove(&name).unwrap_or_default(), new_by_name.remove(&name).unwrap_or_default());
+
+        for idx in 0..olds.len().max(news.len()) {
+            match (olds.get(idx), news.get(idx)) {
+                (Some(old), Some(new)) => {
+                    let lines = diff_lines(&render(old_cp, old), &render(new_cp, new));
+
+                    if !lines.iter().all(is_same) {
+                        changes.push(Change::AttributeChanged { name: name.clone(), lines: lines });
+                    }
+                },
+                (Some(old), None) => changes.push(Change::AttributeRemoved { name: name.clone(), lines: render(old_cp, old) }),
+                (None, Some(new)) => changes.push(Change::AttributeAdded { name: name.clone(), lines: render(new_cp, new) }),
+                (None, None) => ()
+            }
+        }
+    }
+}
+
+fn property(changes: &mut Vec<Change>, name: &'static str, old: String, new: String) {
+    if old != new {
+        changes.push(Change::Property { name: name, old: old, new: new });
+    }
+}
+
+/// An attribute in assembly, or as the model prints it for the odd attribute that can't be rendered
+fn render(cp: &ConstantPool, attribute: &Attribute) -> Vec<String> {
+    asm::disassemble_attribute(cp, attribute).unwrap_or_else(|_| vec![ format!("{:?}", attribute) ])
+}
+
+fn attribute_name(cp: &ConstantPool, attribute: &Attribute) -> String {
+    match attribute {
+        &Attribute::RawAttribute { ref name_index, .. } => utf8(cp, name_index),
+        _ => attribute.name().unwrap_or_default().to_string()
+    }
+}
+
+fn utf8(cp: &ConstantPool, idx: &ConstantPoolIndex) -> String {
+    match cp.get_utf8(idx.idx as u16) {
+        Some(bytes) => mutf8::decode_lossy(bytes).into_owned(),
+        None => format!("#{}", idx.idx)
+    }
+}
+
+fn class_name(cp: &ConstantPool, idx: &ConstantPoolIndex) -> String {
+    match idx.idx {
+        0 => String::from("-"),
+        _ => cp.get_class_name(idx.idx as u16).unwrap_or_else(|| format!("#{}", idx.idx))
+    }
+}
+
+///
+/// The lines removed from and added to the old side to get the new one, along with the lines both
+/// have. The lines both sides start and end with are set aside before looking for the longest common
+/// subsequence of the rest, which keeps the table small for the usual handful of changes.
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let prefix = old.iter().zip(new.iter()).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|&(a, b)| a == b).count();
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().cloned().map(DiffLine::Same).collect();
+    let left = &old[prefix..old.len() - suffix];
+    let right = &new[prefix..new.len() - suffix];
+
+    // lengths[i][j] is the length of the longest common subsequence of left[i..] and right[j..]
+    let mut lengths = vec![vec![0u32; right.len() + 1]; left.len() + 1];
+
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lengths[i][j] = if left[i] == right[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            lines.push(DiffLine::Same(left[i].clone()));
+            i += 1;
+            j += 1;
+        } else if j == right.len() || (i < left.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(DiffLine::Removed(left[i].clone()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(right[j].clone()));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[old.len() - suffix..].iter().cloned().map(DiffLine::Same));
+    lines
+}
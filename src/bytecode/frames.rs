@@ -0,0 +1,222 @@
+use std::collections::{ BTreeMap, BTreeSet };
+use std::error::Error;
+use std::fmt;
+use super::analyzer::{ Analyzer, Frame, Value };
+use super::classfile::*;
+use super::cfg::Offsets;
+use super::hierarchy::*;
+
+/// An error raised while analyzing a method, or computing its frames or maxs
+#[derive(Debug, PartialEq)]
+pub struct FrameError {
+    /// Name and descriptor of the method, eg. `main([Ljava/lang/String;)V`
+    pub method: String,
+    /// Offset of the offending instruction within the code
+    pub offset: usize,
+    pub kind: FrameErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FrameErrorKind {
+    /// An instruction pops more values than there are on the operand stack, or splits a long or
+    /// double value
+    StackUnderflow,
+    /// Two paths reach the same instruction with operand stacks that can't be merged
+    InconsistentStack,
+    /// A local variable is read before it's set, or lies beyond max_locals
+    InvalidLocal(u16),
+    /// A constant pool index doesn't point to the kind of constant the instruction needs
+    BadConstant(u16),
+    /// A method or field descriptor can't be parsed
+    BadDescriptor(String),
+    /// A branch or exception handler points at an offset that isn't the start of an instruction
+    InvalidTarget(i64),
+    /// `JSR` and `RET` can't be described by stack map frames
+    Subroutine,
+    /// The instruction can't be reached, so there's no way to tell which frame it needs
+    Unreachable,
+    /// Execution runs past the last instruction
+    FallsOffEnd,
+    /// An instruction the reader couldn't make sense of
+    UnknownInstruction,
+    /// The constant pool has no room left for the classes or attribute name the frames need
+    ConstantPoolFull,
+}
+
+impl fmt::Display for FrameErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &FrameErrorKind::StackUnderflow => write!(f, "operand stack underflow"),
+            &FrameErrorKind::InconsistentStack => write!(f, "inconsistent operand stacks at join point"),
+            &FrameErrorKind::InvalidLocal(idx) => write!(f, "invalid access to local variable {}", idx),
+            &FrameErrorKind::BadConstant(idx) => write!(f, "invalid constant pool index #{}", idx),
+            &FrameErrorKind::BadDescriptor(ref descriptor) => write!(f, "invalid descriptor {}", descriptor),
+            &FrameErrorKind::InvalidTarget(target) => write!(f, "offset {} is not the start of an instruction", target),
+            &FrameErrorKind::Subroutine => write!(f, "subroutines are not supported"),
+            &FrameErrorKind::Unreachable => write!(f, "unreachable code"),
+            &FrameErrorKind::FallsOffEnd => write!(f, "execution falls off the end of the code"),
+            &FrameErrorKind::UnknownInstruction => write!(f, "unknown instruction"),
+            &FrameErrorKind::ConstantPoolFull => write!(f, "the constant pool is full"),
+        }
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {} of {}", self.kind, self.offset, self.method)
+    }
+}
+
+impl Error for FrameError {}
+
+///
+/// Rebuild the `StackMapTable` of every method that has code, replacing whatever frames the
+/// methods had before. Frames are inferred from the instructions, the exception table and the
+/// method descriptor, and encoded as compactly as possible. `hierarchy` is asked for the common
+/// superclass wherever two different class types meet. Classes older than version 50 don't use
+/// frames and are left untouched.
+pub fn compute_frames<H: ClassHierarchy>(class: &mut Classfile, hierarchy: &H) -> Result<(), FrameError> {
+    if class.version.major_version < 50 {
+        return Ok(());
+    }
+
+    let hierarchy = WithClass { class: ClassInfo::from_class(class), hierarchy: hierarchy };
+    let this_class = hierarchy.class.name.clone();
+
+    for method in class.methods.iter_mut() {
+        let name = class.constant_pool.get_utf8_string(method.name_index.idx as u16).unwrap_or(String::new());
+        let descriptor = class.constant_pool.get_utf8_string(method.descriptor_index.idx as u16).unwrap_or(String::new());
+        let is_static = method.access_flags.has_flag(MethodAccessFlags::Static as u16);
+        let cp = &mut class.constant_pool;
+
+        for attribute in method.attributes.iter_mut() {
+            if let &mut Attribute::Code { max_locals, ref code, ref exception_table, ref mut attributes, .. } = attribute {
+                let error = |(offset, kind)| FrameError { method: format!("{}{}", name, descriptor), offset: offset, kind: kind };
+                let (initial, frames) = {
+                    let analyzed = Analyzer::new(cp, &this_class, &hierarchy).analyze(&name, &descriptor, is_static, max_locals, code, exception_table)?;
+
+                    let initial = Frame::initial(&this_class, &name, &descriptor, is_static, max_locals).map_err(|kind| error((0, kind)))?;
+                    (initial, frame_sites(code, exception_table, analyzed).map_err(error)?)
+                };
+
+                let table = encode_frames(cp, initial, frames).map_err(error)?;
+                let position = attributes.iter().position(|attribute| match attribute { &Attribute::StackMapTable(_) => true, _ => false });
+
+                match (position, table.is_empty()) {
+                    (Some(idx), true) => { attributes.remove(idx); },
+                    (Some(idx), false) => attributes[idx] = Attribute::StackMapTable(table),
+                    (None, true) => (),
+                    (None, false) => {
+                        cp.utf8("StackMapTable").map_err(|_| error((0, FrameErrorKind::ConstantPoolFull)))?;
+                        attributes.push(Attribute::StackMapTable(table));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+type Failure = (usize, FrameErrorKind);
+
+///
+/// Pick the frames of the instructions that need one: branch targets, exception handlers and
+/// instructions following an unconditional jump, return or throw. Every instruction has to be
+/// reachable, there's no way to tell which frame the others need.
+fn frame_sites(code: &[Instruction], exception_table: &[ExceptionHandler], analyzed: BTreeMap<usize, Frame>) -> Result<Vec<(usize, Frame)>, Failure> {
+    let mut sites: BTreeSet<usize> = exception_table.iter().map(|handler| handler.handler_pc as usize).collect();
+    let offsets = Offsets::new(code);
+
+    for position in 0..code.len() {
+        let offset = offsets.offset(position);
+
+        if !analyzed.contains_key(&offset) {
+            return Err((offset, FrameErrorKind::Unreachable));
+        }
+
+        let flow = offsets.flow(code, position).map_err(|target| (offset, FrameErrorKind::InvalidTarget(target)))?;
+        sites.extend(flow.targets().into_iter().map(|target| offsets.offset(target)));
+
+        if !flow.continues() {
+            sites.insert(offsets.offset(position + 1));
+        }
+    }
+
+    Ok(analyzed.into_iter().filter(|&(offset, _)| sites.contains(&offset)).collect())
+}
+
+fn verification_type(cp: &mut ConstantPool, value: &Value) -> Result<VerificationType, ConstantPoolError> {
+    Ok(match value {
+        &Value::Top => VerificationType::Top,
+        &Value::Integer => VerificationType::Integer,
+        &Value::Float => VerificationType::Float,
+        &Value::Long => VerificationType::Long,
+        &Value::Double => VerificationType::Double,
+        &Value::Null => VerificationType::Null,
+        &Value::UninitializedThis => VerificationType::UninitializedThis,
+        &Value::Uninitialized(offset) => VerificationType::Uninitialized { offset: offset as u16 },
+        &Value::Reference(ref name) => VerificationType::Object { cpool_index: cp.class(name)?.into() },
+    })
+}
+
+/// The locals of a frame as listed in the StackMapTable, where longs and doubles take one entry
+/// and trailing unusable slots are left out
+fn verification_locals(cp: &mut ConstantPool, locals: &[Value]) -> Result<Vec<VerificationType>, ConstantPoolError> {
+    let used = locals.iter().rposition(|value| *value != Value::Top).map(|idx| idx + 1).unwrap_or(0);
+    let mut result = vec![];
+    let mut idx = 0;
+
+    while idx < used {
+        result.push(verification_type(cp, &locals[idx])?);
+        idx += if locals[idx].is_wide() { 2 } else { 1 };
+    }
+
+    Ok(result)
+}
+
+fn encode_frames(cp: &mut ConstantPool, initial: Frame, frames: Vec<(usize, Frame)>) -> Result<Vec<StackMapFrame>, Failure> {
+    let mut previous_locals = verification_locals(cp, &initial.locals).map_err(|_| (0, FrameErrorKind::ConstantPoolFull))?;
+    let mut previous_offset = None;
+    let mut table = vec![];
+
+    for (offset, state) in frames {
+        let offset_delta = match previous_offset {
+            Some(previous) => offset - previous - 1,
+            None => offset
+        } as u16;
+
+        let full = |_| (offset, FrameErrorKind::ConstantPoolFull);
+        let locals = verification_locals(cp, &state.locals).map_err(full)?;
+        let stack = state.stack.iter().map(|value| verification_type(cp, value)).collect::<Result<_, _>>().map_err(full)?;
+
+        table.push(encode_frame(&previous_locals, &locals, stack, offset_delta));
+        previous_locals = locals;
+        previous_offset = Some(offset);
+    }
+
+    Ok(table)
+}
+
+/// Pick the most compact encoding of a frame given the locals of the frame before it
+fn encode_frame(previous: &[VerificationType], locals: &[VerificationType], mut stack: Vec<VerificationType>, offset_delta: u16) -> StackMapFrame {
+    if locals == previous {
+        match stack.len() {
+            0 if offset_delta < 64 => return StackMapFrame::SameFrame { tag: offset_delta as u8 },
+            0 => return StackMapFrame::SameFrameExtended { offset_delta: offset_delta },
+            1 if offset_delta < 64 => return StackMapFrame::SameLocals1StackItemFrame { tag: 64 + offset_delta as u8, stack: stack.remove(0) },
+            1 => return StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta: offset_delta, stack: stack.remove(0) },
+            _ => ()
+        }
+    } else if stack.is_empty() && locals.len() > previous.len() && locals.len() - previous.len() <= 3 && locals.starts_with(previous) {
+        return StackMapFrame::AppendFrame {
+            tag: 251 + (locals.len() - previous.len()) as u8,
+            offset_delta: offset_delta,
+            locals: locals[previous.len()..].to_vec(),
+        };
+    } else if stack.is_empty() && previous.len() > locals.len() && previous.len() - locals.len() <= 3 && previous.starts_with(locals) {
+        return StackMapFrame::ChopFrame { tag: 251 - (previous.len() - locals.len()) as u8, offset_delta: offset_delta };
+    }
+
+    StackMapFrame::FullFrame { offset_delta: offset_delta, locals: locals.to_vec(), stack: stack }
+}
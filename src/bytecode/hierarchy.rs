@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use super::classfile::*;
+use super::io::ClassReader;
+use super::io::lazy::LazyClassfile;
+use zip::ZipArchive;
+
+/// The superclass and interfaces of a class, all given as internal names, eg. `java/lang/String`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassInfo {
+    pub name: String,
+    /// `None` only for `java/lang/Object`
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub is_interface: bool,
+}
+
+impl ClassInfo {
+    /// Describe a class from its classfile. Names that can't be resolved are left out.
+    pub fn from_class(class: &Classfile) -> ClassInfo {
+        ClassInfo::describe(&class.constant_pool, &class.access_flags, &class.this_class, &class.super_class, &class.interfaces)
+    }
+
+    /// Describe a class from its header alone, without decoding its members
+    pub fn from_lazy_class(class: &LazyClassfile) -> ClassInfo {
+        ClassInfo::describe(&class.constant_pool, &class.access_flags, &class.this_class, &class.super_class, &class.interfaces)
+    }
+
+    fn describe(cp: &ConstantPool, access_flags: &AccessFlags, this_class: &ConstantPoolIndex, super_class: &ConstantPoolIndex, interfaces: &[ConstantPoolIndex]) -> ClassInfo {
+        ClassInfo {
+            name: cp.get_class_name(this_class.idx as u16).unwrap_or(String::new()),
+            super_class: cp.get_class_name(super_class.idx as u16),
+            interfaces: interfaces.iter().filter_map(|idx| cp.get_class_name(idx.idx as u16)).collect(),
+            is_interface: access_flags.has_flag(ClassAccessFlags::Interface as u16),
+        }
+    }
+}
+
+///
+/// Answers questions about classes other than the one being processed. Merging types when
+/// computing frames and checking assignability when verifying both need to know where a class
+/// sits in the hierarchy, even though the class itself is usually not at hand.
+pub trait ClassHierarchy {
+    /// Look a class up by its internal name, returning `None` if it isn't known
+    fn class_info(&self, name: &str) -> Option<ClassInfo>;
+
+    /// The class itself followed by all of its known superclasses, ending with `java/lang/Object`
+    /// unless part of the chain is unknown
+    fn super_classes(&self, name: &str) -> Vec<String> {
+        let mut chain = vec![ name.to_string() ];
+        let mut seen = HashSet::new();
+        seen.insert(name.to_string());
+
+        while let Some(super_class) = self.class_info(&chain[chain.len() - 1]).and_then(|info| info.super_class) {
+            if !seen.insert(super_class.clone()) {
+                break;
+            }
+
+            chain.push(super_class);
+        }
+
+        chain
+    }
+
+    fn is_interface(&self, name: &str) -> bool {
+        self.class_info(name).map(|info| info.is_interface).unwrap_or(false)
+    }
+
+    /// The most specific type that values of both types can be assigned to, the way the JVM
+    /// verifier merges them. Array types are given as descriptors, eg. `[Ljava/lang/String;`.
+    /// Interfaces merge to `java/lang/Object`, as do classes whose hierarchy isn't fully known.
+    fn common_super_class(&self, a: &str, b: &str) -> String {
+        if a == b {
+            return a.to_string();
+        }
+
+        match (a.starts_with('['), b.starts_with('[')) {
+            (true, true) => match (component_class(a), component_class(b)) {
+                (Some(a), Some(b)) => match self.common_super_class(a, b) {
+                    ref array if array.starts_with('[') => format!("[{}", array),
+                    class => format!("[L{};", class)
+                },
+                _ => String::from("java/lang/Object")
+            },
+            (false, false) if !self.is_interface(a) && !self.is_interface(b) => {
+                let supers = self.super_classes(a);
+
+                self.super_classes(b).into_iter()
+                    .find(|class| supers.contains(class))
+                    .unwrap_or(String::from("java/lang/Object"))
+            },
+            _ => String::from("java/lang/Object")
+        }
+    }
+}
+
+/// A hierarchy that knows no classes at all, so distinct classes always merge to `java/lang/Object`
+pub struct EmptyHierarchy;
+
+impl ClassHierarchy for EmptyHierarchy {
+    fn class_info(&self, _: &str) -> Option<ClassInfo> {
+        None
+    }
+}
+
+/// Extends a hierarchy with one more class, usually the one being processed, which the hierarchy
+/// doesn't know yet
+pub struct WithClass<'h, H: 'h> {
+    pub class: ClassInfo,
+    pub hierarchy: &'h H,
+}
+
+impl<'h, H: ClassHierarchy> ClassHierarchy for WithClass<'h, H> {
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        if name == self.class.name {
+            Some(self.class.clone())
+        } else {
+            self.hierarchy.class_info(name)
+        }
+    }
+}
+
+///
+/// A hierarchy backed by directories and JARs of class files, searched in the given order the way
+/// a class path is. Classes are only read when they are first asked for, and every answer is
+/// cached, including the classes that couldn't be found.
+pub struct ClassPathHierarchy {
+    entries: Vec<ClassPathEntry>,
+    cache: Mutex<HashMap<String, Option<ClassInfo>>>,
+}
+
+enum ClassPathEntry {
+    Directory(PathBuf),
+    Jar(Mutex<ZipArchive<File>>),
+}
+
+impl ClassPathHierarchy {
+    /// Open a class path made of directories and JARs. JARs are opened right away, so that a
+    /// broken one is reported here rather than its classes silently going missing later.
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> io::Result<ClassPathHierarchy> {
+        let entries = paths.iter().map(|path| {
+            let path = path.as_ref();
+
+            if path.is_dir() {
+                Ok(ClassPathEntry::Directory(path.to_path_buf()))
+            } else {
+                let archive = ZipArchive::new(File::open(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+                Ok(ClassPathEntry::Jar(Mutex::new(archive)))
+            }
+        }).collect::<io::Result<Vec<ClassPathEntry>>>()?;
+
+        Ok(ClassPathHierarchy { entries: entries, cache: Mutex::new(HashMap::new()) })
+    }
+
+    /// The bytes of the first class file on the class path with the given internal name
+    fn read_class(&self, name: &str) -> Option<Vec<u8>> {
+        let file_name = format!("{}.class", name);
+
+        self.entries.iter().filter_map(|entry| {
+            let mut bytes = vec![];
+
+            let found = match entry {
+                &ClassPathEntry::Directory(ref dir) => File::open(dir.join(&file_name)).and_then(|mut file| file.read_to_end(&mut bytes)).is_ok(),
+                &ClassPathEntry::Jar(ref archive) => match archive.lock() {
+                    Ok(mut archive) => match archive.by_name(&file_name) {
+                        Ok(mut file) => file.read_to_end(&mut bytes).is_ok(),
+                        Err(_) => false
+                    },
+                    Err(_) => false
+                }
+            };
+
+            if found { Some(bytes) } else { None }
+        }).next()
+    }
+}
+
+impl ClassHierarchy for ClassPathHierarchy {
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(info) = cache.get(name) {
+                return info.clone();
+            }
+        }
+
+        let info = self.read_class(name)
+            .and_then(|bytes| ClassReader::read_lazy(&bytes).ok().map(|class| ClassInfo::from_lazy_class(&class)));
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(name.to_string(), info.clone());
+        }
+
+        info
+    }
+}
+
+///
+/// A hierarchy of the classes the JVM has loaded so far, as seen by the class file load hook. It
+/// is filled by adding each class as it passes through the hook, and can be shared between threads.
+/// Classes are kept along with the loader that defined them, and forgotten once that loader is
+/// unloaded, so classes the JVM no longer has don't pile up.
+pub struct LoadedClassHierarchy {
+    /// The classes by name, along with the loaders that defined a class of that name
+    classes: RwLock<HashMap<String, Vec<(ClassLoaderKey, ClassInfo)>>>,
+}
+
+/// Tells apart the class loaders that define classes, what a key stands for is up to the caller
+pub type ClassLoaderKey = i32;
+
+impl LoadedClassHierarchy {
+    pub fn new() -> LoadedClassHierarchy {
+        LoadedClassHierarchy { classes: RwLock::new(HashMap::new()) }
+    }
+
+    /// Remember a class, replacing an earlier class of the same name defined by the same loader
+    pub fn add(&self, loader: ClassLoaderKey, info: ClassInfo) {
+        if let Ok(mut classes) = self.classes.write() {
+            let defined = classes.entry(info.name.clone()).or_insert_with(Vec::new);
+
+            defined.retain(|&(key, _)| key != loader);
+            defined.push((loader, info));
+        }
+    }
+
+    /// Remember a class handed to the class file load hook
+    pub fn add_class(&self, loader: ClassLoaderKey, class: &LazyClassfile) {
+        self.add(loader, ClassInfo::from_lazy_class(class))
+    }
+
+    /// Forget every class the given loader defined
+    pub fn unload(&self, loader: ClassLoaderKey) {
+        if let Ok(mut classes) = self.classes.write() {
+            for defined in classes.values_mut() {
+                defined.retain(|&(key, _)| key != loader);
+            }
+
+            classes.retain(|_, defined| !defined.is_empty());
+        }
+    }
+
+    /// The number of classes remembered, counting classes of the same name defined by different loaders
+    pub fn len(&self) -> usize {
+        self.classes.read().map(|classes| classes.values().map(|defined| defined.len()).sum()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for LoadedClassHierarchy {
+    fn default() -> Self {
+        LoadedClassHierarchy::new()
+    }
+}
+
+impl ClassHierarchy for LoadedClassHierarchy {
+    /// The class of the given name added last, whichever loader defined it
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        self.classes.read().ok().and_then(|classes| classes.get(name).and_then(|defined| defined.last()).map(|entry| entry.1.clone()))
+    }
+}
+
+/// The class or array type of the components of an array type, or `None` for arrays of primitives
+fn component_class(array: &str) -> Option<&str> {
+    let component = &array[1..];
+
+    if component.starts_with('[') {
+        Some(component)
+    } else if component.starts_with('L') && component.ends_with(';') {
+        Some(&component[1..component.len() - 1])
+    } else {
+        None
+    }
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// An error raised while decoding a class file, pointing at the byte offset and the part of the
+/// class where the problem was found
+#[derive(Debug, PartialEq)]
+pub struct ClassReadError {
+    /// Absolute byte offset into the class file where the offending item starts
+    pub offset: usize,
+    /// The enclosing sections, outermost first, eg. `[Method(2), Attribute("Code")]`
+    pub section: Vec<ClassSection>,
+    pub kind: ClassReadErrorKind,
+}
+
+/// The part of a class file that was being decoded when a read error occurred
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClassSection {
+    /// Magic bytes, version, access flags, this and super class
+    Header,
+    /// A constant pool entry, identified by its constant pool index
+    ConstantPool(u16),
+    Interfaces,
+    /// A field, identified by its position in the field table
+    Field(u16),
+    /// A method, identified by its position in the method table
+    Method(u16),
+    /// An attribute, identified by its name
+    Attribute(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClassReadErrorKind {
+    /// The input ended before the item being read was complete
+    Truncated,
+    /// The input does not start with 0xCAFEBABE
+    BadMagic(u32),
+    /// A constant tag, opcode, frame type or other discriminator that is not defined by the JVM
+    /// specification
+    BadTag(u32),
+    /// A constant pool index that doesn't point to the kind of constant the reader needs
+    BadIndex(u32),
+    /// An attribute whose contents don't add up to its declared attribute_length
+    BadLength { expected: usize, actual: usize },
+    /// A structural constraint was violated, eg. a tableswitch whose high bound is below its low bound
+    Malformed(&'static str),
+    /// The underlying source failed for a reason other than running out of input
+    Io(io::ErrorKind),
+}
+
+impl ClassReadError {
+    pub fn new(offset: usize, section: Vec<ClassSection>, kind: ClassReadErrorKind) -> ClassReadError {
+        ClassReadError { offset: offset, section: section, kind: kind }
+    }
+}
+
+impl fmt::Display for ClassSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ClassSection::Header => write!(f, "header"),
+            &ClassSection::ConstantPool(idx) => write!(f, "constant pool entry #{}", idx),
+            &ClassSection::Interfaces => write!(f, "interfaces"),
+            &ClassSection::Field(idx) => write!(f, "field {}", idx),
+            &ClassSection::Method(idx) => write!(f, "method {}", idx),
+            &ClassSection::Attribute(ref name) => write!(f, "attribute {}", name),
+        }
+    }
+}
+
+impl fmt::Display for ClassReadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ClassReadErrorKind::Truncated => write!(f, "unexpected end of input"),
+            &ClassReadErrorKind::BadMagic(magic) => write!(f, "invalid magic bytes {:#010x}", magic),
+            &ClassReadErrorKind::BadTag(tag) => write!(f, "invalid tag {}", tag),
+            &ClassReadErrorKind::BadIndex(idx) => write!(f, "invalid constant pool index #{}", idx),
+            &ClassReadErrorKind::BadLength { expected, actual } => write!(f, "declared length {} but {} bytes were used", expected, actual),
+            &ClassReadErrorKind::Malformed(reason) => write!(f, "{}", reason),
+            &ClassReadErrorKind::Io(kind) => write!(f, "I/O error: {:?}", kind),
+        }
+    }
+}
+
+impl fmt::Display for ClassReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let section = if self.section.is_empty() {
+            String::from("class file")
+        } else {
+            self.section.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" > ")
+        };
+
+        write!(f, "{} at offset {} ({})", self.kind, self.offset, section)
+    }
+}
+
+impl Error for ClassReadError {}
+
+impl From<ClassReadError> for io::Error {
+    fn from(err: ClassReadError) -> io::Error {
+        let kind = match err.kind {
+            ClassReadErrorKind::Truncated => io::ErrorKind::UnexpectedEof,
+            ClassReadErrorKind::Io(kind) => kind,
+            _ => io::ErrorKind::InvalidData
+        };
+
+        io::Error::new(kind, err.to_string())
+    }
+}
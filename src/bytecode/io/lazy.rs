@@ -0,0 +1,93 @@
+use super::super::classfile::*;
+use super::error::*;
+use super::reader::ClassReader;
+
+///
+/// A class that has only been read as far as needed to tell what it is: the header, the constant
+/// pool, this and super class, the interfaces and the member tables. Attribute tables, including
+/// the `Code` of every method, are left undecoded until they're asked for, which makes deciding
+/// whether a class is interesting at all cheap.
+#[derive(Debug)]
+pub struct LazyClassfile<'a> {
+    /// The bytes the class was read from, to be handed back unchanged if the class isn't modified
+    pub bytes: &'a [u8],
+    pub version: ClassfileVersion,
+    pub constant_pool: ConstantPool<'a>,
+    pub access_flags: AccessFlags,
+    pub this_class: ConstantPoolIndex,
+    pub super_class: ConstantPoolIndex,
+    pub interfaces: Vec<ConstantPoolIndex>,
+    pub fields: Vec<LazyMember<'a>>,
+    pub methods: Vec<LazyMember<'a>>,
+    pub attributes: LazyAttributes<'a>,
+}
+
+impl<'a> LazyClassfile<'a> {
+    /// Decode every attribute table of this class, producing the same class `ClassReader::read_array`
+    /// would have returned
+    pub fn decode(self) -> Result<Classfile<'a>, ClassReadError> {
+        let cp = self.constant_pool;
+
+        let fields = self.fields.into_iter().map(|field| Ok(Field {
+            attributes: field.attributes.decode(&cp)?,
+            access_flags: field.access_flags,
+            name_index: field.name_index,
+            descriptor_index: field.descriptor_index,
+        })).collect::<Result<Vec<Field>, ClassReadError>>()?;
+
+        let methods = self.methods.into_iter().map(|method| Ok(Method {
+            attributes: method.attributes.decode(&cp)?,
+            access_flags: method.access_flags,
+            name_index: method.name_index,
+            descriptor_index: method.descriptor_index,
+        })).collect::<Result<Vec<Method>, ClassReadError>>()?;
+
+        let attributes = self.attributes.decode(&cp)?;
+
+        Ok(Classfile {
+            version: self.version,
+            constant_pool: cp,
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: fields,
+            methods: methods,
+            attributes: attributes,
+        })
+    }
+
+    /// Resolve the name of the class, eg. `java/lang/String`
+    pub fn class_name(&self) -> Option<String> {
+        match self.constant_pool.resolve_index(&self.this_class) {
+            Some(&Constant::Class(ref idx)) => self.constant_pool.get_utf8_string(idx.idx as u16),
+            _ => None
+        }
+    }
+}
+
+/// A field or method whose attributes haven't been decoded yet
+#[derive(Debug)]
+pub struct LazyMember<'a> {
+    pub access_flags: AccessFlags,
+    pub name_index: ConstantPoolIndex,
+    pub descriptor_index: ConstantPoolIndex,
+    pub attributes: LazyAttributes<'a>,
+}
+
+/// An attribute table that has been bounds-checked but not decoded
+#[derive(Debug)]
+pub struct LazyAttributes<'a> {
+    /// The table including its leading attribute count
+    pub bytes: &'a [u8],
+    /// Absolute offset of the table within the class file, for error reporting
+    pub offset: usize,
+    /// The part of the class the table belongs to, for error reporting
+    pub section: Vec<ClassSection>,
+}
+
+impl<'a> LazyAttributes<'a> {
+    pub fn decode(&self, cp: &ConstantPool<'a>) -> Result<Vec<Attribute<'a>>, ClassReadError> {
+        ClassReader::read_lazy_attributes(self, cp)
+    }
+}
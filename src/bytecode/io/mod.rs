@@ -1,5 +1,9 @@
+pub use self::error::*;
+pub use self::lazy::*;
 pub use self::reader::*;
 pub use self::writer::*;
 
+pub mod error;
+pub mod lazy;
 pub mod reader;
 pub mod writer;
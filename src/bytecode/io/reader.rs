@@ -1,272 +1,395 @@
-use std::io::{Cursor, Read, Error, ErrorKind};
-use std::cell::Cell;
+use std::borrow::Cow;
+use std::io::{Read, Error, ErrorKind};
 use super::super::classfile::*;
+use super::super::mutf8;
+use super::error::*;
+use super::lazy::*;
 
 pub struct ClassReader {}
 
+/// Tells whether a constant is of the kind a reference to it expects
+type ConstantCheck = fn(&Constant) -> bool;
+
+/// A step of reading a class, producing the part of the class it is responsible for
+type ReadStage<'a> = fn(&mut BlockReader<'a>, &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError>;
+
 impl ClassReader {
-    pub fn read_class<T>(source: &mut T) -> Result<Classfile, Error> where T: Read {
-        let mut reader = BlockReader::new(source);
-
-        let fns: Vec<fn(&mut BlockReader, &ClassFragment) -> Result<ClassFragment, Error>> = vec![
-            ClassReader::read_magic_bytes,
-            ClassReader::read_classfile_version,
-            ClassReader::read_constant_pool,
-            ClassReader::read_access_flags,
-            ClassReader::read_this_class,
-            ClassReader::read_super_class,
-            ClassReader::read_interfaces,
-            ClassReader::read_fields,
-            ClassReader::read_methods,
-            ClassReader::read_class_attributes
+    /// Reads a class from `source` into a class that owns all its data
+    pub fn read_class<T>(source: &mut T) -> Result<Classfile<'static>, ClassReadError> where T: Read {
+        let mut bytes: Vec<u8> = vec![];
+
+        match source.read_to_end(&mut bytes) {
+            Ok(_) => ClassReader::read_array(&bytes).map(|class| class.into_owned()),
+            Err(err) => Err(ClassReadError::new(bytes.len(), vec![], BlockReader::error_kind(err)))
+        }
+    }
+
+    /// Reads a class directly from `bytes`. `Utf8` constants and the bodies of unrecognised
+    /// attributes are borrowed from `bytes` instead of being copied
+    pub fn read_array<'a>(bytes: &'a [u8]) -> Result<Classfile<'a>, ClassReadError> {
+        let mut reader = BlockReader::new(bytes);
+
+        // every stage is cast on its own, an array of fn items with late-bound lifetimes doesn't unify
+        let fns: Vec<ReadStage<'a>> = vec![
+            ClassReader::read_magic_bytes as ReadStage<'a>,
+            ClassReader::read_classfile_version as ReadStage<'a>,
+            ClassReader::read_constant_pool as ReadStage<'a>,
+            ClassReader::read_access_flags as ReadStage<'a>,
+            ClassReader::read_this_class as ReadStage<'a>,
+            ClassReader::read_super_class as ReadStage<'a>,
+            ClassReader::read_interfaces as ReadStage<'a>,
+            ClassReader::read_fields as ReadStage<'a>,
+            ClassReader::read_methods as ReadStage<'a>,
+            ClassReader::read_class_attributes as ReadStage<'a>
         ];
 
-        let result = fns.iter().fold(Ok(ClassFragment::default()), |acc, x| {
+        ClassReader::read_stages(&mut reader, &fns).map(|fragment| fragment.to_class())
+    }
+
+    /// Reads the header, constant pool, interfaces and member tables of a class, leaving every
+    /// attribute table to be decoded on demand. Attribute tables are only checked for fitting in
+    /// `bytes`, so malformed attributes are reported when they're decoded
+    pub fn read_lazy<'a>(bytes: &'a [u8]) -> Result<LazyClassfile<'a>, ClassReadError> {
+        let mut reader = BlockReader::new(bytes);
+
+        let fns: Vec<ReadStage<'a>> = vec![
+            ClassReader::read_magic_bytes as ReadStage<'a>,
+            ClassReader::read_classfile_version as ReadStage<'a>,
+            ClassReader::read_constant_pool as ReadStage<'a>,
+            ClassReader::read_access_flags as ReadStage<'a>,
+            ClassReader::read_this_class as ReadStage<'a>,
+            ClassReader::read_super_class as ReadStage<'a>,
+            ClassReader::read_interfaces as ReadStage<'a>
+        ];
+
+        let header = ClassReader::read_stages(&mut reader, &fns)?.to_class();
+        let fields = ClassReader::skip_members(&mut reader, &header.constant_pool, ClassSection::Field)?;
+        let methods = ClassReader::skip_members(&mut reader, &header.constant_pool, ClassSection::Method)?;
+
+        Ok(LazyClassfile {
+            bytes: bytes,
+            version: header.version,
+            constant_pool: header.constant_pool,
+            access_flags: header.access_flags,
+            this_class: header.this_class,
+            super_class: header.super_class,
+            interfaces: header.interfaces,
+            fields: fields,
+            methods: methods,
+            attributes: ClassReader::skip_attributes(&mut reader)?,
+        })
+    }
+
+    /// Decodes an attribute table that was skipped by `read_lazy`
+    pub fn read_lazy_attributes<'a>(attributes: &LazyAttributes<'a>, cp: &ConstantPool<'a>) -> Result<Vec<Attribute<'a>>, ClassReadError> {
+        let mut reader = BlockReader::new(attributes.bytes);
+        reader.base = attributes.offset;
+        reader.section = attributes.section.clone();
+
+        ClassReader::read_attributes(&mut reader, cp)
+    }
+
+    fn read_stages<'a>(reader: &mut BlockReader<'a>, fns: &[ReadStage<'a>]) -> Result<ClassFragment<'a>, ClassReadError> {
+        fns.iter().fold(Ok(ClassFragment::default()), |acc, x| {
             match acc {
-                Ok(acc_fragment) => match x(&mut reader, &acc_fragment) {
+                Ok(acc_fragment) => match x(reader, &acc_fragment) {
                     Ok(cur_fragment) => Ok(acc_fragment.merge(cur_fragment)),
                     err @ _ => err
                 },
                 err @ _ => err
             }
-        });
+        })
+    }
 
-        match result {
-            Ok(fragment) => Ok(fragment.to_class()),
-            Err(err) => Err(err)
+    fn skip_members<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool, section: fn(u16) -> ClassSection) -> Result<Vec<LazyMember<'a>>, ClassReadError> {
+        let members_len = reader.read_u16()?;
+        let mut members = vec![];
+
+        for idx in 0..members_len {
+            members.push(reader.within(section(idx), |r| Ok(LazyMember {
+                access_flags: AccessFlags::of(r.read_u16()?),
+                name_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                descriptor_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                attributes: ClassReader::skip_attributes(r)?,
+            }))?);
         }
+
+        Ok(members)
     }
 
-    fn read_magic_bytes(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u32() {
-            Ok(0xCAFEBABE) => Ok(ClassFragment::default()),
-            _ => Err(Error::new(ErrorKind::InvalidData, "Invalid magic bytes"))
+    /// Steps over an attribute table, only checking that every attribute fits in the input
+    fn skip_attributes<'a>(reader: &mut BlockReader<'a>) -> Result<LazyAttributes<'a>, ClassReadError> {
+        let bytes = reader.bytes;
+        let start = reader.position();
+        let offset = reader.offset();
+        let attr_len = reader.read_u16()?;
+
+        for _ in 0..attr_len {
+            reader.read_u16()?;
+            let a_len = reader.read_u32()? as usize;
+            reader.read_n(a_len)?;
         }
+
+        Ok(LazyAttributes {
+            bytes: &bytes[start..reader.position()],
+            offset: offset,
+            section: reader.section.clone(),
+        })
+    }
+
+    fn read_magic_bytes<'a>(reader: &mut BlockReader<'a>, _: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        reader.within(ClassSection::Header, |r| match r.read_u32()? {
+            0xCAFEBABE => Ok(ClassFragment::default()),
+            magic @ _ => r.fail(0, ClassReadErrorKind::BadMagic(magic))
+        })
+    }
+
+    fn read_classfile_version<'a>(reader: &mut BlockReader<'a>, _: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        reader.within(ClassSection::Header, |r| {
+            let minor_version = r.read_u16()?;
+            let major_version = r.read_u16()?;
+
+            Ok(ClassFragment {
+                version: Some(ClassfileVersion::new(major_version, minor_version)),
+                ..Default::default()
+            })
+        })
     }
 
-    fn read_classfile_version(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match (reader.read_u16(), reader.read_u16()) {
-            (Ok(minor_version), Ok(major_version)) => {
-                Ok(ClassFragment {
-                    version: Some(ClassfileVersion::new(major_version, minor_version)),
-                    ..Default::default()
-                })
+    fn read_constant_pool<'a>(reader: &mut BlockReader<'a>, _: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let cp_len = reader.read_u16()? as usize;
+        let mut constants: Vec<Constant> = vec![Constant::Placeholder];
+        let mut offsets = vec![reader.offset()];
+
+        while constants.len() < cp_len {
+            let offset = reader.offset();
+            let constant = reader.within(ClassSection::ConstantPool(constants.len() as u16), |r| {
+                let constant = ClassReader::read_constant(r)?;
+
+                // a long or double in the last slot would claim an index beyond constant_pool_count
+                if constants.len() + constant.cp_size() > cp_len {
+                    r.fail(offset, ClassReadErrorKind::Malformed("8-byte constant overruns the constant pool"))
+                } else {
+                    Ok(constant)
+                }
+            })?;
+            let constant_size = constant.cp_size();
+
+            constants.push(constant);
+            offsets.push(offset);
+
+            for _ in 1..constant_size {
+                constants.push(Constant::Placeholder);
+                offsets.push(offset);
             }
-            _ => Err(Error::new(ErrorKind::UnexpectedEof, "Could not read classfile version number"))
         }
+
+        let cp = ConstantPool::new(constants);
+        ClassReader::check_constant_pool(reader, &cp, &offsets)?;
+
+        Ok(ClassFragment {
+            constant_pool: Some(cp),
+            ..Default::default()
+        })
     }
 
-    fn read_constant_pool(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u16() {
-            Ok(cp_len) => {
-                let mut constants: Vec<Constant> = vec![Constant::Placeholder];
-
-                for _ in 1..cp_len {
-                    if constants.len() < cp_len as usize {
-                        match ClassReader::read_constant(reader) {
-                            Ok(constant) => {
-                                let constant_size = constant.cp_size();
-
-                                constants.push(constant);
-
-                                for _ in 1..constant_size {
-                                    constants.push(Constant::Placeholder);
-                                }
-                            }
-                            Err(err) => return Err(err)
-                        }
-                    }
+    /// Checks that every constant referring to other constants points to constants of the right
+    /// kind, now that all of them are known. Bootstrap method indices point into the
+    /// BootstrapMethods attribute rather than the pool, so they're not checked here
+    fn check_constant_pool<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>, offsets: &[usize]) -> Result<(), ClassReadError> {
+        for (idx, constant) in cp.constants.iter().enumerate() {
+            let references: Vec<(&ConstantPoolIndex, ConstantCheck)> = match constant {
+                &Constant::Class(ref name) | &Constant::String(ref name) | &Constant::MethodType(ref name) | &Constant::Module(ref name) | &Constant::Package(ref name) => vec![ (name, is_utf8) ],
+                &Constant::FieldRef { ref class_index, ref name_and_type_index } |
+                &Constant::MethodRef { ref class_index, ref name_and_type_index } |
+                &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } => vec![ (class_index, is_class), (name_and_type_index, is_name_and_type) ],
+                &Constant::NameAndType { ref name_index, ref descriptor_index } => vec![ (name_index, is_utf8), (descriptor_index, is_utf8) ],
+                &Constant::MethodHandle { ref reference_kind, ref reference_index } => vec![ (reference_index, match reference_kind {
+                    &ReferenceKind::GetField | &ReferenceKind::GetStatic | &ReferenceKind::PutField | &ReferenceKind::PutStatic => is_field_ref,
+                    &ReferenceKind::InvokeVirtual | &ReferenceKind::NewInvokeSpecial => is_method_ref,
+                    &ReferenceKind::InvokeInterface => is_interface_method_ref,
+                    _ => is_any_method_ref
+                }) ],
+                &Constant::Dynamic { ref name_and_type_index, .. } | &Constant::InvokeDynamic { ref name_and_type_index, .. } => vec![ (name_and_type_index, is_name_and_type) ],
+                _ => vec![]
+            };
+
+            for (reference, expected) in references {
+                if !cp.resolve_index(reference).map(expected).unwrap_or(false) {
+                    return reader.within(ClassSection::ConstantPool(idx as u16), |r| r.fail(offsets[idx], ClassReadErrorKind::BadIndex(reference.idx as u32)));
                 }
-
-                Ok(ClassFragment {
-                    constant_pool: Some(ConstantPool::new(constants)),
-                    ..Default::default()
-                })
             }
-            Err(err) => Err(err)
         }
+
+        Ok(())
     }
 
-    fn read_constant(reader: &mut BlockReader) -> Result<Constant, Error> {
-        let tag = reader.read_u8();
+    fn read_constant<'a>(reader: &mut BlockReader<'a>) -> Result<Constant<'a>, ClassReadError> {
+        let offset = reader.offset();
 
-        match tag {
-            Ok(1) => match reader.read_u16() {
-                Ok(str_len) => match reader.read_n(str_len as usize) {
-                    Ok(bytes) => {
-                        Ok(Constant::Utf8(bytes))
-                    }
-                    Err(err) => Err(err)
-                },
-                Err(err) => Err(err)
+        match reader.read_u8()? {
+            1 => {
+                let str_len = reader.read_u16()?;
+                reader.read_n(str_len as usize).map(|bytes| Constant::Utf8(Cow::Borrowed(bytes)))
             },
-            Ok(3) => reader.read_u32().map(|value| Constant::Integer(value)),
-            Ok(4) => reader.read_u32().map(|value| Constant::Float(value)),
-            Ok(5) => reader.read_u64().map(|value| Constant::Long(value)),
-            Ok(6) => reader.read_u64().map(|value| Constant::Double(value)),
-            Ok(7) => reader.read_u16().map(|idx| Constant::Class(ConstantPoolIndex::new(idx as usize))),
-            Ok(8) => reader.read_u16().map(|idx| Constant::String(ConstantPoolIndex::new(idx as usize))),
-            Ok(9) => ClassReader::require_n(reader, 4, |mut r| Constant::FieldRef {
-                class_index: ConstantPoolIndex::new(r.get_u16() as usize),
-                name_and_type_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            3 => reader.read_u32().map(|value| Constant::Integer(value)),
+            4 => reader.read_u32().map(|value| Constant::Float(value)),
+            5 => reader.read_u64().map(|value| Constant::Long(value)),
+            6 => reader.read_u64().map(|value| Constant::Double(value)),
+            7 => ClassReader::read_constant_pool_index(reader).map(|idx| Constant::Class(idx)),
+            8 => ClassReader::read_constant_pool_index(reader).map(|idx| Constant::String(idx)),
+            9 => Ok(Constant::FieldRef {
+                class_index: ClassReader::read_constant_pool_index(reader)?,
+                name_and_type_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(10) => ClassReader::require_n(reader, 4, |mut r| Constant::MethodRef {
-                class_index: ConstantPoolIndex::new(r.get_u16() as usize),
-                name_and_type_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            10 => Ok(Constant::MethodRef {
+                class_index: ClassReader::read_constant_pool_index(reader)?,
+                name_and_type_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(11) => ClassReader::require_n(reader, 4, |mut r| Constant::InterfaceMethodRef {
-                class_index: ConstantPoolIndex::new(r.get_u16() as usize),
-                name_and_type_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            11 => Ok(Constant::InterfaceMethodRef {
+                class_index: ClassReader::read_constant_pool_index(reader)?,
+                name_and_type_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(12) => ClassReader::require_n(reader, 4, |mut r| Constant::NameAndType {
-                name_index: ConstantPoolIndex::new(r.get_u16() as usize),
-                descriptor_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            12 => Ok(Constant::NameAndType {
+                name_index: ClassReader::read_constant_pool_index(reader)?,
+                descriptor_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(15) => ClassReader::require_n(reader, 3, |mut r| Constant::MethodHandle {
-                reference_kind: ReferenceKind::from_u8(r.get_u8()),
-                reference_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            15 => {
+                let kind_offset = reader.offset();
+                let kind = reader.read_u8()?;
+
+                match ReferenceKind::from_u8(kind) {
+                    ReferenceKind::Unknown => reader.fail(kind_offset, ClassReadErrorKind::BadTag(kind as u32)),
+                    reference_kind @ _ => Ok(Constant::MethodHandle {
+                        reference_kind: reference_kind,
+                        reference_index: ClassReader::read_constant_pool_index(reader)?,
+                    })
+                }
+            },
+            16 => ClassReader::read_constant_pool_index(reader).map(|idx| Constant::MethodType(idx)),
+            17 => Ok(Constant::Dynamic {
+                bootstrap_method_attr_index: ClassReader::read_constant_pool_index(reader)?,
+                name_and_type_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(16) => reader.read_u16().map(|idx| Constant::MethodType(ConstantPoolIndex::new(idx as usize))),
-            Ok(18) => ClassReader::require_n(reader, 4, |mut r| Constant::InvokeDynamic {
-                bootstrap_method_attr_index: ConstantPoolIndex::new(r.get_u16() as usize),
-                name_and_type_index: ConstantPoolIndex::new(r.get_u16() as usize),
+            18 => Ok(Constant::InvokeDynamic {
+                bootstrap_method_attr_index: ClassReader::read_constant_pool_index(reader)?,
+                name_and_type_index: ClassReader::read_constant_pool_index(reader)?,
             }),
-            Ok(tag) => Ok(Constant::Unknown(tag)),
-            Err(err) => Err(err)
+            19 => ClassReader::read_constant_pool_index(reader).map(|idx| Constant::Module(idx)),
+            20 => ClassReader::read_constant_pool_index(reader).map(|idx| Constant::Package(idx)),
+            tag @ _ => reader.fail(offset, ClassReadErrorKind::BadTag(tag as u32))
         }
     }
 
-    fn read_access_flags(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u16() {
-            Ok(val) => Ok(ClassFragment {
-                access_flags: Some(AccessFlags::of(val)),
-                ..Default::default()
-            }),
-            Err(err) => Err(err)
-        }
+    fn read_access_flags<'a>(reader: &mut BlockReader<'a>, _: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        reader.within(ClassSection::Header, |r| Ok(ClassFragment {
+            access_flags: Some(AccessFlags::of(r.read_u16()?)),
+            ..Default::default()
+        }))
     }
 
-    fn read_this_class(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match ClassReader::read_constant_pool_index(reader) {
-            Ok(idx) => Ok(ClassFragment {
-                this_class: Some(idx),
-                ..Default::default()
-            }),
-            Err(err) => Err(err)
-        }
-    }
+    fn read_this_class<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
 
-    fn read_super_class(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match ClassReader::read_constant_pool_index(reader) {
-            Ok(idx) => Ok(ClassFragment {
-                super_class: Some(idx),
-                ..Default::default()
-            }),
-            Err(err) => Err(err)
-        }
+        reader.within(ClassSection::Header, |r| Ok(ClassFragment {
+            this_class: Some(ClassReader::read_checked_index(r, cp, false, is_class)?),
+            ..Default::default()
+        }))
     }
 
-    fn read_interfaces(reader: &mut BlockReader, _: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u16() {
-            Ok(ifs_len) => {
-                (0..ifs_len).fold(Ok(vec![]), |acc, _| {
-                    match acc {
-                        Ok(mut ifs) => match ClassReader::read_constant_pool_index(reader) {
-                            Ok(interface) => {
-                                ifs.push(interface);
-                                Ok(ifs)
-                            }
-                            Err(err) => Err(err)
-                        },
-                        err @ _ => err
-                    }
-                })
-            }
-            Err(err) => Err(err)
-        }.map(|ifs| ClassFragment {
-            interfaces: Some(ifs),
+    fn read_super_class<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
+
+        // java/lang/Object and module-info have no superclass
+        reader.within(ClassSection::Header, |r| Ok(ClassFragment {
+            super_class: Some(ClassReader::read_checked_index(r, cp, true, is_class)?),
             ..Default::default()
+        }))
+    }
+
+    fn read_interfaces<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
+
+        reader.within(ClassSection::Interfaces, |r| {
+            let ifs_len = r.read_u16()?;
+
+            Ok(ClassFragment {
+                interfaces: Some(ClassReader::read_table(r, ifs_len as usize, |r| ClassReader::read_checked_index(r, cp, false, is_class))?),
+                ..Default::default()
+            })
         })
     }
 
-    fn read_fields(reader: &mut BlockReader, cf: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u16() {
-            Ok(fields_len) => {
-                (0..fields_len).fold(Ok(vec![]), |acc, _| {
-                    match acc {
-                        Ok(mut fields) => match ClassReader::read_field(reader, cf) {
-                            Ok(field) => {
-                                fields.push(field);
-                                Ok(fields)
-                            }
-                            Err(err) => Err(err)
-                        },
-                        err @ _ => err
+    fn read_fields<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
+        let fields_len = reader.read_u16()?;
+
+        (0..fields_len).fold(Ok(vec![]), |acc, idx| {
+            match acc {
+                Ok(mut fields) => match reader.within(ClassSection::Field(idx), |r| ClassReader::read_field(r, cp)) {
+                    Ok(field) => {
+                        fields.push(field);
+                        Ok(fields)
                     }
-                })
+                    Err(err) => Err(err)
+                },
+                err @ _ => err
             }
-            Err(err) => Err(err)
-        }.map(|fields| ClassFragment {
+        }).map(|fields| ClassFragment {
             fields: Some(fields),
             ..Default::default()
         })
     }
 
-    fn read_field(reader: &mut BlockReader, cf: &ClassFragment) -> Result<Field, Error> {
-        match ClassReader::require_n(reader, 6, |mut r| { (r.get_u16(), r.get_u16(), r.get_u16()) }) {
-            Ok((flags, n_idx, d_idx)) => match ClassReader::read_attributes(reader, cf) {
-                Ok(attributes) => Ok(Field {
-                    access_flags: AccessFlags::of(flags),
-                    name_index: ConstantPoolIndex::new(n_idx as usize),
-                    descriptor_index: ConstantPoolIndex::new(d_idx as usize),
-                    attributes: attributes,
-                }),
-                Err(err) => Err(err)
-            },
-            Err(err) => Err(err)
-        }
+    fn read_field<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>) -> Result<Field<'a>, ClassReadError> {
+        Ok(Field {
+            access_flags: AccessFlags::of(reader.read_u16()?),
+            name_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            descriptor_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            attributes: ClassReader::read_attributes(reader, cp)?,
+        })
     }
 
-    fn read_methods(reader: &mut BlockReader, cf: &ClassFragment) -> Result<ClassFragment, Error> {
-        match reader.read_u16() {
-            Ok(methods_len) => {
-                (0..methods_len).fold(Ok(vec![]), |acc, _| {
-                    match acc {
-                        Ok(mut methods) => match ClassReader::read_method(reader, cf) {
-                            Ok(method) => {
-                                methods.push(method);
-                                Ok(methods)
-                            }
-                            Err(err) => Err(err)
-                        },
-                        err @ _ => err
+    fn read_methods<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
+        let methods_len = reader.read_u16()?;
+
+        (0..methods_len).fold(Ok(vec![]), |acc, idx| {
+            match acc {
+                Ok(mut methods) => match reader.within(ClassSection::Method(idx), |r| ClassReader::read_method(r, cp)) {
+                    Ok(method) => {
+                        methods.push(method);
+                        Ok(methods)
                     }
-                })
+                    Err(err) => Err(err)
+                },
+                err @ _ => err
             }
-            Err(err) => Err(err)
-        }.map(|methods| ClassFragment {
+        }).map(|methods| ClassFragment {
             methods: Some(methods),
             ..Default::default()
         })
     }
 
-    fn read_method(reader: &mut BlockReader, cf: &ClassFragment) -> Result<Method, Error> {
-        match ClassReader::require_n(reader, 6, |mut r| { (r.get_u16(), r.get_u16(), r.get_u16()) }) {
-            Ok((flags, n_idx, d_idx)) => match ClassReader::read_attributes(reader, cf) {
-                Ok(attributes) => Ok(Method {
-                    access_flags: AccessFlags::of(flags),
-                    name_index: ConstantPoolIndex::new(n_idx as usize),
-                    descriptor_index: ConstantPoolIndex::new(d_idx as usize),
-                    attributes: attributes,
-                }),
-                Err(err) => Err(err)
-            },
-            Err(err) => Err(err)
-        }
+    fn read_method<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>) -> Result<Method<'a>, ClassReadError> {
+        Ok(Method {
+            access_flags: AccessFlags::of(reader.read_u16()?),
+            name_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            descriptor_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            attributes: ClassReader::read_attributes(reader, cp)?,
+        })
     }
 
-    fn read_class_attributes(reader: &mut BlockReader, cf: &ClassFragment) -> Result<ClassFragment, Error> {
-        match ClassReader::read_attributes(reader, cf) {
+    fn read_class_attributes<'a>(reader: &mut BlockReader<'a>, cf: &ClassFragment<'a>) -> Result<ClassFragment<'a>, ClassReadError> {
+        let empty = ConstantPool::default();
+        let cp = cf.constant_pool.as_ref().unwrap_or(&empty);
+
+        match ClassReader::read_attributes(reader, cp) {
             Ok(attributes) => Ok(ClassFragment {
                 attributes: Some(attributes),
                 ..Default::default()
@@ -275,73 +398,66 @@ impl ClassReader {
         }
     }
 
-    fn read_attributes(reader: &mut BlockReader, cf: &ClassFragment) -> Result<Vec<Attribute>, Error> {
-        match reader.read_u16() {
-            Ok(attr_len) => (0..attr_len).fold(Ok(vec![]), |acc, _| {
-                match acc {
-                    Ok(mut attributes) => match ClassReader::read_attribute(reader, cf) {
-                        Ok(attribute) => {
-                            attributes.push(attribute);
-                            Ok(attributes)
-                        }
-                        Err(err) => Err(err)
-                    },
-                    err @ _ => err
-                }
-            }),
-            Err(err) => Err(err)
-        }
-    }
+    fn read_attributes<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>) -> Result<Vec<Attribute<'a>>, ClassReadError> {
+        let attr_len = reader.read_u16()?;
 
-    fn read_attribute(reader: &mut BlockReader, cf: &ClassFragment) -> Result<Attribute, Error> {
-        match reader.read_u16() {
-            Ok(n_idx) => match reader.read_u32() {
-                Ok(a_len) => match reader.read_n(a_len as usize) {
-                    Ok(mut bytes) => Ok(ClassReader::parse_attribute(n_idx, BlockReader::new(&mut Cursor::new(&mut bytes)), cf)),
-                    Err(err) => Err(err)
-                },
-                Err(err) => Err(err)
-            },
-            Err(err) => Err(err)
-        }
+        ClassReader::read_table(reader, attr_len as usize, |r| ClassReader::read_attribute(r, cp))
     }
 
-    fn parse_code(len: usize, reader: &mut BlockReader) -> Vec<Instruction> {
-        /*
-        let read_bytes: Cell<usize> = Cell::new(0);
+    fn read_attribute<'a>(reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>) -> Result<Attribute<'a>, ClassReadError> {
+        let offset = reader.offset();
+        let n_idx = reader.read_u16()?;
 
-        (0..len).take_while(|_| read_bytes.get() < len).map(|_| {
-            let instruction = ClassReader::parse_instruction(reader, read_bytes.get());
+        // attributes are told apart by their name, so the name index must resolve to a Utf8 constant
+        let name = match cp.get_utf8(n_idx) {
+            Some(bytes) => mutf8::decode_lossy(bytes).into_owned(),
+            None => return reader.fail(offset, ClassReadErrorKind::BadIndex(n_idx as u32))
+        };
 
-            read_bytes.set(read_bytes.get() + instruction.len());
-            instruction
-        }).collect()*/
-        let read_bytes: Cell<usize> = Cell::new(0);
+        let a_len = reader.read_u32()? as usize;
 
-        (0..len).take_while(|_| read_bytes.get() < len).map(|_| {
-            let instruction = ClassReader::parse_instruction(reader, read_bytes.get());
+        reader.within(ClassSection::Attribute(name.clone()), |r| r.descend(|r| {
+            let mut attr_reader = r.read_block(a_len)?;
 
-            read_bytes.set(reader.position());
-            instruction
-        }).collect()
+            let attribute = ClassReader::parse_attribute(n_idx, &name, &mut attr_reader, cp)?;
+
+            if attr_reader.position() != a_len {
+                attr_reader.fail(offset, ClassReadErrorKind::BadLength { expected: a_len, actual: attr_reader.position() })
+            } else {
+                Ok(attribute)
+            }
+        }))
+    }
+
+    fn parse_code(len: usize, reader: &mut BlockReader, cp: &ConstantPool) -> Result<Vec<Instruction>, ClassReadError> {
+        let mut instructions = vec![];
+
+        while reader.position() < len {
+            let current_offset = reader.position();
+
+            instructions.push(ClassReader::parse_instruction(reader, cp, current_offset)?);
+        }
+
+        Ok(instructions)
     }
 
-    fn parse_instruction(reader: &mut BlockReader, current_offset: usize) -> Instruction {
-        let opcode = reader.get_u8();
+    fn parse_instruction(reader: &mut BlockReader, cp: &ConstantPool, current_offset: usize) -> Result<Instruction, ClassReadError> {
+        let offset = reader.offset();
+        let opcode = reader.read_u8()?;
 
         let instruction = match opcode {
             0x32 => Instruction::AALOAD,
             0x53 => Instruction::AASTORE,
             0x01 => Instruction::ACONST_NULL,
-            0x19 => Instruction::ALOAD(reader.get_u8()),
+            0x19 => Instruction::ALOAD(reader.read_u8()?),
             0x2a => Instruction::ALOAD_0,
             0x2b => Instruction::ALOAD_1,
             0x2c => Instruction::ALOAD_2,
             0x2d => Instruction::ALOAD_3,
-            0xbd => Instruction::ANEWARRAY(reader.get_u16()),
+            0xbd => Instruction::ANEWARRAY(ClassReader::read_operand(reader, cp, is_class)?),
             0xb0 => Instruction::ARETURN,
             0xbe => Instruction::ARRAYLENGTH,
-            0x3a => Instruction::ASTORE(reader.get_u8()),
+            0x3a => Instruction::ASTORE(reader.read_u8()?),
             0x4b => Instruction::ASTORE_0,
             0x4c => Instruction::ASTORE_1,
             0x4d => Instruction::ASTORE_2,
@@ -349,10 +465,10 @@ impl ClassReader {
             0xbf => Instruction::ATHROW,
             0x33 => Instruction::BALOAD,
             0x54 => Instruction::BASTORE,
-            0x10 => Instruction::BIPUSH(reader.get_u8()),
+            0x10 => Instruction::BIPUSH(reader.read_u8()?),
             0x34 => Instruction::CALOAD,
             0x55 => Instruction::CASTORE,
-            0xc0 => Instruction::CHECKCAST(reader.get_u16()),
+            0xc0 => Instruction::CHECKCAST(ClassReader::read_operand(reader, cp, is_class)?),
             0x90 => Instruction::D2F,
             0x8e => Instruction::D2I,
             0x8f => Instruction::D2L,
@@ -364,7 +480,7 @@ impl ClassReader {
             0x0e => Instruction::DCONST_0,
             0x0f => Instruction::DCONST_1,
             0x6f => Instruction::DDIV,
-            0x18 => Instruction::DLOAD(reader.get_u8()),
+            0x18 => Instruction::DLOAD(reader.read_u8()?),
             0x26 => Instruction::DLOAD_0,
             0x27 => Instruction::DLOAD_1,
             0x28 => Instruction::DLOAD_2,
@@ -373,7 +489,7 @@ impl ClassReader {
             0x77 => Instruction::DNEG,
             0x73 => Instruction::DREM,
             0xaf => Instruction::DRETURN,
-            0x39 => Instruction::DSTORE(reader.get_u8()),
+            0x39 => Instruction::DSTORE(reader.read_u8()?),
             0x47 => Instruction::DSTORE_0,
             0x48 => Instruction::DSTORE_1,
             0x49 => Instruction::DSTORE_2,
@@ -397,7 +513,7 @@ impl ClassReader {
             0x0c => Instruction::FCONST_1,
             0x0d => Instruction::FCONST_2,
             0x6e => Instruction::FDIV,
-            0x17 => Instruction::FLOAD(reader.get_u8()),
+            0x17 => Instruction::FLOAD(reader.read_u8()?),
             0x22 => Instruction::FLOAD_0,
             0x23 => Instruction::FLOAD_1,
             0x24 => Instruction::FLOAD_2,
@@ -406,16 +522,16 @@ impl ClassReader {
             0x76 => Instruction::FNEG,
             0x72 => Instruction::FREM,
             0xae => Instruction::FRETURN,
-            0x38 => Instruction::FSTORE(reader.get_u8()),
+            0x38 => Instruction::FSTORE(reader.read_u8()?),
             0x43 => Instruction::FSTORE_0,
             0x44 => Instruction::FSTORE_1,
             0x45 => Instruction::FSTORE_2,
             0x46 => Instruction::FSTORE_3,
             0x66 => Instruction::FSUB,
-            0xb4 => Instruction::GETFIELD(reader.get_u16()),
-            0xb2 => Instruction::GETSTATIC(reader.get_u16()),
-            0xa7 => Instruction::GOTO(reader.get_u16() as i16),
-            0xc8 => Instruction::GOTO_W(reader.get_u32() as i32),
+            0xb4 => Instruction::GETFIELD(ClassReader::read_operand(reader, cp, is_field_ref)?),
+            0xb2 => Instruction::GETSTATIC(ClassReader::read_operand(reader, cp, is_field_ref)?),
+            0xa7 => Instruction::GOTO(reader.read_u16()? as i16),
+            0xc8 => Instruction::GOTO_W(reader.read_u32()? as i32),
             0x91 => Instruction::I2B,
             0x92 => Instruction::I2C,
             0x87 => Instruction::I2D,
@@ -434,42 +550,42 @@ impl ClassReader {
             0x07 => Instruction::ICONST_4,
             0x08 => Instruction::ICONST_5,
             0x6c => Instruction::IDIV,
-            0xa5 => Instruction::IF_ACMPEQ(reader.get_u16() as i16),
-            0xa6 => Instruction::IF_ACMPNE(reader.get_u16() as i16),
-            0x9f => Instruction::IF_ICMPEQ(reader.get_u16() as i16),
-            0xa0 => Instruction::IF_ICMPNE(reader.get_u16() as i16),
-            0xa1 => Instruction::IF_ICMPLT(reader.get_u16() as i16),
-            0xa2 => Instruction::IF_ICMPGE(reader.get_u16() as i16),
-            0xa3 => Instruction::IF_ICMPGT(reader.get_u16() as i16),
-            0xa4 => Instruction::IF_ICMPLE(reader.get_u16() as i16),
-            0x99 => Instruction::IFEQ(reader.get_u16() as i16),
-            0x9a => Instruction::IFNE(reader.get_u16() as i16),
-            0x9b => Instruction::IFLT(reader.get_u16() as i16),
-            0x9c => Instruction::IFGE(reader.get_u16() as i16),
-            0x9d => Instruction::IFGT(reader.get_u16() as i16),
-            0x9e => Instruction::IFLE(reader.get_u16() as i16),
-            0xc7 => Instruction::IFNONNULL(reader.get_u16() as i16),
-            0xc6 => Instruction::IFNULL(reader.get_u16() as i16),
-            0x84 => Instruction::IINC(reader.get_u8(), reader.get_u8() as i8),
-            0x15 => Instruction::ILOAD(reader.get_u8()),
+            0xa5 => Instruction::IF_ACMPEQ(reader.read_u16()? as i16),
+            0xa6 => Instruction::IF_ACMPNE(reader.read_u16()? as i16),
+            0x9f => Instruction::IF_ICMPEQ(reader.read_u16()? as i16),
+            0xa0 => Instruction::IF_ICMPNE(reader.read_u16()? as i16),
+            0xa1 => Instruction::IF_ICMPLT(reader.read_u16()? as i16),
+            0xa2 => Instruction::IF_ICMPGE(reader.read_u16()? as i16),
+            0xa3 => Instruction::IF_ICMPGT(reader.read_u16()? as i16),
+            0xa4 => Instruction::IF_ICMPLE(reader.read_u16()? as i16),
+            0x99 => Instruction::IFEQ(reader.read_u16()? as i16),
+            0x9a => Instruction::IFNE(reader.read_u16()? as i16),
+            0x9b => Instruction::IFLT(reader.read_u16()? as i16),
+            0x9c => Instruction::IFGE(reader.read_u16()? as i16),
+            0x9d => Instruction::IFGT(reader.read_u16()? as i16),
+            0x9e => Instruction::IFLE(reader.read_u16()? as i16),
+            0xc7 => Instruction::IFNONNULL(reader.read_u16()? as i16),
+            0xc6 => Instruction::IFNULL(reader.read_u16()? as i16),
+            0x84 => Instruction::IINC(reader.read_u8()?, reader.read_u8()? as i8),
+            0x15 => Instruction::ILOAD(reader.read_u8()?),
             0x1a => Instruction::ILOAD_0,
             0x1b => Instruction::ILOAD_1,
             0x1c => Instruction::ILOAD_2,
             0x1d => Instruction::ILOAD_3,
             0x68 => Instruction::IMUL,
             0x74 => Instruction::INEG,
-            0xc1 => Instruction::INSTANCEOF(reader.get_u16()),
-            0xba => (Instruction::INVOKEDYNAMIC(reader.get_u16()), reader.get_u16()).0,
-            0xb9 => (Instruction::INVOKEINTERFACE(reader.get_u16(), reader.get_u8()), reader.get_u8()).0,
-            0xb7 => Instruction::INVOKESPECIAL(reader.get_u16()),
-            0xb8 => Instruction::INVOKESTATIC(reader.get_u16()),
-            0xb6 => Instruction::INVOKEVIRTUAL(reader.get_u16()),
+            0xc1 => Instruction::INSTANCEOF(ClassReader::read_operand(reader, cp, is_class)?),
+            0xba => (Instruction::INVOKEDYNAMIC(ClassReader::read_operand(reader, cp, is_invoke_dynamic)?), reader.read_u16()?).0,
+            0xb9 => (Instruction::INVOKEINTERFACE(ClassReader::read_operand(reader, cp, is_interface_method_ref)?, reader.read_u8()?), reader.read_u8()?).0,
+            0xb7 => Instruction::INVOKESPECIAL(ClassReader::read_operand(reader, cp, is_any_method_ref)?),
+            0xb8 => Instruction::INVOKESTATIC(ClassReader::read_operand(reader, cp, is_any_method_ref)?),
+            0xb6 => Instruction::INVOKEVIRTUAL(ClassReader::read_operand(reader, cp, is_method_ref)?),
             0x80 => Instruction::IOR,
             0x70 => Instruction::IREM,
             0xac => Instruction::IRETURN,
             0x78 => Instruction::ISHL,
             0x7a => Instruction::ISHR,
-            0x36 => Instruction::ISTORE(reader.get_u8()),
+            0x36 => Instruction::ISTORE(reader.read_u8()?),
             0x3b => Instruction::ISTORE_0,
             0x3c => Instruction::ISTORE_1,
             0x3d => Instruction::ISTORE_2,
@@ -477,8 +593,8 @@ impl ClassReader {
             0x64 => Instruction::ISUB,
             0x7c => Instruction::IUSHR,
             0x82 => Instruction::IXOR,
-            0xa8 => Instruction::JSR(reader.get_u16() as i16),
-            0xc9 => Instruction::JSR_W(reader.get_u32() as i32),
+            0xa8 => Instruction::JSR(reader.read_u16()? as i16),
+            0xc9 => Instruction::JSR_W(reader.read_u32()? as i32),
             0x8a => Instruction::L2D,
             0x89 => Instruction::L2F,
             0x88 => Instruction::L2I,
@@ -489,11 +605,15 @@ impl ClassReader {
             0x94 => Instruction::LCMP,
             0x09 => Instruction::LCONST_0,
             0x0a => Instruction::LCONST_1,
-            0x12 => Instruction::LDC(reader.get_u8()),
-            0x13 => Instruction::LDC_W(reader.get_u16()),
-            0x14 => Instruction::LDC2_W(reader.get_u16()),
+            0x12 => {
+                let idx = reader.read_u8()?;
+                ClassReader::check_index(reader, offset + 1, cp, idx as usize, is_loadable)?;
+                Instruction::LDC(idx)
+            },
+            0x13 => Instruction::LDC_W(ClassReader::read_operand(reader, cp, is_loadable)?),
+            0x14 => Instruction::LDC2_W(ClassReader::read_operand(reader, cp, is_wide_loadable)?),
             0x6d => Instruction::LDIV,
-            0x16 => Instruction::LLOAD(reader.get_u8()),
+            0x16 => Instruction::LLOAD(reader.read_u8()?),
             0x1e => Instruction::LLOAD_0,
             0x1f => Instruction::LLOAD_1,
             0x20 => Instruction::LLOAD_2,
@@ -502,20 +622,22 @@ impl ClassReader {
             0x75 => Instruction::LNEG,
             0xab => {
                 let padding = (4 - ((current_offset + 1) % 4)) % 4;
-                let _ = reader.get_n(padding);
-                let default = reader.get_u32() as i32;
-                let n = reader.get_u32();
+                reader.read_n(padding)?;
+                let default = reader.read_u32()? as i32;
+                let n = reader.read_u32()? as i32;
+
+                if n < 0 {
+                    return reader.fail(offset, ClassReadErrorKind::Malformed("lookupswitch has a negative number of pairs"));
+                }
 
-                Instruction::LOOKUPSWITCH(default, {
-                    (0..n).map(|_| (reader.get_u32() as i32, reader.get_u32() as i32)).collect()
-                })
+                Instruction::LOOKUPSWITCH(default, ClassReader::read_table(reader, n as usize, |r| Ok((r.read_u32()? as i32, r.read_u32()? as i32)))?)
             }
             0x81 => Instruction::LOR,
             0x71 => Instruction::LREM,
             0xad => Instruction::LRETURN,
             0x79 => Instruction::LSHL,
             0x7b => Instruction::LSHR,
-            0x37 => Instruction::LSTORE(reader.get_u8()),
+            0x37 => Instruction::LSTORE(reader.read_u8()?),
             0x3f => Instruction::LSTORE_0,
             0x40 => Instruction::LSTORE_1,
             0x41 => Instruction::LSTORE_2,
@@ -525,33 +647,38 @@ impl ClassReader {
             0x83 => Instruction::LXOR,
             0xc2 => Instruction::MONITORENTER,
             0xc3 => Instruction::MONITOREXIT,
-            0xc5 => Instruction::MULTIANEWARRAY(reader.get_u16(), reader.get_u8()),
-            0xbb => Instruction::NEW(reader.get_u16()),
-            0xbc => Instruction::NEWARRAY(reader.get_u8()),
+            0xc5 => Instruction::MULTIANEWARRAY(ClassReader::read_operand(reader, cp, is_class)?, reader.read_u8()?),
+            0xbb => Instruction::NEW(ClassReader::read_operand(reader, cp, is_class)?),
+            0xbc => Instruction::NEWARRAY(reader.read_u8()?),
             0x00 => Instruction::NOP,
             0x57 => Instruction::POP,
             0x58 => Instruction::POP2,
-            0xb5 => Instruction::PUTFIELD(reader.get_u16()),
-            0xb3 => Instruction::PUTSTATIC(reader.get_u16()),
-            0xa9 => Instruction::RET(reader.get_u8()),
+            0xb5 => Instruction::PUTFIELD(ClassReader::read_operand(reader, cp, is_field_ref)?),
+            0xb3 => Instruction::PUTSTATIC(ClassReader::read_operand(reader, cp, is_field_ref)?),
+            0xa9 => Instruction::RET(reader.read_u8()?),
             0xb1 => Instruction::RETURN,
             0x35 => Instruction::SALOAD,
             0x56 => Instruction::SASTORE,
-            0x11 => Instruction::SIPUSH(reader.get_u16()),
+            0x11 => Instruction::SIPUSH(reader.read_u16()?),
             0x5f => Instruction::SWAP,
             0xaa => {
                 let padding = (4 - ((current_offset + 1) % 4)) % 4;
-                let _ = reader.get_n(padding);
+                reader.read_n(padding)?;
 
-                let default = reader.get_u32() as i32;
-                let low = reader.get_u32() as i32;
-                let high = reader.get_u32() as i32;
+                let default = reader.read_u32()? as i32;
+                let low = reader.read_u32()? as i32;
+                let high = reader.read_u32()? as i32;
 
-                Instruction::TABLESWITCH(default, low, high, (0..high - low + 1).map(|_| reader.get_u32() as i32).collect())
+                if high < low {
+                    return reader.fail(offset, ClassReadErrorKind::Malformed("tableswitch high bound is below its low bound"));
+                }
+
+                let n = (high as i64 - low as i64 + 1) as usize;
+                Instruction::TABLESWITCH(default, low, high, ClassReader::read_table(reader, n, |r| Ok(r.read_u32()? as i32))?)
             }
             0xc4 => {
-                let opcode = reader.get_u8();
-                let index = reader.get_u16();
+                let opcode = reader.read_u8()?;
+                let index = reader.read_u16()?;
 
                 match opcode {
                     0x15 => Instruction::ILOAD_W(index),
@@ -566,454 +693,609 @@ impl ClassReader {
                     0x39 => Instruction::DSTORE_W(index),
                     0xa9 => Instruction::RET_W(index),
                     0x84 => {
-                        let constbyte = reader.get_u16();
+                        let constbyte = reader.read_u16()?;
                         Instruction::IINC_W(index, constbyte as i16)
                     }
-                    // This should not be happening
-                    _ => Instruction::WTF(opcode as u32)
+                    _ => return reader.fail(offset, ClassReadErrorKind::BadTag(opcode as u32))
                 }
             }
-            _ => Instruction::WTF(opcode as u32)
+            _ => return reader.fail(offset, ClassReadErrorKind::BadTag(opcode as u32))
         };
 
-        instruction
+        Ok(instruction)
     }
 
-    fn parse_attribute(idx: u16, mut reader: BlockReader, cf: &ClassFragment) -> Attribute {
-        match cf.constant_pool {
-            Some(ref cp) => match cp.get_utf8_string(idx) {
-                Some(ref s) => match s.as_str() {
-                    "ConstantValue" => Some(Attribute::ConstantValue(ConstantPoolIndex::new(reader.get_u16() as usize))),
-                    "Code" => Some(Attribute::Code {
-                        max_stack: reader.get_u16(),
-                        max_locals: reader.get_u16(),
-                        code: {
-                            let n = reader.get_u32() as usize;
-                            ClassReader::parse_code(n, &mut BlockReader::new(&mut Cursor::new(reader.get_n(n as usize))))
-                        },
-                        exception_table: {
-                            let n = reader.get_u16();
-                            (0..n).map(|_| ExceptionHandler { start_pc: reader.get_u16(), end_pc: reader.get_u16(), handler_pc: reader.get_u16(), catch_type: ConstantPoolIndex::new(reader.get_u16() as usize) }).collect()
-                        },
-                        attributes: ClassReader::read_attributes(&mut reader, cf).unwrap_or(vec![]),
-                    }),
-                    "StackMapTable" => Some(Attribute::StackMapTable({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| {
-                            let frame_type = reader.get_u8();
-
-                            let read_verification_type = |r: &mut BlockReader| match r.get_u8() {
-                                0 => VerificationType::Top,
-                                1 => VerificationType::Integer,
-                                2 => VerificationType::Float,
-                                3 => VerificationType::Double,
-                                4 => VerificationType::Long,
-                                5 => VerificationType::Null,
-                                6 => VerificationType::UninitializedThis,
-                                7 => VerificationType::Object { cpool_index: ConstantPoolIndex::new(r.get_u16() as usize) },
-                                8 => VerificationType::Uninitialized { offset: r.get_u16() },
-                                _ => VerificationType::Top
-                            };
-
-                            match frame_type {
-                                tag @ 0...63 => StackMapFrame::SameFrame { tag: tag },
-                                tag @ 64...127 => StackMapFrame::SameLocals1StackItemFrame { tag: tag, stack: read_verification_type(&mut reader) },
-                                247 => StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta: reader.get_u16(), stack: read_verification_type(&mut reader) },
-                                tag @ 248...250 => StackMapFrame::ChopFrame { tag: tag, offset_delta: reader.get_u16() },
-                                251 => StackMapFrame::SameFrameExtended { offset_delta: reader.get_u16() },
-                                tag @ 252...254 => StackMapFrame::AppendFrame { tag: tag, offset_delta: reader.get_u16(), locals: (0..tag - 251).map(|_| read_verification_type(&mut reader)).collect() },
-                                255 => StackMapFrame::FullFrame {
-                                    offset_delta: reader.get_u16(),
-                                    locals: {
-                                        let n = reader.get_u16();
-                                        (0..n).map(|_| read_verification_type(&mut reader)).collect()
-                                    },
-                                    stack: {
-                                        let n = reader.get_u16();
-                                        (0..n).map(|_| read_verification_type(&mut reader)).collect()
-                                    },
-                                },
-                                tag @ _ => StackMapFrame::FutureUse { tag: tag },
-                            }
-                        }).collect()
-                    })),
-                    "Exceptions" => Some(Attribute::Exceptions({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| ConstantPoolIndex::new(reader.get_u16() as usize)).collect()
-                    })),
-                    "InnerClasses" => Some(Attribute::InnerClasses({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| InnerClass {
-                            inner_class_info_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            outer_class_info_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            inner_name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            access_flags: AccessFlags::of(reader.get_u16()),
-                        }).collect()
-                    })),
-                    "EnclosingMethod" => Some(Attribute::EnclosingMethod { class_index: ConstantPoolIndex::new(reader.get_u16() as usize), method_index: ConstantPoolIndex::new(reader.get_u16() as usize) }),
-                    "Synthetic" => Some(Attribute::Synthetic),
-                    "Signature" => Some(Attribute::Signature(ConstantPoolIndex::new(reader.get_u16() as usize))),
-                    "SourceFile" => Some(Attribute::SourceFile(ConstantPoolIndex::new(reader.get_u16() as usize))),
-                    "SourceDebugExtension" => Some(Attribute::SourceDebugExtension(reader.get_bytes())),
-                    "LineNumberTable" => Some(Attribute::LineNumberTable({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| LineNumberTable {
-                            start_pc: reader.get_u16(),
-                            line_number: reader.get_u16(),
-                        }).collect()
-                    })),
-                    "LocalVariableTable" => Some(Attribute::LocalVariableTable({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| LocalVariableTable {
-                            start_pc: reader.get_u16(),
-                            length: reader.get_u16(),
-                            name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            descriptor_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            index: reader.get_u16(),
-                        }).collect()
-                    })),
-                    "LocalVariableTypeTable" => Some(Attribute::LocalVariableTypeTable({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| LocalVariableTypeTable {
-                            start_pc: reader.get_u16(),
-                            length: reader.get_u16(),
-                            name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            signature_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            index: reader.get_u16(),
-                        }).collect()
-                    })),
-                    "Deprecated" => Some(Attribute::Deprecated),
-                    "RuntimeVisibleAnnotations" => Some(Attribute::RuntimeVisibleAnnotations({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| ClassReader::read_annotation(&mut reader)).collect()
-                    })),
-                    "RuntimeInvisibleAnnotations" => Some(Attribute::RuntimeInvisibleAnnotations({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| ClassReader::read_annotation(&mut reader)).collect()
-                    })),
-                    "RuntimeVisibleParameterAnnotations" => Some(Attribute::RuntimeVisibleParameterAnnotations({
-                        let n = reader.get_u8();
-
-                        (0..n).map(|_| {
-                            let m = reader.get_u16();
-                            (0..m).map(|_| ClassReader::read_annotation(&mut reader)).collect()
-                        }).collect()
-                    })),
-                    "RuntimeInvisibleParameterAnnotations" => Some(Attribute::RuntimeInvisibleParameterAnnotations({
-                        let n = reader.get_u8();
-
-                        (0..n).map(|_| {
-                            let m = reader.get_u16();
-                            (0..m).map(|_| ClassReader::read_annotation(&mut reader)).collect()
-                        }).collect()
-                    })),
-                    "RuntimeVisibleTypeAnnotations" => Some(Attribute::RuntimeVisibleTypeAnnotations({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| ClassReader::read_type_annotation(&mut reader)).collect()
-                    })),
-                    "AnnotationDefault" => Some(Attribute::AnnotationDefault(ClassReader::read_element_value(&mut reader))),
-                    "BootstrapMethods" => Some(Attribute::BootstrapMethods({
-                        let n = reader.get_u16();
-                        (0..n).map(|_| BootstrapMethod {
-                            bootstrap_method_ref: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            bootstrap_arguments: {
-                                let m = reader.get_u16();
-                                (0..m).map(|_| ConstantPoolIndex::new(reader.get_u16() as usize)).collect()
-                            },
-                        }).collect()
-                    })),
-                    "MethodParameters" => Some(Attribute::MethodParameters({
-                        let n = reader.get_u8();
-                        (0..n).map(|_| MethodParameter {
-                            name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                            access_flags: AccessFlags::of(reader.get_u16()),
-                        }).collect()
-                    })),
-                    _ => None
+    fn parse_attribute<'a>(idx: u16, name: &str, reader: &mut BlockReader<'a>, cp: &ConstantPool<'a>) -> Result<Attribute<'a>, ClassReadError> {
+        Ok(match name {
+            "ConstantValue" => Attribute::ConstantValue(ClassReader::read_checked_index(reader, cp, false, is_loadable_value)?),
+            "Code" => Attribute::Code {
+                max_stack: reader.read_u16()?,
+                max_locals: reader.read_u16()?,
+                code: {
+                    let n = reader.read_u32()? as usize;
+                    let mut code_reader = reader.read_block(n)?;
+
+                    ClassReader::parse_code(n, &mut code_reader, cp)?
+                },
+                exception_table: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| Ok(ExceptionHandler {
+                        start_pc: r.read_u16()?,
+                        end_pc: r.read_u16()?,
+                        handler_pc: r.read_u16()?,
+                        catch_type: ClassReader::read_checked_index(r, cp, true, is_class)?
+                    }))?
                 },
-                _ => None
+                attributes: ClassReader::read_attributes(reader, cp)?,
             },
-            _ => None
-        }.unwrap_or(Attribute::RawAttribute { name_index: ConstantPoolIndex::new(idx as usize), info: reader.get_bytes() })
+            "StackMapTable" => Attribute::StackMapTable({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| ClassReader::read_stack_map_frame(r, cp))?
+            }),
+            "Exceptions" => Attribute::Exceptions(ClassReader::read_checked_indices(reader, cp, is_class)?),
+            "InnerClasses" => Attribute::InnerClasses({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(InnerClass {
+                    inner_class_info_index: ClassReader::read_checked_index(r, cp, false, is_class)?,
+                    outer_class_info_index: ClassReader::read_checked_index(r, cp, true, is_class)?,
+                    inner_name_index: ClassReader::read_checked_index(r, cp, true, is_utf8)?,
+                    access_flags: AccessFlags::of(r.read_u16()?),
+                }))?
+            }),
+            "EnclosingMethod" => Attribute::EnclosingMethod { class_index: ClassReader::read_checked_index(reader, cp, false, is_class)?, method_index: ClassReader::read_checked_index(reader, cp, true, is_name_and_type)? },
+            "Synthetic" => Attribute::Synthetic,
+            "Signature" => Attribute::Signature(ClassReader::read_checked_index(reader, cp, false, is_utf8)?),
+            "SourceFile" => Attribute::SourceFile(ClassReader::read_checked_index(reader, cp, false, is_utf8)?),
+            "SourceDebugExtension" => Attribute::SourceDebugExtension(reader.read_bytes()?.to_vec()),
+            "LineNumberTable" => Attribute::LineNumberTable({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(LineNumberTable {
+                    start_pc: r.read_u16()?,
+                    line_number: r.read_u16()?,
+                }))?
+            }),
+            "LocalVariableTable" => Attribute::LocalVariableTable({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(LocalVariableTable {
+                    start_pc: r.read_u16()?,
+                    length: r.read_u16()?,
+                    name_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    descriptor_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    index: r.read_u16()?,
+                }))?
+            }),
+            "LocalVariableTypeTable" => Attribute::LocalVariableTypeTable({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(LocalVariableTypeTable {
+                    start_pc: r.read_u16()?,
+                    length: r.read_u16()?,
+                    name_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    signature_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    index: r.read_u16()?,
+                }))?
+            }),
+            "Deprecated" => Attribute::Deprecated,
+            "RuntimeVisibleAnnotations" => Attribute::RuntimeVisibleAnnotations({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| ClassReader::read_annotation(r, cp))?
+            }),
+            "RuntimeInvisibleAnnotations" => Attribute::RuntimeInvisibleAnnotations({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| ClassReader::read_annotation(r, cp))?
+            }),
+            "RuntimeVisibleParameterAnnotations" => Attribute::RuntimeVisibleParameterAnnotations({
+                let n = reader.read_u8()?;
+                ClassReader::read_table(reader, n as usize, |r| {
+                    let m = r.read_u16()?;
+                    ClassReader::read_table(r, m as usize, |r| ClassReader::read_annotation(r, cp))
+                })?
+            }),
+            "RuntimeInvisibleParameterAnnotations" => Attribute::RuntimeInvisibleParameterAnnotations({
+                let n = reader.read_u8()?;
+                ClassReader::read_table(reader, n as usize, |r| {
+                    let m = r.read_u16()?;
+                    ClassReader::read_table(r, m as usize, |r| ClassReader::read_annotation(r, cp))
+                })?
+            }),
+            "RuntimeVisibleTypeAnnotations" => Attribute::RuntimeVisibleTypeAnnotations({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| ClassReader::read_type_annotation(r, cp))?
+            }),
+            "AnnotationDefault" => Attribute::AnnotationDefault(ClassReader::read_element_value(reader, cp)?),
+            "BootstrapMethods" => Attribute::BootstrapMethods({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(BootstrapMethod {
+                    bootstrap_method_ref: ClassReader::read_checked_index(r, cp, false, is_method_handle)?,
+                    bootstrap_arguments: ClassReader::read_checked_indices(r, cp, is_bootstrap_argument)?,
+                }))?
+            }),
+            "MethodParameters" => Attribute::MethodParameters({
+                let n = reader.read_u8()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(MethodParameter {
+                    name_index: ClassReader::read_checked_index(r, cp, true, is_utf8)?,
+                    access_flags: AccessFlags::of(r.read_u16()?),
+                }))?
+            }),
+            "Module" => Attribute::Module {
+                name_index: ClassReader::read_checked_index(reader, cp, false, is_module)?,
+                flags: AccessFlags::of(reader.read_u16()?),
+                version_index: ClassReader::read_checked_index(reader, cp, true, is_utf8)?,
+                requires: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| Ok(ModuleRequires {
+                        requires_index: ClassReader::read_checked_index(r, cp, false, is_module)?,
+                        requires_flags: AccessFlags::of(r.read_u16()?),
+                        requires_version_index: ClassReader::read_checked_index(r, cp, true, is_utf8)?,
+                    }))?
+                },
+                exports: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| Ok(ModuleExports {
+                        exports_index: ClassReader::read_checked_index(r, cp, false, is_package)?,
+                        exports_flags: AccessFlags::of(r.read_u16()?),
+                        exports_to: ClassReader::read_checked_indices(r, cp, is_module)?,
+                    }))?
+                },
+                opens: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| Ok(ModuleOpens {
+                        opens_index: ClassReader::read_checked_index(r, cp, false, is_package)?,
+                        opens_flags: AccessFlags::of(r.read_u16()?),
+                        opens_to: ClassReader::read_checked_indices(r, cp, is_module)?,
+                    }))?
+                },
+                uses: ClassReader::read_checked_indices(reader, cp, is_class)?,
+                provides: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| Ok(ModuleProvides {
+                        provides_index: ClassReader::read_checked_index(r, cp, false, is_class)?,
+                        provides_with: ClassReader::read_checked_indices(r, cp, is_class)?,
+                    }))?
+                },
+            },
+            "ModulePackages" => Attribute::ModulePackages(ClassReader::read_checked_indices(reader, cp, is_package)?),
+            "ModuleMainClass" => Attribute::ModuleMainClass(ClassReader::read_checked_index(reader, cp, false, is_class)?),
+            "NestHost" => Attribute::NestHost(ClassReader::read_checked_index(reader, cp, false, is_class)?),
+            "NestMembers" => Attribute::NestMembers(ClassReader::read_checked_indices(reader, cp, is_class)?),
+            "Record" => Attribute::Record({
+                let n = reader.read_u16()?;
+                ClassReader::read_table(reader, n as usize, |r| Ok(RecordComponent {
+                    name_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    descriptor_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+                    attributes: ClassReader::read_attributes(r, cp)?,
+                }))?
+            }),
+            "PermittedSubclasses" => Attribute::PermittedSubclasses(ClassReader::read_checked_indices(reader, cp, is_class)?),
+            _ => Attribute::RawAttribute { name_index: ConstantPoolIndex::new(idx as usize), info: Cow::Borrowed(reader.read_bytes()?) }
+        })
     }
 
-    fn read_annotation(reader: &mut BlockReader) -> Annotation {
-        Annotation {
-            type_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-            element_value_pairs: {
-                let en = reader.get_u16();
-                (0..en).map(|_| ElementValuePair {
-                    element_name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                    value: ClassReader::read_element_value(reader),
-                }).collect()
+    fn read_stack_map_frame(reader: &mut BlockReader, cp: &ConstantPool) -> Result<StackMapFrame, ClassReadError> {
+        let offset = reader.offset();
+
+        Ok(match reader.read_u8()? {
+            tag @ 0...63 => StackMapFrame::SameFrame { tag: tag },
+            tag @ 64...127 => StackMapFrame::SameLocals1StackItemFrame { tag: tag, stack: ClassReader::read_verification_type(reader, cp)? },
+            247 => StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta: reader.read_u16()?, stack: ClassReader::read_verification_type(reader, cp)? },
+            tag @ 248...250 => StackMapFrame::ChopFrame { tag: tag, offset_delta: reader.read_u16()? },
+            251 => StackMapFrame::SameFrameExtended { offset_delta: reader.read_u16()? },
+            tag @ 252...254 => StackMapFrame::AppendFrame { tag: tag, offset_delta: reader.read_u16()?, locals: ClassReader::read_table(reader, (tag - 251) as usize, |r| ClassReader::read_verification_type(r, cp))? },
+            255 => StackMapFrame::FullFrame {
+                offset_delta: reader.read_u16()?,
+                locals: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| ClassReader::read_verification_type(r, cp))?
+                },
+                stack: {
+                    let n = reader.read_u16()?;
+                    ClassReader::read_table(reader, n as usize, |r| ClassReader::read_verification_type(r, cp))?
+                },
             },
-        }
+            // frame types 128-246 are reserved
+            tag @ _ => return reader.fail(offset, ClassReadErrorKind::BadTag(tag as u32))
+        })
+    }
+
+    fn read_verification_type(reader: &mut BlockReader, cp: &ConstantPool) -> Result<VerificationType, ClassReadError> {
+        let offset = reader.offset();
+
+        Ok(match reader.read_u8()? {
+            0 => VerificationType::Top,
+            1 => VerificationType::Integer,
+            2 => VerificationType::Float,
+            3 => VerificationType::Double,
+            4 => VerificationType::Long,
+            5 => VerificationType::Null,
+            6 => VerificationType::UninitializedThis,
+            7 => VerificationType::Object { cpool_index: ClassReader::read_checked_index(reader, cp, false, is_class)? },
+            8 => VerificationType::Uninitialized { offset: reader.read_u16()? },
+            tag @ _ => return reader.fail(offset, ClassReadErrorKind::BadTag(tag as u32))
+        })
+    }
+
+    fn read_annotation(reader: &mut BlockReader, cp: &ConstantPool) -> Result<Annotation, ClassReadError> {
+        Ok(Annotation {
+            type_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            element_value_pairs: ClassReader::read_element_value_pairs(reader, cp)?,
+        })
+    }
+
+    fn read_element_value_pairs(reader: &mut BlockReader, cp: &ConstantPool) -> Result<Vec<ElementValuePair>, ClassReadError> {
+        let n = reader.read_u16()?;
+
+        ClassReader::read_table(reader, n as usize, |r| Ok(ElementValuePair {
+            element_name_index: ClassReader::read_checked_index(r, cp, false, is_utf8)?,
+            value: ClassReader::read_element_value(r, cp)?,
+        }))
     }
 
-    fn read_type_annotation(reader: &mut BlockReader) -> TypeAnnotation {
-        TypeAnnotation {
-            target_info: match reader.get_u8() {
+    fn read_type_annotation(reader: &mut BlockReader, cp: &ConstantPool) -> Result<TypeAnnotation, ClassReadError> {
+        let offset = reader.offset();
+
+        Ok(TypeAnnotation {
+            target_info: match reader.read_u8()? {
                 // 0x00 type parameter declaration of generic class or interface
                 // 0x01 type parameter declaration of generic method or constructor
-                subtype @ 0x00...0x01 => TargetInfo::TypeParameter { subtype: subtype, idx: reader.get_u8() },
+                subtype @ 0x00...0x01 => TargetInfo::TypeParameter { subtype: subtype, idx: reader.read_u8()? },
                 // type in extends or implements clause of class declaration (including the direct superclass or direct superinterface of an anonymous class declaration), or in extends clause of interface declaration
-                0x10 => TargetInfo::SuperType { idx: reader.get_u16() },
+                0x10 => TargetInfo::SuperType { idx: reader.read_u16()? },
                 // 0x11 type in bound of type parameter declaration of generic class or interface
                 // 0x12 type in bound of type parameter declaration of generic method or constructor
-                subtype @ 0x11...0x12 => TargetInfo::TypeParameterBound { subtype: subtype, param_idx: reader.get_u8(), bound_index: reader.get_u8() },
+                subtype @ 0x11...0x12 => TargetInfo::TypeParameterBound { subtype: subtype, param_idx: reader.read_u8()?, bound_index: reader.read_u8()? },
                 // 0x13 type in field declaration
                 // 0x14 return type of method, or type of newly constructed object
                 // 0x15 receiver type of method or constructor
                 subtype @ 0x13...0x15 => TargetInfo::Empty { subtype: subtype },
                 // type in formal parameter declaration of method, constructor, or lambda expression
-                0x16 => TargetInfo::MethodFormalParameter { idx: reader.get_u8() },
+                0x16 => TargetInfo::MethodFormalParameter { idx: reader.read_u8()? },
                 // type in throws clause of method or constructor
-                0x17 => TargetInfo::Throws { idx: reader.get_u16() },
+                0x17 => TargetInfo::Throws { idx: reader.read_u16()? },
                 // 0x40 type in local variable declaration
                 // 0x41 type in resource variable declaration
                 subtype @ 0x40...0x41 => TargetInfo::LocalVar {
                     subtype: subtype,
                     target: {
-                        let count = reader.get_u16();
+                        let count = reader.read_u16()?;
 
                         //u2 start_pc;    u2 length;        u2 index;
-                        (0..count).map(|_| (reader.get_u16(), reader.get_u16(), reader.get_u16())).collect()
+                        ClassReader::read_table(reader, count as usize, |r| Ok((r.read_u16()?, r.read_u16()?, r.read_u16()?)))?
                     },
                 },
                 // type in exception parameter declaration
-                0x42 => TargetInfo::Catch { idx: reader.get_u16() },
+                0x42 => TargetInfo::Catch { idx: reader.read_u16()? },
                 // 0x43 type in instanceof expression
                 // 0x44 type in new expression
                 // 0x45 type in method reference expression using ::new
                 // 0x46 type in method reference expression using ::Identifier
-                subtype @ 0x43...0x46 => TargetInfo::Offset { subtype: subtype, idx: reader.get_u16() },
+                subtype @ 0x43...0x46 => TargetInfo::Offset { subtype: subtype, idx: reader.read_u16()? },
                 // 0x48 type argument for generic constructor in new expression or explicit constructor invocation statement
                 // 0x49 type argument for generic method in method invocation expression
                 // 0x4A type argument for generic constructor in method reference expression using ::new
                 // 0x4B type argument for generic method in method reference expression using ::Identifier
-                subtype @ 0x47...0x4b => TargetInfo::TypeArgument { subtype: subtype, offset: reader.get_u16(), type_arg_idx: reader.get_u8() },
-                // TODO replace the below fallback branch with proper error handling
-                _ => TargetInfo::Empty { subtype: 0 }
+                subtype @ 0x47...0x4b => TargetInfo::TypeArgument { subtype: subtype, offset: reader.read_u16()?, type_arg_idx: reader.read_u8()? },
+                tag @ _ => return reader.fail(offset, ClassReadErrorKind::BadTag(tag as u32))
             },
             target_path: TypePath {
                 path: {
-                    let n = reader.get_u8();
-                    (0..n).map(|_| (match reader.get_u8() {
-                        0 => TypePathKind::Array,
-                        1 => TypePathKind::Nested,
-                        2 => TypePathKind::Wildcard,
-                        3 => TypePathKind::TypeArgument,
-                        // TODO replace the below fallback branch with proper error handling
-                        _ => TypePathKind::Array
-                    }, reader.get_u8())).collect()
+                    let n = reader.read_u8()?;
+                    ClassReader::read_table(reader, n as usize, |r| {
+                        let kind_offset = r.offset();
+
+                        let kind = match r.read_u8()? {
+                            0 => TypePathKind::Array,
+                            1 => TypePathKind::Nested,
+                            2 => TypePathKind::Wildcard,
+                            3 => TypePathKind::TypeArgument,
+                            tag @ _ => return r.fail(kind_offset, ClassReadErrorKind::BadTag(tag as u32))
+                        };
+
+                        Ok((kind, r.read_u8()?))
+                    })?
                 }
             },
-            type_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-            element_value_pairs: {
-                let n = reader.get_u16();
-                (0..n).map(|_| ElementValuePair {
-                    element_name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                    value: ClassReader::read_element_value(reader),
-                }).collect()
-            },
-        }
+            type_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+            element_value_pairs: ClassReader::read_element_value_pairs(reader, cp)?,
+        })
     }
 
-    fn read_element_value(reader: &mut BlockReader) -> ElementValue {
-        let tag = reader.get_u8();
-
-        match tag {
-            66 /* B */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            67 /* C */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            68 /* D */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            70 /* F */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            73 /* I */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            74 /* J */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            83 /* S */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            90 /* Z */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
-            115 /* s */ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(reader.get_u16() as usize)),
+    fn read_element_value(reader: &mut BlockReader, cp: &ConstantPool) -> Result<ElementValue, ClassReadError> {
+        let offset = reader.offset();
+        let tag = reader.read_u8()?;
+
+        Ok(match tag {
+            66 /* B */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_integer)?),
+            67 /* C */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_integer)?),
+            68 /* D */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_double)?),
+            70 /* F */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_float)?),
+            73 /* I */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_integer)?),
+            74 /* J */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_long)?),
+            83 /* S */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_integer)?),
+            90 /* Z */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_integer)?),
+            115 /* s */ => ElementValue::ConstantValue(tag, ClassReader::read_checked_index(reader, cp, false, is_utf8)?),
             101 /* e */ => ElementValue::Enum {
-                type_name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
-                const_name_index: ConstantPoolIndex::new(reader.get_u16() as usize),
+                type_name_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
+                const_name_index: ClassReader::read_checked_index(reader, cp, false, is_utf8)?,
             },
-            99 /* c */ => ElementValue::ClassInfo(ConstantPoolIndex::new(reader.get_u16() as usize)),
-            64 /* @ */ => ElementValue::Annotation(ClassReader::read_annotation(reader)),
+            99 /* c */ => ElementValue::ClassInfo(ClassReader::read_checked_index(reader, cp, false, is_utf8)?),
+            64 /* @ */ => ElementValue::Annotation(reader.descend(|r| ClassReader::read_annotation(r, cp))?),
             91 /* [ */ => ElementValue::Array({
-                let n = reader.get_u16();
-                (0..n).map(|_| ClassReader::read_element_value(reader)).collect()
+                let n = reader.read_u16()?;
+                reader.descend(|r| ClassReader::read_table(r, n as usize, |r| ClassReader::read_element_value(r, cp)))?
             }),
-            _ => ElementValue::ConstantValue(tag, ConstantPoolIndex::new(0)) // TODO this deserves a better error handling
+            _ => return reader.fail(offset, ClassReadErrorKind::BadTag(tag as u32))
+        })
+    }
+
+    fn read_constant_pool_index(reader: &mut BlockReader) -> Result<ConstantPoolIndex, ClassReadError> {
+        reader.read_u16().map(|idx| ConstantPoolIndex::new(idx as usize))
+    }
+
+    /// Reads a constant pool index, failing with `BadIndex` unless it points to a constant accepted
+    /// by `expected`. Some items use 0 to mean "none", those pass `accepts_zero`
+    fn read_checked_index(reader: &mut BlockReader, cp: &ConstantPool, accepts_zero: bool, expected: ConstantCheck) -> Result<ConstantPoolIndex, ClassReadError> {
+        let offset = reader.offset();
+        let idx = ClassReader::read_constant_pool_index(reader)?;
+
+        if accepts_zero && idx.idx == 0 {
+            Ok(idx)
+        } else {
+            ClassReader::check_index(reader, offset, cp, idx.idx, expected).map(|_| idx)
         }
     }
 
-    fn read_constant_pool_index(reader: &mut BlockReader) -> Result<ConstantPoolIndex, Error> {
-        match reader.read_u16() {
-            Ok(idx) => Ok(ConstantPoolIndex::new(idx as usize)),
-            Err(err) => Err(err)
+    /// Reads a u2 length-prefixed table of constant pool indices, each accepted by `expected`
+    fn read_checked_indices(reader: &mut BlockReader, cp: &ConstantPool, expected: ConstantCheck) -> Result<Vec<ConstantPoolIndex>, ClassReadError> {
+        let n = reader.read_u16()?;
+
+        ClassReader::read_table(reader, n as usize, |r| ClassReader::read_checked_index(r, cp, false, expected))
+    }
+
+    /// Reads the u2 constant pool index an instruction takes as its operand
+    fn read_operand(reader: &mut BlockReader, cp: &ConstantPool, expected: ConstantCheck) -> Result<u16, ClassReadError> {
+        ClassReader::read_checked_index(reader, cp, false, expected).map(|idx| idx.idx as u16)
+    }
+
+    /// Fails with `BadIndex` at `offset` unless `idx` points to a constant accepted by `expected`
+    fn check_index(reader: &BlockReader, offset: usize, cp: &ConstantPool, idx: usize, expected: ConstantCheck) -> Result<(), ClassReadError> {
+        if cp.resolve_index(&ConstantPoolIndex::new(idx)).map(expected).unwrap_or(false) {
+            Ok(())
+        } else {
+            reader.fail(offset, ClassReadErrorKind::BadIndex(idx as u32))
         }
     }
 
-    fn require_n<T, U>(reader: &mut BlockReader, count: usize, extractor: U) -> Result<T, Error> where U: Fn(BlockReader) -> T {
-        match reader.read_n(count) {
-            Ok(bytes) => {
-                let mut cursor = Cursor::new(bytes);
-                let r = BlockReader::new(&mut cursor);
+    /// Reads `count` consecutive items, stopping at the first one that fails to decode
+    fn read_table<'a, T, U>(reader: &mut BlockReader<'a>, count: usize, mut extractor: U) -> Result<Vec<T>, ClassReadError> where U: FnMut(&mut BlockReader<'a>) -> Result<T, ClassReadError> {
+        let mut items = vec![];
 
-                Ok(extractor(r))
-            }
-            Err(err) => Err(err)
+        for _ in 0..count {
+            items.push(extractor(reader)?);
         }
+
+        Ok(items)
     }
 }
 
+fn is_utf8(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Utf8(_))
+}
+
+fn is_class(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Class(_))
+}
+
+fn is_name_and_type(constant: &Constant) -> bool {
+    matches!(constant, &Constant::NameAndType { .. })
+}
+
+fn is_field_ref(constant: &Constant) -> bool {
+    matches!(constant, &Constant::FieldRef { .. })
+}
+
+fn is_method_ref(constant: &Constant) -> bool {
+    matches!(constant, &Constant::MethodRef { .. })
+}
+
+fn is_interface_method_ref(constant: &Constant) -> bool {
+    matches!(constant, &Constant::InterfaceMethodRef { .. })
+}
+
+/// Static and special method handles may point to interface methods since Java 8
+fn is_any_method_ref(constant: &Constant) -> bool {
+    is_method_ref(constant) || is_interface_method_ref(constant)
+}
+
+fn is_invoke_dynamic(constant: &Constant) -> bool {
+    matches!(constant, &Constant::InvokeDynamic { .. })
+}
+
+fn is_method_handle(constant: &Constant) -> bool {
+    matches!(constant, &Constant::MethodHandle { .. })
+}
+
+fn is_module(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Module(_))
+}
+
+fn is_package(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Package(_))
+}
+
+fn is_integer(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Integer(_))
+}
+
+fn is_float(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Float(_))
+}
+
+fn is_long(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Long(_))
+}
+
+fn is_double(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Double(_))
+}
+
+/// The constants a ConstantValue attribute may hold
+fn is_loadable_value(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Integer(_) | &Constant::Float(_) | &Constant::Long(_) | &Constant::Double(_) | &Constant::String(_))
+}
+
+/// The constants `ldc` and `ldc_w` may load. The type of a dynamic constant isn't checked
+fn is_loadable(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Integer(_) | &Constant::Float(_) | &Constant::String(_) | &Constant::Class(_) |
+        &Constant::MethodType(_) | &Constant::MethodHandle { .. } | &Constant::Dynamic { .. })
+}
+
+/// The constants `ldc2_w` may load
+fn is_wide_loadable(constant: &Constant) -> bool {
+    matches!(constant, &Constant::Long(_) | &Constant::Double(_) | &Constant::Dynamic { .. })
+}
+
+/// Static arguments of a bootstrap method may be any loadable constant
+fn is_bootstrap_argument(constant: &Constant) -> bool {
+    is_loadable(constant) || is_wide_loadable(constant)
+}
+
+/// How deeply attributes and element values may nest before the input is rejected. Real class
+/// files stay in the single digits, this only exists to keep hostile input from exhausting the stack
+const MAX_NESTING: usize = 64;
+
 // TODO remove pub after testing
 pub struct BlockReader<'a> {
-    source: &'a mut Read,
+    bytes: &'a [u8],
     position: usize,
+    /// Absolute offset of this block within the class file, for error reporting
+    base: usize,
+    section: Vec<ClassSection>,
+    depth: usize,
 }
 
 impl<'a> BlockReader<'a> {
-    pub fn new<T>(source: &'a mut T) -> BlockReader where T: Read {
-        BlockReader { source: source, position: 0 }
+    pub fn new(bytes: &'a [u8]) -> BlockReader<'a> {
+        BlockReader { bytes: bytes, position: 0, base: 0, section: vec![], depth: 0 }
     }
 
-    pub fn read_u64(&mut self) -> Result<u64, Error> {
-        let mut buf: [u8; 8] = [0; 8];
-
-        match self.source.read_exact(&mut buf) {
-            Ok(_) => {
-                self.position += 8;
-
-                Ok(
-                    ((buf[0] as u64) << 56) +
-                        ((buf[1] as u64) << 48) +
-                        ((buf[2] as u64) << 40) +
-                        ((buf[3] as u64) << 32) +
-                        ((buf[4] as u64) << 24) +
-                        ((buf[5] as u64) << 16) +
-                        ((buf[6] as u64) << 8) +
-                        buf[7] as u64)
-            }
-            Err(err) => Err(err)
-        }
-    }
+    /// Splits the next `count` bytes off into a reader of their own. Errors raised by the new
+    /// reader still report absolute offsets and the sections of this reader
+    fn read_block(&mut self, count: usize) -> Result<BlockReader<'a>, ClassReadError> {
+        let base = self.offset();
+        let bytes = self.read_n(count)?;
 
-    #[allow(dead_code)]
-    pub fn get_u64(&mut self) -> u64 {
-        self.read_u64().unwrap_or(0)
+        Ok(BlockReader { bytes: bytes, position: 0, base: base, section: self.section.clone(), depth: self.depth })
     }
 
-    pub fn read_u32(&mut self) -> Result<u32, Error> {
-        let mut buf: [u8; 4] = [0; 4];
-
-        match self.source.read_exact(&mut buf) {
-            Ok(_) => {
-                self.position += 4;
-                Ok(
-                    ((buf[0] as u32) << 24) +
-                        ((buf[1] as u32) << 16) +
-                        ((buf[2] as u32) << 8) +
-                        buf[3] as u32)
-            }
-            Err(err) => Err(err)
-        }
+    /// Runs `f` with `section` pushed on the section stack of this reader
+    fn within<T, U>(&mut self, section: ClassSection, f: U) -> Result<T, ClassReadError> where U: FnOnce(&mut Self) -> Result<T, ClassReadError> {
+        self.section.push(section);
+        let result = f(self);
+        self.section.pop();
+        result
     }
 
-    #[allow(dead_code)]
-    pub fn get_u32(&mut self) -> u32 {
-        self.read_u32().unwrap_or(0)
-    }
-
-    pub fn read_u16(&mut self) -> Result<u16, Error> {
-        let mut buf: [u8; 2] = [0; 2];
-
-        match self.source.read_exact(&mut buf) {
-            Ok(_) => {
-                self.position += 2;
-                Ok(((buf[0] as u16) << 8) + buf[1] as u16)
-            }
-            Err(err) => Err(err)
+    /// Runs `f` one nesting level deeper, failing once `MAX_NESTING` is exceeded
+    fn descend<T, U>(&mut self, f: U) -> Result<T, ClassReadError> where U: FnOnce(&mut Self) -> Result<T, ClassReadError> {
+        if self.depth >= MAX_NESTING {
+            let offset = self.offset();
+            return self.fail(offset, ClassReadErrorKind::Malformed("nesting depth limit exceeded"));
         }
-    }
 
-    pub fn get_u16(&mut self) -> u16 {
-        self.read_u16().unwrap_or(0)
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
     }
 
-    pub fn read_u8(&mut self) -> Result<u8, Error> {
-        let mut buf: [u8; 1] = [0; 1];
+    fn fail<T>(&self, offset: usize, kind: ClassReadErrorKind) -> Result<T, ClassReadError> {
+        Err(ClassReadError::new(offset, self.section.clone(), kind))
+    }
 
-        match self.source.read_exact(&mut buf) {
-            Ok(_) => {
-                self.position += 1;
-                Ok(buf[0])
-            }
-            Err(err) => Err(err)
+    fn error_kind(err: Error) -> ClassReadErrorKind {
+        match err.kind() {
+            ErrorKind::UnexpectedEof => ClassReadErrorKind::Truncated,
+            kind @ _ => ClassReadErrorKind::Io(kind)
         }
     }
 
-    pub fn get_u8(&mut self) -> u8 {
-        self.read_u8().unwrap_or(0)
+    pub fn read_u64(&mut self) -> Result<u64, ClassReadError> {
+        self.read_n(8).map(|buf| {
+            ((buf[0] as u64) << 56) +
+                ((buf[1] as u64) << 48) +
+                ((buf[2] as u64) << 40) +
+                ((buf[3] as u64) << 32) +
+                ((buf[4] as u64) << 24) +
+                ((buf[5] as u64) << 16) +
+                ((buf[6] as u64) << 8) +
+                buf[7] as u64
+        })
     }
 
-    pub fn read_n(&mut self, count: usize) -> Result<Vec<u8>, Error> {
-        let mut tmp: Vec<u8> = Vec::with_capacity(count);
+    pub fn read_u32(&mut self) -> Result<u32, ClassReadError> {
+        self.read_n(4).map(|buf| {
+            ((buf[0] as u32) << 24) +
+                ((buf[1] as u32) << 16) +
+                ((buf[2] as u32) << 8) +
+                buf[3] as u32
+        })
+    }
 
-        match self.source.take(count as u64).read_to_end(&mut tmp) {
-            Ok(_) => {
-                self.position += count;
-                Ok(tmp)
-            }
-            Err(err) => Err(err)
-        }
+    pub fn read_u16(&mut self) -> Result<u16, ClassReadError> {
+        self.read_n(2).map(|buf| ((buf[0] as u16) << 8) + buf[1] as u16)
     }
 
-    pub fn get_n(&mut self, count: usize) -> Vec<u8> {
-        match self.read_n(count) {
-            Ok(bytes) => bytes,
-            Err(_) => vec![]
-        }
+    pub fn read_u8(&mut self) -> Result<u8, ClassReadError> {
+        self.read_n(1).map(|buf| buf[0])
     }
 
-    pub fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
-        let mut tmp: Vec<u8> = vec![];
+    /// Reads the next `count` bytes without copying them
+    pub fn read_n(&mut self, count: usize) -> Result<&'a [u8], ClassReadError> {
+        let bytes = self.bytes;
 
-        match self.source.read_to_end(&mut tmp) {
-            Ok(_) => {
-                self.position += tmp.len();
-                Ok(tmp)
+        match self.position.checked_add(count) {
+            Some(end) if end <= bytes.len() => {
+                let start = self.position;
+                self.position = end;
+                Ok(&bytes[start..end])
+            }
+            _ => {
+                let offset = self.offset();
+                self.fail(offset, ClassReadErrorKind::Truncated)
             }
-            Err(err) => Err(err)
         }
     }
 
-    pub fn get_bytes(&mut self) -> Vec<u8> {
-        let mut tmp: Vec<u8> = vec![];
-
-        let _ = self.source.read_to_end(&mut tmp);
+    /// Reads every remaining byte of this block without copying them
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], ClassReadError> {
+        let remaining = self.bytes.len() - self.position;
 
-        tmp
+        self.read_n(remaining)
     }
 
+    /// The number of bytes consumed from this block
     pub fn position(&self) -> usize {
         self.position
     }
-}
 
+    /// The absolute offset of the next byte within the class file
+    pub fn offset(&self) -> usize {
+        self.base + self.position
+    }
+}
 
-struct ClassFragment {
+struct ClassFragment<'a> {
     pub version: Option<ClassfileVersion>,
-    pub constant_pool: Option<ConstantPool>,
+    pub constant_pool: Option<ConstantPool<'a>>,
     pub access_flags: Option<AccessFlags>,
     pub this_class: Option<ConstantPoolIndex>,
     pub super_class: Option<ConstantPoolIndex>,
     pub interfaces: Option<Vec<ConstantPoolIndex>>,
-    pub fields: Option<Vec<Field>>,
-    pub methods: Option<Vec<Method>>,
-    pub attributes: Option<Vec<Attribute>>,
+    pub fields: Option<Vec<Field<'a>>>,
+    pub methods: Option<Vec<Method<'a>>>,
+    pub attributes: Option<Vec<Attribute<'a>>>,
 }
 
-impl ClassFragment {
+impl<'a> ClassFragment<'a> {
     pub fn merge(mut self, other: Self) -> Self {
         ClassFragment {
             version: other.version.or(self.version),
@@ -1030,7 +1312,7 @@ impl ClassFragment {
 
     /// Transform this class fragment into a final class file. Members set on the fragment will
     /// be defined on the class too, other members will be initialized with their default values
-    pub fn to_class(self) -> Classfile {
+    pub fn to_class(self) -> Classfile<'a> {
         Classfile {
             version: self.version.unwrap_or(ClassfileVersion::default()),
             constant_pool: self.constant_pool.unwrap_or(ConstantPool::default()),
@@ -1045,7 +1327,7 @@ impl ClassFragment {
     }
 }
 
-impl Default for ClassFragment {
+impl<'a> Default for ClassFragment<'a> {
     fn default() -> Self {
         ClassFragment {
             version: None,
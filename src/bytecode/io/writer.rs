@@ -1,13 +1,38 @@
 use std::io::{Write, Error, ErrorKind};
 use super::super::classfile::*;
+use super::super::maxs::method_maxs;
 
 pub struct ClassWriter<'a> {
-    target: &'a mut Write
+    target: &'a mut Write,
+    compute_maxs: bool,
+    /// Positions of the methods whose maxs are recomputed, all of them when `None`
+    touched: Option<Vec<usize>>
 }
 
 impl<'a> ClassWriter<'a> {
     pub fn new<T>(target: &'a mut T) -> ClassWriter where T: Write {
-        ClassWriter { target: target }
+        ClassWriter { target: target, compute_maxs: false, touched: None }
+    }
+
+    ///
+    /// Recalculate `max_stack` and `max_locals` from the instructions of every method with code
+    /// instead of writing the values stored in its `Code` attribute. Transformers that add or change
+    /// instructions can enable this rather than keeping track of the maxs themselves. Methods that
+    /// weren't touched end up with the same values, as compilers already emit exact maxs. Methods whose
+    /// code can't be sized, eg. because it refers to a missing constant, keep their stored values.
+    pub fn compute_maxs(mut self, enabled: bool) -> ClassWriter<'a> {
+        self.compute_maxs = enabled;
+        self
+    }
+
+    ///
+    /// Recalculate `max_stack` and `max_locals` only for the methods at the given positions of the
+    /// class, writing the stored values for the rest. Unlike `compute_maxs`, failing to size one of
+    /// these methods fails the write, as its stored values no longer fit its code.
+    pub fn compute_maxs_of(mut self, methods: Vec<usize>) -> ClassWriter<'a> {
+        self.compute_maxs = true;
+        self.touched = Some(methods);
+        self
     }
 
     pub fn write_class(&mut self, classfile: &Classfile) -> Result<usize, Error> {
@@ -33,7 +58,7 @@ impl<'a> ClassWriter<'a> {
     }
 
     pub fn write_constant_pool(&mut self, cp: &ConstantPool) -> Result<usize, Error> {
-        cp.constants.iter().fold(self.write_u16(cp.cp_len() as u16), |acc, x| {
+        cp.constants.iter().fold(self.write_count(cp.cp_len()), |acc, x| {
             match acc {
                 Ok(ctr) => self.write_constant(x).map(|c| c + ctr),
                 err @ _ => err
@@ -43,20 +68,23 @@ impl<'a> ClassWriter<'a> {
 
     fn write_constant(&mut self, constant: &Constant) -> Result<usize, Error> {
         match constant {
-            &Constant::Utf8(ref bytes) => self.write_u8(1).and(self.write_u16(bytes.len() as u16)).and(self.write_n(bytes)),
+            &Constant::Utf8(ref bytes) => self.write_u8(1).and(self.write_count(bytes.len())).and(self.write_n(bytes)),
             &Constant::Integer(ref value) => self.write_u8(3).and(self.write_u32(*value)),
             &Constant::Float(ref value) => self.write_u8(4).and(self.write_u32(*value)),
             &Constant::Long(ref value) => self.write_u8(5).and(self.write_u64(*value)),
             &Constant::Double(ref value) => self.write_u8(6).and(self.write_u64(*value)),
-            &Constant::Class(ref idx) => self.write_u8(7).and(self.write_u16(idx.idx as u16)),
-            &Constant::String(ref idx) => self.write_u8(8).and(self.write_u16(idx.idx as u16)),
-            &Constant::MethodType(ref idx) => self.write_u8(16).and(self.write_u16(idx.idx as u16)),
-            &Constant::FieldRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(9).and(self.write_u16(c_idx.idx as u16)).and(self.write_u16(n_idx.idx as u16)),
-            &Constant::MethodRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(10).and(self.write_u16(c_idx.idx as u16)).and(self.write_u16(n_idx.idx as u16)),
-            &Constant::InterfaceMethodRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(11).and(self.write_u16(c_idx.idx as u16)).and(self.write_u16(n_idx.idx as u16)),
-            &Constant::NameAndType { name_index: ref n_idx, descriptor_index: ref d_idx } => self.write_u8(12).and(self.write_u16(n_idx.idx as u16)).and(self.write_u16(d_idx.idx as u16)),
-            &Constant::MethodHandle { reference_kind: ref kind, reference_index: ref r_idx } => self.write_u8(15).and(self.write_u8(kind.to_u8())).and(self.write_u16(r_idx.idx as u16)),
-            &Constant::InvokeDynamic { bootstrap_method_attr_index: ref m_idx, name_and_type_index: ref n_idx } => self.write_u8(18).and(self.write_u16(m_idx.idx as u16)).and(self.write_u16(n_idx.idx as u16)),
+            &Constant::Class(ref idx) => self.write_u8(7).and(self.write_constant_pool_index(idx)),
+            &Constant::String(ref idx) => self.write_u8(8).and(self.write_constant_pool_index(idx)),
+            &Constant::MethodType(ref idx) => self.write_u8(16).and(self.write_constant_pool_index(idx)),
+            &Constant::FieldRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(9).and(self.write_constant_pool_index(c_idx)).and(self.write_constant_pool_index(n_idx)),
+            &Constant::MethodRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(10).and(self.write_constant_pool_index(c_idx)).and(self.write_constant_pool_index(n_idx)),
+            &Constant::InterfaceMethodRef { class_index: ref c_idx, name_and_type_index: ref n_idx } => self.write_u8(11).and(self.write_constant_pool_index(c_idx)).and(self.write_constant_pool_index(n_idx)),
+            &Constant::NameAndType { name_index: ref n_idx, descriptor_index: ref d_idx } => self.write_u8(12).and(self.write_constant_pool_index(n_idx)).and(self.write_constant_pool_index(d_idx)),
+            &Constant::MethodHandle { reference_kind: ref kind, reference_index: ref r_idx } => self.write_u8(15).and(self.write_u8(kind.to_u8())).and(self.write_constant_pool_index(r_idx)),
+            &Constant::Dynamic { bootstrap_method_attr_index: ref m_idx, name_and_type_index: ref n_idx } => self.write_u8(17).and(self.write_constant_pool_index(m_idx)).and(self.write_constant_pool_index(n_idx)),
+            &Constant::InvokeDynamic { bootstrap_method_attr_index: ref m_idx, name_and_type_index: ref n_idx } => self.write_u8(18).and(self.write_constant_pool_index(m_idx)).and(self.write_constant_pool_index(n_idx)),
+            &Constant::Module(ref idx) => self.write_u8(19).and(self.write_constant_pool_index(idx)),
+            &Constant::Package(ref idx) => self.write_u8(20).and(self.write_constant_pool_index(idx)),
             &Constant::Placeholder => Ok(0),
             _ => Err(Error::new(ErrorKind::InvalidData, "Unknown constant detected"))
         }
@@ -67,20 +95,59 @@ impl<'a> ClassWriter<'a> {
     }
 
     fn write_constant_pool_index(&mut self, class_index: &ConstantPoolIndex) -> Result<usize, Error> {
-        self.write_u16(class_index.idx as u16)
+        if class_index.idx > 0xFFFF {
+            Err(Error::new(ErrorKind::InvalidData, format!("Constant pool index {} doesn't fit in a u2", class_index.idx)))
+        } else {
+            self.write_u16(class_index.idx as u16)
+        }
+    }
+
+    /// Writes the name index of a standard attribute, failing if its name is missing from the pool
+    fn write_attribute_name(&mut self, cp: &ConstantPool, name: &'static str) -> Result<usize, Error> {
+        match cp.find_ut8_index(name) {
+            Some(idx) => self.write_constant_pool_index(&ConstantPoolIndex::new(idx)),
+            None => Err(Error::new(ErrorKind::InvalidData, format!("The constant pool doesn't contain the {} attribute name", name)))
+        }
+    }
+
+    /// Writes a u2 item count, failing instead of silently truncating tables that are too long
+    fn write_count(&mut self, count: usize) -> Result<usize, Error> {
+        if count > 0xFFFF {
+            Err(Error::new(ErrorKind::InvalidData, format!("Table of {} items doesn't fit in a u2 count", count)))
+        } else {
+            self.write_u16(count as u16)
+        }
+    }
+
+    /// Writes a u1 item count, as used by parameter tables and type paths
+    fn write_small_count(&mut self, count: usize) -> Result<usize, Error> {
+        if count > 0xFF {
+            Err(Error::new(ErrorKind::InvalidData, format!("Table of {} items doesn't fit in a u1 count", count)))
+        } else {
+            self.write_u8(count as u8)
+        }
+    }
+
+    /// Writes a u4 attribute or code length
+    fn write_length(&mut self, length: usize) -> Result<usize, Error> {
+        if length > 0xFFFF_FFFF {
+            Err(Error::new(ErrorKind::InvalidData, format!("Length {} doesn't fit in a u4", length)))
+        } else {
+            self.write_u32(length as u32)
+        }
     }
 
     fn write_interfaces(&mut self, ifs: &Vec<ConstantPoolIndex>) -> Result<usize, Error> {
-        ifs.iter().fold(self.write_u16(ifs.len() as u16), |acc, x| {
+        ifs.iter().fold(self.write_count(ifs.len()), |acc, x| {
             match acc {
-                Ok(ctr) => self.write_u16(x.idx as u16).map(|c| c + ctr),
+                Ok(ctr) => self.write_constant_pool_index(x).map(|c| c + ctr),
                 err @ _ => err
             }
         })
     }
 
     fn write_fields(&mut self, fields: &Vec<Field>, cp: &ConstantPool) -> Result<usize, Error> {
-        fields.iter().fold(self.write_u16(fields.len() as u16), |acc, x| {
+        fields.iter().fold(self.write_count(fields.len()), |acc, x| {
             match acc {
                 Ok(ctr) => self.write_field(x, cp).map(|c| c + ctr),
                 err @ _ => err
@@ -96,23 +163,36 @@ impl<'a> ClassWriter<'a> {
     }
 
     fn write_methods(&mut self, methods: &Vec<Method>, cp: &ConstantPool) -> Result<usize, Error> {
-        methods.iter().fold(self.write_u16(methods.len() as u16), |acc, x| {
+        methods.iter().enumerate().fold(self.write_count(methods.len()), |acc, (idx, x)| {
             match acc {
-                Ok(ctr) => self.write_method(x, cp).map(|c| c + ctr),
+                Ok(ctr) => self.write_method(idx, x, cp).map(|c| c + ctr),
                 err @ _ => err
             }
         })
     }
 
-    fn write_method(&mut self, method: &Method, cp: &ConstantPool) -> Result<usize, Error> {
+    fn write_method(&mut self, idx: usize, method: &Method, cp: &ConstantPool) -> Result<usize, Error> {
+        let maxs = match self.touched {
+            Some(ref touched) if touched.contains(&idx) => method_maxs(cp, method).map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?,
+            Some(_) => None,
+            None if self.compute_maxs => method_maxs(cp, method).unwrap_or(None),
+            None => None
+        };
+
         self.write_access_flags(&method.access_flags)
             .and(self.write_constant_pool_index(&method.name_index))
             .and(self.write_constant_pool_index(&method.descriptor_index))
-            .and(self.write_attributes(&method.attributes, cp))
+            .and(method.attributes.iter().fold(self.write_count(method.attributes.len()), |acc, x| {
+                match (acc, x, maxs) {
+                    (Ok(ctr), &Attribute::Code { ref code, ref exception_table, ref attributes, .. }, Some((max_stack, max_locals))) => self.write_code(max_stack, max_locals, code, exception_table, attributes, cp).map(|c| c + ctr),
+                    (Ok(ctr), _, _) => self.write_attribute(x, cp).map(|c| c + ctr),
+                    (err @ _, _, _) => err
+                }
+            }))
     }
 
     fn write_attributes(&mut self, attributes: &Vec<Attribute>, cp: &ConstantPool) -> Result<usize, Error> {
-        attributes.iter().fold(self.write_u16(attributes.len() as u16), |acc, x| {
+        attributes.iter().fold(self.write_count(attributes.len()), |acc, x| {
             match acc {
                 Ok(ctr) => self.write_attribute(x, cp).map(|c| c + ctr),
                 err @ _ => err
@@ -120,173 +200,260 @@ impl<'a> ClassWriter<'a> {
         })
     }
 
-    fn write_attribute(&mut self, attribute: &Attribute, cp: &ConstantPool) -> Result<usize, Error> {
-        match attribute {
-            &Attribute::RawAttribute { name_index: ref n_idx, info: ref bytes } => self.write_u16(n_idx.idx as u16).and(self.write_u32(bytes.len() as u32)).and(self.write_n(bytes)),
-            &Attribute::ConstantValue(ref idx) => self.write_u16(cp.get_utf8_index("ConstantValue") as u16).and(self.write_u32(2)).and(self.write_u16(idx.idx as u16)),
-            &Attribute::Code { max_stack, max_locals, ref code, ref exception_table, ref attributes } => {
-                let mut target: Vec<u8> = vec![];
+    fn write_code(&mut self, max_stack: u16, max_locals: u16, code: &Vec<Instruction>, exception_table: &Vec<ExceptionHandler>, attributes: &Vec<Attribute>, cp: &ConstantPool) -> Result<usize, Error> {
+        let mut target: Vec<u8> = vec![];
 
-                {
-                    let mut code_writer = ClassWriter::new(&mut target);
+        {
+            let mut code_writer = ClassWriter::new(&mut target);
 
-                    let _ = code_writer.write_u16(max_stack)
-                        .and(code_writer.write_u16(max_locals))
-                        .and(code_writer.write_instructions(code))
-                        .and(code_writer.write_exception_handlers(exception_table))
-                        .and(code_writer.write_attributes(attributes, cp));
-                }
+            code_writer.write_u16(max_stack)
+                .and(code_writer.write_u16(max_locals))
+                .and(code_writer.write_instructions(code))
+                .and(code_writer.write_exception_handlers(exception_table))
+                .and(code_writer.write_attributes(attributes, cp))?;
+        }
 
-                self.write_u16(cp.get_utf8_index("Code") as u16)
-                    .and(self.write_u32(target.len() as u32))
-                    .and(self.write_n(&target))
-            }
+        self.write_attribute_name(cp, "Code")
+            .and(self.write_length(target.len()))
+            .and(self.write_n(&target))
+    }
+
+    fn write_attribute(&mut self, attribute: &Attribute, cp: &ConstantPool) -> Result<usize, Error> {
+        match attribute {
+            &Attribute::RawAttribute { name_index: ref n_idx, info: ref bytes } => self.write_constant_pool_index(n_idx).and(self.write_length(bytes.len())).and(self.write_n(bytes)),
+            &Attribute::ConstantValue(ref idx) => self.write_attribute_name(cp, "ConstantValue").and(self.write_u32(2)).and(self.write_constant_pool_index(idx)),
+            &Attribute::Code { max_stack, max_locals, ref code, ref exception_table, ref attributes } => self.write_code(max_stack, max_locals, code, exception_table, attributes, cp),
             &Attribute::StackMapTable(ref table) => self.write_stack_map_table(table, cp),
-            &Attribute::Exceptions(ref table) => self.write_u16(cp.get_utf8_index("Exceptions") as u16).and(self.write_u32(2 + (table.len() as u32) * 2)).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| self.write_u16(x.idx as u16))),
-            &Attribute::InnerClasses(ref table) => self.write_u16(cp.get_utf8_index("InnerClasses") as u16).and(self.write_u32(2 + (table.len() as u32) * 8)).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| {
-                self.write_u16(x.inner_class_info_index.idx as u16)
-                    .and(self.write_u16(x.outer_class_info_index.idx as u16))
-                    .and(self.write_u16(x.inner_name_index.idx as u16))
-                    .and(self.write_u16(x.access_flags.flags))
+            &Attribute::Exceptions(ref table) => self.write_attribute_name(cp, "Exceptions").and(self.write_length(2 + table.len() * 2)).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| self.write_constant_pool_index(x).map(|m| n + m))),
+            &Attribute::InnerClasses(ref table) => self.write_attribute_name(cp, "InnerClasses").and(self.write_length(2 + table.len() * 8)).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| {
+                self.write_constant_pool_index(&x.inner_class_info_index)
+                    .and(self.write_constant_pool_index(&x.outer_class_info_index))
+                    .and(self.write_constant_pool_index(&x.inner_name_index))
+                    .and(self.write_u16(x.access_flags.flags)).map(|m| n + m)
             })),
-            &Attribute::EnclosingMethod { ref class_index, ref method_index } => self.write_u16(cp.get_utf8_index("EnclosingMethod") as u16).and(self.write_u32(4)).and(self.write_u16(class_index.idx as u16)).and(self.write_u16(method_index.idx as u16)),
-            &Attribute::Synthetic => self.write_u16(cp.get_utf8_index("Synthetic") as u16).and(self.write_u32(0)),
-            &Attribute::Signature(ref idx) => self.write_u16(cp.get_utf8_index("Signature") as u16).and(self.write_u32(2)).and(self.write_u16(idx.idx as u16)),
-            &Attribute::SourceFile(ref idx) => self.write_u16(cp.get_utf8_index("SourceFile") as u16).and(self.write_u32(2)).and(self.write_u16(idx.idx as u16)),
-            &Attribute::SourceDebugExtension(ref vec) => self.write_u16(cp.get_utf8_index("SourceDebugExtension") as u16).and(self.write_u32(vec.len() as u32)).and(self.write_n(vec)),
-            &Attribute::LineNumberTable(ref table) => self.write_u16(cp.get_utf8_index("LineNumberTable") as u16).and(self.write_u32(2 + table.len() as u32 * 4)).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| {
-                self.write_u16(x.start_pc).and(self.write_u16(x.line_number))
+            &Attribute::EnclosingMethod { ref class_index, ref method_index } => self.write_attribute_name(cp, "EnclosingMethod").and(self.write_u32(4)).and(self.write_constant_pool_index(class_index)).and(self.write_constant_pool_index(method_index)),
+            &Attribute::Synthetic => self.write_attribute_name(cp, "Synthetic").and(self.write_u32(0)),
+            &Attribute::Signature(ref idx) => self.write_attribute_name(cp, "Signature").and(self.write_u32(2)).and(self.write_constant_pool_index(idx)),
+            &Attribute::SourceFile(ref idx) => self.write_attribute_name(cp, "SourceFile").and(self.write_u32(2)).and(self.write_constant_pool_index(idx)),
+            &Attribute::SourceDebugExtension(ref vec) => self.write_attribute_name(cp, "SourceDebugExtension").and(self.write_length(vec.len())).and(self.write_n(vec)),
+            &Attribute::LineNumberTable(ref table) => self.write_attribute_name(cp, "LineNumberTable").and(self.write_length(2 + table.len() * 4)).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| {
+                self.write_u16(x.start_pc).and(self.write_u16(x.line_number)).map(|m| n + m)
             })),
-            &Attribute::LocalVariableTable(ref table) => self.write_u16(cp.get_utf8_index("LocalVariableTable") as u16).and(self.write_u32(2 + table.len() as u32 * 10)).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| {
+            &Attribute::LocalVariableTable(ref table) => self.write_attribute_name(cp, "LocalVariableTable").and(self.write_length(2 + table.len() * 10)).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| {
                 self.write_u16(x.start_pc)
                     .and(self.write_u16(x.length))
-                    .and(self.write_u16(x.name_index.idx as u16))
-                    .and(self.write_u16(x.descriptor_index.idx as u16))
-                    .and(self.write_u16(x.index))
+                    .and(self.write_constant_pool_index(&x.name_index))
+                    .and(self.write_constant_pool_index(&x.descriptor_index))
+                    .and(self.write_u16(x.index)).map(|m| n + m)
             })),
-            &Attribute::LocalVariableTypeTable(ref table) => self.write_u16(cp.get_utf8_index("LocalVariableTypeTable") as u16).and(self.write_u32(2 + table.len() as u32 * 10)).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| {
+            &Attribute::LocalVariableTypeTable(ref table) => self.write_attribute_name(cp, "LocalVariableTypeTable").and(self.write_length(2 + table.len() * 10)).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| {
                 self.write_u16(x.start_pc)
                     .and(self.write_u16(x.length))
-                    .and(self.write_u16(x.name_index.idx as u16))
-                    .and(self.write_u16(x.signature_index.idx as u16))
-                    .and(self.write_u16(x.index))
+                    .and(self.write_constant_pool_index(&x.name_index))
+                    .and(self.write_constant_pool_index(&x.signature_index))
+                    .and(self.write_u16(x.index)).map(|m| n + m)
             })),
-            &Attribute::Deprecated => self.write_u16(cp.get_utf8_index("Deprecated") as u16).and(self.write_u32(0)),
+            &Attribute::Deprecated => self.write_attribute_name(cp, "Deprecated").and(self.write_u32(0)),
             &Attribute::RuntimeVisibleAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeVisibleAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeVisibleAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(2, |acc, x| acc + x.len() as u32)))
+                    .and(self.write_length(table.iter().fold(2, |acc, x| acc + x.len())))
                     // num_annotations
-                    .and(self.write_u16(table.len() as u16))
+                    .and(self.write_count(table.len()))
                     // annotations
-                    .and(table.iter().fold(Ok(0), |_, x| self.write_annotation(x, cp)))
+                    .and(table.iter().try_fold(0, |n, x| self.write_annotation(x, cp).map(|m| n + m)))
             }
             &Attribute::RuntimeInvisibleAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeInvisibleAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeInvisibleAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(2, |acc, x| acc + x.len() as u32)))
+                    .and(self.write_length(table.iter().fold(2, |acc, x| acc + x.len())))
                     // num_annotations
-                    .and(self.write_u16(table.len() as u16))
+                    .and(self.write_count(table.len()))
                     // annotations
-                    .and(table.iter().fold(Ok(0), |_, x| self.write_annotation(x, cp)))
+                    .and(table.iter().try_fold(0, |n, x| self.write_annotation(x, cp).map(|m| n + m)))
             }
             &Attribute::RuntimeVisibleParameterAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeVisibleParameterAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeVisibleParameterAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(1, |acc, x| acc + x.iter().fold(2, |acc2, x2| acc2 + x2.len()) as u32)))
+                    .and(self.write_length(table.iter().fold(1, |acc, x| acc + x.iter().fold(2, |acc2, x2| acc2 + x2.len()))))
                     // num_parameters
-                    .and(self.write_u8(table.len() as u8))
+                    .and(self.write_small_count(table.len()))
                     // parameter_annotations
-                    .and(table.iter().fold(Ok(0), |_, ann_table| self.write_u16(ann_table.len() as u16).and(ann_table.iter().fold(Ok(0), |_, ann| self.write_annotation(ann, cp)))))
+                    .and(table.iter().try_fold(0, |n, ann_table| self.write_count(ann_table.len()).and(ann_table.iter().try_fold(0, |n, ann| self.write_annotation(ann, cp).map(|m| n + m))).map(|m| n + m)))
             }
             &Attribute::RuntimeInvisibleParameterAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeInvisibleParameterAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeInvisibleParameterAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(1, |acc, x| acc + x.iter().fold(2, |acc2, x2| acc2 + x2.len()) as u32)))
+                    .and(self.write_length(table.iter().fold(1, |acc, x| acc + x.iter().fold(2, |acc2, x2| acc2 + x2.len()))))
                     // num_parameters
-                    .and(self.write_u8(table.len() as u8))
+                    .and(self.write_small_count(table.len()))
                     // parameter_annotations
-                    .and(table.iter().fold(Ok(0), |_, ann_table| self.write_u16(ann_table.len() as u16).and(ann_table.iter().fold(Ok(0), |_, ann| self.write_annotation(ann, cp)))))
+                    .and(table.iter().try_fold(0, |n, ann_table| self.write_count(ann_table.len()).and(ann_table.iter().try_fold(0, |n, ann| self.write_annotation(ann, cp).map(|m| n + m))).map(|m| n + m)))
             }
             &Attribute::RuntimeVisibleTypeAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeVisibleTypeAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeVisibleTypeAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(2, |acc, x| acc + x.len() as u32)))
+                    .and(self.write_length(table.iter().fold(2, |acc, x| acc + x.len())))
                     // num_annotations
-                    .and(self.write_u16(table.len() as u16))
+                    .and(self.write_count(table.len()))
                     // annotations
-                    .and(table.iter().fold(Ok(0), |_, x| self.write_type_annotation(x, cp)))
+                    .and(table.iter().try_fold(0, |n, x| self.write_type_annotation(x, cp).map(|m| n + m)))
             }
             &Attribute::RuntimeInvisibleTypeAnnotations(ref table) => {
-                self.write_u16(cp.get_utf8_index("RuntimeInvisibleTypeAnnotations") as u16)
+                self.write_attribute_name(cp, "RuntimeInvisibleTypeAnnotations")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(2, |acc, x| acc + x.len() as u32)))
+                    .and(self.write_length(table.iter().fold(2, |acc, x| acc + x.len())))
                     // num_annotations
-                    .and(self.write_u16(table.len() as u16))
+                    .and(self.write_count(table.len()))
                     // annotations
-                    .and(table.iter().fold(Ok(0), |_, x| self.write_type_annotation(x, cp)))
+                    .and(table.iter().try_fold(0, |n, x| self.write_type_annotation(x, cp).map(|m| n + m)))
             }
             &Attribute::AnnotationDefault(ref value) => {
-                self.write_u16(cp.get_utf8_index("AnnotationDefault") as u16)
-                    .and(self.write_u32(value.len() as u32))
+                self.write_attribute_name(cp, "AnnotationDefault")
+                    .and(self.write_length(value.len()))
                     .and(self.write_element_value(value, cp))
             }
             &Attribute::BootstrapMethods(ref table) => {
-                self.write_u16(cp.get_utf8_index("BootstrapMethods") as u16)
+                self.write_attribute_name(cp, "BootstrapMethods")
                     // attribute_length
-                    .and(self.write_u32(table.iter().fold(2, |acc, method| acc + 4 + method.bootstrap_arguments.len() as u32 * 2)))
+                    .and(self.write_length(table.iter().fold(2, |acc, method| acc + 4 + method.bootstrap_arguments.len() * 2)))
                     // num_bootstrap_methods
-                    .and(self.write_u16(table.len() as u16))
+                    .and(self.write_count(table.len()))
                     // bootstrap_methods
-                    .and(table.iter().fold(Ok(0), |_, method| {
+                    .and(table.iter().try_fold(0, |n, method| {
                         // bootstrap_method_ref
-                        self.write_u16(method.bootstrap_method_ref.idx as u16)
+                        self.write_constant_pool_index(&method.bootstrap_method_ref)
                             // num_bootstrap_arguments
-                            .and(self.write_u16(method.bootstrap_arguments.len() as u16))
+                            .and(self.write_count(method.bootstrap_arguments.len()))
                             // bootstrap_arguments
-                            .and(method.bootstrap_arguments.iter().fold(Ok(0), |_, arg| self.write_u16(arg.idx as u16)))
+                            .and(method.bootstrap_arguments.iter().try_fold(0, |n, arg| self.write_constant_pool_index(arg).map(|m| n + m))).map(|m| n + m)
                     }))
             }
             &Attribute::MethodParameters(ref table) => {
-                self.write_u16(cp.get_utf8_index("MethodParameters") as u16)
-                    .and(self.write_u32(1 + table.len() as u32 * 4))
-                    .and(table.iter().fold(Ok(0), |_, p| self.write_u16(p.name_index.idx as u16).and(self.write_u16(p.access_flags.flags as u16))))
+                self.write_attribute_name(cp, "MethodParameters")
+                    .and(self.write_length(1 + table.len() * 4))
+                    .and(self.write_small_count(table.len()))
+                    .and(table.iter().try_fold(0, |n, p| self.write_constant_pool_index(&p.name_index).and(self.write_u16(p.access_flags.flags as u16)).map(|m| n + m)))
+            }
+            &Attribute::Module { ref name_index, ref flags, ref version_index, ref requires, ref exports, ref opens, ref uses, ref provides } => {
+                self.write_attribute_name(cp, "Module")
+                    // attribute_length
+                    .and(self.write_length(6 + 2 + requires.iter().fold(0, |acc, x| acc + x.len())
+                        + 2 + exports.iter().fold(0, |acc, x| acc + x.len())
+                        + 2 + opens.iter().fold(0, |acc, x| acc + x.len())
+                        + 2 + uses.len() * 2
+                        + 2 + provides.iter().fold(0, |acc, x| acc + x.len())))
+                    // module_name_index, module_flags, module_version_index
+                    .and(self.write_constant_pool_index(name_index))
+                    .and(self.write_u16(flags.flags))
+                    .and(self.write_constant_pool_index(version_index))
+                    // requires
+                    .and(self.write_count(requires.len()))
+                    .and(requires.iter().fold(Ok(0), |acc, x| {
+                        acc.and(self.write_constant_pool_index(&x.requires_index))
+                            .and(self.write_u16(x.requires_flags.flags))
+                            .and(self.write_constant_pool_index(&x.requires_version_index))
+                    }))
+                    // exports
+                    .and(self.write_count(exports.len()))
+                    .and(exports.iter().fold(Ok(0), |acc, x| {
+                        acc.and(self.write_constant_pool_index(&x.exports_index))
+                            .and(self.write_u16(x.exports_flags.flags))
+                            .and(self.write_constant_pool_indices(&x.exports_to))
+                    }))
+                    // opens
+                    .and(self.write_count(opens.len()))
+                    .and(opens.iter().fold(Ok(0), |acc, x| {
+                        acc.and(self.write_constant_pool_index(&x.opens_index))
+                            .and(self.write_u16(x.opens_flags.flags))
+                            .and(self.write_constant_pool_indices(&x.opens_to))
+                    }))
+                    // uses
+                    .and(self.write_constant_pool_indices(uses))
+                    // provides
+                    .and(self.write_count(provides.len()))
+                    .and(provides.iter().fold(Ok(0), |acc, x| {
+                        acc.and(self.write_constant_pool_index(&x.provides_index))
+                            .and(self.write_constant_pool_indices(&x.provides_with))
+                    }))
+            }
+            &Attribute::ModulePackages(ref table) => {
+                self.write_attribute_name(cp, "ModulePackages")
+                    .and(self.write_length(2 + table.len() * 2))
+                    .and(self.write_constant_pool_indices(table))
+            }
+            &Attribute::ModuleMainClass(ref idx) => self.write_attribute_name(cp, "ModuleMainClass").and(self.write_u32(2)).and(self.write_constant_pool_index(idx)),
+            &Attribute::NestHost(ref idx) => self.write_attribute_name(cp, "NestHost").and(self.write_u32(2)).and(self.write_constant_pool_index(idx)),
+            &Attribute::NestMembers(ref table) => {
+                self.write_attribute_name(cp, "NestMembers")
+                    .and(self.write_length(2 + table.len() * 2))
+                    .and(self.write_constant_pool_indices(table))
+            }
+            &Attribute::Record(ref components) => {
+                let mut target: Vec<u8> = vec![];
+
+                {
+                    // component attributes are variable-length, so the body is rendered first to learn its size
+                    let mut record_writer = ClassWriter::new(&mut target);
+
+                    components.iter().fold(record_writer.write_count(components.len()), |acc, x| {
+                        acc.and(record_writer.write_constant_pool_index(&x.name_index))
+                            .and(record_writer.write_constant_pool_index(&x.descriptor_index))
+                            .and(record_writer.write_attributes(&x.attributes, cp))
+                    })?;
+                }
+
+                self.write_attribute_name(cp, "Record")
+                    .and(self.write_length(target.len()))
+                    .and(self.write_n(&target))
+            }
+            &Attribute::PermittedSubclasses(ref table) => {
+                self.write_attribute_name(cp, "PermittedSubclasses")
+                    .and(self.write_length(2 + table.len() * 2))
+                    .and(self.write_constant_pool_indices(table))
             }
         }
     }
 
+    /// Writes a u2 length-prefixed table of constant pool indices
+    fn write_constant_pool_indices(&mut self, table: &Vec<ConstantPoolIndex>) -> Result<usize, Error> {
+        table.iter().fold(self.write_count(table.len()), |acc, x| acc.and(self.write_constant_pool_index(x)))
+    }
+
     fn write_stack_map_table(&mut self, table: &Vec<StackMapFrame>, cp: &ConstantPool) -> Result<usize, Error> {
         // attribute_name_index
-        self.write_u16(cp.get_utf8_index("StackMapTable") as u16)
+        self.write_attribute_name(cp, "StackMapTable")
             // attribute_length = number_of_entries length (2) + sum of entries' length
-            .and(self.write_u32(2 + table.iter().map(|st| st.len()).fold(0, |acc, x| acc + x) as u32))
+            .and(self.write_length(2 + table.iter().map(|st| st.len()).fold(0, |acc, x| acc + x)))
             // number_of_entries
-            .and(self.write_u16(table.len() as u16))
+            .and(self.write_count(table.len()))
             // entries
-            .and(table.iter().fold(Ok(0), |_, x| {
-                match x {
+            .and(table.iter().try_fold(0, |n, x| {
+                let written = match x {
                     &StackMapFrame::SameFrame { tag } => self.write_u8(tag),
                     &StackMapFrame::SameLocals1StackItemFrame { tag, ref stack } => self.write_u8(tag).and(self.write_verification_type(stack)),
                     &StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => self.write_u8(247).and(self.write_u16(offset_delta)).and(self.write_verification_type(stack)),
                     &StackMapFrame::ChopFrame { tag, offset_delta } => self.write_u8(tag).and(self.write_u16(offset_delta)),
                     &StackMapFrame::SameFrameExtended { offset_delta } => self.write_u8(251).and(self.write_u16(offset_delta)),
-                    &StackMapFrame::AppendFrame { tag, offset_delta, ref locals } => self.write_u8(tag).and(self.write_u16(offset_delta)).and(locals.iter().fold(Ok(0), |_, x| self.write_verification_type(x))),
+                    &StackMapFrame::AppendFrame { tag, offset_delta, ref locals } => self.write_u8(tag).and(self.write_u16(offset_delta)).and(locals.iter().try_fold(0, |n, x| self.write_verification_type(x).map(|m| n + m))),
                     &StackMapFrame::FullFrame { offset_delta, ref locals, ref stack } => {
                         // full frame tag
                         self.write_u8(255)
                             // offset_delta
                             .and(self.write_u16(offset_delta))
                             // number_of_locals
-                            .and(self.write_u16(locals.len() as u16))
+                            .and(self.write_count(locals.len()))
                             // locals
-                            .and(locals.iter().fold(Ok(0), |_, x| self.write_verification_type(x)))
+                            .and(locals.iter().try_fold(0, |n, x| self.write_verification_type(x).map(|m| n + m)))
                             // number_of_stack_items
-                            .and(self.write_u16(stack.len() as u16))
+                            .and(self.write_count(stack.len()))
                             // stack
-                            .and(stack.iter().fold(Ok(0), |_, x| self.write_verification_type(x)))
+                            .and(stack.iter().try_fold(0, |n, x| self.write_verification_type(x).map(|m| n + m)))
                     }
                     &StackMapFrame::FutureUse { tag } => self.write_u8(tag)
-                }
+                };
+
+                written.map(|m| n + m)
             }))
     }
 
@@ -299,32 +466,32 @@ impl<'a> ClassWriter<'a> {
             &VerificationType::Double => self.write_u8(3),
             &VerificationType::Null => self.write_u8(5),
             &VerificationType::UninitializedThis => self.write_u8(6),
-            &VerificationType::Object { ref cpool_index } => self.write_u8(7).and(self.write_u16(cpool_index.idx as u16)),
+            &VerificationType::Object { ref cpool_index } => self.write_u8(7).and(self.write_constant_pool_index(cpool_index)),
             &VerificationType::Uninitialized { offset } => self.write_u8(8).and(self.write_u16(offset))
         }
     }
 
     fn write_element_value(&mut self, element_value: &ElementValue, cp: &ConstantPool) -> Result<usize, Error> {
         match element_value {
-            &ElementValue::ConstantValue(tag, ref idx) => self.write_u8(tag).and(self.write_u16(idx.idx as u16)),
-            &ElementValue::Enum { ref type_name_index, ref const_name_index } => self.write_u8(101).and(self.write_u16(type_name_index.idx as u16)).and(self.write_u16(const_name_index.idx as u16)),
-            &ElementValue::ClassInfo(ref idx) => self.write_u8(99).and(self.write_u16(idx.idx as u16)),
+            &ElementValue::ConstantValue(tag, ref idx) => self.write_u8(tag).and(self.write_constant_pool_index(idx)),
+            &ElementValue::Enum { ref type_name_index, ref const_name_index } => self.write_u8(101).and(self.write_constant_pool_index(type_name_index)).and(self.write_constant_pool_index(const_name_index)),
+            &ElementValue::ClassInfo(ref idx) => self.write_u8(99).and(self.write_constant_pool_index(idx)),
             &ElementValue::Annotation(ref annotation) => self.write_u8(64).and(self.write_annotation(annotation, cp)),
-            &ElementValue::Array(ref table) => self.write_u8(91).and(self.write_u16(table.len() as u16)).and(table.iter().fold(Ok(0), |_, x| { self.write_element_value(x, cp) }))
+            &ElementValue::Array(ref table) => self.write_u8(91).and(self.write_count(table.len())).and(table.iter().try_fold(0, |n, x| { self.write_element_value(x, cp).map(|m| n + m) }))
         }
     }
 
     fn write_element_value_pair(&mut self, pair: &ElementValuePair, cp: &ConstantPool) -> Result<usize, Error> {
-        self.write_u16(pair.element_name_index.idx as u16).and(self.write_element_value(&pair.value, cp))
+        self.write_constant_pool_index(&pair.element_name_index).and(self.write_element_value(&pair.value, cp))
     }
 
     fn write_annotation(&mut self, annotation: &Annotation, cp: &ConstantPool) -> Result<usize, Error> {
         // type_index
-        self.write_u16(annotation.type_index.idx as u16)
+        self.write_constant_pool_index(&annotation.type_index)
             // num_element_value_pairs
-            .and(self.write_u16(annotation.element_value_pairs.len() as u16))
+            .and(self.write_count(annotation.element_value_pairs.len()))
             // element_value_pairs
-            .and(annotation.element_value_pairs.iter().fold(Ok(0), |_, x| self.write_element_value_pair(x, cp)))
+            .and(annotation.element_value_pairs.iter().try_fold(0, |n, x| self.write_element_value_pair(x, cp).map(|m| n + m)))
     }
 
     fn write_type_annotation(&mut self, annotation: &TypeAnnotation, cp: &ConstantPool) -> Result<usize, Error> {
@@ -339,7 +506,7 @@ impl<'a> ClassWriter<'a> {
                     &TargetInfo::Empty { subtype: _ } => Ok(0),
                     &TargetInfo::MethodFormalParameter { idx } => self.write_u8(idx),
                     &TargetInfo::Throws { idx } => self.write_u16(idx),
-                    &TargetInfo::LocalVar { subtype: _, ref target } => self.write_u16(target.len() as u16).and(target.iter().fold(Ok(0), |_, x| self.write_u16(x.0).and(self.write_u16(x.1)).and(self.write_u16(x.2)))),
+                    &TargetInfo::LocalVar { subtype: _, ref target } => self.write_count(target.len()).and(target.iter().try_fold(0, |n, x| self.write_u16(x.0).and(self.write_u16(x.1)).and(self.write_u16(x.2)).map(|m| n + m))),
                     &TargetInfo::Catch { idx } => self.write_u16(idx),
                     &TargetInfo::Offset { subtype: _, idx } => self.write_u16(idx),
                     &TargetInfo::TypeArgument { subtype: _, offset, type_arg_idx } => self.write_u16(offset).and(self.write_u8(type_arg_idx))
@@ -347,30 +514,31 @@ impl<'a> ClassWriter<'a> {
             })
             .and({
                 // path_length
-                self.write_u8(annotation.target_path.path.len() as u8)
+                self.write_small_count(annotation.target_path.path.len())
                     // path
-                    .and(annotation.target_path.path.iter().fold(Ok(0), |_, x| self.write_u8(x.0.value()).and(self.write_u8(x.1))))
+                    .and(annotation.target_path.path.iter().try_fold(0, |n, x| self.write_u8(x.0.value()).and(self.write_u8(x.1)).map(|m| n + m)))
             })
-            .and(self.write_u16(annotation.type_index.idx as u16))
-            .and(self.write_u16(annotation.element_value_pairs.len() as u16))
-            .and(annotation.element_value_pairs.iter().fold(Ok(0), |_, x| self.write_element_value_pair(x, cp)))
+            .and(self.write_constant_pool_index(&annotation.type_index))
+            .and(self.write_count(annotation.element_value_pairs.len()))
+            .and(annotation.element_value_pairs.iter().try_fold(0, |n, x| self.write_element_value_pair(x, cp).map(|m| n + m)))
     }
 
     fn write_instructions(&mut self, instructions: &Vec<Instruction>) -> Result<usize, Error> {
         let mut target: Vec<u8> = vec![];
-        let _ /*written_bytes*/ = {
+
+        {
             let mut instr_writer = ClassWriter::new(&mut target);
 
-            instructions.iter().fold(0 as usize, |counter, instr| {
-                counter + instr_writer.render_instruction(instr, counter)
-            })
-        };
+            instructions.iter().try_fold(0, |offset, instr| {
+                instr_writer.render_instruction(instr, offset).map(|len| offset + len)
+            })?;
+        }
 
-        self.write_u32(target.len() as u32).and_then(|x| self.write_n(&target).map(|y| x + y))
+        self.write_length(target.len()).and_then(|x| self.write_n(&target).map(|y| x + y))
     }
 
     /// Renders a single instruction into the output stream
-    fn render_instruction(&mut self, instruction: &Instruction, offset: usize) -> usize {
+    fn render_instruction(&mut self, instruction: &Instruction, offset: usize) -> Result<usize, Error> {
         match instruction {
             &Instruction::AALOAD => self.write_u8(0x32),
             &Instruction::AASTORE => self.write_u8(0x53),
@@ -543,26 +711,23 @@ impl<'a> ClassWriter<'a> {
             &Instruction::LMUL => self.write_u8(0x69),
             &Instruction::LNEG => self.write_u8(0x75),
             &Instruction::LOOKUPSWITCH(a, ref l) => {
-                let _ = self.write_u8(0xab);
+                self.write_u8(0xab)?;
 
                 let padding = (4 - ((offset + 1) % 4)) % 4;
 
-
                 for _ in 0..padding {
-                    let _ = self.write_u8(0);
+                    self.write_u8(0)?;
                 }
 
-                let _ = self.write_u32(a as u32);
-                let _ = self.write_u32(l.len() as u32);
+                self.write_u32(a as u32)?;
+                self.write_length(l.len())?;
 
                 for &(p1, p2) in l {
-                    let _ = self.write_u32(p1 as u32);
-                    let _ = self.write_u32(p2 as u32);
+                    self.write_u32(p1 as u32)?;
+                    self.write_u32(p2 as u32)?;
                 }
 
-                let len = 9 + padding + l.len() * 8;
-
-                Ok(len)
+                Ok(9 + padding + l.len() * 8)
             }
             &Instruction::LOR => self.write_u8(0x81),
             &Instruction::LREM => self.write_u8(0x71),
@@ -595,20 +760,20 @@ impl<'a> ClassWriter<'a> {
             &Instruction::SWAP => self.write_u8(0x5f),
             //TABLESWITCH(i32, i32, i32, Vec<i32>),
             &Instruction::TABLESWITCH(a, b, c, ref d) => {
-                let _ = self.write_u8(0xaa);
+                self.write_u8(0xaa)?;
 
                 let padding = (4 - ((offset + 1) % 4)) % 4;
 
                 for _ in 0..padding {
-                    let _ = self.write_u8(0);
+                    self.write_u8(0)?;
                 }
 
-                let _ = self.write_u32(a as u32);
-                let _ = self.write_u32(b as u32);
-                let _ = self.write_u32(c as u32);
+                self.write_u32(a as u32)?;
+                self.write_u32(b as u32)?;
+                self.write_u32(c as u32)?;
 
                 for &v in d {
-                    let _ = self.write_u32(v as u32);
+                    self.write_u32(v as u32)?;
                 }
 
                 Ok(13 + padding + d.len() * 4)
@@ -625,21 +790,21 @@ impl<'a> ClassWriter<'a> {
             &Instruction::DSTORE_W(value) => self.write_u16(0xc439).and(self.write_u16(value)).and(Ok(4)),
             &Instruction::RET_W(value) => self.write_u16(0xc4a9).and(self.write_u16(value)).and(Ok(4)),
             &Instruction::IINC_W(a, b) => self.write_u16(0xc484).and(self.write_u16(a)).and(self.write_u16(b as u16)).and(Ok(6)),
-            _ => self.write_u8(0xFF)
-        }.ok().unwrap_or(0)
+            &Instruction::PADDED_INSTRUCTION(_) | &Instruction::WTF(_) => Err(Error::new(ErrorKind::InvalidData, format!("Can't write the {} at offset {}", instruction.mnemonic(), offset)))
+        }
     }
 
     fn write_exception_handlers(&mut self, exception_table: &Vec<ExceptionHandler>) -> Result<usize, Error> {
-        self.write_u16(exception_table.len() as u16)
-            .and(exception_table.iter().fold(Ok(0), |_, x| {
+        self.write_count(exception_table.len())
+            .and(exception_table.iter().try_fold(0, |n, x| {
                 self.write_u16(x.start_pc)
                     .and(self.write_u16(x.end_pc))
                     .and(self.write_u16(x.handler_pc))
-                    .and(self.write_u16(x.catch_type.idx as u16))
+                    .and(self.write_constant_pool_index(&x.catch_type)).map(|m| n + m)
             }))
     }
 
-    pub fn write_n(&mut self, bytes: &Vec<u8>) -> Result<usize, Error> {
+    pub fn write_n(&mut self, bytes: &[u8]) -> Result<usize, Error> {
         bytes.iter().fold(Ok(0), |acc, x| match acc {
             Ok(ctr) => self.write_u8(*x).map(|c| c + ctr),
             err @ _ => err
@@ -0,0 +1,157 @@
+//!
+//! JSON serialisation of the class file model. Every type of the model implements serde's
+//! `Serialize` and `Deserialize`, so classes can be serialised with any serde format, and this
+//! module adds JSON shorthands along with a resolved form for humans.
+//!
+//! Constant pool indices are serialised as plain numbers. In the resolved form they're serialised
+//! as an object holding the index along with the value the index refers to, eg.
+//! `{"idx":7,"value":"java/lang/Object"}`. Both forms deserialise to the same class, which writes
+//! back to the bytes it was read from. Instructions keep referring to constants by plain number in
+//! either form.
+
+use std::cell::RefCell;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use serde_json;
+use super::classfile::*;
+use super::mutf8;
+
+thread_local! {
+    /// The values of the constants of the class being serialised in resolved form, by index
+    static RESOLVED_VALUES: RefCell<Option<Vec<Option<String>>>> = const { RefCell::new(None) };
+}
+
+/// Serialise a class to JSON, with constant pool indices as plain numbers
+pub fn to_json(classfile: &Classfile) -> Result<String, serde_json::Error> {
+    serde_json::to_string(classfile)
+}
+
+/// Serialise a class to indented JSON in resolved form, for reading and snapshot tests
+pub fn to_json_resolved(classfile: &Classfile) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&Resolved(classfile))
+}
+
+/// Deserialise a class from JSON in either form
+pub fn from_json(json: &str) -> Result<Classfile<'static>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+///
+/// Serialises the class it wraps in resolved form, that is with the values of constants next to
+/// the indices that refer to them.
+pub struct Resolved<'c>(pub &'c Classfile<'c>);
+
+impl<'c> Serialize for Resolved<'c> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let cp = &self.0.constant_pool;
+        let values = cp.constants.iter().map(|constant| describe(cp, constant)).collect();
+
+        let previous = RESOLVED_VALUES.with(|resolved| resolved.replace(Some(values)));
+        let result = self.0.serialize(serializer);
+
+        RESOLVED_VALUES.with(|resolved| resolved.replace(previous));
+        result
+    }
+}
+
+impl Serialize for ConstantPoolIndex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let value = RESOLVED_VALUES.with(|resolved| resolved.borrow().as_ref().map(|values| values.get(self.idx).cloned().unwrap_or(None)));
+
+        match value {
+            None => serializer.serialize_u64(self.idx as u64),
+            Some(value) => {
+                let mut state = serializer.serialize_struct("ConstantPoolIndex", 2)?;
+                state.serialize_field("idx", &self.idx)?;
+                state.serialize_field("value", &value)?;
+                state.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConstantPoolIndex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Form {
+            Plain(usize),
+            Resolved { idx: usize },
+        }
+
+        match Form::deserialize(deserializer)? {
+            Form::Plain(idx) | Form::Resolved { idx } => Ok(ConstantPoolIndex::new(idx))
+        }
+    }
+}
+
+/// Serialise an index that doesn't point into the constant pool as a plain number in either form
+pub fn serialize_unresolved<S>(idx: &ConstantPoolIndex, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    serializer.serialize_u64(idx.idx as u64)
+}
+
+///
+/// Serialisation of the bytes of `Utf8` constants as strings. Bytes that don't decode to a string
+/// that encodes back to the same bytes, such as unpaired surrogates, are kept as an array of bytes.
+pub mod utf8 {
+    use std::borrow::Cow;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use super::super::mutf8;
+
+    pub fn serialize<S>(bytes: &Cow<[u8]>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        match mutf8::decode(bytes) {
+            Ok(ref value) if mutf8::encode(value)[..] == bytes[..] => serializer.serialize_str(value),
+            _ => serializer.collect_seq(bytes.iter())
+        }
+    }
+
+    pub fn deserialize<'de, 'a, D>(deserializer: D) -> Result<Cow<'a, [u8]>, D::Error> where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Form {
+            String(String),
+            Bytes(Vec<u8>),
+        }
+
+        match Form::deserialize(deserializer)? {
+            Form::String(value) => Ok(Cow::Owned(mutf8::encode(&value).into_owned())),
+            Form::Bytes(bytes) => Ok(Cow::Owned(bytes))
+        }
+    }
+}
+
+/// The value of a constant as shown in the resolved form, `None` for the slots that hold none
+fn describe(cp: &ConstantPool, constant: &Constant) -> Option<String> {
+    let utf8 = |idx: &ConstantPoolIndex| cp.get_utf8(idx.idx as u16).map(|bytes| mutf8::decode_lossy(bytes).into_owned());
+    let name_and_type = |idx: &ConstantPoolIndex| match cp.get(idx) {
+        Ok(constant @ &Constant::NameAndType { .. }) => describe(cp, constant),
+        _ => None
+    };
+
+    match constant {
+        &Constant::Utf8(ref bytes) => Some(mutf8::decode_lossy(bytes).into_owned()),
+        &Constant::Integer(value) => Some((value as i32).to_string()),
+        &Constant::Float(bits) => Some(f32::from_bits(bits).to_string()),
+        &Constant::Long(value) => Some((value as i64).to_string()),
+        &Constant::Double(bits) => Some(f64::from_bits(bits).to_string()),
+        &Constant::Class(ref idx) | &Constant::String(ref idx) | &Constant::MethodType(ref idx) |
+        &Constant::Module(ref idx) | &Constant::Package(ref idx) => utf8(idx),
+        &Constant::FieldRef { ref class_index, ref name_and_type_index } |
+        &Constant::MethodRef { ref class_index, ref name_and_type_index } |
+        &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } => match (cp.get_class_name(class_index.idx as u16), name_and_type(name_and_type_index)) {
+            (Some(class), Some(member)) => Some(format!("{}.{}", class, member)),
+            _ => None
+        },
+        &Constant::NameAndType { ref name_index, ref descriptor_index } => match (utf8(name_index), utf8(descriptor_index)) {
+            (Some(name), Some(descriptor)) => Some(format!("{}:{}", name, descriptor)),
+            _ => None
+        },
+        // Handles refer to field and method references, which don't refer to handles in turn
+        &Constant::MethodHandle { ref reference_kind, ref reference_index } => match cp.get(reference_index) {
+            Ok(reference @ &Constant::FieldRef { .. }) | Ok(reference @ &Constant::MethodRef { .. }) | Ok(reference @ &Constant::InterfaceMethodRef { .. }) => describe(cp, reference).map(|reference| format!("{:?} {}", reference_kind, reference)),
+            _ => None
+        },
+        &Constant::Dynamic { ref name_and_type_index, .. } | &Constant::InvokeDynamic { ref name_and_type_index, .. } => name_and_type(name_and_type_index),
+        &Constant::Unknown(_) | &Constant::Placeholder => None
+    }
+}
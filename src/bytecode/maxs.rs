@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use super::cfg::{ Flow, Offsets };
+use super::classfile::*;
+use super::descriptor::*;
+use super::frames::*;
+
+///
+/// Recompute `max_stack` and `max_locals` of every method that has code, which is needed after
+/// instructions have been added or changed. Both are counted in words the way the JVM counts
+/// them, so longs and doubles take two entries on the operand stack and two local variables.
+pub fn compute_maxs(class: &mut Classfile) -> Result<(), FrameError> {
+    for method in class.methods.iter_mut() {
+        if let Some((stack, locals)) = method_maxs(&class.constant_pool, method)? {
+            for attribute in method.attributes.iter_mut() {
+                if let &mut Attribute::Code { ref mut max_stack, ref mut max_locals, .. } = attribute {
+                    *max_stack = stack;
+                    *max_locals = locals;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///
+/// The `max_stack` and `max_locals` a method needs for its current code, or `None` if the method
+/// has no `Code` attribute. Instructions that can't be reached are sized as straight-line code
+/// starting from an empty operand stack, so dead code left behind by a transformer still fits, and
+/// every local variable they use is counted as well.
+pub fn method_maxs(cp: &ConstantPool, method: &Method) -> Result<Option<(u16, u16)>, FrameError> {
+    let name = cp.get_utf8_string(method.name_index.idx as u16).unwrap_or(String::new());
+    let descriptor = cp.get_utf8_string(method.descriptor_index.idx as u16).unwrap_or(String::new());
+    let is_static = method.access_flags.has_flag(MethodAccessFlags::Static as u16);
+
+    for attribute in &method.attributes {
+        if let &Attribute::Code { ref code, ref exception_table, .. } = attribute {
+            return maxs(cp, &descriptor, is_static, code, exception_table).map(Some).map_err(|(offset, kind)| FrameError {
+                method: format!("{}{}", name, descriptor),
+                offset: offset,
+                kind: kind,
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+type Failure = (usize, FrameErrorKind);
+
+/// How an instruction changes the operand stack, in words, and the local variables it touches
+struct Effect {
+    pops: usize,
+    pushes: usize,
+    /// Index of the first local variable used and the number of words used from there
+    local: Option<(u16, u16)>,
+}
+
+fn maxs(cp: &ConstantPool, descriptor: &str, is_static: bool, code: &Vec<Instruction>, exception_table: &Vec<ExceptionHandler>) -> Result<(u16, u16), Failure> {
+    let (params, _) = descriptor_words(descriptor).ok_or((0, FrameErrorKind::BadDescriptor(descriptor.to_string())))?;
+    let mut max_locals = params + if is_static { 0 } else { 1 };
+    let mut max_stack = 0;
+
+    let mut effects = Vec::with_capacity(code.len());
+    let mut length = 0;
+
+    for instruction in code {
+        let effect = effect(cp, instruction).map_err(|kind| (length, kind))?;
+
+        if let Some((idx, size)) = effect.local {
+            max_locals = max_locals.max(idx as usize + size as usize);
+        }
+
+        effects.push(effect);
+        length += instruction.len_at(length);
+    }
+
+    if code.is_empty() {
+        return Ok((0, max_locals.min(u16::max_value() as usize) as u16));
+    }
+
+    let offsets = Offsets::new(code);
+    let handlers = exception_table.iter().map(|handler| {
+        let position = offsets.instruction_at(handler.handler_pc as i64).map_err(|target| (handler.handler_pc as usize, FrameErrorKind::InvalidTarget(target)))?;
+        Ok((handler.start_pc as usize, handler.end_pc as usize, position))
+    }).collect::<Result<Vec<(usize, usize, usize)>, Failure>>()?;
+
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut queue = VecDeque::new();
+
+    depths[0] = Some(0);
+    queue.push_back(0);
+
+    while let Some(position) = queue.pop_front() {
+        let offset = offsets.offset(position);
+        let depth = depths[position].unwrap_or(0);
+        let effect = &effects[position];
+
+        if effect.pops > depth {
+            return Err((offset, FrameErrorKind::StackUnderflow));
+        }
+
+        let after = depth - effect.pops + effect.pushes;
+        max_stack = max_stack.max(after);
+
+        let mut successors = vec![];
+        let next = position + 1;
+
+        for &(start, end, handler) in &handlers {
+            if start <= offset && offset < end {
+                max_stack = max_stack.max(1);
+                successors.push((handler, 1));
+            }
+        }
+
+        let flow = offsets.flow(code, position).map_err(|target| (offset, FrameErrorKind::InvalidTarget(target)))?;
+
+        for target in flow.targets() {
+            successors.push((target, after));
+        }
+
+        match flow {
+            // The subroutine returns to the next instruction without the return address
+            Flow::Subroutine(_) => successors.push((next, depth)),
+            ref flow if flow.continues() => successors.push((next, after)),
+            _ => ()
+        }
+
+        for (target, depth) in successors {
+            if target >= code.len() {
+                return Err((offset, FrameErrorKind::FallsOffEnd));
+            }
+
+            match depths[target] {
+                Some(existing) if existing != depth => return Err((offsets.offset(target), FrameErrorKind::InconsistentStack)),
+                Some(_) => (),
+                None => {
+                    depths[target] = Some(depth);
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+
+    // Dead code never runs, but the JVM still checks it against max_stack
+    let mut dead: usize = 0;
+
+    for (position, effect) in effects.iter().enumerate() {
+        match depths[position] {
+            Some(_) => dead = 0,
+            None => {
+                dead = dead.saturating_sub(effect.pops) + effect.pushes;
+                max_stack = max_stack.max(dead);
+            }
+        }
+    }
+
+    let limit = u16::max_value() as usize;
+    Ok((max_stack.min(limit) as u16, max_locals.min(limit) as u16))
+}
+
+/// The number of words taken by the parameters and by the return value of a method descriptor
+fn descriptor_words(descriptor: &str) -> Option<(usize, usize)> {
+    MethodDescriptor::parse(descriptor).ok().map(|method| (method.param_slots(), method.return_slots()))
+}
+
+/// The number of words taken by the field a field reference points to
+fn field_words(cp: &ConstantPool, idx: u16) -> Result<usize, FrameErrorKind> {
+    let (_, _, descriptor) = cp.get_member_ref(idx).ok_or(FrameErrorKind::BadConstant(idx))?;
+    FieldType::parse(&descriptor).map(|field_type| field_type.slots()).map_err(|_| FrameErrorKind::BadDescriptor(descriptor))
+}
+
+/// The number of words taken by the arguments and the return value of the method a method
+/// reference points to
+fn method_words(cp: &ConstantPool, idx: u16) -> Result<(usize, usize), FrameErrorKind> {
+    let (_, _, descriptor) = cp.get_member_ref(idx).ok_or(FrameErrorKind::BadConstant(idx))?;
+    descriptor_words(&descriptor).ok_or(FrameErrorKind::BadDescriptor(descriptor))
+}
+
+fn effect(cp: &ConstantPool, instruction: &Instruction) -> Result<Effect, FrameErrorKind> {
+    let stack = |pops: usize, pushes: usize| Effect { pops: pops, pushes: pushes, local: None };
+    let load = |idx: u16, size: u16| Effect { pops: 0, pushes: size as usize, local: Some((idx, size)) };
+    let store = |idx: u16, size: u16| Effect { pops: size as usize, pushes: 0, local: Some((idx, size)) };
+
+    Ok(match *instruction {
+        Instruction::NOP => stack(0, 0),
+        Instruction::ACONST_NULL |
+        Instruction::ICONST_M1 | Instruction::ICONST_0 | Instruction::ICONST_1 | Instruction::ICONST_2 |
+        Instruction::ICONST_3 | Instruction::ICONST_4 | Instruction::ICONST_5 |
+        Instruction::FCONST_0 | Instruction::FCONST_1 | Instruction::FCONST_2 |
+        Instruction::BIPUSH(_) | Instruction::SIPUSH(_) | Instruction::LDC(_) | Instruction::LDC_W(_) => stack(0, 1),
+        Instruction::LCONST_0 | Instruction::LCONST_1 | Instruction::DCONST_0 | Instruction::DCONST_1 | Instruction::LDC2_W(_) => stack(0, 2),
+
+        Instruction::ILOAD(idx) | Instruction::FLOAD(idx) | Instruction::ALOAD(idx) => load(idx as u16, 1),
+        Instruction::LLOAD(idx) | Instruction::DLOAD(idx) => load(idx as u16, 2),
+        Instruction::ILOAD_W(idx) | Instruction::FLOAD_W(idx) | Instruction::ALOAD_W(idx) => load(idx, 1),
+        Instruction::LLOAD_W(idx) | Instruction::DLOAD_W(idx) => load(idx, 2),
+        Instruction::ILOAD_0 | Instruction::FLOAD_0 | Instruction::ALOAD_0 => load(0, 1),
+        Instruction::ILOAD_1 | Instruction::FLOAD_1 | Instruction::ALOAD_1 => load(1, 1),
+        Instruction::ILOAD_2 | Instruction::FLOAD_2 | Instruction::ALOAD_2 => load(2, 1),
+        Instruction::ILOAD_3 | Instruction::FLOAD_3 | Instruction::ALOAD_3 => load(3, 1),
+        Instruction::LLOAD_0 | Instruction::DLOAD_0 => load(0, 2),
+        Instruction::LLOAD_1 | Instruction::DLOAD_1 => load(1, 2),
+        Instruction::LLOAD_2 | Instruction::DLOAD_2 => load(2, 2),
+        Instruction::LLOAD_3 | Instruction::DLOAD_3 => load(3, 2),
+
+        Instruction::ISTORE(idx) | Instruction::FSTORE(idx) | Instruction::ASTORE(idx) => store(idx as u16, 1),
+        Instruction::LSTORE(idx) | Instruction::DSTORE(idx) => store(idx as u16, 2),
+        Instruction::ISTORE_W(idx) | Instruction::FSTORE_W(idx) | Instruction::ASTORE_W(idx) => store(idx, 1),
+        Instruction::LSTORE_W(idx) | Instruction::DSTORE_W(idx) => store(idx, 2),
+        Instruction::ISTORE_0 | Instruction::FSTORE_0 | Instruction::ASTORE_0 => store(0, 1),
+        Instruction::ISTORE_1 | Instruction::FSTORE_1 | Instruction::ASTORE_1 => store(1, 1),
+        Instruction::ISTORE_2 | Instruction::FSTORE_2 | Instruction::ASTORE_2 => store(2, 1),
+        Instruction::ISTORE_3 | Instruction::FSTORE_3 | Instruction::ASTORE_3 => store(3, 1),
+        Instruction::LSTORE_0 | Instruction::DSTORE_0 => store(0, 2),
+        Instruction::LSTORE_1 | Instruction::DSTORE_1 => store(1, 2),
+        Instruction::LSTORE_2 | Instruction::DSTORE_2 => store(2, 2),
+        Instruction::LSTORE_3 | Instruction::DSTORE_3 => store(3, 2),
+
+        Instruction::IALOAD | Instruction::FALOAD | Instruction::AALOAD | Instruction::BALOAD | Instruction::CALOAD | Instruction::SALOAD => stack(2, 1),
+        Instruction::LALOAD | Instruction::DALOAD => stack(2, 2),
+        Instruction::IASTORE | Instruction::FASTORE | Instruction::AASTORE | Instruction::BASTORE | Instruction::CASTORE | Instruction::SASTORE => stack(3, 0),
+        Instruction::LASTORE | Instruction::DASTORE => stack(4, 0),
+
+        Instruction::POP => stack(1, 0),
+        Instruction::POP2 => stack(2, 0),
+        Instruction::DUP => stack(1, 2),
+        Instruction::DUP_X1 => stack(2, 3),
+        Instruction::DUP_X2 => stack(3, 4),
+        Instruction::DUP2 => stack(2, 4),
+        Instruction::DUP2_X1 => stack(3, 5),
+        Instruction::DUP2_X2 => stack(4, 6),
+        Instruction::SWAP => stack(2, 2),
+
+        Instruction::IADD | Instruction::ISUB | Instruction::IMUL | Instruction::IDIV | Instruction::IREM |
+        Instruction::IAND | Instruction::IOR | Instruction::IXOR | Instruction::ISHL | Instruction::ISHR | Instruction::IUSHR |
+        Instruction::FADD | Instruction::FSUB | Instruction::FMUL | Instruction::FDIV | Instruction::FREM |
+        Instruction::FCMPL | Instruction::FCMPG => stack(2, 1),
+        Instruction::LADD | Instruction::LSUB | Instruction::LMUL | Instruction::LDIV | Instruction::LREM |
+        Instruction::LAND | Instruction::LOR | Instruction::LXOR |
+        Instruction::DADD | Instruction::DSUB | Instruction::DMUL | Instruction::DDIV | Instruction::DREM => stack(4, 2),
+        Instruction::LSHL | Instruction::LSHR | Instruction::LUSHR => stack(3, 2),
+        Instruction::LCMP | Instruction::DCMPL | Instruction::DCMPG => stack(4, 1),
+
+        Instruction::INEG | Instruction::FNEG | Instruction::I2F | Instruction::F2I |
+        Instruction::I2B | Instruction::I2C | Instruction::I2S => stack(1, 1),
+        Instruction::LNEG | Instruction::DNEG | Instruction::L2D | Instruction::D2L => stack(2, 2),
+        Instruction::I2L | Instruction::I2D | Instruction::F2L | Instruction::F2D => stack(1, 2),
+        Instruction::L2I | Instruction::L2F | Instruction::D2I | Instruction::D2F => stack(2, 1),
+
+        Instruction::IINC(idx, _) => Effect { pops: 0, pushes: 0, local: Some((idx as u16, 1)) },
+        Instruction::IINC_W(idx, _) => Effect { pops: 0, pushes: 0, local: Some((idx, 1)) },
+
+        Instruction::IFEQ(_) | Instruction::IFNE(_) | Instruction::IFLT(_) | Instruction::IFGE(_) |
+        Instruction::IFGT(_) | Instruction::IFLE(_) | Instruction::IFNULL(_) | Instruction::IFNONNULL(_) => stack(1, 0),
+        Instruction::IF_ICMPEQ(_) | Instruction::IF_ICMPNE(_) | Instruction::IF_ICMPLT(_) | Instruction::IF_ICMPGE(_) |
+        Instruction::IF_ICMPGT(_) | Instruction::IF_ICMPLE(_) | Instruction::IF_ACMPEQ(_) | Instruction::IF_ACMPNE(_) => stack(2, 0),
+        Instruction::GOTO(_) | Instruction::GOTO_W(_) => stack(0, 0),
+        Instruction::JSR(_) | Instruction::JSR_W(_) => stack(0, 1),
+        Instruction::RET(idx) => Effect { pops: 0, pushes: 0, local: Some((idx as u16, 1)) },
+        Instruction::RET_W(idx) => Effect { pops: 0, pushes: 0, local: Some((idx, 1)) },
+        Instruction::TABLESWITCH(..) | Instruction::LOOKUPSWITCH(..) => stack(1, 0),
+        Instruction::IRETURN | Instruction::FRETURN | Instruction::ARETURN | Instruction::ATHROW => stack(1, 0),
+        Instruction::LRETURN | Instruction::DRETURN => stack(2, 0),
+        Instruction::RETURN => stack(0, 0),
+
+        Instruction::GETSTATIC(idx) => stack(0, field_words(cp, idx)?),
+        Instruction::PUTSTATIC(idx) => stack(field_words(cp, idx)?, 0),
+        Instruction::GETFIELD(idx) => stack(1, field_words(cp, idx)?),
+        Instruction::PUTFIELD(idx) => stack(1 + field_words(cp, idx)?, 0),
+        Instruction::INVOKESTATIC(idx) => { let (args, ret) = method_words(cp, idx)?; stack(args, ret) },
+        Instruction::INVOKEVIRTUAL(idx) | Instruction::INVOKESPECIAL(idx) | Instruction::INVOKEINTERFACE(idx, _) => {
+            let (args, ret) = method_words(cp, idx)?;
+            stack(args + 1, ret)
+        },
+        Instruction::INVOKEDYNAMIC(idx) => {
+            let descriptor = match cp.resolve_index(&ConstantPoolIndex::new(idx as usize)) {
+                Some(&Constant::InvokeDynamic { ref name_and_type_index, .. }) => cp.get_name_and_type(name_and_type_index.idx as u16).ok_or(FrameErrorKind::BadConstant(idx))?.1,
+                _ => return Err(FrameErrorKind::BadConstant(idx))
+            };
+
+            let (args, ret) = descriptor_words(&descriptor).ok_or(FrameErrorKind::BadDescriptor(descriptor.clone()))?;
+            stack(args, ret)
+        },
+        Instruction::NEW(_) => stack(0, 1),
+        Instruction::NEWARRAY(_) | Instruction::ANEWARRAY(_) | Instruction::ARRAYLENGTH |
+        Instruction::CHECKCAST(_) | Instruction::INSTANCEOF(_) => stack(1, 1),
+        Instruction::MULTIANEWARRAY(_, dimensions) => stack(dimensions as usize, 1),
+        Instruction::MONITORENTER | Instruction::MONITOREXIT => stack(1, 0),
+        Instruction::PADDED_INSTRUCTION(_) | Instruction::WTF(_) => return Err(FrameErrorKind::UnknownInstruction),
+    })
+}
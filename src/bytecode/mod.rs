@@ -1,9 +1,31 @@
 pub use self::classfile::*;
+pub use self::code::*;
+pub use self::compact::*;
+pub use self::descriptor::*;
+pub use self::frames::*;
+pub use self::hierarchy::*;
 pub use self::io::*;
+pub use self::maxs::*;
+pub use self::signature::*;
+pub use self::verify::*;
 
+pub mod analyzer;
+pub mod asm;
+pub mod cfg;
 pub mod classfile;
+pub mod code;
+pub mod compact;
+pub mod descriptor;
+pub mod diff;
+pub mod frames;
+pub mod hierarchy;
 pub mod io;
+pub mod json;
+pub mod maxs;
+pub mod mutf8;
 pub mod printer;
+pub mod signature;
+pub mod verify;
 
 /*
 
@@ -0,0 +1,136 @@
+//!
+//! Conversion between Rust strings and the modified UTF-8 the JVM uses in `Utf8` constants and
+//! in the strings JNI and JVMTI hand out. It differs from standard UTF-8 in two ways: NUL is
+//! encoded in two bytes, so that encoded strings never contain a zero byte, and characters beyond
+//! the Basic Multilingual Plane are encoded as a surrogate pair of three bytes each.
+//!
+//! Strings without either are encoded the same way in both, in which case no copy is made.
+
+use std::borrow::Cow;
+use std::char;
+use std::error::Error;
+use std::fmt;
+use std::str;
+
+#[derive(Debug, PartialEq)]
+pub enum Mutf8Error {
+    /// The byte at this offset doesn't start or continue a valid modified UTF-8 sequence
+    InvalidByte(usize),
+    /// The string ends in the middle of a sequence
+    UnexpectedEnd,
+    /// The surrogate at this offset isn't part of a pair, which Java strings may contain but
+    /// Rust strings can't
+    UnpairedSurrogate(usize),
+}
+
+impl fmt::Display for Mutf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Mutf8Error::InvalidByte(offset) => write!(f, "invalid modified UTF-8 byte at offset {}", offset),
+            &Mutf8Error::UnexpectedEnd => write!(f, "unexpected end of modified UTF-8 string"),
+            &Mutf8Error::UnpairedSurrogate(offset) => write!(f, "unpaired surrogate at offset {}", offset),
+        }
+    }
+}
+
+impl Error for Mutf8Error {}
+
+/// Encode a string in modified UTF-8
+pub fn encode<'a>(value: &'a str) -> Cow<'a, [u8]> {
+    if !value.chars().any(|c| c == '\0' || c as u32 > 0xFFFF) {
+        return Cow::Borrowed(value.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(value.len() + 2);
+
+    for c in value.chars() {
+        match c as u32 {
+            0 => bytes.extend_from_slice(&[ 0xC0, 0x80 ]),
+            code @ 0x10000..=0x10FFFF => {
+                let code = code - 0x10000;
+
+                push_three_bytes(&mut bytes, 0xD800 | (code >> 10));
+                push_three_bytes(&mut bytes, 0xDC00 | (code & 0x3FF));
+            },
+            _ => {
+                let mut buffer = [ 0; 4 ];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+
+    Cow::Owned(bytes)
+}
+
+/// Decode a modified UTF-8 string, failing on malformed input and unpaired surrogates
+pub fn decode<'a>(bytes: &'a [u8]) -> Result<Cow<'a, str>, Mutf8Error> {
+    decode_with(bytes, false)
+}
+
+/// Decode a modified UTF-8 string, replacing malformed sequences and unpaired surrogates with
+/// U+FFFD, which is how strings are printed
+pub fn decode_lossy<'a>(bytes: &'a [u8]) -> Cow<'a, str> {
+    decode_with(bytes, true).unwrap_or(Cow::Borrowed(""))
+}
+
+fn decode_with<'a>(bytes: &'a [u8], lossy: bool) -> Result<Cow<'a, str>, Mutf8Error> {
+    // Without zero bytes, the lead bytes of surrogates and four byte sequences, valid UTF-8 is
+    // valid modified UTF-8 with the same meaning
+    if !bytes.iter().any(|&b| b == 0 || b == 0xED || b >= 0xF0) {
+        if let Ok(value) = str::from_utf8(bytes) {
+            return Ok(Cow::Borrowed(value));
+        }
+    }
+
+    // The UTF-16 code units of the string along with the offsets they start at
+    let mut units: Vec<(usize, u32)> = Vec::with_capacity(bytes.len());
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let continuation = |idx: usize| match bytes.get(offset + idx) {
+            Some(&b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u32),
+            Some(_) => Err(Mutf8Error::InvalidByte(offset + idx)),
+            None => Err(Mutf8Error::UnexpectedEnd)
+        };
+
+        let decoded = match bytes[offset] {
+            b @ 0x01..=0x7F => Ok((b as u32, 1)),
+            b @ 0xC0..=0xDF => continuation(1).map(|low| ((((b & 0x1F) as u32) << 6) | low, 2)),
+            b @ 0xE0..=0xEF => continuation(1).and_then(|mid| continuation(2).map(|low| ((((b & 0x0F) as u32) << 12) | (mid << 6) | low, 3))),
+            _ => Err(Mutf8Error::InvalidByte(offset))
+        };
+
+        match decoded {
+            Ok((unit, len)) => { units.push((offset, unit)); offset += len; },
+            Err(_) if lossy => { units.push((offset, 0xFFFD)); offset += 1; },
+            Err(err) => return Err(err)
+        }
+    }
+
+    let mut value = String::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < units.len() {
+        let (offset, unit) = units[idx];
+
+        match (unit, units.get(idx + 1)) {
+            (0xD800..=0xDBFF, Some(&(_, low @ 0xDC00..=0xDFFF))) => {
+                value.push(char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)).unwrap_or(char::REPLACEMENT_CHARACTER));
+                idx += 1;
+            },
+            (0xD800..=0xDFFF, _) if lossy => value.push(char::REPLACEMENT_CHARACTER),
+            (0xD800..=0xDFFF, _) => return Err(Mutf8Error::UnpairedSurrogate(offset)),
+            _ => value.push(char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER))
+        }
+
+        idx += 1;
+    }
+
+    Ok(Cow::Owned(value))
+}
+
+fn push_three_bytes(bytes: &mut Vec<u8>, unit: u32) {
+    bytes.push(0xE0 | ((unit >> 12) & 0x0F) as u8);
+    bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+    bytes.push(0x80 | (unit & 0x3F) as u8);
+}
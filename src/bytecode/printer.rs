@@ -1,4 +1,9 @@
+use std::mem;
+use std::slice;
 use super::classfile::*;
+use super::descriptor::{FieldType, MethodDescriptor};
+use super::mutf8;
+use super::signature::{GenericClassSignature, GenericMethodSignature, TypeParameter, TypeSignature};
 
 pub struct ClassfilePrinter;
 
@@ -29,9 +34,26 @@ impl ClassfilePrinter {
             .map(|line| lines.push(line))
             .collect();
 
+        let _: Vec<()> = classfile.attributes.iter()
+            .flat_map(|attribute| ClassfilePrinter::render_attribute(attribute, &classfile.constant_pool))
+            .map(|line| lines.push(line))
+            .collect();
+
         lines
     }
 
+    ///
+    /// Render the class the way `javap -v` does, so that the two can be diffed, save for the lines
+    /// naming the class file, its modification time and its checksum, which the class itself
+    /// doesn't know. Private members and inner classes are left out unless `show_private` is set,
+    /// which corresponds to `javap -p`.
+    pub fn render_javap(classfile: &Classfile, show_private: bool) -> Vec<String> {
+        let mut writer = JavapWriter::new(classfile, show_private);
+
+        writer.write_class();
+        writer.finish()
+    }
+
     pub fn render_flags(flags: &AccessFlags) -> String {
         let mut flag_vec = vec![];
 
@@ -59,7 +81,7 @@ impl ClassfilePrinter {
 
     pub fn render_constant(constant: &Constant, pool: &ConstantPool) -> String {
         match constant {
-            &Constant::Utf8(ref content) => format!("Utf8               {}", String::from_utf8_lossy(content.as_slice())),
+            &Constant::Utf8(ref content) => format!("Utf8               {}", mutf8::decode_lossy(content)),
             &Constant::Integer(value) => format!("Integer            {}", value),
             &Constant::Float(value) => format!("Float               {}", value),
             &Constant::Long(value) => format!("Long               {}", value),
@@ -72,7 +94,10 @@ impl ClassfilePrinter {
             &Constant::NameAndType { name_index: ref ni, descriptor_index: ref dp } => format!("NameAndType        {:<14} // {}:{}", format!("#{}:#{}", ni.idx, dp.idx), ClassfilePrinter::resolve_utf8(ni, pool), ClassfilePrinter::resolve_utf8(dp, pool)),
             &Constant::MethodHandle { reference_kind: ref kind, reference_index: ref ri } => format!("MethodHandle       {} #{}", ClassfilePrinter::resolve_reference_kind(kind), ri.idx),
             &Constant::MethodType(ref cpi) => format!("MethodType         #{}", cpi.idx),
+            &Constant::Dynamic { bootstrap_method_attr_index: ref bi, name_and_type_index: ref ni } => format!("Dynamic            #{}.{}", bi.idx, ClassfilePrinter::resolve_name_and_type(ni, pool)),
             &Constant::InvokeDynamic { bootstrap_method_attr_index: ref bi, name_and_type_index: ref ni } => format!("InvokeDynamic      #{}.{}", bi.idx, ClassfilePrinter::resolve_name_and_type(ni, pool)),
+            &Constant::Module(ref cpi) => format!("Module             #{:<14}// {}", cpi.idx, ClassfilePrinter::resolve_utf8(cpi, pool)),
+            &Constant::Package(ref cpi) => format!("Package            #{:<14}// {}", cpi.idx, ClassfilePrinter::resolve_utf8(cpi, pool)),
             &Constant::Unknown(value) => format!("Unknown constant        {}", value),
             &Constant::Placeholder => format!("Placeholder")
         }
@@ -93,6 +118,20 @@ impl ClassfilePrinter {
         }).unwrap_or(String::from("<Not found>"))
     }
 
+    pub fn resolve_module(index: &ConstantPoolIndex, cp: &ConstantPool) -> String {
+        cp.resolve_index(index).map(|constant| match constant {
+            &Constant::Module(ref idx) => ClassfilePrinter::resolve_utf8(idx, cp),
+            _ => String::from("<Not a module>")
+        }).unwrap_or(String::from("<Not found>"))
+    }
+
+    pub fn resolve_package(index: &ConstantPoolIndex, cp: &ConstantPool) -> String {
+        cp.resolve_index(index).map(|constant| match constant {
+            &Constant::Package(ref idx) => ClassfilePrinter::resolve_utf8(idx, cp),
+            _ => String::from("<Not a package>")
+        }).unwrap_or(String::from("<Not found>"))
+    }
+
     pub fn resolve_name_and_type(nandt: &ConstantPoolIndex, cp: &ConstantPool) -> String {
         cp.resolve_index(nandt).map(|constant| match constant {
             &Constant::NameAndType { name_index: ref ni, descriptor_index: ref di } => format!("{}:{}", ClassfilePrinter::resolve_utf8(ni, cp), ClassfilePrinter::resolve_utf8(di, cp)),
@@ -386,6 +425,35 @@ impl ClassfilePrinter {
                 let _: Vec<()> = table.iter().map(|var_type| ClassfilePrinter::render_local_variable_type(var_type)).map(|line| lines.push(format!("    {}", line))).collect();
             }
             &Attribute::Deprecated => { lines.push(format!("    Deprecated")); }
+            &Attribute::Module { ref name_index, ref flags, ref version_index, ref requires, ref exports, ref opens, ref uses, ref provides } => {
+                lines.push(format!("    Module: {} flags: {:#06x} version: {}", ClassfilePrinter::resolve_module(name_index, cp), flags.flags, ClassfilePrinter::resolve_optional_utf8(version_index, cp)));
+                let _: Vec<()> = requires.iter().map(|r| lines.push(format!("      requires {} flags: {:#06x} version: {}", ClassfilePrinter::resolve_module(&r.requires_index, cp), r.requires_flags.flags, ClassfilePrinter::resolve_optional_utf8(&r.requires_version_index, cp)))).collect();
+                let _: Vec<()> = exports.iter().map(|e| lines.push(format!("      exports {} flags: {:#06x}{}", ClassfilePrinter::resolve_package(&e.exports_index, cp), e.exports_flags.flags, ClassfilePrinter::render_targets(" to ", &e.exports_to, cp, ClassfilePrinter::resolve_module)))).collect();
+                let _: Vec<()> = opens.iter().map(|o| lines.push(format!("      opens {} flags: {:#06x}{}", ClassfilePrinter::resolve_package(&o.opens_index, cp), o.opens_flags.flags, ClassfilePrinter::render_targets(" to ", &o.opens_to, cp, ClassfilePrinter::resolve_module)))).collect();
+                let _: Vec<()> = uses.iter().map(|u| lines.push(format!("      uses {}", ClassfilePrinter::resolve_class(u, cp)))).collect();
+                let _: Vec<()> = provides.iter().map(|p| lines.push(format!("      provides {}{}", ClassfilePrinter::resolve_class(&p.provides_index, cp), ClassfilePrinter::render_targets(" with ", &p.provides_with, cp, ClassfilePrinter::resolve_class)))).collect();
+            }
+            &Attribute::ModulePackages(ref table) => {
+                lines.push(String::from("    ModulePackages"));
+                let _: Vec<()> = table.iter().map(|package| lines.push(format!("      {}", ClassfilePrinter::resolve_package(package, cp)))).collect();
+            }
+            &Attribute::ModuleMainClass(ref idx) => { lines.push(format!("    ModuleMainClass: {}", ClassfilePrinter::resolve_class(idx, cp))); }
+            &Attribute::NestHost(ref idx) => { lines.push(format!("    NestHost: {}", ClassfilePrinter::resolve_class(idx, cp))); }
+            &Attribute::NestMembers(ref table) => {
+                lines.push(String::from("    NestMembers"));
+                let _: Vec<()> = table.iter().map(|member| lines.push(format!("      {}", ClassfilePrinter::resolve_class(member, cp)))).collect();
+            }
+            &Attribute::Record(ref components) => {
+                lines.push(String::from("    Record"));
+                let _: Vec<()> = components.iter().map(|component| {
+                    lines.push(format!("      {} {}", ClassfilePrinter::resolve_utf8(&component.name_index, cp), ClassfilePrinter::resolve_utf8(&component.descriptor_index, cp)));
+                    let _: Vec<()> = component.attributes.iter().flat_map(|att| ClassfilePrinter::render_attribute(att, cp)).map(|line| lines.push(format!("    {}", line))).collect();
+                }).collect();
+            }
+            &Attribute::PermittedSubclasses(ref table) => {
+                lines.push(String::from("    PermittedSubclasses"));
+                let _: Vec<()> = table.iter().map(|subclass| lines.push(format!("      {}", ClassfilePrinter::resolve_class(subclass, cp)))).collect();
+            }
             _ => {
                 lines.push(format!("RandomAttribute"));
                 ()
@@ -396,6 +464,23 @@ impl ClassfilePrinter {
         lines
     }
 
+    /// Renders a list of module or class references, eg. the targets of a qualified export
+    fn render_targets(prefix: &str, targets: &Vec<ConstantPoolIndex>, cp: &ConstantPool, resolve: fn(&ConstantPoolIndex, &ConstantPool) -> String) -> String {
+        if targets.is_empty() {
+            String::new()
+        } else {
+            format!("{}{}", prefix, targets.iter().map(|target| resolve(target, cp)).collect::<Vec<String>>().join(", "))
+        }
+    }
+
+    /// Resolves an optional UTF8 reference where index 0 stands for the absence of a value
+    fn resolve_optional_utf8(index: &ConstantPoolIndex, cp: &ConstantPool) -> String {
+        match index.idx {
+            0 => String::from("<none>"),
+            _ => ClassfilePrinter::resolve_utf8(index, cp)
+        }
+    }
+
     pub fn render_stack_map_frame(frame: &StackMapFrame) -> String {
         match frame {
             &StackMapFrame::SameFrame { tag: tag } => format!("SameFrame {}", tag),
@@ -421,3 +506,1519 @@ impl ClassfilePrinter {
         table.iter().map(|line| format!("line {}: {}", line.line_number, line.start_pc)).collect()
     }
 }
+
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_STATIC: u16 = 0x0008;
+const ACC_VARARGS: u16 = 0x0080;
+const ACC_INTERFACE: u16 = 0x0200;
+const ACC_ABSTRACT: u16 = 0x0400;
+const ACC_MODULE: u16 = 0x8000;
+
+const CLASS_FLAGS: &'static [(u16, &'static str)] = &[
+    (0x0001, "ACC_PUBLIC"), (0x0010, "ACC_FINAL"), (0x0020, "ACC_SUPER"), (0x0200, "ACC_INTERFACE"), (0x0400, "ACC_ABSTRACT"),
+    (0x1000, "ACC_SYNTHETIC"), (0x2000, "ACC_ANNOTATION"), (0x4000, "ACC_ENUM"), (0x8000, "ACC_MODULE")
+];
+
+const FIELD_FLAGS: &'static [(u16, &'static str)] = &[
+    (0x0001, "ACC_PUBLIC"), (0x0002, "ACC_PRIVATE"), (0x0004, "ACC_PROTECTED"), (0x0008, "ACC_STATIC"), (0x0010, "ACC_FINAL"),
+    (0x0040, "ACC_VOLATILE"), (0x0080, "ACC_TRANSIENT"), (0x1000, "ACC_SYNTHETIC"), (0x4000, "ACC_ENUM")
+];
+
+const METHOD_FLAGS: &'static [(u16, &'static str)] = &[
+    (0x0001, "ACC_PUBLIC"), (0x0002, "ACC_PRIVATE"), (0x0004, "ACC_PROTECTED"), (0x0008, "ACC_STATIC"), (0x0010, "ACC_FINAL"),
+    (0x0020, "ACC_SYNCHRONIZED"), (0x0040, "ACC_BRIDGE"), (0x0080, "ACC_VARARGS"), (0x0100, "ACC_NATIVE"), (0x0400, "ACC_ABSTRACT"),
+    (0x0800, "ACC_STRICT"), (0x1000, "ACC_SYNTHETIC")
+];
+
+const CLASS_MODIFIERS: &'static [(u16, &'static str)] = &[ (0x0001, "public"), (0x0010, "final"), (0x0400, "abstract") ];
+
+const INNER_CLASS_MODIFIERS: &'static [(u16, &'static str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0008, "static"), (0x0010, "final"), (0x0400, "abstract")
+];
+
+const FIELD_MODIFIERS: &'static [(u16, &'static str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0008, "static"), (0x0010, "final"), (0x0040, "volatile"),
+    (0x0080, "transient")
+];
+
+const METHOD_MODIFIERS: &'static [(u16, &'static str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0008, "static"), (0x0010, "final"), (0x0020, "synchronized"),
+    (0x0100, "native"), (0x0400, "abstract"), (0x0800, "strictfp")
+];
+
+/// Comments are aligned to this column, counted from the current indentation
+const TAB_COLUMN: usize = 40;
+
+const INDENT_WIDTH: usize = 2;
+
+/// The indentation of the cases of switch instructions relative to the instruction
+const SWITCH_INDENT: isize = 3;
+
+///
+/// Writes a class as `javap -v` does. Lines are assembled the way `javap` assembles them:
+/// indentation is applied when the first character of a line is printed, spaces are held back until
+/// something follows them, so that lines never end in spaces, and comments are aligned by `tab`.
+struct JavapWriter<'c> {
+    classfile: &'c Classfile<'c>,
+    show_private: bool,
+    lines: Vec<String>,
+    buffer: String,
+    pending_spaces: usize,
+    /// Set after each method to separate it from the next one with an empty line
+    pending_newline: bool,
+    indent: usize,
+    /// The `args_size` of the method being written, reported along with its code
+    args_size: usize,
+}
+
+impl<'c> JavapWriter<'c> {
+    fn new(classfile: &'c Classfile<'c>, show_private: bool) -> JavapWriter<'c> {
+        JavapWriter {
+            classfile: classfile,
+            show_private: show_private,
+            lines: vec![],
+            buffer: String::new(),
+            pending_spaces: 0,
+            pending_newline: false,
+            indent: 0,
+            args_size: 0,
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if !self.buffer.is_empty() {
+            self.newline();
+        }
+
+        self.lines
+    }
+
+    fn print<S: AsRef<str>>(&mut self, value: S) {
+        if self.pending_newline {
+            self.pending_newline = false;
+            self.newline();
+        }
+
+        for c in value.as_ref().chars() {
+            match c {
+                ' ' => self.pending_spaces += 1,
+                '\n' => self.newline(),
+                _ => {
+                    if self.buffer.is_empty() {
+                        self.buffer.push_str(&" ".repeat(self.indent * INDENT_WIDTH));
+                    }
+
+                    self.buffer.push_str(&" ".repeat(self.pending_spaces));
+                    self.pending_spaces = 0;
+                    self.buffer.push(c);
+                }
+            }
+        }
+    }
+
+    fn println<S: AsRef<str>>(&mut self, value: S) {
+        self.print(value);
+        self.newline();
+    }
+
+    fn newline(&mut self) {
+        self.pending_spaces = 0;
+
+        let line = mem::take(&mut self.buffer);
+        self.lines.push(line);
+    }
+
+    /// Pad the line up to the comment column, or by a single space if it's past that already
+    fn tab(&mut self) {
+        let column = self.indent * INDENT_WIDTH + TAB_COLUMN;
+        let width = self.buffer.chars().count();
+
+        self.pending_spaces += if width < column { column - width } else { 1 };
+    }
+
+    fn indent(&mut self, delta: isize) {
+        self.indent = (self.indent as isize + delta).max(0) as usize;
+    }
+
+    fn print_flags(&mut self, flags: u16, names: &[(u16, &str)]) {
+        let mut set: Vec<String> = names.iter().filter(|&&(flag, _)| flags & flag != 0).map(|&(_, name)| name.to_string()).collect();
+        let known = names.iter().fold(0, |known, &(flag, _)| known | flag);
+
+        // Bits without a meaning in this context are shown as they are
+        for bit in (0..16).map(|bit| 1u16 << bit).filter(|bit| flags & !known & bit != 0) {
+            set.push(format!("0x{:x}", bit));
+        }
+
+        self.println(format!("flags: (0x{:04x}) {}", flags, set.join(", ")));
+    }
+
+    fn print_modifiers(&mut self, modifiers: &[&str]) {
+        for modifier in modifiers {
+            self.print(modifier);
+            self.print(" ");
+        }
+    }
+
+    /// Whether a member or inner class with these flags is shown
+    fn is_shown(&self, flags: u16) -> bool {
+        self.show_private || flags & ACC_PRIVATE == 0
+    }
+
+    // -- Constants
+
+    fn cp(&self) -> &'c ConstantPool<'c> {
+        &self.classfile.constant_pool
+    }
+
+    fn utf8(&self, idx: &ConstantPoolIndex) -> String {
+        match self.cp().get_utf8(idx.idx as u16) {
+            Some(bytes) => mutf8::decode_lossy(bytes).into_owned(),
+            None => format!("#{}", idx.idx)
+        }
+    }
+
+    fn class_name(&self, idx: &ConstantPoolIndex) -> String {
+        match self.cp().get(idx) {
+            Ok(&Constant::Class(ref name)) => self.utf8(name),
+            _ => format!("#{}", idx.idx)
+        }
+    }
+
+    /// The name of a class in Java source, eg. `java.lang.String`
+    fn java_class_name(&self, idx: &ConstantPoolIndex) -> String {
+        self.class_name(idx).replace("/", ".")
+    }
+
+    /// Render the value of a constant as its comment shows it
+    fn string_value(&self, idx: &ConstantPoolIndex) -> String {
+        match self.cp().get(idx) {
+            Ok(constant) => self.constant_value(constant),
+            Err(_) => format!("#{}", idx.idx)
+        }
+    }
+
+    fn constant_value(&self, constant: &Constant) -> String {
+        match constant {
+            &Constant::Utf8(ref bytes) => escape(&mutf8::decode_lossy(bytes)),
+            &Constant::Integer(value) => format!("{}", value as i32),
+            &Constant::Float(bits) => format!("{}f", java_float(f32::from_bits(bits))),
+            &Constant::Long(value) => format!("{}l", value as i64),
+            &Constant::Double(bits) => format!("{}d", java_double(f64::from_bits(bits))),
+            &Constant::Class(ref name) => check_name(&self.utf8(name)),
+            &Constant::String(ref value) => self.string_value(value),
+            &Constant::FieldRef { ref class_index, ref name_and_type_index } |
+            &Constant::MethodRef { ref class_index, ref name_and_type_index } |
+            &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } => format!("{}.{}", check_name(&self.class_name(class_index)), self.string_value(name_and_type_index)),
+            &Constant::NameAndType { ref name_index, ref descriptor_index } => format!("{}:{}", check_name(&self.utf8(name_index)), self.utf8(descriptor_index)),
+            &Constant::MethodHandle { ref reference_kind, ref reference_index } => format!("{} {}", reference_kind_name(reference_kind), self.string_value(reference_index)),
+            &Constant::MethodType(ref descriptor) => self.string_value(descriptor),
+            &Constant::Dynamic { ref bootstrap_method_attr_index, ref name_and_type_index } |
+            &Constant::InvokeDynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => format!("#{}:{}", bootstrap_method_attr_index.idx, self.string_value(name_and_type_index)),
+            &Constant::Module(ref name) | &Constant::Package(ref name) => check_name(&self.utf8(name)),
+            &Constant::Unknown(tag) => format!("(unknown tag {})", tag),
+            &Constant::Placeholder => String::new()
+        }
+    }
+
+    /// Render a constant along with its kind, as operands are commented. References to members of
+    /// the class itself leave out the class name.
+    fn constant_reference(&self, idx: &ConstantPoolIndex) -> String {
+        let constant = match self.cp().get(idx) {
+            Ok(constant) => constant,
+            Err(_) => return format!("#{}", idx.idx)
+        };
+
+        let value = match constant {
+            &Constant::FieldRef { ref class_index, ref name_and_type_index } |
+            &Constant::MethodRef { ref class_index, ref name_and_type_index } |
+            &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } if class_index.idx == self.classfile.this_class.idx => self.string_value(name_and_type_index),
+            _ => self.constant_value(constant)
+        };
+
+        let kind = match constant {
+            &Constant::Utf8(_) => "Utf8",
+            &Constant::Integer(_) => "int",
+            &Constant::Float(_) => "float",
+            &Constant::Long(_) => "long",
+            &Constant::Double(_) => "double",
+            &Constant::Class(_) => "class",
+            &Constant::String(_) => "String",
+            &Constant::FieldRef { .. } => "Field",
+            &Constant::MethodRef { .. } => "Method",
+            &Constant::InterfaceMethodRef { .. } => "InterfaceMethod",
+            &Constant::NameAndType { .. } => "NameAndType",
+            &Constant::MethodHandle { .. } => "MethodHandle",
+            &Constant::MethodType(_) => "MethodType",
+            &Constant::Dynamic { .. } => "Dynamic",
+            &Constant::InvokeDynamic { .. } => "InvokeDynamic",
+            &Constant::Module(_) => "Module",
+            &Constant::Package(_) => "Package",
+            &Constant::Unknown(_) | &Constant::Placeholder => "Unknown"
+        };
+
+        format!("{} {}", kind, value)
+    }
+
+    fn find_utf8_attribute(&self, attributes: &[Attribute], name: &str) -> Option<String> {
+        attributes.iter().filter_map(|attribute| match attribute {
+            &Attribute::Signature(ref idx) if name == "Signature" => Some(self.utf8(idx)),
+            &Attribute::SourceFile(ref idx) if name == "SourceFile" => Some(self.utf8(idx)),
+            _ => None
+        }).next()
+    }
+
+    fn java_field_type(&self, descriptor: &str) -> String {
+        FieldType::parse(descriptor).map(|field_type| java_type(&field_type)).unwrap_or(descriptor.to_string())
+    }
+
+    // -- Class
+
+    fn write_class(&mut self) {
+        let classfile = self.classfile;
+        let flags = classfile.access_flags.flags;
+        let is_interface = flags & ACC_INTERFACE != 0;
+
+        if let Some(source_file) = self.find_utf8_attribute(&classfile.attributes, "SourceFile") {
+            self.indent(1);
+            self.println(format!("Compiled from \"{}\"", source_file));
+            self.indent(-1);
+        }
+
+        self.print_modifiers(&modifiers(if is_interface { flags & !ACC_ABSTRACT } else { flags }, CLASS_MODIFIERS));
+
+        let module = classfile.attributes.iter().filter_map(|attribute| match attribute {
+            &Attribute::Module { ref name_index, ref flags, ref version_index, .. } => Some((name_index, flags.flags, version_index)),
+            _ => None
+        }).next();
+
+        match module {
+            Some((name_index, module_flags, version_index)) if flags & ACC_MODULE != 0 => {
+                let name = match self.cp().get(name_index) {
+                    Ok(&Constant::Module(ref name)) => self.utf8(name),
+                    _ => self.utf8(name_index)
+                };
+
+                if module_flags & 0x0020 != 0 {
+                    self.print("open ");
+                }
+
+                self.print(format!("module {}", name.replace("/", ".")));
+
+                if version_index.idx != 0 {
+                    let version = self.utf8(version_index);
+                    self.print(format!("@{}", version));
+                }
+            },
+            _ => {
+                self.print(if is_interface { "interface " } else { "class " });
+
+                let name = self.java_class_name(&classfile.this_class);
+                self.print(name);
+            }
+        }
+
+        match self.find_utf8_attribute(&classfile.attributes, "Signature").and_then(|signature| GenericClassSignature::parse(&signature).ok()) {
+            // `javap` can't tell a signature naming only the superclass from a field signature and
+            // shows the superclass even for interfaces then
+            Some(ref signature) if signature.type_parameters.is_empty() && signature.interfaces.is_empty() => {
+                self.print(format!(" extends {}", signature.super_class));
+            },
+            Some(signature) => {
+                self.print(type_parameters(&signature.type_parameters));
+
+                let interfaces: Vec<String> = signature.interfaces.iter().map(|interface| interface.to_string()).collect();
+
+                if is_interface {
+                    if !interfaces.is_empty() {
+                        self.print(format!(" extends {}", interfaces.join(", ")));
+                    }
+                } else {
+                    self.print(format!(" extends {}", signature.super_class));
+
+                    if !interfaces.is_empty() {
+                        self.print(format!(" implements {}", interfaces.join(", ")));
+                    }
+                }
+            },
+            None => {
+                if !is_interface && classfile.super_class.idx != 0 {
+                    let super_class = self.java_class_name(&classfile.super_class);
+
+                    if super_class != "java.lang.Object" {
+                        self.print(format!(" extends {}", super_class));
+                    }
+                }
+
+                for (idx, interface) in classfile.interfaces.iter().enumerate() {
+                    let name = self.java_class_name(interface);
+
+                    self.print(match idx {
+                        0 if is_interface => " extends ",
+                        0 => " implements ",
+                        _ => ","
+                    });
+                    self.print(name);
+                }
+            }
+        }
+
+        self.newline();
+        self.indent(1);
+        self.println(format!("minor version: {}", classfile.version.minor_version));
+        self.println(format!("major version: {}", classfile.version.major_version));
+        self.print_flags(flags, CLASS_FLAGS);
+        self.write_class_index("this_class", &classfile.this_class);
+        self.write_class_index("super_class", &classfile.super_class);
+        self.println(format!("interfaces: {}, fields: {}, methods: {}, attributes: {}", classfile.interfaces.len(), classfile.fields.len(), classfile.methods.len(), classfile.attributes.len()));
+        self.indent(-1);
+
+        self.write_constant_pool();
+
+        self.println("{");
+        self.indent(1);
+
+        for field in classfile.fields.iter() {
+            self.write_field(field);
+        }
+
+        for method in classfile.methods.iter() {
+            self.write_method(method);
+        }
+
+        self.pending_newline = false;
+        self.indent(-1);
+        self.println("}");
+
+        for attribute in classfile.attributes.iter() {
+            self.write_attribute(attribute);
+        }
+    }
+
+    fn write_class_index(&mut self, label: &str, idx: &ConstantPoolIndex) {
+        self.print(format!("{}: #{}", label, idx.idx));
+
+        if idx.idx != 0 {
+            self.tab();
+            let name = self.string_value(idx);
+            self.print(format!("// {}", name));
+        }
+
+        self.newline();
+    }
+
+    fn write_constant_pool(&mut self) {
+        let constants = &self.cp().constants;
+        let width = constants.len().to_string().len() + 1;
+
+        self.println("Constant pool:");
+        self.indent(1);
+
+        for (idx, constant) in constants.iter().enumerate().skip(1) {
+            let (kind, operands) = match constant {
+                &Constant::Utf8(_) => ("Utf8", None),
+                &Constant::Integer(_) => ("Integer", None),
+                &Constant::Float(_) => ("Float", None),
+                &Constant::Long(_) => ("Long", None),
+                &Constant::Double(_) => ("Double", None),
+                &Constant::Class(ref name) => ("Class", Some(format!("#{}", name.idx))),
+                &Constant::String(ref value) => ("String", Some(format!("#{}", value.idx))),
+                &Constant::FieldRef { ref class_index, ref name_and_type_index } => ("Fieldref", Some(format!("#{}.#{}", class_index.idx, name_and_type_index.idx))),
+                &Constant::MethodRef { ref class_index, ref name_and_type_index } => ("Methodref", Some(format!("#{}.#{}", class_index.idx, name_and_type_index.idx))),
+                &Constant::InterfaceMethodRef { ref class_index, ref name_and_type_index } => ("InterfaceMethodref", Some(format!("#{}.#{}", class_index.idx, name_and_type_index.idx))),
+                &Constant::NameAndType { ref name_index, ref descriptor_index } => ("NameAndType", Some(format!("#{}:#{}", name_index.idx, descriptor_index.idx))),
+                &Constant::MethodHandle { ref reference_kind, ref reference_index } => ("MethodHandle", Some(format!("{}:#{}", reference_kind.to_u8(), reference_index.idx))),
+                &Constant::MethodType(ref descriptor) => ("MethodType", Some(format!("#{}", descriptor.idx))),
+                &Constant::Dynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => ("Dynamic", Some(format!("#{}:#{}", bootstrap_method_attr_index.idx, name_and_type_index.idx))),
+                &Constant::InvokeDynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => ("InvokeDynamic", Some(format!("#{}:#{}", bootstrap_method_attr_index.idx, name_and_type_index.idx))),
+                &Constant::Module(ref name) => ("Module", Some(format!("#{}", name.idx))),
+                &Constant::Package(ref name) => ("Package", Some(format!("#{}", name.idx))),
+                &Constant::Unknown(_) | &Constant::Placeholder => continue
+            };
+
+            self.print(format!("{:>width$} = {:<18} ", format!("#{}", idx), kind, width = width));
+
+            let value = self.constant_value(constant);
+
+            match operands {
+                Some(operands) => {
+                    self.print(operands);
+                    self.tab();
+
+                    // `javap` separates the descriptors of method types by two spaces
+                    match constant {
+                        &Constant::MethodType(_) => self.println(format!("//  {}", value)),
+                        _ => self.println(format!("// {}", value))
+                    }
+                },
+                None => self.println(value)
+            }
+        }
+
+        self.indent(-1);
+    }
+
+    // -- Members
+
+    fn write_field(&mut self, field: &Field) {
+        let flags = field.access_flags.flags;
+
+        if !self.is_shown(flags) {
+            return;
+        }
+
+        let descriptor = self.utf8(&field.descriptor_index);
+        let field_type = match self.find_utf8_attribute(&field.attributes, "Signature").and_then(|signature| TypeSignature::parse(&signature).ok()) {
+            Some(signature) => signature.to_string(),
+            None => self.java_field_type(&descriptor)
+        };
+        let name = self.utf8(&field.name_index);
+
+        self.print_modifiers(&modifiers(flags, FIELD_MODIFIERS));
+        self.println(format!("{} {};", field_type, name));
+        self.indent(1);
+        self.println(format!("descriptor: {}", descriptor));
+        self.print_flags(flags, FIELD_FLAGS);
+
+        for attribute in field.attributes.iter() {
+            self.write_attribute(attribute);
+        }
+
+        self.indent(-1);
+        self.newline();
+    }
+
+    fn write_method(&mut self, method: &Method) {
+        let classfile = self.classfile;
+        let flags = method.access_flags.flags;
+
+        if !self.is_shown(flags) {
+            return;
+        }
+
+        let name = self.utf8(&method.name_index);
+        let descriptor = self.utf8(&method.descriptor_index);
+        let parsed = MethodDescriptor::parse(&descriptor).ok();
+        let signature = self.find_utf8_attribute(&method.attributes, "Signature").and_then(|signature| GenericMethodSignature::parse(&signature).ok());
+
+        let mut method_modifiers = modifiers(flags, METHOD_MODIFIERS);
+
+        // Interface methods that aren't abstract, static or private are default methods, which
+        // there may be only from version 52 on
+        if classfile.access_flags.flags & ACC_INTERFACE != 0 && flags & (ACC_ABSTRACT | ACC_STATIC | ACC_PRIVATE) == 0 && name != "<clinit>" && classfile.version.major_version >= 52 {
+            method_modifiers.push("default");
+        }
+
+        let (params, ret) = match (&signature, &parsed) {
+            (&Some(ref signature), _) => (
+                signature.params.iter().map(|param| param.to_string()).collect(),
+                signature.ret.as_ref().map(|ret| ret.to_string()).unwrap_or(String::from("void"))
+            ),
+            (&None, &Some(ref parsed)) => (
+                parsed.params.iter().map(java_type).collect(),
+                parsed.ret.as_ref().map(java_type).unwrap_or(String::from("void"))
+            ),
+            (&None, &None) => (vec![], descriptor.clone())
+        };
+
+        let mut params = format!("({})", params.join(", "));
+
+        if flags & ACC_VARARGS != 0 {
+            if let Some(idx) = params.rfind("[]") {
+                params.replace_range(idx..idx + 2, "...");
+            }
+        }
+
+        self.print_modifiers(&method_modifiers);
+
+        if let Some(ref signature) = signature {
+            if !signature.type_parameters.is_empty() {
+                self.print(format!("{} ", type_parameters(&signature.type_parameters)));
+            }
+        }
+
+        match name.as_str() {
+            "<init>" => {
+                let class_name = self.java_class_name(&classfile.this_class);
+                self.print(format!("{}{}", class_name, params));
+            },
+            "<clinit>" => self.print("{}"),
+            _ => self.print(format!("{} {}{}", ret, name, params))
+        }
+
+        let exceptions = method.attributes.iter().filter_map(|attribute| match attribute {
+            &Attribute::Exceptions(ref exceptions) => Some(exceptions),
+            _ => None
+        }).next();
+
+        if let Some(exceptions) = exceptions {
+            let thrown: Vec<String> = match signature {
+                Some(ref signature) if !signature.throws.is_empty() => signature.throws.iter().map(|thrown| thrown.to_string()).collect(),
+                _ => exceptions.iter().map(|exception| self.java_class_name(exception)).collect()
+            };
+
+            self.print(format!(" throws {}", thrown.join(", ")));
+        }
+
+        self.println(";");
+        self.indent(1);
+        self.println(format!("descriptor: {}", descriptor));
+        self.print_flags(flags, METHOD_FLAGS);
+
+        self.args_size = parsed.map(|parsed| parsed.params.len()).unwrap_or(0) + if flags & ACC_STATIC == 0 { 1 } else { 0 };
+
+        for attribute in method.attributes.iter() {
+            self.write_attribute(attribute);
+        }
+
+        self.indent(-1);
+        self.pending_newline = true;
+    }
+
+    // -- Code
+
+    fn write_code(&mut self, max_stack: u16, max_locals: u16, code: &[Instruction], exception_table: &[ExceptionHandler], attributes: &[Attribute]) {
+        self.println("Code:");
+        self.indent(1);
+
+        let args_size = self.args_size;
+        self.println(format!("stack={}, locals={}, args_size={}", max_stack, max_locals, args_size));
+
+        let mut offset = 0;
+
+        for instruction in code {
+            self.write_instruction(instruction, offset);
+            offset += instruction.len_at(offset);
+        }
+
+        if !exception_table.is_empty() {
+            self.println("Exception table:");
+            self.indent(1);
+            self.println(" from    to  target type");
+
+            for handler in exception_table {
+                self.print(format!("{:6}{:6}{:6}   ", handler.start_pc, handler.end_pc, handler.handler_pc));
+
+                match handler.catch_type.idx {
+                    0 => self.println("any"),
+                    _ => {
+                        let class_name = self.string_value(&handler.catch_type);
+                        self.println(format!("Class {}", class_name));
+                    }
+                }
+            }
+
+            self.indent(-1);
+        }
+
+        for attribute in attributes {
+            self.write_attribute(attribute);
+        }
+
+        self.indent(-1);
+    }
+
+    fn write_instruction(&mut self, instruction: &Instruction, offset: usize) {
+        self.print(format!("{:4}: {:<13} ", offset, instruction.mnemonic()));
+
+        let target = |delta: i64| offset as i64 + delta;
+
+        match instruction {
+            &Instruction::ALOAD(idx) | &Instruction::ASTORE(idx) | &Instruction::DLOAD(idx) | &Instruction::DSTORE(idx) |
+            &Instruction::FLOAD(idx) | &Instruction::FSTORE(idx) | &Instruction::ILOAD(idx) | &Instruction::ISTORE(idx) |
+            &Instruction::LLOAD(idx) | &Instruction::LSTORE(idx) | &Instruction::RET(idx) => self.print(idx.to_string()),
+            &Instruction::ALOAD_W(idx) | &Instruction::ASTORE_W(idx) | &Instruction::DLOAD_W(idx) | &Instruction::DSTORE_W(idx) |
+            &Instruction::FLOAD_W(idx) | &Instruction::FSTORE_W(idx) | &Instruction::ILOAD_W(idx) | &Instruction::ISTORE_W(idx) |
+            &Instruction::LLOAD_W(idx) | &Instruction::LSTORE_W(idx) | &Instruction::RET_W(idx) => self.print(idx.to_string()),
+            &Instruction::IINC(idx, value) => self.print(format!("{}, {}", idx, value)),
+            &Instruction::IINC_W(idx, value) => self.print(format!("{}, {}", idx, value)),
+            &Instruction::BIPUSH(value) => self.print((value as i8).to_string()),
+            &Instruction::SIPUSH(value) => self.print((value as i16).to_string()),
+            &Instruction::NEWARRAY(atype) => self.print(format!(" {}", array_type_name(atype))),
+            &Instruction::GOTO(delta) | &Instruction::JSR(delta) |
+            &Instruction::IF_ACMPEQ(delta) | &Instruction::IF_ACMPNE(delta) | &Instruction::IF_ICMPEQ(delta) | &Instruction::IF_ICMPNE(delta) |
+            &Instruction::IF_ICMPLT(delta) | &Instruction::IF_ICMPGE(delta) | &Instruction::IF_ICMPGT(delta) | &Instruction::IF_ICMPLE(delta) |
+            &Instruction::IFEQ(delta) | &Instruction::IFNE(delta) | &Instruction::IFLT(delta) | &Instruction::IFGE(delta) |
+            &Instruction::IFGT(delta) | &Instruction::IFLE(delta) | &Instruction::IFNONNULL(delta) | &Instruction::IFNULL(delta) => self.print(target(delta as i64).to_string()),
+            &Instruction::GOTO_W(delta) | &Instruction::JSR_W(delta) => self.print(target(delta as i64).to_string()),
+            &Instruction::LDC(idx) => self.write_constant_operand(idx as u16, None),
+            &Instruction::ANEWARRAY(idx) | &Instruction::CHECKCAST(idx) | &Instruction::GETFIELD(idx) | &Instruction::GETSTATIC(idx) |
+            &Instruction::INSTANCEOF(idx) | &Instruction::INVOKESPECIAL(idx) | &Instruction::INVOKESTATIC(idx) | &Instruction::INVOKEVIRTUAL(idx) |
+            &Instruction::LDC_W(idx) | &Instruction::LDC2_W(idx) | &Instruction::NEW(idx) | &Instruction::PUTFIELD(idx) |
+            &Instruction::PUTSTATIC(idx) => self.write_constant_operand(idx, None),
+            &Instruction::INVOKEINTERFACE(idx, count) => self.write_constant_operand(idx, Some(count)),
+            &Instruction::INVOKEDYNAMIC(idx) => self.write_constant_operand(idx, Some(0)),
+            &Instruction::MULTIANEWARRAY(idx, dimensions) => self.write_constant_operand(idx, Some(dimensions)),
+            &Instruction::TABLESWITCH(default, low, high, ref offsets) => {
+                self.print(format!("{{ // {} to {}", low, high));
+                self.indent(SWITCH_INDENT);
+
+                for (idx, delta) in offsets.iter().enumerate() {
+                    self.print(format!("\n{:12}: {}", low as i64 + idx as i64, target(*delta as i64)));
+                }
+
+                self.print(format!("\n     default: {}\n}}", target(default as i64)));
+                self.indent(-SWITCH_INDENT);
+            },
+            &Instruction::LOOKUPSWITCH(default, ref pairs) => {
+                self.print(format!("{{ // {}", pairs.len()));
+                self.indent(SWITCH_INDENT);
+
+                for &(value, delta) in pairs.iter() {
+                    self.print(format!("\n{:12}: {}", value, target(delta as i64)));
+                }
+
+                self.print(format!("\n     default: {}\n}}", target(default as i64)));
+                self.indent(-SWITCH_INDENT);
+            },
+            _ => ()
+        }
+
+        self.newline();
+    }
+
+    fn write_constant_operand(&mut self, idx: u16, value: Option<u8>) {
+        match value {
+            Some(value) => self.print(format!("#{},  {}", idx, value)),
+            None => self.print(format!("#{}", idx))
+        }
+
+        self.tab();
+
+        let constant = self.constant_reference(&ConstantPoolIndex::new(idx as usize));
+        self.print(format!("// {}", constant));
+    }
+
+    fn write_stack_map_frame(&mut self, frame: &StackMapFrame) {
+        let (frame_type, kind) = match frame {
+            &StackMapFrame::SameFrame { tag } => (tag, "same"),
+            &StackMapFrame::SameLocals1StackItemFrame { tag, .. } => (tag, "same_locals_1_stack_item"),
+            &StackMapFrame::SameLocals1StackItemFrameExtended { .. } => (247, "same_locals_1_stack_item_frame_extended"),
+            &StackMapFrame::ChopFrame { tag, .. } => (tag, "chop"),
+            &StackMapFrame::SameFrameExtended { .. } => (251, "same_frame_extended"),
+            &StackMapFrame::AppendFrame { tag, .. } => (tag, "append"),
+            &StackMapFrame::FullFrame { .. } => (255, "full_frame"),
+            &StackMapFrame::FutureUse { tag } => (tag, "unknown")
+        };
+
+        self.println(format!("frame_type = {} /* {} */", frame_type, kind));
+        self.indent(1);
+
+        match frame {
+            &StackMapFrame::SameLocals1StackItemFrame { ref stack, .. } => self.write_verification_types("stack", slice::from_ref(stack)),
+            &StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => {
+                self.println(format!("offset_delta = {}", offset_delta));
+                self.write_verification_types("stack", slice::from_ref(stack));
+            },
+            &StackMapFrame::ChopFrame { offset_delta, .. } | &StackMapFrame::SameFrameExtended { offset_delta } => self.println(format!("offset_delta = {}", offset_delta)),
+            &StackMapFrame::AppendFrame { offset_delta, ref locals, .. } => {
+                self.println(format!("offset_delta = {}", offset_delta));
+                self.write_verification_types("locals", locals);
+            },
+            &StackMapFrame::FullFrame { offset_delta, ref locals, ref stack } => {
+                self.println(format!("offset_delta = {}", offset_delta));
+                self.write_verification_types("locals", locals);
+                self.write_verification_types("stack", stack);
+            },
+            _ => ()
+        }
+
+        self.indent(-1);
+    }
+
+    fn write_verification_types(&mut self, label: &str, types: &[VerificationType]) {
+        self.print(format!("{} = [", label));
+
+        for (idx, verification_type) in types.iter().enumerate() {
+            let rendered = match verification_type {
+                &VerificationType::Top => String::from("top"),
+                &VerificationType::Integer => String::from("int"),
+                &VerificationType::Float => String::from("float"),
+                &VerificationType::Long => String::from("long"),
+                &VerificationType::Double => String::from("double"),
+                &VerificationType::Null => String::from("null"),
+                &VerificationType::UninitializedThis => String::from("this"),
+                &VerificationType::Object { ref cpool_index } => self.constant_reference(cpool_index),
+                &VerificationType::Uninitialized { offset } => format!("uninitialized {}", offset)
+            };
+
+            self.print(format!(" {}{}", rendered, if idx == types.len() - 1 { " " } else { "," }));
+        }
+
+        self.println("]");
+    }
+
+    // -- Attributes
+
+    fn write_attribute(&mut self, attribute: &Attribute) {
+        let name = attribute.name().unwrap_or("");
+
+        match attribute {
+            &Attribute::ConstantValue(ref idx) => {
+                let value = self.constant_reference(idx);
+                self.println(format!("ConstantValue: {}", value));
+            },
+            &Attribute::Code { max_stack, max_locals, ref code, ref exception_table, ref attributes } => self.write_code(max_stack, max_locals, code, exception_table, attributes),
+            &Attribute::StackMapTable(ref frames) => {
+                self.println(format!("StackMapTable: number_of_entries = {}", frames.len()));
+                self.indent(1);
+
+                for frame in frames {
+                    self.write_stack_map_frame(frame);
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::Exceptions(ref exceptions) => {
+                let names: Vec<String> = exceptions.iter().map(|exception| self.java_class_name(exception)).collect();
+
+                self.println("Exceptions:");
+                self.indent(1);
+                self.println(format!("throws {}", names.join(", ")));
+                self.indent(-1);
+            },
+            &Attribute::InnerClasses(ref classes) => self.write_inner_classes(classes),
+            &Attribute::EnclosingMethod { ref class_index, ref method_index } => {
+                self.print(format!("EnclosingMethod: #{}.#{}", class_index.idx, method_index.idx));
+                self.tab();
+
+                let mut comment = format!("// {}", self.java_class_name(class_index));
+
+                if method_index.idx != 0 {
+                    if let Ok(&Constant::NameAndType { ref name_index, .. }) = self.cp().get(method_index) {
+                        comment = format!("{}.{}", comment, self.utf8(name_index));
+                    }
+                }
+
+                self.println(comment);
+            },
+            &Attribute::Synthetic => self.println("Synthetic: true"),
+            &Attribute::Deprecated => self.println("Deprecated: true"),
+            &Attribute::Signature(ref idx) => {
+                self.print(format!("Signature: #{}", idx.idx));
+                self.tab();
+
+                let signature = self.utf8(idx);
+                self.println(format!("// {}", signature));
+            },
+            &Attribute::SourceFile(ref idx) => {
+                let source_file = self.utf8(idx);
+                self.println(format!("SourceFile: \"{}\"", source_file));
+            },
+            &Attribute::SourceDebugExtension(ref value) => {
+                self.println("SourceDebugExtension:");
+                self.indent(1);
+
+                for line in mutf8::decode_lossy(value).split(['\r', '\n']).filter(|line| !line.is_empty()) {
+                    self.println(line);
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::LineNumberTable(ref table) => {
+                self.println("LineNumberTable:");
+                self.indent(1);
+
+                for line in table {
+                    self.println(format!("line {}: {}", line.line_number, line.start_pc));
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::LocalVariableTable(ref table) => {
+                let rows = table.iter().map(|variable| (variable.start_pc, variable.length, variable.index, &variable.name_index, &variable.descriptor_index)).collect();
+                self.write_local_variables(name, rows);
+            },
+            &Attribute::LocalVariableTypeTable(ref table) => {
+                let rows = table.iter().map(|variable| (variable.start_pc, variable.length, variable.index, &variable.name_index, &variable.signature_index)).collect();
+                self.write_local_variables(name, rows);
+            },
+            &Attribute::RuntimeVisibleAnnotations(ref annotations) | &Attribute::RuntimeInvisibleAnnotations(ref annotations) => {
+                self.println(format!("{}:", name));
+                self.indent(1);
+
+                for (idx, annotation) in annotations.iter().enumerate() {
+                    self.print(format!("{}: ", idx));
+                    self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, false);
+                    self.newline();
+                    self.indent(1);
+                    self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, true);
+                    self.indent(-1);
+                    self.newline();
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::RuntimeVisibleParameterAnnotations(ref parameters) | &Attribute::RuntimeInvisibleParameterAnnotations(ref parameters) => {
+                self.println(format!("{}:", name));
+                self.indent(1);
+
+                for (param, annotations) in parameters.iter().enumerate() {
+                    self.println(format!("parameter {}: ", param));
+                    self.indent(1);
+
+                    for (idx, annotation) in annotations.iter().enumerate() {
+                        self.print(format!("{}: ", idx));
+                        self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, false);
+                        self.newline();
+                        self.indent(1);
+                        self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, true);
+                        self.indent(-1);
+                        self.newline();
+                    }
+
+                    self.indent(-1);
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::RuntimeVisibleTypeAnnotations(ref annotations) | &Attribute::RuntimeInvisibleTypeAnnotations(ref annotations) => {
+                self.println(format!("{}:", name));
+                self.indent(1);
+
+                for (idx, annotation) in annotations.iter().enumerate() {
+                    self.print(format!("{}: ", idx));
+                    self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, false);
+                    self.print(format!(": {}", type_annotation_target(annotation)));
+                    self.newline();
+                    self.indent(1);
+                    self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, true);
+                    self.indent(-1);
+                    self.newline();
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::AnnotationDefault(ref value) => {
+                self.println("AnnotationDefault:");
+                self.indent(1);
+                self.print("default_value: ");
+                self.write_element_value(value, false);
+                self.newline();
+                self.indent(1);
+                self.write_element_value(value, true);
+                self.indent(-2);
+                self.newline();
+            },
+            &Attribute::BootstrapMethods(ref methods) => {
+                self.println("BootstrapMethods:");
+
+                for (idx, method) in methods.iter().enumerate() {
+                    let handle = self.string_value(&method.bootstrap_method_ref);
+
+                    self.indent(1);
+                    self.println(format!("{}: #{} {}", idx, method.bootstrap_method_ref.idx, handle));
+                    self.indent(1);
+                    self.println("Method arguments:");
+                    self.indent(1);
+
+                    for argument in method.bootstrap_arguments.iter() {
+                        let value = self.string_value(argument);
+                        self.println(format!("#{} {}", argument.idx, value));
+                    }
+
+                    self.indent(-3);
+                }
+            },
+            &Attribute::MethodParameters(ref parameters) => {
+                self.println("MethodParameters:");
+                self.indent(1);
+                self.println(format!("{:<30} {}", "Name", "Flags"));
+
+                for parameter in parameters {
+                    let name = match parameter.name_index.idx {
+                        0 => String::from("<no name>"),
+                        _ => self.string_value(&parameter.name_index)
+                    };
+                    let flags = parameter.access_flags.flags;
+                    let modifiers = format!("{}{}{}",
+                        if flags & 0x0010 != 0 { "final " } else { "" },
+                        if flags & 0x8000 != 0 { "mandated " } else { "" },
+                        if flags & 0x1000 != 0 { "synthetic" } else { "" });
+
+                    self.println(format!("{:<30} {}", name, modifiers));
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::Module { ref name_index, ref flags, ref version_index, ref requires, ref exports, ref opens, ref uses, ref provides } => {
+                self.println("Module:");
+                self.indent(1);
+                self.write_module_entry(name_index, Some(flags.flags), &[(0x0020, "ACC_OPEN"), (0x8000, "ACC_MANDATED"), (0x1000, "ACC_SYNTHETIC")]);
+                self.write_module_version(version_index);
+
+                self.write_module_count(requires.len(), "requires");
+                for entry in requires {
+                    self.write_module_entry(&entry.requires_index, Some(entry.requires_flags.flags), &[(0x0020, "ACC_TRANSITIVE"), (0x0040, "ACC_STATIC_PHASE"), (0x1000, "ACC_SYNTHETIC"), (0x8000, "ACC_MANDATED")]);
+                    self.write_module_version(&entry.requires_version_index);
+                }
+                self.indent(-1);
+
+                self.write_module_count(exports.len(), "exports");
+                for entry in exports {
+                    self.write_module_targets(&entry.exports_index, entry.exports_flags.flags, &entry.exports_to);
+                }
+                self.indent(-1);
+
+                self.write_module_count(opens.len(), "opens");
+                for entry in opens {
+                    self.write_module_targets(&entry.opens_index, entry.opens_flags.flags, &entry.opens_to);
+                }
+                self.indent(-1);
+
+                self.write_module_count(uses.len(), "uses");
+                for entry in uses {
+                    self.write_module_entry(entry, None, &[]);
+                }
+                self.indent(-1);
+
+                self.write_module_count(provides.len(), "provides");
+                for entry in provides {
+                    self.print(format!("#{}", entry.provides_index.idx));
+                    self.tab();
+
+                    let service = self.string_value(&entry.provides_index);
+                    self.println(format!("// {} with ... {}", service, entry.provides_with.len()));
+                    self.indent(1);
+
+                    for implementation in entry.provides_with.iter() {
+                        self.print(format!("#{}", implementation.idx));
+                        self.tab();
+
+                        let name = self.string_value(implementation);
+                        self.println(format!("// ... with {}", name));
+                    }
+
+                    self.indent(-1);
+                }
+                self.indent(-2);
+            },
+            &Attribute::ModulePackages(ref packages) => {
+                self.println("ModulePackages: ");
+                self.indent(1);
+
+                for package in packages {
+                    self.print(format!("#{}", package.idx));
+                    self.tab();
+
+                    let name = match self.cp().get(package) {
+                        Ok(&Constant::Package(ref name)) => self.utf8(name).replace("/", "."),
+                        _ => format!("#{}", package.idx)
+                    };
+                    self.println(format!("// {}", name));
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::ModuleMainClass(ref idx) => {
+                self.print(format!("ModuleMainClass: #{}", idx.idx));
+                self.tab();
+
+                let name = self.java_class_name(idx);
+                self.println(format!("// {}", name));
+            },
+            &Attribute::NestHost(ref idx) => {
+                let host = self.constant_reference(idx);
+                self.println(format!("NestHost: {}", host));
+            },
+            &Attribute::NestMembers(ref classes) | &Attribute::PermittedSubclasses(ref classes) => {
+                self.println(format!("{}:", name));
+                self.indent(1);
+
+                for class in classes {
+                    let class_name = self.string_value(class);
+                    self.println(class_name);
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::Record(ref components) => {
+                self.println("Record:");
+                self.indent(1);
+
+                for component in components {
+                    let descriptor = self.utf8(&component.descriptor_index);
+                    let component_type = match self.find_utf8_attribute(&component.attributes, "Signature").and_then(|signature| TypeSignature::parse(&signature).ok()) {
+                        Some(signature) => signature.to_string(),
+                        None => self.java_field_type(&descriptor)
+                    };
+                    let component_name = self.utf8(&component.name_index);
+
+                    self.println(format!("{} {};", component_type, component_name));
+                    self.indent(1);
+                    self.println(format!("descriptor: {}", descriptor));
+
+                    for attribute in component.attributes.iter() {
+                        self.write_attribute(attribute);
+                    }
+
+                    self.newline();
+                    self.indent(-1);
+                }
+
+                self.indent(-1);
+            },
+            &Attribute::RawAttribute { ref name_index, ref info } => {
+                let name = self.utf8(name_index);
+
+                self.println(format!("  {}: length = 0x{:X}", name, info.len()));
+                self.print("   ");
+
+                for (idx, byte) in info.iter().enumerate() {
+                    self.print(format!("{:02X}", byte));
+
+                    if idx % 16 == 15 {
+                        self.newline();
+                        self.print("   ");
+                    } else {
+                        self.print(" ");
+                    }
+                }
+
+                self.newline();
+            }
+        }
+    }
+
+    fn write_local_variables(&mut self, name: &str, rows: Vec<(u16, u16, u16, &ConstantPoolIndex, &ConstantPoolIndex)>) {
+        self.println(format!("{}:", name));
+        self.indent(1);
+        self.println("Start  Length  Slot  Name   Signature");
+
+        for (start_pc, length, index, name_index, descriptor_index) in rows {
+            let variable_name = self.string_value(name_index);
+            let descriptor = self.string_value(descriptor_index);
+
+            self.println(format!("{:5} {:7} {:5} {:>5}   {}", start_pc, length, index, variable_name, descriptor));
+        }
+
+        self.indent(-1);
+    }
+
+    fn write_inner_classes(&mut self, classes: &[InnerClass]) {
+        let mut first = true;
+
+        for class in classes {
+            let flags = class.access_flags.flags;
+
+            if !self.is_shown(flags) {
+                continue;
+            }
+
+            if first {
+                self.println("InnerClasses:");
+                self.indent(1);
+                first = false;
+            }
+
+            self.print_modifiers(&modifiers(if flags & ACC_INTERFACE != 0 { flags & !ACC_ABSTRACT } else { flags }, INNER_CLASS_MODIFIERS));
+
+            if class.inner_name_index.idx != 0 {
+                self.print(format!("#{}= ", class.inner_name_index.idx));
+            }
+
+            self.print(format!("#{}", class.inner_class_info_index.idx));
+
+            if class.outer_class_info_index.idx != 0 {
+                self.print(format!(" of #{}", class.outer_class_info_index.idx));
+            }
+
+            self.print(";");
+            self.tab();
+
+            let mut comment = String::from("// ");
+
+            if class.inner_name_index.idx != 0 {
+                comment.push_str(&format!("{}=", self.utf8(&class.inner_name_index)));
+            }
+
+            comment.push_str(&self.constant_reference(&class.inner_class_info_index));
+
+            if class.outer_class_info_index.idx != 0 {
+                comment.push_str(&format!(" of {}", self.constant_reference(&class.outer_class_info_index)));
+            }
+
+            self.println(comment);
+        }
+
+        if !first {
+            self.indent(-1);
+        }
+    }
+
+    fn write_module_count(&mut self, count: usize, kind: &str) {
+        self.print(count.to_string());
+        self.tab();
+        self.println(format!("// {}", kind));
+        self.indent(1);
+    }
+
+    fn write_module_entry(&mut self, idx: &ConstantPoolIndex, flags: Option<u16>, names: &[(u16, &str)]) {
+        match flags {
+            Some(flags) => self.print(format!("#{},{:x}", idx.idx, flags)),
+            None => self.print(format!("#{}", idx.idx))
+        }
+
+        self.tab();
+
+        let mut comment = format!("// {}", self.string_value(idx));
+
+        for &(flag, name) in names {
+            if flags.unwrap_or(0) & flag != 0 {
+                comment.push_str(&format!(" {}", name));
+            }
+        }
+
+        self.println(comment);
+    }
+
+    fn write_module_version(&mut self, idx: &ConstantPoolIndex) {
+        self.print(format!("#{}", idx.idx));
+
+        if idx.idx != 0 {
+            self.tab();
+
+            let version = self.string_value(idx);
+            self.print(format!("// {}", version));
+        }
+
+        self.newline();
+    }
+
+    fn write_module_targets(&mut self, idx: &ConstantPoolIndex, flags: u16, targets: &[ConstantPoolIndex]) {
+        self.print(format!("#{},{:x}", idx.idx, flags));
+        self.tab();
+
+        let mut comment = format!("// {}", self.string_value(idx));
+
+        for &(flag, name) in &[(0x8000, "ACC_MANDATED"), (0x1000, "ACC_SYNTHETIC")] {
+            if flags & flag != 0 {
+                comment.push_str(&format!(" {}", name));
+            }
+        }
+
+        if targets.is_empty() {
+            self.println(comment);
+            return;
+        }
+
+        self.println(format!("{} to ... {}", comment, targets.len()));
+        self.indent(1);
+
+        for target in targets {
+            self.print(format!("#{}", target.idx));
+            self.tab();
+
+            let name = self.string_value(target);
+            self.println(format!("// ... to {}", name));
+        }
+
+        self.indent(-1);
+    }
+
+    // -- Annotations
+
+    /// Write an annotation either with constant pool indices, on a single line, or resolved, on as
+    /// many lines as it has elements
+    fn write_annotation(&mut self, type_index: &ConstantPoolIndex, pairs: &[ElementValuePair], resolve: bool) {
+        if !resolve {
+            self.print(format!("#{}(", type_index.idx));
+
+            for (idx, pair) in pairs.iter().enumerate() {
+                if idx > 0 {
+                    self.print(",");
+                }
+
+                self.print(format!("#{}=", pair.element_name_index.idx));
+                self.write_element_value(&pair.value, false);
+            }
+
+            self.print(")");
+            return;
+        }
+
+        let descriptor = self.utf8(type_index);
+        let annotation_type = self.java_field_type(&descriptor);
+
+        self.print(annotation_type);
+
+        if pairs.is_empty() {
+            return;
+        }
+
+        self.println("(");
+        self.indent(1);
+
+        for pair in pairs {
+            let name = self.string_value(&pair.element_name_index);
+
+            self.print(format!("{}=", name));
+            self.write_element_value(&pair.value, true);
+            self.newline();
+        }
+
+        self.indent(-1);
+        self.print(")");
+    }
+
+    fn write_element_value(&mut self, value: &ElementValue, resolve: bool) {
+        match value {
+            &ElementValue::ConstantValue(tag, ref idx) if resolve => {
+                let constant = self.string_value(idx);
+                let int_value = match self.cp().get(idx) {
+                    Ok(&Constant::Integer(value)) => value,
+                    _ => 0
+                };
+
+                self.print(match tag {
+                    b'B' => format!("(byte) {}", constant),
+                    b'S' => format!("(short) {}", constant),
+                    b'C' => format!("'{}'", ::std::char::from_u32(int_value & 0xFFFF).unwrap_or(::std::char::REPLACEMENT_CHARACTER)),
+                    b'Z' => format!("{}", int_value != 0),
+                    b's' => format!("\"{}\"", constant),
+                    _ => constant
+                });
+            },
+            &ElementValue::ConstantValue(tag, ref idx) => self.print(format!("{}#{}", tag as char, idx.idx)),
+            &ElementValue::Enum { ref type_name_index, ref const_name_index } if resolve => {
+                let rendered = format!("{}.{}", self.string_value(type_name_index), self.string_value(const_name_index));
+                self.print(rendered);
+            },
+            &ElementValue::Enum { ref type_name_index, ref const_name_index } => self.print(format!("e#{}.#{}", type_name_index.idx, const_name_index.idx)),
+            &ElementValue::ClassInfo(ref idx) if resolve => {
+                let class = self.string_value(idx);
+                self.print(format!("class {}", class));
+            },
+            &ElementValue::ClassInfo(ref idx) => self.print(format!("c#{}", idx.idx)),
+            &ElementValue::Annotation(ref annotation) => {
+                self.print("@");
+                self.write_annotation(&annotation.type_index, &annotation.element_value_pairs, resolve);
+            },
+            &ElementValue::Array(ref values) => {
+                self.print("[");
+
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        self.print(",");
+                    }
+
+                    self.write_element_value(value, resolve);
+                }
+
+                self.print("]");
+            }
+        }
+    }
+}
+
+fn modifiers(flags: u16, names: &[(u16, &'static str)]) -> Vec<&'static str> {
+    names.iter().filter(|&&(flag, _)| flags & flag != 0).map(|&(_, name)| name).collect()
+}
+
+/// The name of a type in Java source, eg. `java.lang.String[]`
+fn java_type(field_type: &FieldType) -> String {
+    match field_type {
+        &FieldType::Object(ref name) => name.replace("/", "."),
+        &FieldType::Array(ref component) => format!("{}[]", java_type(component)),
+        primitive => TypeSignature::Base(primitive.clone()).to_string()
+    }
+}
+
+/// Type parameters with their bounds, which `javap -v` shows even if they're just `Object`
+fn type_parameters(params: &[TypeParameter]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+
+    let rendered: Vec<String> = params.iter().map(|param| {
+        let bounds: Vec<String> = param.class_bound.iter().chain(param.interface_bounds.iter()).map(|bound| bound.to_string()).collect();
+
+        match bounds.is_empty() {
+            true => param.name.clone(),
+            false => format!("{} extends {}", param.name, bounds.join(" & "))
+        }
+    }).collect();
+
+    format!("<{}>", rendered.join(", "))
+}
+
+/// Quote names that aren't made of Java identifiers separated by slashes
+fn check_name(name: &str) -> String {
+    let mut previous = '/';
+
+    for c in name.chars() {
+        let is_start = c.is_alphabetic() || c == '$' || c == '_';
+        let is_part = is_start || c.is_numeric() || (c.is_control() && !c.is_whitespace());
+
+        if name.is_empty() || (previous == '/' && !is_start) || (c != '/' && !is_part) {
+            return format!("\"{}\"", escape(name));
+        }
+
+        previous = c;
+    }
+
+    match name.is_empty() {
+        true => String::from("\"\""),
+        false => name.to_string()
+    }
+}
+
+/// Escape a string the way Java string literals are escaped
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+fn java_float(value: f32) -> String {
+    match value {
+        _ if value.is_nan() => String::from("NaN"),
+        _ if value.is_infinite() => String::from(if value > 0.0 { "Infinity" } else { "-Infinity" }),
+        _ => java_decimal(&format!("{:e}", value))
+    }
+}
+
+fn java_double(value: f64) -> String {
+    match value {
+        _ if value.is_nan() => String::from("NaN"),
+        _ if value.is_infinite() => String::from(if value > 0.0 { "Infinity" } else { "-Infinity" }),
+        _ => java_decimal(&format!("{:e}", value))
+    }
+}
+
+///
+/// Turn the shortest scientific rendering of a finite value, eg. `-1.25e-5`, into the one Java's
+/// `Double.toString` produces, which has at least one fractional digit and uses plain notation for
+/// magnitudes from 10^-3 up to 10^7
+fn java_decimal(scientific: &str) -> String {
+    let (sign, scientific) = match scientific.starts_with('-') {
+        true => ("-", &scientific[1..]),
+        false => ("", scientific)
+    };
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap_or(scientific.len()));
+    let exponent: i32 = exponent.trim_start_matches('e').parse().unwrap_or(0);
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+
+    if digits == "0" {
+        return format!("{}0.0", sign);
+    }
+
+    match exponent {
+        -3..=-1 => format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits),
+        0..=6 => {
+            let point = exponent as usize + 1;
+            let padded = format!("{:0<width$}", digits, width = point);
+            let (integer, fraction) = padded.split_at(point);
+
+            format!("{}{}.{}", sign, integer, if fraction.is_empty() { "0" } else { fraction })
+        },
+        _ => {
+            let (first, rest) = digits.split_at(1);
+
+            format!("{}{}.{}E{}", sign, first, if rest.is_empty() { "0" } else { rest }, exponent)
+        }
+    }
+}
+
+fn reference_kind_name(kind: &ReferenceKind) -> &'static str {
+    match kind {
+        &ReferenceKind::GetField => "REF_getField",
+        &ReferenceKind::GetStatic => "REF_getStatic",
+        &ReferenceKind::PutField => "REF_putField",
+        &ReferenceKind::PutStatic => "REF_putStatic",
+        &ReferenceKind::InvokeVirtual => "REF_invokeVirtual",
+        &ReferenceKind::InvokeStatic => "REF_invokeStatic",
+        &ReferenceKind::InvokeSpecial => "REF_invokeSpecial",
+        &ReferenceKind::NewInvokeSpecial => "REF_newInvokeSpecial",
+        &ReferenceKind::InvokeInterface => "REF_invokeInterface",
+        &ReferenceKind::Unknown => "REF_unknown"
+    }
+}
+
+fn array_type_name(atype: u8) -> String {
+    match atype {
+        4 => String::from("boolean"),
+        5 => String::from("char"),
+        6 => String::from("float"),
+        7 => String::from("double"),
+        8 => String::from("byte"),
+        9 => String::from("short"),
+        10 => String::from("int"),
+        11 => String::from("long"),
+        _ => format!("[type {}]", atype)
+    }
+}
+
+/// The target of a type annotation followed by its type path, eg. `FIELD, location=[ARRAY]`
+fn type_annotation_target(annotation: &TypeAnnotation) -> String {
+    let target = match annotation.target_info {
+        TargetInfo::TypeParameter { subtype, idx } => format!("{}, param_index={}", target_type_name(subtype), idx),
+        TargetInfo::SuperType { idx } => format!("CLASS_EXTENDS, type_index={}", if idx == 0xFFFF { -1 } else { idx as i32 }),
+        TargetInfo::TypeParameterBound { subtype, param_idx, bound_index } => format!("{}, param_index={}, bound_index={}", target_type_name(subtype), param_idx, bound_index),
+        TargetInfo::Empty { subtype } => String::from(target_type_name(subtype)),
+        TargetInfo::MethodFormalParameter { idx } => format!("METHOD_FORMAL_PARAMETER, param_index={}", idx),
+        TargetInfo::Throws { idx } => format!("THROWS, type_index={}", idx),
+        TargetInfo::LocalVar { subtype, ref target } => {
+            let ranges: Vec<String> = target.iter().map(|&(start_pc, length, index)| format!("start_pc={}, length={}, index={}", start_pc, length, index)).collect();
+            format!("{}, {{{}}}", target_type_name(subtype), ranges.join("; "))
+        },
+        TargetInfo::Catch { idx } => format!("EXCEPTION_PARAMETER, exception_index={}", idx),
+        TargetInfo::Offset { subtype, idx } => format!("{}, offset={}", target_type_name(subtype), idx),
+        TargetInfo::TypeArgument { subtype, offset, type_arg_idx } => format!("{}, offset={}, type_index={}", target_type_name(subtype), offset, type_arg_idx)
+    };
+
+    if annotation.target_path.path.is_empty() {
+        return target;
+    }
+
+    let path: Vec<String> = annotation.target_path.path.iter().map(|&(ref kind, argument)| match kind {
+        &TypePathKind::Array => String::from("ARRAY"),
+        &TypePathKind::Nested => String::from("INNER_TYPE"),
+        &TypePathKind::Wildcard => String::from("WILDCARD"),
+        &TypePathKind::TypeArgument => format!("TYPE_ARGUMENT({})", argument)
+    }).collect();
+
+    format!("{}, location=[{}]", target, path.join(", "))
+}
+
+fn target_type_name(target_type: u8) -> &'static str {
+    match target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4A => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        0x4B => "METHOD_REFERENCE_TYPE_ARGUMENT",
+        _ => "UNKNOWN"
+    }
+}
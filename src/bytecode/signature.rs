@@ -0,0 +1,472 @@
+use std::error::Error;
+use std::fmt;
+use super::descriptor::FieldType;
+
+///
+/// A type as written in a generic signature. Types render to Java source, with fully qualified
+/// class names by default and simple ones with the alternate flag, ie. `{:#}`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeSignature {
+    /// A primitive type, which is never an `Object` or an `Array`
+    Base(FieldType),
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<TypeSignature>),
+}
+
+impl TypeSignature {
+    /// Parse a field signature, ie. the signature of a field's reference type
+    pub fn parse(signature: &str) -> Result<TypeSignature, SignatureError> {
+        let mut parser = Parser { signature: signature, offset: 0 };
+        let field_type = parser.reference_type()?;
+
+        parser.end()?;
+        Ok(field_type)
+    }
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &TypeSignature::Base(ref base) => write!(f, "{}", match *base {
+                FieldType::Byte => "byte",
+                FieldType::Char => "char",
+                FieldType::Double => "double",
+                FieldType::Float => "float",
+                FieldType::Int => "int",
+                FieldType::Long => "long",
+                FieldType::Short => "short",
+                _ => "boolean"
+            }),
+            &TypeSignature::Class(ref class) => fmt::Display::fmt(class, f),
+            &TypeSignature::TypeVariable(ref name) => write!(f, "{}", name),
+            &TypeSignature::Array(ref component) => { fmt::Display::fmt(component, f)?; write!(f, "[]") }
+        }
+    }
+}
+
+///
+/// A class type along with its type arguments, eg. `java/util/Map<TK;TV;>.Entry<TK;TV;>`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    /// The package in internal form, eg. `java/util`, empty for the default package
+    pub package: String,
+    /// The outermost class followed by the inner classes nested in it
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+impl ClassTypeSignature {
+    /// The internal name of the class, eg. `java/util/Map$Entry`
+    pub fn internal_name(&self) -> String {
+        let names: Vec<&str> = self.classes.iter().map(|class| class.name.as_str()).collect();
+
+        match self.package.is_empty() {
+            true => names.join("$"),
+            false => format!("{}/{}", self.package, names.join("$"))
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() && !self.package.is_empty() {
+            write!(f, "{}.", self.package.replace("/", "."))?;
+        }
+
+        for (idx, class) in self.classes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ".")?;
+            }
+
+            write!(f, "{}", class.name)?;
+
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                write_list(f, &class.type_arguments)?;
+                write!(f, ">")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    /// The unbounded wildcard `?`, written `*` in signatures
+    Any,
+    Exact(TypeSignature),
+    /// `? extends`, written `+` in signatures
+    Extends(TypeSignature),
+    /// `? super`, written `-` in signatures
+    Super(TypeSignature),
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &TypeArgument::Any => write!(f, "?"),
+            &TypeArgument::Exact(ref bound) => fmt::Display::fmt(bound, f),
+            &TypeArgument::Extends(ref bound) => { write!(f, "? extends ")?; fmt::Display::fmt(bound, f) },
+            &TypeArgument::Super(ref bound) => { write!(f, "? super ")?; fmt::Display::fmt(bound, f) }
+        }
+    }
+}
+
+///
+/// A type parameter of a generic class or method. The class bound is `None` if the parameter is
+/// bounded by interfaces only.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: String,
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        // A lone `Object` bound is what `<T>` compiles to, so it isn't worth mentioning
+        let bounds: Vec<&TypeSignature> = self.class_bound.iter().filter(|bound| !is_object(bound) || !self.interface_bounds.is_empty()).chain(self.interface_bounds.iter()).collect();
+
+        for (idx, bound) in bounds.iter().enumerate() {
+            write!(f, "{}", if idx == 0 { " extends " } else { " & " })?;
+            fmt::Display::fmt(bound, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// The contents of a class's `Signature` attribute, as well as the generic signature JVMTI's
+/// `GetClassSignature` reports
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GenericClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+impl GenericClassSignature {
+    pub fn parse(signature: &str) -> Result<GenericClassSignature, SignatureError> {
+        let mut parser = Parser { signature: signature, offset: 0 };
+
+        let type_parameters = parser.type_parameters()?;
+        let super_class = parser.class_type()?;
+        let mut interfaces = vec![];
+
+        while parser.peek().is_some() {
+            interfaces.push(parser.class_type()?);
+        }
+
+        Ok(GenericClassSignature { type_parameters: type_parameters, super_class: super_class, interfaces: interfaces })
+    }
+}
+
+impl fmt::Display for GenericClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "extends ")?;
+        fmt::Display::fmt(&self.super_class, f)?;
+
+        if !self.interfaces.is_empty() {
+            write!(f, " implements ")?;
+            write_list(f, &self.interfaces)?;
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// The contents of a method's `Signature` attribute. The return type is `None` for `void`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GenericMethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub params: Vec<TypeSignature>,
+    pub ret: Option<TypeSignature>,
+    pub throws: Vec<TypeSignature>,
+}
+
+impl GenericMethodSignature {
+    pub fn parse(signature: &str) -> Result<GenericMethodSignature, SignatureError> {
+        let mut parser = Parser { signature: signature, offset: 0 };
+
+        let type_parameters = parser.type_parameters()?;
+        let mut params = vec![];
+
+        parser.expect('(')?;
+
+        while parser.peek() != Some(')') {
+            params.push(parser.java_type()?);
+        }
+
+        parser.expect(')')?;
+
+        let ret = match parser.peek() {
+            Some('V') => { parser.offset += 1; None },
+            _ => Some(parser.java_type()?)
+        };
+
+        let mut throws = vec![];
+
+        while parser.peek().is_some() {
+            parser.expect('^')?;
+
+            throws.push(match parser.peek() {
+                Some('T') => parser.type_variable()?,
+                _ => TypeSignature::Class(parser.class_type()?)
+            });
+        }
+
+        Ok(GenericMethodSignature { type_parameters: type_parameters, params: params, ret: ret, throws: throws })
+    }
+
+    /// Render the method as it would be declared in Java source under the given name
+    pub fn declaration(&self, name: &str) -> String {
+        let signature = self.to_string();
+        let params = signature.find('(').unwrap_or(0);
+
+        format!("{}{}{}", &signature[..params], name, &signature[params..])
+    }
+}
+
+impl fmt::Display for GenericMethodSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+
+        match self.ret {
+            Some(ref ret) => { fmt::Display::fmt(ret, f)?; write!(f, " ")? },
+            None => write!(f, "void ")?
+        }
+
+        write!(f, "(")?;
+        write_list(f, &self.params)?;
+        write!(f, ")")?;
+
+        if !self.throws.is_empty() {
+            write!(f, " throws ")?;
+            write_list(f, &self.throws)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SignatureError {
+    pub signature: String,
+    /// Offset of the first character that couldn't be parsed
+    pub offset: usize,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid signature {} at offset {}", self.signature, self.offset)
+    }
+}
+
+impl Error for SignatureError {}
+
+fn is_object(signature: &TypeSignature) -> bool {
+    match signature {
+        &TypeSignature::Class(ref class) => class.package == "java/lang" && class.classes.len() == 1 && class.classes[0].name == "Object" && class.classes[0].type_arguments.is_empty(),
+        _ => false
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+
+        fmt::Display::fmt(item, f)?;
+    }
+
+    Ok(())
+}
+
+fn write_type_parameters(f: &mut fmt::Formatter, type_parameters: &[TypeParameter]) -> fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+
+    write!(f, "<")?;
+    write_list(f, type_parameters)?;
+    write!(f, "> ")
+}
+
+struct Parser<'s> {
+    signature: &'s str,
+    offset: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn peek(&self) -> Option<char> {
+        self.signature[self.offset..].chars().next()
+    }
+
+    fn error(&self) -> SignatureError {
+        SignatureError { signature: self.signature.to_string(), offset: self.offset }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SignatureError> {
+        match self.peek() {
+            Some(c) if c == expected => { self.offset += c.len_utf8(); Ok(()) },
+            _ => Err(self.error())
+        }
+    }
+
+    fn end(&self) -> Result<(), SignatureError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error())
+        }
+    }
+
+    /// An identifier runs up to the next character that has a meaning in signatures
+    fn identifier(&mut self) -> Result<String, SignatureError> {
+        let rest = &self.signature[self.offset..];
+        let end = rest.find(|c| ".;[/<>:".contains(c)).unwrap_or(rest.len());
+
+        if end == 0 {
+            return Err(self.error());
+        }
+
+        self.offset += end;
+        Ok(rest[..end].to_string())
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut type_parameters = vec![];
+
+        if self.peek() != Some('<') {
+            return Ok(type_parameters);
+        }
+
+        self.offset += 1;
+
+        while self.peek() != Some('>') {
+            let name = self.identifier()?;
+
+            self.expect(':')?;
+
+            let class_bound = match self.peek() {
+                Some(':') => None,
+                _ => Some(self.reference_type()?)
+            };
+
+            let mut interface_bounds = vec![];
+
+            while self.peek() == Some(':') {
+                self.offset += 1;
+                interface_bounds.push(self.reference_type()?);
+            }
+
+            type_parameters.push(TypeParameter { name: name, class_bound: class_bound, interface_bounds: interface_bounds });
+        }
+
+        if type_parameters.is_empty() {
+            return Err(self.error());
+        }
+
+        self.expect('>')?;
+        Ok(type_parameters)
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature, SignatureError> {
+        let base = match self.peek() {
+            Some('B') => FieldType::Byte,
+            Some('C') => FieldType::Char,
+            Some('D') => FieldType::Double,
+            Some('F') => FieldType::Float,
+            Some('I') => FieldType::Int,
+            Some('J') => FieldType::Long,
+            Some('S') => FieldType::Short,
+            Some('Z') => FieldType::Boolean,
+            _ => return self.reference_type()
+        };
+
+        self.offset += 1;
+        Ok(TypeSignature::Base(base))
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature, SignatureError> {
+        match self.peek() {
+            Some('L') => Ok(TypeSignature::Class(self.class_type()?)),
+            Some('T') => self.type_variable(),
+            Some('[') => {
+                self.offset += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            },
+            _ => Err(self.error())
+        }
+    }
+
+    fn type_variable(&mut self) -> Result<TypeSignature, SignatureError> {
+        self.expect('T')?;
+
+        let name = self.identifier()?;
+
+        self.expect(';')?;
+        Ok(TypeSignature::TypeVariable(name))
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect('L')?;
+
+        let mut segments = vec![self.identifier()?];
+
+        while self.peek() == Some('/') {
+            self.offset += 1;
+            segments.push(self.identifier()?);
+        }
+
+        let name = segments.pop().unwrap_or_default();
+        let mut classes = vec![self.simple_class_type(name)?];
+
+        while self.peek() == Some('.') {
+            self.offset += 1;
+
+            let name = self.identifier()?;
+            classes.push(self.simple_class_type(name)?);
+        }
+
+        self.expect(';')?;
+        Ok(ClassTypeSignature { package: segments.join("/"), classes: classes })
+    }
+
+    fn simple_class_type(&mut self, name: String) -> Result<SimpleClassTypeSignature, SignatureError> {
+        let mut type_arguments = vec![];
+
+        if self.peek() == Some('<') {
+            self.offset += 1;
+
+            while self.peek() != Some('>') {
+                type_arguments.push(match self.peek() {
+                    Some('*') => { self.offset += 1; TypeArgument::Any },
+                    Some('+') => { self.offset += 1; TypeArgument::Extends(self.reference_type()?) },
+                    Some('-') => { self.offset += 1; TypeArgument::Super(self.reference_type()?) },
+                    _ => TypeArgument::Exact(self.reference_type()?)
+                });
+            }
+
+            if type_arguments.is_empty() {
+                return Err(self.error());
+            }
+
+            self.expect('>')?;
+        }
+
+        Ok(SimpleClassTypeSignature { name: name, type_arguments: type_arguments })
+    }
+}
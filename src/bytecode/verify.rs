@@ -0,0 +1,1007 @@
+use std::error::Error;
+use std::fmt;
+use super::cfg::Offsets;
+use super::classfile::*;
+use super::descriptor::*;
+use super::hierarchy::*;
+
+/// A reason the JVM would reject a method, found while type checking its code
+#[derive(Debug, PartialEq)]
+pub struct VerifyError {
+    /// Name and descriptor of the method, eg. `main([Ljava/lang/String;)V`
+    pub method: String,
+    /// Offset of the offending instruction within the code
+    pub offset: usize,
+    pub kind: VerifyErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyErrorKind {
+    /// A value on the operand stack doesn't have the type the instruction expects
+    TypeMismatch { expected: String, actual: String },
+    /// A local variable doesn't have the type the instruction expects
+    LocalMismatch { index: u16, expected: String, actual: String },
+    /// The state flowing into an instruction, handler or branch target isn't assignable to the
+    /// stack map frame recorded for it
+    FrameMismatch { expected: String, actual: String },
+    /// An instruction pops more values than there are on the operand stack
+    StackUnderflow,
+    /// The operand stack grows beyond max_stack
+    StackOverflow,
+    /// A local variable lies beyond max_locals
+    InvalidLocal(u16),
+    /// A constant pool index doesn't point to the kind of constant the instruction needs
+    BadConstant(u16),
+    /// A method or field descriptor can't be parsed
+    BadDescriptor(String),
+    /// A branch, exception handler or frame points at an offset that isn't the start of an instruction
+    InvalidTarget(i64),
+    /// A branch target, exception handler or instruction following an unconditional jump has no
+    /// stack map frame
+    MissingFrame,
+    /// The `StackMapTable` can't be decoded, or describes more locals than max_locals
+    InvalidStackMapTable,
+    /// A constructor returns without calling another constructor on `this`
+    UninitializedThis,
+    /// `JSR` and `RET` aren't allowed in classes that are type checked
+    Subroutine,
+    /// Execution runs past the last instruction
+    FallsOffEnd,
+    /// An instruction the reader couldn't make sense of
+    UnknownInstruction,
+}
+
+impl fmt::Display for VerifyErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &VerifyErrorKind::TypeMismatch { ref expected, ref actual } => write!(f, "expected {} on the operand stack but found {}", expected, actual),
+            &VerifyErrorKind::LocalMismatch { index, ref expected, ref actual } => write!(f, "expected {} in local variable {} but found {}", expected, index, actual),
+            &VerifyErrorKind::FrameMismatch { ref expected, ref actual } => write!(f, "current frame {} is not assignable to stack map frame {}", actual, expected),
+            &VerifyErrorKind::StackUnderflow => write!(f, "operand stack underflow"),
+            &VerifyErrorKind::StackOverflow => write!(f, "operand stack exceeds max_stack"),
+            &VerifyErrorKind::InvalidLocal(idx) => write!(f, "local variable {} exceeds max_locals", idx),
+            &VerifyErrorKind::BadConstant(idx) => write!(f, "invalid constant pool index #{}", idx),
+            &VerifyErrorKind::BadDescriptor(ref descriptor) => write!(f, "invalid descriptor {}", descriptor),
+            &VerifyErrorKind::InvalidTarget(target) => write!(f, "offset {} is not the start of an instruction", target),
+            &VerifyErrorKind::MissingFrame => write!(f, "missing stack map frame"),
+            &VerifyErrorKind::InvalidStackMapTable => write!(f, "invalid StackMapTable"),
+            &VerifyErrorKind::UninitializedThis => write!(f, "constructor returns before this is initialized"),
+            &VerifyErrorKind::Subroutine => write!(f, "subroutines are not allowed"),
+            &VerifyErrorKind::FallsOffEnd => write!(f, "execution falls off the end of the code"),
+            &VerifyErrorKind::UnknownInstruction => write!(f, "unknown instruction"),
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {} of {}", self.kind, self.offset, self.method)
+    }
+}
+
+impl Error for VerifyError {}
+
+///
+/// Type check the code of every method the way the JVM's type checking verifier does, using the
+/// `StackMapTable` frames recorded in the class. `hierarchy` is asked whether one class type can
+/// be assigned to another. Classes it doesn't know are given the benefit of the doubt, so an
+/// `EmptyHierarchy` still catches everything except mistakes in the class hierarchy. Classes older
+/// than version 50 have no frames and are verified by type inference in the JVM, they pass
+/// unchecked.
+pub fn verify<H: ClassHierarchy>(class: &Classfile, hierarchy: &H) -> Result<(), VerifyError> {
+    if class.version.major_version < 50 {
+        return Ok(());
+    }
+
+    let cp = &class.constant_pool;
+    let hierarchy = WithClass { class: ClassInfo::from_class(class), hierarchy: hierarchy };
+
+    for method in &class.methods {
+        let name = cp.get_utf8_string(method.name_index.idx as u16).unwrap_or(String::new());
+        let descriptor = cp.get_utf8_string(method.descriptor_index.idx as u16).unwrap_or(String::new());
+        let signature = Signature {
+            class: &hierarchy.class.name,
+            name: &name,
+            descriptor: &descriptor,
+            is_static: method.access_flags.has_flag(MethodAccessFlags::Static as u16),
+        };
+
+        for attribute in &method.attributes {
+            if let &Attribute::Code { max_stack, max_locals, ref code, ref exception_table, ref attributes } = attribute {
+                let table = attributes.iter().filter_map(|attribute| match attribute {
+                    &Attribute::StackMapTable(ref table) => Some(&table[..]),
+                    _ => None
+                }).next().unwrap_or(&[]);
+
+                verify_code(cp, &signature, max_stack, max_locals, code, exception_table, table, &hierarchy).map_err(|(offset, kind)| VerifyError {
+                    method: format!("{}{}", name, descriptor),
+                    offset: offset,
+                    kind: kind,
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A verification type. Longs and doubles take a single operand stack entry, but two local
+/// variable slots, the second of which holds `Top`.
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// An object created by the `NEW` at this offset whose constructor hasn't been called yet
+    Uninitialized(usize),
+    /// A class by internal name, or an array by descriptor
+    Reference(String),
+}
+
+impl Type {
+    fn is_wide(&self) -> bool {
+        match *self {
+            Type::Long | Type::Double => true,
+            _ => false
+        }
+    }
+
+    /// The type of a value of the given field type, arrays being referred to by their descriptor
+    fn from_field_type(field_type: &FieldType) -> Type {
+        match *field_type {
+            FieldType::Byte | FieldType::Char | FieldType::Int | FieldType::Short | FieldType::Boolean => Type::Integer,
+            FieldType::Float => Type::Float,
+            FieldType::Long => Type::Long,
+            FieldType::Double => Type::Double,
+            FieldType::Object(ref class) => Type::Reference(class.clone()),
+            FieldType::Array(_) => Type::Reference(field_type.descriptor())
+        }
+    }
+
+    /// The type of a value of the given field descriptor, eg. `I` or `[Ljava/lang/String;`
+    fn from_descriptor(descriptor: &str) -> Option<Type> {
+        FieldType::parse(descriptor).ok().map(|field_type| Type::from_field_type(&field_type))
+    }
+
+    /// Whether a value of this type may be used where a value of type `to` is expected
+    fn is_assignable<H: ClassHierarchy>(&self, to: &Type, hierarchy: &H) -> bool {
+        match (self, to) {
+            (a, b) if a == b => true,
+            (_, &Type::Top) => true,
+            (&Type::Null, &Type::Reference(_)) => true,
+            (&Type::Reference(ref from), &Type::Reference(ref to)) => is_assignable_class(from, to, hierarchy),
+            _ => false
+        }
+    }
+
+    fn is_reference(&self) -> bool {
+        match *self {
+            Type::Null | Type::Reference(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Type::Top => write!(f, "top"),
+            &Type::Integer => write!(f, "int"),
+            &Type::Float => write!(f, "float"),
+            &Type::Long => write!(f, "long"),
+            &Type::Double => write!(f, "double"),
+            &Type::Null => write!(f, "null"),
+            &Type::UninitializedThis => write!(f, "uninitializedThis"),
+            &Type::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+            &Type::Reference(ref class) => write!(f, "{}", class),
+        }
+    }
+}
+
+/// Whether a class or array type is assignable to another one. Interfaces are treated like
+/// `java/lang/Object`, as the JVM does, and classes whose place in the hierarchy isn't known are
+/// assumed to be assignable.
+fn is_assignable_class<H: ClassHierarchy>(from: &str, to: &str, hierarchy: &H) -> bool {
+    if from == to || to == "java/lang/Object" {
+        return true;
+    }
+
+    match (from.starts_with('['), to.starts_with('[')) {
+        (true, true) => match (Type::from_descriptor(&from[1..]), Type::from_descriptor(&to[1..])) {
+            (Some(Type::Reference(ref from)), Some(Type::Reference(ref to))) => is_assignable_class(from, to, hierarchy),
+            _ => false
+        },
+        (true, false) => to == "java/lang/Cloneable" || to == "java/io/Serializable",
+        (false, true) => false,
+        (false, false) => {
+            if hierarchy.class_info(to).map(|info| info.is_interface).unwrap_or(true) {
+                return true;
+            }
+
+            let supers = hierarchy.super_classes(from);
+            supers.iter().any(|class| class == to) || supers.last().map(|class| class != "java/lang/Object").unwrap_or(true)
+        }
+    }
+}
+
+/// Split a method descriptor into the types of its parameters and its return type, `None` for void
+fn parse_method_descriptor(descriptor: &str) -> Option<(Vec<Type>, Option<Type>)> {
+    MethodDescriptor::parse(descriptor).ok().map(|method| (method.params.iter().map(Type::from_field_type).collect(), method.ret.as_ref().map(Type::from_field_type)))
+}
+
+/// The method being verified
+struct Signature<'s> {
+    class: &'s str,
+    name: &'s str,
+    descriptor: &'s str,
+    is_static: bool,
+}
+
+type Failure = (usize, VerifyErrorKind);
+
+/// The local variables and operand stack before an instruction
+#[derive(Clone, Debug, PartialEq)]
+struct State {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+    /// Set in constructors until the constructor of the superclass, or another constructor of the
+    /// same class, has been called
+    this_uninitialized: bool,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let locals = self.locals.len() - self.locals.iter().rev().take_while(|&value| *value == Type::Top).count();
+        let list = |values: &[Type]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(", ");
+
+        write!(f, "{{ locals: [{}], stack: [{}] }}", list(&self.locals[..locals]), list(&self.stack))
+    }
+}
+
+impl State {
+    fn pop(&mut self) -> Result<Type, VerifyErrorKind> {
+        self.stack.pop().ok_or(VerifyErrorKind::StackUnderflow)
+    }
+
+    /// Pop a value that must be assignable to `expected`
+    fn pop_type<H: ClassHierarchy>(&mut self, expected: &Type, hierarchy: &H) -> Result<Type, VerifyErrorKind> {
+        let value = self.pop()?;
+
+        if value.is_assignable(expected, hierarchy) {
+            Ok(value)
+        } else {
+            Err(VerifyErrorKind::TypeMismatch { expected: expected.to_string(), actual: value.to_string() })
+        }
+    }
+
+    /// Pop an initialized reference, or `null`
+    fn pop_reference(&mut self) -> Result<Type, VerifyErrorKind> {
+        match self.pop()? {
+            value @ Type::Null | value @ Type::Reference(_) => Ok(value),
+            value => Err(VerifyErrorKind::TypeMismatch { expected: String::from("reference"), actual: value.to_string() })
+        }
+    }
+
+    /// Pop any reference, including objects whose constructor hasn't been called yet
+    fn pop_any_reference(&mut self) -> Result<Type, VerifyErrorKind> {
+        match self.pop()? {
+            value @ Type::Null | value @ Type::Reference(_) | value @ Type::UninitializedThis | value @ Type::Uninitialized(_) => Ok(value),
+            value => Err(VerifyErrorKind::TypeMismatch { expected: String::from("reference"), actual: value.to_string() })
+        }
+    }
+
+    /// Pop an array of one of the given descriptors, or `null`
+    fn pop_array(&mut self, descriptors: &[&str]) -> Result<(), VerifyErrorKind> {
+        match self.pop()? {
+            Type::Null => Ok(()),
+            Type::Reference(ref array) if descriptors.contains(&&array[..]) => Ok(()),
+            value => Err(VerifyErrorKind::TypeMismatch { expected: descriptors.join(" or "), actual: value.to_string() })
+        }
+    }
+
+    /// Pop an array of references, or `null`, returning the type of its elements
+    fn pop_reference_array(&mut self) -> Result<Type, VerifyErrorKind> {
+        match self.pop()? {
+            Type::Null => Ok(Type::Null),
+            Type::Reference(ref array) if array.starts_with("[L") || array.starts_with("[[") => Type::from_descriptor(&array[1..]).ok_or(VerifyErrorKind::BadDescriptor(array.clone())),
+            value => Err(VerifyErrorKind::TypeMismatch { expected: String::from("array of references"), actual: value.to_string() })
+        }
+    }
+
+    /// Pop values of the given types, topmost last, and push the result
+    fn apply<H: ClassHierarchy>(&mut self, pops: &[&Type], push: &Type, hierarchy: &H) -> Result<(), VerifyErrorKind> {
+        for expected in pops.iter().rev() {
+            self.pop_type(expected, hierarchy)?;
+        }
+
+        self.push(push.clone());
+        Ok(())
+    }
+
+    /// Pop values covering exactly `words` stack words, as the untyped stack instructions do,
+    /// returning them bottom first
+    fn take_words(&mut self, words: usize) -> Result<Vec<Type>, VerifyErrorKind> {
+        let mut taken = vec![];
+        let mut count = 0;
+
+        while count < words {
+            let value = self.pop()?;
+            count += if value.is_wide() { 2 } else { 1 };
+
+            if count > words {
+                return Err(VerifyErrorKind::TypeMismatch { expected: String::from("category 1 value"), actual: value.to_string() });
+            }
+
+            taken.insert(0, value);
+        }
+
+        Ok(taken)
+    }
+
+    fn push(&mut self, value: Type) {
+        self.stack.push(value);
+    }
+
+    fn push_all(&mut self, values: &[Type]) {
+        self.stack.extend(values.iter().cloned());
+    }
+
+    /// The number of words on the operand stack
+    fn words(&self) -> usize {
+        self.stack.iter().map(|value| if value.is_wide() { 2 } else { 1 }).sum()
+    }
+
+    /// The value of a local variable, which must be assignable to `expected`
+    fn local<H: ClassHierarchy>(&self, idx: u16, expected: &Type, hierarchy: &H) -> Result<Type, VerifyErrorKind> {
+        let slot = idx as usize;
+
+        if slot + if expected.is_wide() { 2 } else { 1 } > self.locals.len() {
+            return Err(VerifyErrorKind::InvalidLocal(idx));
+        }
+
+        if self.locals[slot].is_assignable(expected, hierarchy) {
+            Ok(self.locals[slot].clone())
+        } else {
+            Err(VerifyErrorKind::LocalMismatch { index: idx, expected: expected.to_string(), actual: self.locals[slot].to_string() })
+        }
+    }
+
+    fn load<H: ClassHierarchy>(&mut self, idx: u16, expected: Type, hierarchy: &H) -> Result<(), VerifyErrorKind> {
+        self.local(idx, &expected, hierarchy)?;
+        self.push(expected);
+        Ok(())
+    }
+
+    /// Load any reference, including objects whose constructor hasn't been called yet
+    fn load_reference(&mut self, idx: u16) -> Result<(), VerifyErrorKind> {
+        match self.locals.get(idx as usize) {
+            None => Err(VerifyErrorKind::InvalidLocal(idx)),
+            Some(value @ &Type::Null) | Some(value @ &Type::Reference(_)) | Some(value @ &Type::UninitializedThis) | Some(value @ &Type::Uninitialized(_)) => {
+                self.stack.push(value.clone());
+                Ok(())
+            },
+            Some(value) => Err(VerifyErrorKind::LocalMismatch { index: idx, expected: String::from("reference"), actual: value.to_string() })
+        }
+    }
+
+    fn store(&mut self, idx: u16, value: Type) -> Result<(), VerifyErrorKind> {
+        let slot = idx as usize;
+
+        if slot + if value.is_wide() { 2 } else { 1 } > self.locals.len() {
+            return Err(VerifyErrorKind::InvalidLocal(idx));
+        }
+
+        if slot > 0 && self.locals[slot - 1].is_wide() {
+            self.locals[slot - 1] = Type::Top;
+        }
+
+        if value.is_wide() {
+            self.locals[slot + 1] = Type::Top;
+        }
+
+        self.locals[slot] = value;
+        Ok(())
+    }
+
+    fn store_type<H: ClassHierarchy>(&mut self, idx: u16, expected: Type, hierarchy: &H) -> Result<(), VerifyErrorKind> {
+        self.pop_type(&expected, hierarchy)?;
+        self.store(idx, expected)
+    }
+
+    /// Replace every occurrence of an uninitialized object once its constructor has been called
+    fn initialize(&mut self, uninitialized: &Type, initialized: Type) {
+        for value in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if *value == *uninitialized {
+                *value = initialized.clone();
+            }
+        }
+
+        if *uninitialized == Type::UninitializedThis {
+            self.this_uninitialized = false;
+        }
+    }
+
+    /// Check that this state can flow into an instruction recorded with the given frame
+    fn check_frame<H: ClassHierarchy>(&self, frame: &State, hierarchy: &H) -> Result<(), VerifyErrorKind> {
+        let assignable = self.stack.len() == frame.stack.len() &&
+            self.stack.iter().zip(frame.stack.iter()).all(|(value, expected)| value.is_assignable(expected, hierarchy)) &&
+            self.locals.iter().zip(frame.locals.iter()).all(|(value, expected)| value.is_assignable(expected, hierarchy)) &&
+            (!self.this_uninitialized || frame.this_uninitialized);
+
+        if assignable {
+            Ok(())
+        } else {
+            Err(VerifyErrorKind::FrameMismatch { expected: frame.to_string(), actual: self.to_string() })
+        }
+    }
+}
+
+fn class_name(cp: &ConstantPool, idx: u16) -> Result<String, VerifyErrorKind> {
+    cp.get_class_name(idx).ok_or(VerifyErrorKind::BadConstant(idx))
+}
+
+/// The class, name and descriptor of a field or method reference
+fn member(cp: &ConstantPool, idx: u16) -> Result<(String, String, String), VerifyErrorKind> {
+    cp.get_member_ref(idx).ok_or(VerifyErrorKind::BadConstant(idx))
+}
+
+fn field_type(descriptor: String) -> Result<Type, VerifyErrorKind> {
+    Type::from_descriptor(&descriptor).ok_or(VerifyErrorKind::BadDescriptor(descriptor))
+}
+
+fn method_type(descriptor: String) -> Result<(Vec<Type>, Option<Type>), VerifyErrorKind> {
+    parse_method_descriptor(&descriptor).ok_or(VerifyErrorKind::BadDescriptor(descriptor))
+}
+
+/// The type `LDC`, `LDC_W` or `LDC2_W` pushes for a constant, `wide` telling which of them it is
+fn constant_type(cp: &ConstantPool, idx: u16, wide: bool) -> Result<Type, VerifyErrorKind> {
+    let value = match cp.resolve_index(&ConstantPoolIndex::new(idx as usize)) {
+        Some(&Constant::Integer(_)) => Type::Integer,
+        Some(&Constant::Float(_)) => Type::Float,
+        Some(&Constant::Long(_)) => Type::Long,
+        Some(&Constant::Double(_)) => Type::Double,
+        Some(&Constant::String(_)) => Type::Reference(String::from("java/lang/String")),
+        Some(&Constant::Class(_)) => Type::Reference(String::from("java/lang/Class")),
+        Some(&Constant::MethodType(_)) => Type::Reference(String::from("java/lang/invoke/MethodType")),
+        Some(&Constant::MethodHandle { .. }) => Type::Reference(String::from("java/lang/invoke/MethodHandle")),
+        Some(&Constant::Dynamic { ref name_and_type_index, .. }) => match cp.get_name_and_type(name_and_type_index.idx as u16) {
+            Some((_, descriptor)) => field_type(descriptor)?,
+            None => return Err(VerifyErrorKind::BadConstant(idx))
+        },
+        _ => return Err(VerifyErrorKind::BadConstant(idx))
+    };
+
+    if value.is_wide() == wide { Ok(value) } else { Err(VerifyErrorKind::BadConstant(idx)) }
+}
+
+/// The type of an array with elements of the given class or array type
+fn array_of(class: &str) -> Type {
+    if class.starts_with('[') {
+        Type::Reference(format!("[{}", class))
+    } else {
+        Type::Reference(format!("[L{};", class))
+    }
+}
+
+/// Check the operands of a single instruction and apply its effect to the state before it
+fn execute<H: ClassHierarchy>(cp: &ConstantPool, method: &Signature, ret: &Option<Type>, code: &Vec<Instruction>, offsets: &Offsets, position: usize, state: &mut State, hierarchy: &H) -> Result<(), VerifyErrorKind> {
+    let offset = offsets.offset(position);
+
+    let int = Type::Integer;
+    let long = Type::Long;
+    let float = Type::Float;
+    let double = Type::Double;
+
+    match code[position] {
+        Instruction::NOP => (),
+        Instruction::ACONST_NULL => state.push(Type::Null),
+        Instruction::ICONST_M1 | Instruction::ICONST_0 | Instruction::ICONST_1 | Instruction::ICONST_2 |
+        Instruction::ICONST_3 | Instruction::ICONST_4 | Instruction::ICONST_5 |
+        Instruction::BIPUSH(_) | Instruction::SIPUSH(_) => state.push(int),
+        Instruction::LCONST_0 | Instruction::LCONST_1 => state.push(long),
+        Instruction::FCONST_0 | Instruction::FCONST_1 | Instruction::FCONST_2 => state.push(float),
+        Instruction::DCONST_0 | Instruction::DCONST_1 => state.push(double),
+        Instruction::LDC(idx) => state.push(constant_type(cp, idx as u16, false)?),
+        Instruction::LDC_W(idx) => state.push(constant_type(cp, idx, false)?),
+        Instruction::LDC2_W(idx) => state.push(constant_type(cp, idx, true)?),
+
+        Instruction::ILOAD(idx) => state.load(idx as u16, int, hierarchy)?,
+        Instruction::LLOAD(idx) => state.load(idx as u16, long, hierarchy)?,
+        Instruction::FLOAD(idx) => state.load(idx as u16, float, hierarchy)?,
+        Instruction::DLOAD(idx) => state.load(idx as u16, double, hierarchy)?,
+        Instruction::ALOAD(idx) => state.load_reference(idx as u16)?,
+        Instruction::ILOAD_W(idx) => state.load(idx, int, hierarchy)?,
+        Instruction::LLOAD_W(idx) => state.load(idx, long, hierarchy)?,
+        Instruction::FLOAD_W(idx) => state.load(idx, float, hierarchy)?,
+        Instruction::DLOAD_W(idx) => state.load(idx, double, hierarchy)?,
+        Instruction::ALOAD_W(idx) => state.load_reference(idx)?,
+        Instruction::ILOAD_0 => state.load(0, int, hierarchy)?,
+        Instruction::ILOAD_1 => state.load(1, int, hierarchy)?,
+        Instruction::ILOAD_2 => state.load(2, int, hierarchy)?,
+        Instruction::ILOAD_3 => state.load(3, int, hierarchy)?,
+        Instruction::LLOAD_0 => state.load(0, long, hierarchy)?,
+        Instruction::LLOAD_1 => state.load(1, long, hierarchy)?,
+        Instruction::LLOAD_2 => state.load(2, long, hierarchy)?,
+        Instruction::LLOAD_3 => state.load(3, long, hierarchy)?,
+        Instruction::FLOAD_0 => state.load(0, float, hierarchy)?,
+        Instruction::FLOAD_1 => state.load(1, float, hierarchy)?,
+        Instruction::FLOAD_2 => state.load(2, float, hierarchy)?,
+        Instruction::FLOAD_3 => state.load(3, float, hierarchy)?,
+        Instruction::DLOAD_0 => state.load(0, double, hierarchy)?,
+        Instruction::DLOAD_1 => state.load(1, double, hierarchy)?,
+        Instruction::DLOAD_2 => state.load(2, double, hierarchy)?,
+        Instruction::DLOAD_3 => state.load(3, double, hierarchy)?,
+        Instruction::ALOAD_0 => state.load_reference(0)?,
+        Instruction::ALOAD_1 => state.load_reference(1)?,
+        Instruction::ALOAD_2 => state.load_reference(2)?,
+        Instruction::ALOAD_3 => state.load_reference(3)?,
+
+        Instruction::ISTORE(idx) => state.store_type(idx as u16, int, hierarchy)?,
+        Instruction::LSTORE(idx) => state.store_type(idx as u16, long, hierarchy)?,
+        Instruction::FSTORE(idx) => state.store_type(idx as u16, float, hierarchy)?,
+        Instruction::DSTORE(idx) => state.store_type(idx as u16, double, hierarchy)?,
+        Instruction::ASTORE(idx) => { let value = state.pop_any_reference()?; state.store(idx as u16, value)? },
+        Instruction::ISTORE_W(idx) => state.store_type(idx, int, hierarchy)?,
+        Instruction::LSTORE_W(idx) => state.store_type(idx, long, hierarchy)?,
+        Instruction::FSTORE_W(idx) => state.store_type(idx, float, hierarchy)?,
+        Instruction::DSTORE_W(idx) => state.store_type(idx, double, hierarchy)?,
+        Instruction::ASTORE_W(idx) => { let value = state.pop_any_reference()?; state.store(idx, value)? },
+        Instruction::ISTORE_0 => state.store_type(0, int, hierarchy)?,
+        Instruction::ISTORE_1 => state.store_type(1, int, hierarchy)?,
+        Instruction::ISTORE_2 => state.store_type(2, int, hierarchy)?,
+        Instruction::ISTORE_3 => state.store_type(3, int, hierarchy)?,
+        Instruction::LSTORE_0 => state.store_type(0, long, hierarchy)?,
+        Instruction::LSTORE_1 => state.store_type(1, long, hierarchy)?,
+        Instruction::LSTORE_2 => state.store_type(2, long, hierarchy)?,
+        Instruction::LSTORE_3 => state.store_type(3, long, hierarchy)?,
+        Instruction::FSTORE_0 => state.store_type(0, float, hierarchy)?,
+        Instruction::FSTORE_1 => state.store_type(1, float, hierarchy)?,
+        Instruction::FSTORE_2 => state.store_type(2, float, hierarchy)?,
+        Instruction::FSTORE_3 => state.store_type(3, float, hierarchy)?,
+        Instruction::DSTORE_0 => state.store_type(0, double, hierarchy)?,
+        Instruction::DSTORE_1 => state.store_type(1, double, hierarchy)?,
+        Instruction::DSTORE_2 => state.store_type(2, double, hierarchy)?,
+        Instruction::DSTORE_3 => state.store_type(3, double, hierarchy)?,
+        Instruction::ASTORE_0 => { let value = state.pop_any_reference()?; state.store(0, value)? },
+        Instruction::ASTORE_1 => { let value = state.pop_any_reference()?; state.store(1, value)? },
+        Instruction::ASTORE_2 => { let value = state.pop_any_reference()?; state.store(2, value)? },
+        Instruction::ASTORE_3 => { let value = state.pop_any_reference()?; state.store(3, value)? },
+
+        Instruction::IALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[I" ])?; state.push(int) },
+        Instruction::BALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[B", "[Z" ])?; state.push(int) },
+        Instruction::CALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[C" ])?; state.push(int) },
+        Instruction::SALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[S" ])?; state.push(int) },
+        Instruction::LALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[J" ])?; state.push(long) },
+        Instruction::FALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[F" ])?; state.push(float) },
+        Instruction::DALOAD => { state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[D" ])?; state.push(double) },
+        Instruction::AALOAD => {
+            state.pop_type(&int, hierarchy)?;
+            let element = state.pop_reference_array()?;
+            state.push(element);
+        },
+        Instruction::IASTORE => { state.pop_type(&int, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[I" ])? },
+        Instruction::BASTORE => { state.pop_type(&int, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[B", "[Z" ])? },
+        Instruction::CASTORE => { state.pop_type(&int, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[C" ])? },
+        Instruction::SASTORE => { state.pop_type(&int, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[S" ])? },
+        Instruction::LASTORE => { state.pop_type(&long, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[J" ])? },
+        Instruction::FASTORE => { state.pop_type(&float, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[F" ])? },
+        Instruction::DASTORE => { state.pop_type(&double, hierarchy)?; state.pop_type(&int, hierarchy)?; state.pop_array(&[ "[D" ])? },
+        Instruction::AASTORE => { state.pop_reference()?; state.pop_type(&int, hierarchy)?; state.pop_reference_array()?; },
+
+        Instruction::POP => { state.take_words(1)?; },
+        Instruction::POP2 => { state.take_words(2)?; },
+        Instruction::DUP => { let a = state.take_words(1)?; state.push_all(&a); state.push_all(&a) },
+        Instruction::DUP_X1 => { let a = state.take_words(1)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP_X2 => { let a = state.take_words(1)?; let b = state.take_words(2)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP2 => { let a = state.take_words(2)?; state.push_all(&a); state.push_all(&a) },
+        Instruction::DUP2_X1 => { let a = state.take_words(2)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::DUP2_X2 => { let a = state.take_words(2)?; let b = state.take_words(2)?; state.push_all(&a); state.push_all(&b); state.push_all(&a) },
+        Instruction::SWAP => { let a = state.take_words(1)?; let b = state.take_words(1)?; state.push_all(&a); state.push_all(&b) },
+
+        Instruction::IADD | Instruction::ISUB | Instruction::IMUL | Instruction::IDIV | Instruction::IREM |
+        Instruction::IAND | Instruction::IOR | Instruction::IXOR | Instruction::ISHL | Instruction::ISHR | Instruction::IUSHR => state.apply(&[ &int, &int ], &int, hierarchy)?,
+        Instruction::LADD | Instruction::LSUB | Instruction::LMUL | Instruction::LDIV | Instruction::LREM |
+        Instruction::LAND | Instruction::LOR | Instruction::LXOR => state.apply(&[ &long, &long ], &long, hierarchy)?,
+        Instruction::LSHL | Instruction::LSHR | Instruction::LUSHR => state.apply(&[ &long, &int ], &long, hierarchy)?,
+        Instruction::FADD | Instruction::FSUB | Instruction::FMUL | Instruction::FDIV | Instruction::FREM => state.apply(&[ &float, &float ], &float, hierarchy)?,
+        Instruction::DADD | Instruction::DSUB | Instruction::DMUL | Instruction::DDIV | Instruction::DREM => state.apply(&[ &double, &double ], &double, hierarchy)?,
+        Instruction::LCMP => state.apply(&[ &long, &long ], &int, hierarchy)?,
+        Instruction::FCMPL | Instruction::FCMPG => state.apply(&[ &float, &float ], &int, hierarchy)?,
+        Instruction::DCMPL | Instruction::DCMPG => state.apply(&[ &double, &double ], &int, hierarchy)?,
+
+        Instruction::INEG | Instruction::I2B | Instruction::I2C | Instruction::I2S => state.apply(&[ &int ], &int, hierarchy)?,
+        Instruction::I2L => state.apply(&[ &int ], &long, hierarchy)?,
+        Instruction::I2F => state.apply(&[ &int ], &float, hierarchy)?,
+        Instruction::I2D => state.apply(&[ &int ], &double, hierarchy)?,
+        Instruction::LNEG => state.apply(&[ &long ], &long, hierarchy)?,
+        Instruction::L2I => state.apply(&[ &long ], &int, hierarchy)?,
+        Instruction::L2F => state.apply(&[ &long ], &float, hierarchy)?,
+        Instruction::L2D => state.apply(&[ &long ], &double, hierarchy)?,
+        Instruction::FNEG => state.apply(&[ &float ], &float, hierarchy)?,
+        Instruction::F2I => state.apply(&[ &float ], &int, hierarchy)?,
+        Instruction::F2L => state.apply(&[ &float ], &long, hierarchy)?,
+        Instruction::F2D => state.apply(&[ &float ], &double, hierarchy)?,
+        Instruction::DNEG => state.apply(&[ &double ], &double, hierarchy)?,
+        Instruction::D2I => state.apply(&[ &double ], &int, hierarchy)?,
+        Instruction::D2L => state.apply(&[ &double ], &long, hierarchy)?,
+        Instruction::D2F => state.apply(&[ &double ], &float, hierarchy)?,
+
+        Instruction::IINC(idx, _) => { state.local(idx as u16, &int, hierarchy)?; },
+        Instruction::IINC_W(idx, _) => { state.local(idx, &int, hierarchy)?; },
+
+        Instruction::IFEQ(_) | Instruction::IFNE(_) | Instruction::IFLT(_) |
+        Instruction::IFGE(_) | Instruction::IFGT(_) | Instruction::IFLE(_) => { state.pop_type(&int, hierarchy)?; },
+        Instruction::IFNULL(_) | Instruction::IFNONNULL(_) => { state.pop_reference()?; },
+        Instruction::IF_ICMPEQ(_) | Instruction::IF_ICMPNE(_) | Instruction::IF_ICMPLT(_) |
+        Instruction::IF_ICMPGE(_) | Instruction::IF_ICMPGT(_) | Instruction::IF_ICMPLE(_) => {
+            state.pop_type(&int, hierarchy)?;
+            state.pop_type(&int, hierarchy)?;
+        },
+        Instruction::IF_ACMPEQ(_) | Instruction::IF_ACMPNE(_) => {
+            state.pop_reference()?;
+            state.pop_reference()?;
+        },
+        Instruction::GOTO(_) | Instruction::GOTO_W(_) => (),
+        Instruction::JSR(_) | Instruction::JSR_W(_) | Instruction::RET(_) | Instruction::RET_W(_) => return Err(VerifyErrorKind::Subroutine),
+        Instruction::TABLESWITCH(..) | Instruction::LOOKUPSWITCH(..) => { state.pop_type(&int, hierarchy)?; },
+        Instruction::IRETURN | Instruction::LRETURN | Instruction::FRETURN | Instruction::DRETURN | Instruction::ARETURN => {
+            let returned = match code[position] {
+                Instruction::IRETURN => int,
+                Instruction::LRETURN => long,
+                Instruction::FRETURN => float,
+                Instruction::DRETURN => double,
+                _ => Type::Reference(String::from("java/lang/Object"))
+            };
+
+            match *ret {
+                Some(ref ret) if *ret == returned || (ret.is_reference() && returned.is_reference()) => { state.pop_type(ret, hierarchy)?; },
+                _ => return Err(VerifyErrorKind::TypeMismatch {
+                    expected: ret.as_ref().map(|ret| ret.to_string()).unwrap_or(String::from("void")),
+                    actual: if returned.is_reference() { String::from("reference") } else { returned.to_string() },
+                })
+            }
+        },
+        Instruction::RETURN => {
+            if let Some(ref ret) = *ret {
+                return Err(VerifyErrorKind::TypeMismatch { expected: ret.to_string(), actual: String::from("void") });
+            }
+
+            if state.this_uninitialized {
+                return Err(VerifyErrorKind::UninitializedThis);
+            }
+        },
+        Instruction::ATHROW => { state.pop_type(&Type::Reference(String::from("java/lang/Throwable")), hierarchy)?; },
+
+        Instruction::GETSTATIC(idx) => { let (_, _, descriptor) = member(cp, idx)?; state.push(field_type(descriptor)?) },
+        Instruction::PUTSTATIC(idx) => { let (_, _, descriptor) = member(cp, idx)?; state.pop_type(&field_type(descriptor)?, hierarchy)?; },
+        Instruction::GETFIELD(idx) => {
+            let (class, _, descriptor) = member(cp, idx)?;
+            state.pop_type(&Type::Reference(class), hierarchy)?;
+            state.push(field_type(descriptor)?);
+        },
+        Instruction::PUTFIELD(idx) => {
+            let (class, _, descriptor) = member(cp, idx)?;
+            state.pop_type(&field_type(descriptor)?, hierarchy)?;
+
+            // Constructors may set their own fields before calling the constructor of the superclass
+            match state.pop_any_reference()? {
+                Type::UninitializedThis if class == method.class => (),
+                receiver => if !receiver.is_assignable(&Type::Reference(class.clone()), hierarchy) {
+                    return Err(VerifyErrorKind::TypeMismatch { expected: class, actual: receiver.to_string() });
+                }
+            }
+        },
+        Instruction::INVOKEVIRTUAL(idx) | Instruction::INVOKESPECIAL(idx) | Instruction::INVOKESTATIC(idx) | Instruction::INVOKEINTERFACE(idx, _) => {
+            let (class, name, descriptor) = member(cp, idx)?;
+            let (params, ret) = method_type(descriptor)?;
+            let is_init = name == "<init>";
+
+            match code[position] {
+                Instruction::INVOKESPECIAL(_) if is_init => (),
+                _ if name.starts_with('<') => return Err(VerifyErrorKind::BadConstant(idx)),
+                _ => ()
+            }
+
+            for param in params.iter().rev() {
+                state.pop_type(param, hierarchy)?;
+            }
+
+            match code[position] {
+                Instruction::INVOKESTATIC(_) => (),
+                Instruction::INVOKEINTERFACE(..) => { state.pop_reference()?; },
+                Instruction::INVOKESPECIAL(_) if is_init => {
+                    let receiver = state.pop_any_reference()?;
+                    let initialized = match receiver {
+                        Type::UninitializedThis => Type::Reference(method.class.to_string()),
+                        Type::Uninitialized(created) => match offsets.instruction_at(created as i64).ok().map(|idx| &code[idx]) {
+                            Some(&Instruction::NEW(new)) => match class_name(cp, new)? {
+                                ref created if *created == class => Type::Reference(class.clone()),
+                                created => return Err(VerifyErrorKind::TypeMismatch { expected: format!("uninitialized {}", class), actual: format!("uninitialized {}", created) })
+                            },
+                            _ => return Err(VerifyErrorKind::InvalidTarget(created as i64))
+                        },
+                        receiver => return Err(VerifyErrorKind::TypeMismatch { expected: String::from("uninitialized object"), actual: receiver.to_string() })
+                    };
+
+                    state.initialize(&receiver, initialized);
+                },
+                _ => { state.pop_type(&Type::Reference(class), hierarchy)?; }
+            }
+
+            if let Some(ret) = ret {
+                state.push(ret);
+            }
+        },
+        Instruction::INVOKEDYNAMIC(idx) => {
+            let descriptor = match cp.resolve_index(&ConstantPoolIndex::new(idx as usize)) {
+                Some(&Constant::InvokeDynamic { ref name_and_type_index, .. }) => cp.get_name_and_type(name_and_type_index.idx as u16).map(|(_, descriptor)| descriptor),
+                _ => None
+            };
+
+            let (params, ret) = method_type(descriptor.ok_or(VerifyErrorKind::BadConstant(idx))?)?;
+
+            for param in params.iter().rev() {
+                state.pop_type(param, hierarchy)?;
+            }
+
+            if let Some(ret) = ret {
+                state.push(ret);
+            }
+        },
+        Instruction::NEW(idx) => {
+            if class_name(cp, idx)?.starts_with('[') {
+                return Err(VerifyErrorKind::BadConstant(idx));
+            }
+
+            state.push(Type::Uninitialized(offset));
+        },
+        Instruction::NEWARRAY(atype) => {
+            state.pop_type(&int, hierarchy)?;
+            state.push(Type::Reference(match atype {
+                4 => "[Z", 5 => "[C", 6 => "[F", 7 => "[D", 8 => "[B", 9 => "[S", 10 => "[I", 11 => "[J",
+                _ => return Err(VerifyErrorKind::UnknownInstruction)
+            }.to_string()));
+        },
+        Instruction::ANEWARRAY(idx) => { state.pop_type(&int, hierarchy)?; state.push(array_of(&class_name(cp, idx)?)) },
+        Instruction::MULTIANEWARRAY(idx, dimensions) => {
+            let class = class_name(cp, idx)?;
+
+            if dimensions == 0 || class.len() - class.trim_start_matches('[').len() < dimensions as usize {
+                return Err(VerifyErrorKind::BadConstant(idx));
+            }
+
+            for _ in 0..dimensions {
+                state.pop_type(&int, hierarchy)?;
+            }
+
+            state.push(Type::Reference(class));
+        },
+        Instruction::ARRAYLENGTH => {
+            match state.pop()? {
+                Type::Null => (),
+                Type::Reference(ref array) if array.starts_with('[') => (),
+                value => return Err(VerifyErrorKind::TypeMismatch { expected: String::from("array"), actual: value.to_string() })
+            }
+
+            state.push(int);
+        },
+        Instruction::CHECKCAST(idx) => { state.pop_reference()?; state.push(Type::Reference(class_name(cp, idx)?)) },
+        Instruction::INSTANCEOF(idx) => { class_name(cp, idx)?; state.pop_reference()?; state.push(int) },
+        Instruction::MONITORENTER | Instruction::MONITOREXIT => { state.pop_reference()?; },
+        Instruction::PADDED_INSTRUCTION(_) | Instruction::WTF(_) => return Err(VerifyErrorKind::UnknownInstruction),
+    }
+
+    Ok(())
+}
+
+/// The state on entry to the method, as implied by its descriptor
+fn initial_state(method: &Signature, params: &[Type], max_locals: u16) -> Result<State, VerifyErrorKind> {
+    let mut locals = vec![];
+    let mut this_uninitialized = false;
+
+    if !method.is_static {
+        if method.name == "<init>" && method.class != "java/lang/Object" {
+            locals.push(Type::UninitializedThis);
+            this_uninitialized = true;
+        } else {
+            locals.push(Type::Reference(method.class.to_string()));
+        }
+    }
+
+    for param in params {
+        locals.push(param.clone());
+
+        if param.is_wide() {
+            locals.push(Type::Top);
+        }
+    }
+
+    if locals.len() > max_locals as usize {
+        return Err(VerifyErrorKind::InvalidLocal(locals.len() as u16 - 1));
+    }
+
+    locals.resize(max_locals as usize, Type::Top);
+    Ok(State { locals: locals, stack: vec![], this_uninitialized: this_uninitialized })
+}
+
+fn decode_type(cp: &ConstantPool, value: &VerificationType) -> Result<Type, VerifyErrorKind> {
+    Ok(match value {
+        &VerificationType::Top => Type::Top,
+        &VerificationType::Integer => Type::Integer,
+        &VerificationType::Float => Type::Float,
+        &VerificationType::Long => Type::Long,
+        &VerificationType::Double => Type::Double,
+        &VerificationType::Null => Type::Null,
+        &VerificationType::UninitializedThis => Type::UninitializedThis,
+        &VerificationType::Object { ref cpool_index } => Type::Reference(cp.get_class_name(cpool_index.idx as u16).ok_or(VerifyErrorKind::InvalidStackMapTable)?),
+        &VerificationType::Uninitialized { offset } => Type::Uninitialized(offset as usize),
+    })
+}
+
+///
+/// Expand the frames of a `StackMapTable` into the full state at each of their offsets. Frames
+/// describe locals as a list in which longs and doubles take a single entry, which is what chop
+/// and append frames operate on.
+fn decode_frames(cp: &ConstantPool, initial: &State, table: &[StackMapFrame], max_locals: u16) -> Result<Vec<(usize, State)>, VerifyErrorKind> {
+    let mut entries = vec![];
+    let mut slots = initial.locals.iter();
+
+    while let Some(value) = slots.next() {
+        entries.push(value.clone());
+
+        if value.is_wide() {
+            slots.next();
+        }
+    }
+
+    while entries.last() == Some(&Type::Top) {
+        entries.pop();
+    }
+
+    let mut frames: Vec<(usize, State)> = vec![];
+
+    for frame in table {
+        let (offset_delta, stack) = match frame {
+            &StackMapFrame::SameFrame { tag } => (tag as usize, vec![]),
+            &StackMapFrame::SameLocals1StackItemFrame { tag, ref stack } => (tag as usize - 64, vec![ decode_type(cp, stack)? ]),
+            &StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => (offset_delta as usize, vec![ decode_type(cp, stack)? ]),
+            &StackMapFrame::ChopFrame { tag, offset_delta } => {
+                let chopped = 251 - tag as usize;
+
+                if chopped > entries.len() {
+                    return Err(VerifyErrorKind::InvalidStackMapTable);
+                }
+
+                let remaining = entries.len() - chopped;
+                entries.truncate(remaining);
+                (offset_delta as usize, vec![])
+            },
+            &StackMapFrame::SameFrameExtended { offset_delta } => (offset_delta as usize, vec![]),
+            &StackMapFrame::AppendFrame { offset_delta, ref locals, .. } => {
+                for value in locals {
+                    entries.push(decode_type(cp, value)?);
+                }
+
+                (offset_delta as usize, vec![])
+            },
+            &StackMapFrame::FullFrame { offset_delta, ref locals, ref stack } => {
+                entries = locals.iter().map(|value| decode_type(cp, value)).collect::<Result<Vec<Type>, VerifyErrorKind>>()?;
+                (offset_delta as usize, stack.iter().map(|value| decode_type(cp, value)).collect::<Result<Vec<Type>, VerifyErrorKind>>()?)
+            },
+            &StackMapFrame::FutureUse { .. } => return Err(VerifyErrorKind::InvalidStackMapTable)
+        };
+
+        let mut locals = vec![];
+
+        for value in &entries {
+            locals.push(value.clone());
+
+            if value.is_wide() {
+                locals.push(Type::Top);
+            }
+        }
+
+        if locals.len() > max_locals as usize {
+            return Err(VerifyErrorKind::InvalidStackMapTable);
+        }
+
+        locals.resize(max_locals as usize, Type::Top);
+
+        let offset = match frames.last() {
+            Some(&(previous, _)) => previous + offset_delta + 1,
+            None => offset_delta
+        };
+
+        let this_uninitialized = locals.contains(&Type::UninitializedThis);
+        frames.push((offset, State { locals: locals, stack: stack, this_uninitialized: this_uninitialized }));
+    }
+
+    Ok(frames)
+}
+
+///
+/// Check the instructions of a method one after the other, as the type checker does. The state
+/// before each instruction is the one left by the instruction before it, or the stack map frame
+/// recorded for it, which the incoming state must be assignable to. Branch targets and exception
+/// handlers must have frames the state at the branch is assignable to.
+fn verify_code<H: ClassHierarchy>(cp: &ConstantPool, method: &Signature, max_stack: u16, max_locals: u16, code: &Vec<Instruction>, exception_table: &Vec<ExceptionHandler>, table: &[StackMapFrame], hierarchy: &H) -> Result<(), Failure> {
+    let (params, ret) = method_type(method.descriptor.to_string()).map_err(|kind| (0, kind))?;
+    let initial = initial_state(method, &params, max_locals).map_err(|kind| (0, kind))?;
+
+    if code.is_empty() {
+        return Err((0, VerifyErrorKind::FallsOffEnd));
+    }
+
+    let offsets = Offsets::new(code);
+    let mut frames: Vec<Option<State>> = vec![None; code.len()];
+
+    for (offset, state) in decode_frames(cp, &initial, table, max_locals).map_err(|kind| (0, kind))? {
+        let position = offsets.instruction_at(offset as i64).map_err(|target| (offset, VerifyErrorKind::InvalidTarget(target)))?;
+        frames[position] = Some(state);
+    }
+
+    let handlers = exception_table.iter().map(|handler| {
+        let catch_type = match handler.catch_type.idx {
+            0 => String::from("java/lang/Throwable"),
+            idx => class_name(cp, idx as u16).map_err(|kind| (handler.handler_pc as usize, kind))?
+        };
+
+        let position = offsets.instruction_at(handler.handler_pc as i64).map_err(|target| (handler.handler_pc as usize, VerifyErrorKind::InvalidTarget(target)))?;
+        Ok((handler.start_pc as usize, handler.end_pc as usize, position, Type::Reference(catch_type)))
+    }).collect::<Result<Vec<(usize, usize, usize, Type)>, Failure>>()?;
+
+    let check_target = |from: usize, target: usize, state: &State| -> Result<(), Failure> {
+        match frames[target] {
+            Some(ref frame) => state.check_frame(frame, hierarchy).map_err(|kind| (from, kind)),
+            None => Err((from, VerifyErrorKind::MissingFrame))
+        }
+    };
+
+    let mut current = Some(initial);
+
+    for (position, frame) in frames.iter().enumerate() {
+        let offset = offsets.offset(position);
+        let state = match (current.take(), frame.as_ref()) {
+            (Some(state), Some(frame)) => {
+                state.check_frame(frame, hierarchy).map_err(|kind| (offset, kind))?;
+                frame.clone()
+            },
+            (None, Some(frame)) => frame.clone(),
+            (Some(state), None) => state,
+            (None, None) => return Err((offset, VerifyErrorKind::MissingFrame))
+        };
+
+        for &(start, end, handler, ref catch_type) in &handlers {
+            if start <= offset && offset < end {
+                let thrown = State { locals: state.locals.clone(), stack: vec![ catch_type.clone() ], this_uninitialized: state.this_uninitialized };
+                check_target(offset, handler, &thrown)?;
+            }
+        }
+
+        let mut after = state;
+        execute(cp, method, &ret, code, &offsets, position, &mut after, hierarchy).map_err(|kind| (offset, kind))?;
+
+        if after.words() > max_stack as usize {
+            return Err((offset, VerifyErrorKind::StackOverflow));
+        }
+
+        let flow = offsets.flow(code, position).map_err(|target| (offset, VerifyErrorKind::InvalidTarget(target)))?;
+
+        for target in flow.targets() {
+            check_target(offset, target, &after)?;
+        }
+
+        if flow.continues() {
+            current = Some(after);
+        }
+    }
+
+    match current {
+        Some(_) => Err((offsets.offset(code.len() - 1), VerifyErrorKind::FallsOffEnd)),
+        None => Ok(())
+    }
+}
@@ -1,78 +1,7 @@
+use super::bytecode::descriptor::{DescriptorError, FieldType};
+use super::bytecode::signature::GenericClassSignature;
 use super::native::JavaClass;
 
-///
-/// Enumeration of the possible Java types.
-///
-#[derive(Debug, Eq, PartialEq)]
-pub enum JavaType<'a> {
-    Boolean,
-    Byte,
-    Char,
-    Double,
-    Float,
-    Int,
-    Long,
-    Short,
-    Void,
-    Class(&'a str),
-    Array(Box<JavaType<'a>>),
-}
-
-impl<'a> JavaType<'a> {
-    /// Convert a given type signature into a JavaType instance (if possible). None is returned
-    /// if the conversation was not successful.
-    pub fn parse(signature: &'a str) -> Option<JavaType<'a>> {
-        match signature.len() {
-            0 => None,
-            1 => match &*signature {
-                "B" => Some(JavaType::Byte),
-                "C" => Some(JavaType::Char),
-                "D" => Some(JavaType::Double),
-                "F" => Some(JavaType::Float),
-                "I" => Some(JavaType::Int),
-                "J" => Some(JavaType::Long),
-                "S" => Some(JavaType::Short),
-                "V" => Some(JavaType::Void),
-                "Z" => Some(JavaType::Boolean),
-                _ => None
-            },
-            _ => {
-                match signature.chars().nth(0).unwrap() {
-                    '[' => {
-                        let (_, local_type) = signature.split_at(1);
-
-                        match JavaType::parse(local_type) {
-                            Some(result) => Some(JavaType::Array(Box::new(result))),
-                            None => None
-                        }
-                    }
-                    'L' => Some(JavaType::Class(signature)),
-                    _ => None
-                }
-            }
-        }
-    }
-
-    ///
-    /// Converts the given Java type into a conventional human-readable representation
-    ///
-    pub fn to_string(java_type: &JavaType) -> String {
-        match *java_type {
-            JavaType::Byte => "byte".to_string(),
-            JavaType::Char => "char".to_string(),
-            JavaType::Double => "double".to_string(),
-            JavaType::Float => "float".to_string(),
-            JavaType::Int => "int".to_string(),
-            JavaType::Long => "long".to_string(),
-            JavaType::Short => "short".to_string(),
-            JavaType::Void => "void".to_string(),
-            JavaType::Boolean => "boolean".to_string(),
-            JavaType::Array(ref inner_type) => format!("{}[]", JavaType::to_string(inner_type)),
-            JavaType::Class(cls) => cls.trim_left_matches("L").trim_right_matches(";").replace(";", "").replace("/", ".").to_string()
-        }
-    }
-}
-
 ///
 /// Represents a JNI local reference to a Java class
 ///
@@ -83,22 +12,30 @@ pub struct ClassId {
 pub struct ClassSignature {
     pub package: String,
     pub name: String,
+    /// The type parameters, super class and interfaces of generic classes
+    pub generic: Option<GenericClassSignature>,
 }
 
 impl ClassSignature {
-    pub fn new(java_type: &JavaType) -> ClassSignature {
-        let str = JavaType::to_string(java_type);
-        match str.rfind('.') {
-            Some(idx) => {
-                let (pkg, name) = str.split_at(idx + 1);
+    /// Constructs the signature of a class from its JVM type signature, eg. `Ljava/lang/String;`,
+    /// along with its generic signature, if it's got a valid one
+    pub fn parse(signature: &str, generic: Option<&str>) -> Result<ClassSignature, DescriptorError> {
+        // void.class is the one class whose type isn't a field type
+        let name = match signature {
+            "V" => "void".to_string(),
+            _ => source_name(&FieldType::parse(signature)?)
+        };
+
+        let (package, name) = match name.rfind('.') {
+            Some(idx) => (name[..idx].to_string(), name[idx + 1..].to_string()),
+            None => (String::new(), name)
+        };
 
-                ClassSignature {
-                    package: pkg.trim_right_matches(".").to_string(),
-                    name: name.to_string(),
-                }
-            }
-            None => ClassSignature { package: "".to_string(), name: str.to_string() }
-        }
+        Ok(ClassSignature {
+            package: package,
+            name: name,
+            generic: generic.and_then(|signature| GenericClassSignature::parse(signature).ok()),
+        })
     }
 
     pub fn to_string(&self) -> String {
@@ -116,8 +53,8 @@ pub struct Class {
 
 impl Class {
     /// Constructs a new Class instance.
-    pub fn new<'a>(id: ClassId, signature: JavaType<'a>) -> Class {
-        Class { id: id, signature: ClassSignature::new(&signature) }
+    pub fn new(id: ClassId, signature: ClassSignature) -> Class {
+        Class { id: id, signature: signature }
     }
 
     /// Returns the readable name of this class
@@ -125,3 +62,19 @@ impl Class {
         self.signature.to_string()
     }
 }
+
+/// The name of a type as written in Java source, eg. `java.lang.String[]`
+fn source_name(field_type: &FieldType) -> String {
+    match field_type {
+        &FieldType::Byte => "byte".to_string(),
+        &FieldType::Char => "char".to_string(),
+        &FieldType::Double => "double".to_string(),
+        &FieldType::Float => "float".to_string(),
+        &FieldType::Int => "int".to_string(),
+        &FieldType::Long => "long".to_string(),
+        &FieldType::Short => "short".to_string(),
+        &FieldType::Boolean => "boolean".to_string(),
+        &FieldType::Object(ref class) => class.replace('/', "."),
+        &FieldType::Array(ref component) => format!("{}[]", source_name(component)),
+    }
+}
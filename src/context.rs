@@ -1,3 +1,4 @@
+use super::bytecode::hierarchy::LoadedClassHierarchy;
 use super::config::Config;
 use super::thread::ThreadId;
 use std::collections::HashMap;
@@ -26,6 +27,8 @@ pub fn static_context() -> &'static AgentContext {
 pub struct AgentContext {
     context: Arc<RwLock<Context>>,
     pub config: Arc<RwLock<Config>>,
+    /// Every class that passed through the class file load hook so far
+    pub loaded_classes: LoadedClassHierarchy,
 }
 
 impl AgentContext {
@@ -33,6 +36,7 @@ impl AgentContext {
         AgentContext {
             context: Arc::new(RwLock::new(Context::new())),
             config: Arc::new(RwLock::new(Config::default())),
+            loaded_classes: LoadedClassHierarchy::new(),
         }
     }
 
@@ -1,6 +1,7 @@
 use super::capabilities::Capabilities;
 use super::class::{ClassId, ClassSignature};
 use super::error::NativeError;
+use super::environment::jni::JNI;
 use super::environment::jvm::JVMF;
 use super::environment::jvmti::JVMTI;
 use super::event::{EventCallbacks, VMEvent};
@@ -10,8 +11,9 @@ use super::native::JavaThread;
 use super::runtime::*;
 use super::thread::Thread;
 use super::version::VersionNumber;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use native::{MutByteArray, JavaObject};
+use native::{MutByteArray, JavaClass, JavaObject};
 use table::LocalVariableTable;
 use thread::ThreadId;
 
@@ -20,6 +22,34 @@ pub struct JVMEmulator {
     pub capabilities: Capabilities,
     pub callbacks: EventCallbacks,
     pub events: HashMap<VMEvent, bool>,
+    /// Every class ever loaded, indexed by its id less one. Unloaded classes leave their slot empty
+    pub classes: RefCell<Vec<Option<EmulatedClass>>>,
+    /// Object tags by class id
+    pub tags: RefCell<HashMap<usize, i32>>,
+    /// The number of times a class signature was asked for
+    pub signatures: Cell<usize>,
+}
+
+/// A class loaded into the emulated JVM. Classes are named by their type signature
+pub struct EmulatedClass {
+    pub signature: String,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub is_interface: bool,
+    /// The interfaces of unprepared classes can't be listed yet
+    pub prepared: bool,
+}
+
+impl EmulatedClass {
+    pub fn new(signature: &str, super_class: Option<&str>, interfaces: &[&str], is_interface: bool) -> EmulatedClass {
+        EmulatedClass {
+            signature: signature.to_string(),
+            super_class: super_class.map(|name| name.to_string()),
+            interfaces: interfaces.iter().map(|name| name.to_string()).collect(),
+            is_interface: is_interface,
+            prepared: true,
+        }
+    }
 }
 
 impl JVMEmulator {
@@ -28,6 +58,43 @@ impl JVMEmulator {
             capabilities: Capabilities::new(),
             callbacks: EventCallbacks::new(),
             events: HashMap::new(),
+            classes: RefCell::new(vec![]),
+            tags: RefCell::new(HashMap::new()),
+            signatures: Cell::new(0),
+        }
+    }
+
+    /// Load a class, returning its id
+    pub fn load_class(&self, class: EmulatedClass) -> usize {
+        let mut classes = self.classes.borrow_mut();
+        classes.push(Some(class));
+        classes.len()
+    }
+
+    pub fn unload_class(&self, id: usize) {
+        if let Some(slot) = self.classes.borrow_mut().get_mut(id - 1) {
+            *slot = None;
+        }
+
+        self.tags.borrow_mut().remove(&id);
+    }
+
+    /// The id of a loaded class with the given signature
+    pub fn find_class(&self, signature: &str) -> Option<usize> {
+        self.classes.borrow().iter()
+            .position(|class| class.as_ref().map(|class| class.signature == signature).unwrap_or(false))
+            .map(|idx| idx + 1)
+    }
+
+    fn class_id(id: usize) -> ClassId {
+        ClassId { native_id: id as JavaClass }
+    }
+
+    /// Look at a loaded class through its id
+    fn with_class<T, F: FnOnce(&EmulatedClass) -> T>(&self, id: usize, f: F) -> Result<T, NativeError> {
+        match self.classes.borrow().get(id.wrapping_sub(1)) {
+            Some(&Some(ref class)) => Ok(f(class)),
+            _ => Err(NativeError::InvalidClass)
         }
     }
 
@@ -113,15 +180,54 @@ impl JVMTI for JVMEmulator {
     }
 
     fn get_tag(&self, obj: &JavaObject) -> Result<i32, NativeError> {
-        Err(NativeError::NotImplemented)
+        match self.capabilities.can_tag_objects {
+            true => Ok(self.tags.borrow().get(&(*obj as usize)).cloned().unwrap_or(0)),
+            false => Err(NativeError::MustPossessCapability)
+        }
+    }
+
+    fn set_tag(&self, obj: &JavaObject, tag: i32) -> Option<NativeError> {
+        match self.capabilities.can_tag_objects {
+            true => {
+                match tag {
+                    0 => self.tags.borrow_mut().remove(&(*obj as usize)),
+                    _ => self.tags.borrow_mut().insert(*obj as usize, tag)
+                };
+
+                None
+            },
+            false => Some(NativeError::MustPossessCapability)
+        }
     }
 
     fn get_class_signature(&self, class_id: &ClassId) -> Result<ClassSignature, NativeError> {
-        match class_id.native_id as u64 {
-            _ => Err(NativeError::NotImplemented)
+        self.signatures.set(self.signatures.get() + 1);
+
+        let signature = self.with_class(class_id.native_id as usize, |class| class.signature.clone())?;
+
+        ClassSignature::parse(&signature, None).map_err(|_| NativeError::UnexpectedInternalError)
+    }
+
+    fn get_loaded_classes(&self) -> Result<Vec<ClassId>, NativeError> {
+        Ok(self.classes.borrow().iter().enumerate()
+            .filter(|entry| entry.1.is_some())
+            .map(|(idx, _)| JVMEmulator::class_id(idx + 1))
+            .collect())
+    }
+
+    fn get_implemented_interfaces(&self, class_id: &ClassId) -> Result<Vec<ClassId>, NativeError> {
+        let (interfaces, prepared) = self.with_class(class_id.native_id as usize, |class| (class.interfaces.clone(), class.prepared))?;
+
+        match prepared {
+            true => Ok(interfaces.iter().filter_map(|name| self.find_class(name)).map(JVMEmulator::class_id).collect()),
+            false => Err(NativeError::ClassNotPrepared)
         }
     }
 
+    fn is_interface(&self, class_id: &ClassId) -> Result<bool, NativeError> {
+        self.with_class(class_id.native_id as usize, |class| class.is_interface)
+    }
+
     fn allocate(&self, len: usize) -> Result<MemoryAllocation, NativeError> {
         Ok(MemoryAllocation { ptr: ::std::ptr::null_mut(), len: len })
     }
@@ -130,3 +236,22 @@ impl JVMTI for JVMEmulator {
         Some(NativeError::NotImplemented)
     }
 }
+
+impl JNI for JVMEmulator {
+    fn get_object_class(&self, object_id: &JavaObject) -> ClassId {
+        JVMEmulator::class_id(0)
+    }
+
+    fn get_superclass(&self, class_id: &ClassId) -> Option<ClassId> {
+        self.with_class(class_id.native_id as usize, |class| match class.is_interface {
+            // Like JNI, interfaces have no superclass
+            true => None,
+            false => class.super_class.clone()
+        }).ok()
+            .and_then(|super_class| super_class)
+            .and_then(|name| self.find_class(&name))
+            .map(JVMEmulator::class_id)
+    }
+
+    fn delete_local_ref(&self, object_id: &JavaObject) {}
+}
@@ -0,0 +1,149 @@
+use super::jni::JNI;
+use super::jvmti::JVMTI;
+use super::unique_tag;
+use super::super::bytecode::hierarchy::{ClassHierarchy, ClassInfo};
+use super::super::class::ClassId;
+use super::super::error::NativeError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+///
+/// The loaded classes described so far, meant to live as long as the agent does. Hierarchies come
+/// and go with the callbacks that need them, while this keeps each class signed and described only
+/// once.
+///
+pub struct JVMClasses {
+    cache: Mutex<Cache>,
+}
+
+struct Cache {
+    classes: HashMap<String, ClassInfo>,
+    /// The tag of the classes this cache described, which other caches don't know about
+    tag: i32,
+    /// Cleared once the JVM turns out not to support tags, from then on every walk signs every class
+    tagging: bool,
+}
+
+impl JVMClasses {
+    pub fn new() -> JVMClasses {
+        JVMClasses { cache: Mutex::new(Cache { classes: HashMap::new(), tag: unique_tag(), tagging: true }) }
+    }
+}
+
+///
+/// A hierarchy backed by the live JVM, describing the classes it has loaded through
+/// `GetLoadedClasses`, `GetSuperclass` and `GetImplementedInterfaces`. Classes that aren't loaded
+/// yet are unknown.
+///
+/// Described classes are kept in `JVMClasses` and tagged. Asking for a class that isn't known yet
+/// walks the loaded classes again, but only signs the ones that aren't tagged: those loaded since
+/// the last walk and those that couldn't be described then. Tags other parts of the agent put on
+/// a class are left alone, so such classes are signed on every walk, as is every class without the
+/// `can_tag_objects` capability. Local references are released as soon as a class is described,
+/// but they still count against the capacity of the current JNI frame until then, so a hierarchy
+/// is meant to be created in a callback and dropped at its end.
+pub struct JVMHierarchy<'e, E: 'e> {
+    env: &'e E,
+    classes: &'e JVMClasses,
+}
+
+impl<'e, E: JVMTI + JNI> JVMHierarchy<'e, E> {
+    pub fn new(env: &'e E, classes: &'e JVMClasses) -> JVMHierarchy<'e, E> {
+        JVMHierarchy { env: env, classes: classes }
+    }
+
+    /// Describe every loaded class that isn't tagged as described yet
+    fn scan(&self, cache: &mut Cache) {
+        let classes = match self.env.get_loaded_classes() {
+            Ok(classes) => classes,
+            Err(_) => return
+        };
+
+        for class in classes.iter() {
+            let tag = match cache.tagging {
+                true => match self.env.get_tag(&class.native_id) {
+                    Ok(tag) => Some(tag),
+                    Err(NativeError::MustPossessCapability) => { cache.tagging = false; None },
+                    Err(_) => None
+                },
+                false => None
+            };
+
+            if tag == Some(cache.tag) {
+                continue;
+            }
+
+            let described = match self.class_name(class) {
+                Some(ref name) if !cache.classes.contains_key(name) => match self.describe(name, class) {
+                    Some(info) => { cache.classes.insert(name.clone(), info); true },
+                    // Interfaces can't be listed until the class is prepared, try again next time
+                    None => false
+                },
+                _ => true
+            };
+
+            // Classes that fail to be tagged are simply signed again on the next walk
+            if described && tag == Some(0) {
+                if let Some(NativeError::MustPossessCapability) = self.env.set_tag(&class.native_id, cache.tag) {
+                    cache.tagging = false;
+                }
+            }
+        }
+
+        for class in classes {
+            self.env.delete_local_ref(&class.native_id);
+        }
+    }
+
+    fn describe(&self, name: &str, class: &ClassId) -> Option<ClassInfo> {
+        let is_interface = self.env.is_interface(class).ok()?;
+
+        let super_class = match is_interface {
+            // The JVM reports no superclass for interfaces, while their class files name Object
+            true => Some(String::from("java/lang/Object")),
+            false => self.env.get_superclass(class).and_then(|super_class| self.release_name(super_class))
+        };
+
+        let interfaces = self.env.get_implemented_interfaces(class).ok()?
+            .into_iter()
+            .filter_map(|interface| self.release_name(interface))
+            .collect();
+
+        Some(ClassInfo { name: name.to_string(), super_class: super_class, interfaces: interfaces, is_interface: is_interface })
+    }
+
+    /// The internal name of a class, or `None` for array classes
+    fn class_name(&self, class: &ClassId) -> Option<String> {
+        let signature = self.env.get_class_signature(class).ok()?;
+
+        if signature.name.ends_with("[]") {
+            None
+        } else if signature.package.is_empty() {
+            Some(signature.name)
+        } else {
+            Some(format!("{}/{}", signature.package.replace('.', "/"), signature.name))
+        }
+    }
+
+    /// The internal name of a class, deleting the local reference to it
+    fn release_name(&self, class: ClassId) -> Option<String> {
+        let name = self.class_name(&class);
+        self.env.delete_local_ref(&class.native_id);
+        name
+    }
+}
+
+impl<'e, E: JVMTI + JNI> ClassHierarchy for JVMHierarchy<'e, E> {
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        let mut cache = match self.classes.cache.lock() {
+            Ok(cache) => cache,
+            Err(_) => return None
+        };
+
+        if !cache.classes.contains_key(name) {
+            self.scan(&mut cache);
+        }
+
+        cache.classes.get(name).cloned()
+    }
+}
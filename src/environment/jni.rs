@@ -7,6 +7,9 @@ use super::super::class::ClassId;
 pub trait JNI {
     /// Return an `ClassId` belonging to the given Java object instance.
     fn get_object_class(&self, object_id: &JavaObject) -> ClassId;
+    /// Return the superclass of a class, or `None` for `java.lang.Object`, interfaces and
+    /// primitive types.
+    fn get_superclass(&self, class_id: &ClassId) -> Option<ClassId>;
     fn delete_local_ref(&self, object_id: &JavaObject);
 }
 
@@ -32,6 +35,17 @@ impl JNI for JNIEnvironment {
         }
     }
 
+    fn get_superclass(&self, class_id: &ClassId) -> Option<ClassId> {
+        unsafe {
+            let super_class = (**self.jni).GetSuperclass.unwrap()(self.jni, class_id.native_id);
+
+            match super_class.is_null() {
+                true => None,
+                false => Some(ClassId { native_id: super_class })
+            }
+        }
+    }
+
     fn delete_local_ref(&self, object_id: &JavaObject) {
         unsafe {
             (**self.jni).DeleteLocalRef.unwrap()(self.jni, *object_id);
@@ -1,5 +1,5 @@
 use super::super::capabilities::Capabilities;
-use super::super::class::{ClassId, ClassSignature, JavaType};
+use super::super::class::{ClassId, ClassSignature};
 use super::super::event::{EventCallbacks, VMEvent};
 use super::super::event_handler::*;
 use super::super::mem::MemoryAllocation;
@@ -39,7 +39,15 @@ pub trait JVMTI {
     fn get_frame_location(&self, thread_id: &ThreadId, method_id: &MethodId, depth: i32) -> Result<i32, NativeError>;
     fn get_local_object(&self, thread_id: &ThreadId, depth: i32, slot: i32) -> Result<JavaObject, NativeError>;
     fn get_tag(&self, object_id: &JavaObject) -> Result<i32, NativeError>;
+    /// Set the tag of an object, 0 clearing it. Needs the `can_tag_objects` capability.
+    fn set_tag(&self, object_id: &JavaObject, tag: i32) -> Option<NativeError>;
     fn get_class_signature(&self, class_id: &ClassId) -> Result<ClassSignature, NativeError>;
+    /// Return all classes loaded in the virtual machine, including array classes. Each returned
+    /// `ClassId` is a local reference.
+    fn get_loaded_classes(&self) -> Result<Vec<ClassId>, NativeError>;
+    /// Return the direct superinterfaces of a class, or the direct superinterfaces of an interface.
+    fn get_implemented_interfaces(&self, class_id: &ClassId) -> Result<Vec<ClassId>, NativeError>;
+    fn is_interface(&self, class_id: &ClassId) -> Result<bool, NativeError>;
     fn allocate(&self, len: usize) -> Result<MemoryAllocation, NativeError>;
     fn deallocate(&self, mem_ptr: MutByteArray) -> Option<NativeError>;
 }
@@ -52,6 +60,17 @@ impl JVMTIEnvironment {
     pub fn new(env_ptr: JVMTIEnvPtr) -> JVMTIEnvironment {
         JVMTIEnvironment { jvmti: env_ptr }
     }
+
+    /// Copy an array of classes allocated by the JVM and release the array itself
+    unsafe fn take_classes(&self, classes: *mut JavaClass, count: i32) -> Vec<ClassId> {
+        if classes.is_null() {
+            return vec![];
+        }
+
+        let ids = (0..count as isize).map(|idx| ClassId { native_id: *classes.offset(idx) }).collect();
+        self.deallocate(classes as MutByteArray);
+        ids
+    }
 }
 
 impl JVMTI for JVMTIEnvironment {
@@ -186,7 +205,7 @@ impl JVMTI for JVMTIEnvironment {
 
         unsafe {
             match (**self.jvmti).GetMethodName.unwrap()(self.jvmti, method_id.native_id, method_ptr, signature_ptr, generic_sig_ptr).translate() {
-                NativeError::NoError => Ok(MethodSignature::new(stringify(*method_ptr))),
+                NativeError::NoError => Ok(MethodSignature::with_descriptor(stringify(*method_ptr), &stringify(*signature_ptr))),
                 err @ _ => Err(err)
             }
         }
@@ -257,6 +276,15 @@ impl JVMTI for JVMTIEnvironment {
         }
     }
 
+    fn set_tag(&self, object_id: &JavaObject, tag: i32) -> Option<NativeError> {
+        unsafe {
+            match (**self.jvmti).SetTag.unwrap()(self.jvmti, *object_id, tag as i64).translate() {
+                NativeError::NoError => None,
+                err @ _ => Some(err)
+            }
+        }
+    }
+
     fn get_class_signature(&self, class_id: &ClassId) -> Result<ClassSignature, NativeError> {
         unsafe {
             let mut native_sig: MutString = ptr::null_mut();
@@ -265,7 +293,58 @@ impl JVMTI for JVMTIEnvironment {
             let p2: *mut MutString = &mut native_sig;
 
             match (**self.jvmti).GetClassSignature.unwrap()(self.jvmti, class_id.native_id, p1, p2).translate() {
-                NativeError::NoError => Ok(ClassSignature::new(&JavaType::parse(&stringify(sig)).unwrap())),
+                NativeError::NoError => {
+                    // The generic signature is only reported for generic classes
+                    let generic = match native_sig.is_null() {
+                        true => None,
+                        false => Some(stringify(native_sig))
+                    };
+                    let signature = stringify(sig);
+
+                    // Both strings were allocated by JVMTI and are ours to free
+                    self.deallocate(sig as MutByteArray);
+
+                    if !native_sig.is_null() {
+                        self.deallocate(native_sig as MutByteArray);
+                    }
+
+                    ClassSignature::parse(&signature, generic.as_ref().map(|generic| generic.as_str())).map_err(|_| NativeError::UnexpectedInternalError)
+                },
+                err @ _ => Err(err)
+            }
+        }
+    }
+
+    fn get_loaded_classes(&self) -> Result<Vec<ClassId>, NativeError> {
+        let mut count = 0;
+        let mut classes: *mut JavaClass = ptr::null_mut();
+
+        unsafe {
+            match (**self.jvmti).GetLoadedClasses.unwrap()(self.jvmti, &mut count, &mut classes).translate() {
+                NativeError::NoError => Ok(self.take_classes(classes, count)),
+                err @ _ => Err(err)
+            }
+        }
+    }
+
+    fn get_implemented_interfaces(&self, class_id: &ClassId) -> Result<Vec<ClassId>, NativeError> {
+        let mut count = 0;
+        let mut interfaces: *mut JavaClass = ptr::null_mut();
+
+        unsafe {
+            match (**self.jvmti).GetImplementedInterfaces.unwrap()(self.jvmti, class_id.native_id, &mut count, &mut interfaces).translate() {
+                NativeError::NoError => Ok(self.take_classes(interfaces, count)),
+                err @ _ => Err(err)
+            }
+        }
+    }
+
+    fn is_interface(&self, class_id: &ClassId) -> Result<bool, NativeError> {
+        let mut is_interface = 0;
+
+        unsafe {
+            match (**self.jvmti).IsInterface.unwrap()(self.jvmti, class_id.native_id, &mut is_interface).translate() {
+                NativeError::NoError => Ok(is_interface != 0),
                 err @ _ => Err(err)
             }
         }
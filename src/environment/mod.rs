@@ -10,13 +10,24 @@ use super::native::{JavaObject, JavaThread};
 use super::thread::Thread;
 use super::version::VersionNumber;
 use native::MutByteArray;
+use std::sync::atomic::{ AtomicUsize, Ordering };
 use table::LocalVariableTable;
 use thread::ThreadId;
 
+pub mod hierarchy;
 pub mod jni;
 pub mod jvm;
 pub mod jvmti;
 
+/// Hands out the object tags of the agent
+static TAGS: AtomicUsize = AtomicUsize::new(1);
+
+/// A tag no other part of the agent puts on objects, so that objects tagged with it can be told
+/// apart, also when they're freed
+pub fn unique_tag() -> i32 {
+    TAGS.fetch_add(1, Ordering::Relaxed) as i32
+}
+
 /// `Environment` combines the functionality of both `JNI` and `JVMTI` by wrapping an instance of
 /// both and delegating the method calls to their corresponding recipients.
 pub struct Environment {
@@ -83,10 +94,26 @@ impl JVMTI for Environment {
         self.jvmti.get_tag(object_id)
     }
 
+    fn set_tag(&self, object_id: &JavaObject, tag: i32) -> Option<NativeError> {
+        self.jvmti.set_tag(object_id, tag)
+    }
+
     fn get_class_signature(&self, class_id: &ClassId) -> Result<ClassSignature, NativeError> {
         self.jvmti.get_class_signature(class_id)
     }
 
+    fn get_loaded_classes(&self) -> Result<Vec<ClassId>, NativeError> {
+        self.jvmti.get_loaded_classes()
+    }
+
+    fn get_implemented_interfaces(&self, class_id: &ClassId) -> Result<Vec<ClassId>, NativeError> {
+        self.jvmti.get_implemented_interfaces(class_id)
+    }
+
+    fn is_interface(&self, class_id: &ClassId) -> Result<bool, NativeError> {
+        self.jvmti.is_interface(class_id)
+    }
+
     fn allocate(&self, len: usize) -> Result<MemoryAllocation, NativeError> {
         self.jvmti.allocate(len)
     }
@@ -101,6 +128,10 @@ impl JNI for Environment {
         self.jni.get_object_class(object_id)
     }
 
+    fn get_superclass(&self, class_id: &ClassId) -> Option<ClassId> {
+        self.jni.get_superclass(class_id)
+    }
+
     fn delete_local_ref(&self, object_id: &JavaObject) {
         self.jni.delete_local_ref(object_id)
     }
@@ -8,7 +8,7 @@ pub type FnVMInit = fn() -> ();
 pub type FnVMDeath = fn() -> ();
 pub type FnVMStart = fn() -> ();
 pub type FnVMObjectAlloc = fn(event: ObjectAllocationEvent) -> ();
-pub type FnVMObjectFree = fn() -> ();
+pub type FnVMObjectFree = fn(event: ObjectFreeEvent) -> ();
 pub type FnThreadStart = fn(thread: Thread) -> ();
 pub type FnThreadEnd = fn(thread: Thread) -> ();
 pub type FnException = fn() -> ();
@@ -1,4 +1,4 @@
-use super::environment::Environment;
+use super::environment::{Environment, unique_tag};
 use super::environment::jni::{JNI, JNIEnvironment};
 use super::environment::jvmti::{JVMTI, JVMTIEnvironment};
 use super::error::NativeError;
@@ -10,9 +10,9 @@ use super::runtime::*;
 use libc::{c_char, c_uchar};
 use std::mem::size_of;
 use std::ptr;
+use std::slice;
 use super::util::stringify;
 use super::bytecode::*;
-use std::io::Cursor;
 use std::ffi::c_void;
 use error::NativeErrorTranslator;
 
@@ -432,6 +432,26 @@ unsafe extern "C" fn local_cb_garbage_collection_finish(jvmti_env: *mut jvmtiEnv
 #[allow(unused_variables)]
 unsafe extern "C" fn local_cb_breakpoint(jvmti_env: *mut jvmtiEnv, jni_env: *mut JNIEnv, thread: jthread, method: jmethodID, location: jlocation) -> () {}
 
+/// The tag of a class loader, tagging it first if nobody did yet
+fn loader_tag(env: &Environment, loader: JavaObject) -> i32 {
+    if loader.is_null() {
+        return 0;
+    }
+
+    match env.get_tag(&loader) {
+        Ok(0) => {
+            let tag = unique_tag();
+
+            match env.set_tag(&loader, tag) {
+                None => tag,
+                Some(_) => 0
+            }
+        },
+        Ok(tag) => tag,
+        Err(_) => 0
+    }
+}
+
 #[allow(unused_variables)]
 unsafe extern "C" fn local_cb_class_file_load_hook(jvmti_env: JVMTIEnvPtr, jni_env: JNIEnvPtr, class_being_redefined: JavaClass, loader: JavaObject,
                                                    name: *const c_char, protection_domain: JavaObject, class_data_len: jint, class_data: *const c_uchar,
@@ -440,14 +460,17 @@ unsafe extern "C" fn local_cb_class_file_load_hook(jvmti_env: JVMTIEnvPtr, jni_e
         Some(function) => {
             let env = Environment::new(JVMTIEnvironment::new(jvmti_env), JNIEnvironment::new(jni_env));
 
-            let mut raw_data: Vec<u8> = Vec::with_capacity(class_data_len as usize);
-            let data_ptr = raw_data.as_mut_ptr();
+            if class_data.is_null() || class_data_len < 0 {
+                println!("Invalid class data received for class {}", stringify(name));
+                return;
+            }
 
-            ptr::copy_nonoverlapping(class_data, data_ptr, class_data_len as usize);
-            raw_data.set_len(class_data_len as usize);
+            // the buffer stays valid for the duration of the hook, so the class can borrow from it.
+            // Classes the callback doesn't transform are left to the JVM as they are
+            let raw_data: &[u8] = slice::from_raw_parts(class_data, class_data_len as usize);
 
-            if let Ok(classfile) = parse_class(&raw_data) {
-                match function(ClassFileLoadEvent { class_name: stringify(name), class: classfile }) {
+            match parse_class(raw_data) {
+                Ok(classfile) => match function(ClassFileLoadEvent { class_name: stringify(name), class: classfile, loader: loader_tag(&env, loader) }) {
                     Some(transformed) => {
                         println!("Transformed class {}", stringify(name));
 
@@ -463,9 +486,8 @@ unsafe extern "C" fn local_cb_class_file_load_hook(jvmti_env: JVMTIEnvPtr, jni_e
                         }
                     }
                     None => ()
-                }
-            } else {
-                println!("Coult not parse class file");
+                },
+                Err(err) => println!("Could not parse class file {}: {}", stringify(name), err)
             }
 
 
@@ -475,11 +497,9 @@ unsafe extern "C" fn local_cb_class_file_load_hook(jvmti_env: JVMTIEnvPtr, jni_e
     }
 }
 
-fn parse_class(data: &Vec<u8>) -> Result<Classfile, ::std::io::Error> {
-    let mut cursor = Cursor::new(data);
-
+fn parse_class<'a>(data: &'a [u8]) -> Result<LazyClassfile<'a>, ClassReadError> {
     //let class_result = ClassReader::read_class(&mut cursor);
-    ClassReader::read_class(&mut cursor)
+    ClassReader::read_lazy(data)
 
     /*
         match class_result {
@@ -570,7 +590,7 @@ unsafe extern "C" fn local_cb_native_method_bind(jvmti_env: *mut jvmtiEnv, jni_e
 unsafe extern "C" fn local_cb_object_free(jvmti_env: *mut jvmtiEnv, tag: jlong) -> () {
     match CALLBACK_TABLE.vm_object_free {
         Some(function) => {
-            function();
+            function(ObjectFreeEvent { tag: tag as i32 });
         }
         None => println!("No dynamic callback method was found for object free events")
     }
@@ -29,7 +29,7 @@ impl Class {
         self.version = new_version;
     }
 
-    pub fn to_classfile(&self) -> ClassfileImpl {
+    pub fn to_classfile(&self) -> ClassfileImpl<'static> {
         let mut cf = ClassfileImpl::new();
 
         cf.version.major_version = match &self.version {
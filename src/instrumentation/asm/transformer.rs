@@ -1,17 +1,27 @@
 use super::super::super::bytecode::*;
 
-pub struct Transformer<'a> {
-    class: &'a mut Classfile
+pub struct Transformer<'a, 'c: 'a> {
+    class: &'a mut Classfile<'c>,
+    constants: usize
 }
 
-impl<'a> Transformer<'a> {
-    pub fn new(class: &mut Classfile) -> Transformer {
+impl<'a, 'c> Transformer<'a, 'c> {
+    pub fn new(class: &'a mut Classfile<'c>) -> Transformer<'a, 'c> {
+        let constants = class.constant_pool.cp_len();
+
         Transformer {
-            class: class
+            class: class,
+            constants: constants
         }
     }
 
-    pub fn ensure_constant(&mut self, constant: Constant) -> ConstantPoolIndex {
-        self.class.constant_pool.get_constant_index(&constant).unwrap_or(self.class.constant_pool.add_constant(constant))
+    /// The constant pool of the transformed class, whose helpers add the constants new code refers to
+    pub fn constant_pool(&mut self) -> &mut ConstantPool<'c> {
+        &mut self.class.constant_pool
+    }
+
+    /// Whether the class was changed. Constants are only ever added, so a grown constant pool is a change
+    pub fn is_modified(&self) -> bool {
+        self.class.constant_pool.cp_len() != self.constants
     }
 }
@@ -27,7 +27,7 @@ impl JavaClass {
         }
     }
 
-    pub fn to_classfile(&self) -> Classfile {
+    pub fn to_classfile(&self) -> Classfile<'static> {
         Classfile::new()
     }
 
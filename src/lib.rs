@@ -3,13 +3,15 @@ extern crate libc;
 extern crate lazy_static;
 extern crate time;
 extern crate toml;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+extern crate zip;
 
 use agent::Agent;
-use bytecode::printer::ClassfilePrinter;
-use bytecode::classfile::Constant;
 use bytecode::io::ClassWriter;
+use bytecode::verify::verify;
 use config::Config;
 use context::static_context;
 use instrumentation::asm::transformer::Transformer;
@@ -107,48 +109,46 @@ fn on_monitor_contended_entered(thread: Thread) {
     }
 }
 
-fn on_class_file_load(mut event: ClassFileLoadEvent) -> Option<Vec<u8>> {
+fn on_class_file_load(event: ClassFileLoadEvent) -> Option<Vec<u8>> {
+    static_context().loaded_classes.add_class(event.loader, &event.class);
+
     let shall_transform = match static_context().config.read() {
         Ok(cfg) => (*cfg).entry_points.iter().any(|item| item.starts_with(event.class_name.as_str())), //event.class_name.as_str() == item),
         _ => false
     };
 
     if shall_transform {
-        {
-            let mut transformer = Transformer::new(&mut event.class);
-            let result = transformer.ensure_constant(Constant::Utf8(String::from("Cde").into_bytes()));
-
-            println!("Result: {:?}", result);
+        match event.class.decode() {
+            Ok(mut class) => {
+                let modified = {
+                    let mut transformer = Transformer::new(&mut class);
+
+                    match transformer.constant_pool().utf8("Cde") {
+                        Ok(_) => transformer.is_modified(),
+                        Err(err) => { println!("Could not transform class {}: {}", event.class_name, err); false }
+                    }
+                };
+
+                // Classes the transformer left alone keep the bytes they were loaded with
+                if modified {
+                    match verify(&class, &static_context().loaded_classes) {
+                        Ok(_) => {
+                            let mut write_cursor = Cursor::new(vec![]);
+                            let result = ClassWriter::new(&mut write_cursor).write_class(&class);
+
+                            match result {
+                                Ok(_) => return Some(write_cursor.into_inner()),
+                                Err(err) => println!("Could not write class {}: {}", event.class_name, err)
+                            }
+                        },
+                        Err(err) => println!("Transformed class {} failed verification, keeping the original: {}", event.class_name, err)
+                    }
+                }
+            },
+            Err(err) => println!("Could not decode class {}: {}", event.class_name, err)
         }
-        let _: Vec<()> = ClassfilePrinter::render_lines(&event.class).iter().map(|line| println!("{}", line)).collect();
     }
-    /*
-        let output_class: Vec<u8> = vec![];
-        let mut write_cursor = Cursor::new(output_class);
-
-        let mut new_class = event.class;
-
-        new_class.constant_pool.constants = new_class.constant_pool.constants.into_iter().map(|constant| {
-            match constant {
-                Constant::Utf8(bytes) => String::from_utf8(bytes.clone()).map(|string| match string.as_str() {
-                    "Hello World" => Constant::Utf8(String::from("Lofasz").into_bytes()),
-                    _ => Constant::Utf8(string.into_bytes())
-                }).unwrap_or(Constant::Utf8(bytes)),
-                other @ _ => other
-            }
-        }).collect();
-
-        let result = {
-            let mut writer = ClassWriter::new(&mut write_cursor);
-            writer.write_class(&new_class)
-        };
-
-        if let Ok(_) = result {
-            Some(write_cursor.into_inner())
-        } else {
-            None
-        }
-        */
+
     None
 }
 
@@ -164,8 +164,9 @@ fn on_object_alloc(event: ObjectAllocationEvent) {
     println!("Object allocation: (size: {})", event.size);
 }
 
-fn on_object_free() {
-    println!("Object free");
+fn on_object_free(event: ObjectFreeEvent) {
+    // Loaders are tagged as they define classes, which go away along with them
+    static_context().loaded_classes.unload(event.tag);
 }
 
 ///
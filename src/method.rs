@@ -1,3 +1,4 @@
+use super::bytecode::descriptor::MethodDescriptor;
 use super::native::JavaMethod;
 
 pub struct MethodId {
@@ -9,15 +10,22 @@ pub struct Method {
 }
 
 pub struct MethodSignature {
-    pub name: String
+    pub name: String,
+    /// The parameter and return types of the method, if its descriptor is known and valid
+    pub descriptor: Option<MethodDescriptor>,
 }
 
 impl MethodSignature {
     pub fn new(raw_signature: String) -> MethodSignature {
-        MethodSignature { name: raw_signature }
+        MethodSignature { name: raw_signature, descriptor: None }
+    }
+
+    /// Constructs a signature of a method with the given name and JVM descriptor, eg. `(I)V`
+    pub fn with_descriptor(name: String, descriptor: &str) -> MethodSignature {
+        MethodSignature { name: name, descriptor: MethodDescriptor::parse(descriptor).ok() }
     }
 
     pub fn unknown() -> MethodSignature {
-        MethodSignature { name: "<UNKNOWN METHOD>".to_string() }
+        MethodSignature { name: "<UNKNOWN METHOD>".to_string(), descriptor: None }
     }
 }
@@ -1,4 +1,4 @@
-use super::bytecode::Classfile;
+use super::bytecode::LazyClassfile;
 use super::class::{ClassId, ClassSignature};
 use super::method::{MethodId, MethodSignature};
 use super::thread::Thread;
@@ -11,7 +11,10 @@ pub struct ObjectAllocationEvent {
     pub size: i64,
 }
 
-pub struct ObjectFreeEvent {}
+pub struct ObjectFreeEvent {
+    /// The tag of the freed object, only tagged objects are reported
+    pub tag: i32,
+}
 
 pub struct MethodInvocationEvent {
     pub method_id: MethodId,
@@ -24,9 +27,16 @@ impl RuntimeEvent for ObjectAllocationEvent {}
 
 impl RuntimeEvent for MethodInvocationEvent {}
 
-pub struct ClassFileLoadEvent {
+/// A class about to be loaded by the JVM. The class borrows from the buffer handed over by the JVM,
+/// so it can only be inspected during the callback unless it's turned into an owned class first.
+/// Only the class header and member tables are decoded up front, call `decode` on the class to
+/// get at its attributes and code
+pub struct ClassFileLoadEvent<'a> {
     pub class_name: String,
-    pub class: Classfile,
+    pub class: LazyClassfile<'a>,
+    /// The tag of the loader defining the class, which is reported again when the loader is freed.
+    /// 0 for the bootstrap loader, and for any loader when objects can't be tagged
+    pub loader: i32,
 }
 
-impl RuntimeEvent for ClassFileLoadEvent {}
+impl<'a> RuntimeEvent for ClassFileLoadEvent<'a> {}
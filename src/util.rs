@@ -1,18 +1,17 @@
+use super::bytecode::mutf8;
 use super::native::RawString;
 use std::ffi::CStr;
 use std::ptr;
 
 ///
 /// Turns a C-style string pointer into a String instance. If the string pointer points to NULL,
-/// then a "(NULL)" string will be returned.
+/// then a "(NULL)" string will be returned. Strings coming from the JVM are encoded in modified
+/// UTF-8, anything that can't be decoded is replaced with U+FFFD.
 ///
 pub fn stringify(input: RawString) -> String {
     unsafe {
         if input != ptr::null_mut() {
-            match CStr::from_ptr(input).to_str() {
-                Ok(string) => string.to_string(),
-                Err(_) => "(UTF8-ERROR)".to_string()
-            }
+            mutf8::decode_lossy(CStr::from_ptr(input).to_bytes()).into_owned()
         } else {
             "(NULL)".to_string()
         }
@@ -4,8 +4,10 @@ extern crate jvmti;
 mod tests {
 
     use jvmti::bytecode::*;
+    use std::borrow::Cow;
+    use std::convert::TryFrom;
     use std::fs::File;
-    use std::io::{ Cursor, Read, Write, Error };
+    use std::io::{ Cursor, Read, Write };
 
     #[test]
     fn test_read_simple() {
@@ -41,9 +43,8 @@ mod tests {
 
     #[test]
     fn test_read_n() {
-        let mut target: Vec<u8> = vec![ 1, 2, 3, 4, 5, 6, 7, 8, 9 ];
-        let mut cursor = Cursor::new(&mut target);
-        let mut reader = BlockReader::new(&mut cursor);
+        let target: Vec<u8> = vec![ 1, 2, 3, 4, 5, 6, 7, 8, 9 ];
+        let mut reader = BlockReader::new(&target);
 
         let r1 = reader.read_n(4);
         assert_eq!(vec![1, 2, 3, 4], r1.ok().unwrap());
@@ -65,23 +66,23 @@ mod tests {
                 Constant::Integer(14),
                 Constant::Long(5),
                 Constant::Placeholder,
-                Constant::Utf8("AAAAAA".to_string().into_bytes()),
-                Constant::Class(ConstantPoolIndex::new(3))
+                Constant::Utf8("AAAAAA".to_string().into_bytes().into()),
+                Constant::Class(ConstantPoolIndex::new(4))
             ]),
             access_flags: AccessFlags::of(0x000F),
-            this_class: ConstantPoolIndex::new(1),
-            super_class: ConstantPoolIndex::new(2),
+            this_class: ConstantPoolIndex::new(5),
+            super_class: ConstantPoolIndex::new(0),
             interfaces: vec![
-                ConstantPoolIndex::new(7),
-                ConstantPoolIndex::new(8)
+                ConstantPoolIndex::new(5),
+                ConstantPoolIndex::new(5)
             ],
             fields: vec![
-                Field { access_flags: AccessFlags::of(0x0011), name_index: ConstantPoolIndex::new(70), descriptor_index: ConstantPoolIndex::new(71), attributes: vec![] }
+                Field { access_flags: AccessFlags::of(0x0011), name_index: ConstantPoolIndex::new(4), descriptor_index: ConstantPoolIndex::new(4), attributes: vec![] }
             ],
             methods: vec![],
             attributes: vec![
-                Attribute::RawAttribute { name_index: ConstantPoolIndex::new(4), info: vec![ 1, 2, 3, 4 ] },
-                Attribute::RawAttribute { name_index: ConstantPoolIndex::new(4), info: vec![ 11, 12, 13, 14, 15 ] }
+                Attribute::RawAttribute { name_index: ConstantPoolIndex::new(4), info: vec![ 1, 2, 3, 4 ].into() },
+                Attribute::RawAttribute { name_index: ConstantPoolIndex::new(4), info: vec![ 11, 12, 13, 14, 15 ].into() }
             ]
         };
 
@@ -107,7 +108,7 @@ mod tests {
         }
 //        assert!(false, format!("{:?}", target));
         {
-            let read_result: Result<Classfile, Error> = ClassReader::read_class(&mut Cursor::new(&mut target));
+            let read_result: Result<Classfile, ClassReadError> = ClassReader::read_class(&mut Cursor::new(&mut target));
 
             assert!(read_result.is_ok(), format!("{:?}", read_result.err()));
 
@@ -129,6 +130,1773 @@ mod tests {
         assert!(true, format!("{:?}", target));
     }
 
+    fn read_test_data(name: &str) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        match File::open(format!("test-data/{}", name)) {
+            Ok(mut file) => { let _ = file.read_to_end(&mut bytes); },
+            Err(err) => assert!(false, format!("{:?}", err))
+        }
+
+        bytes
+    }
+
+    fn write_to_vec(class: &Classfile) -> Vec<u8> {
+        let mut target: Vec<u8> = vec![];
+        {
+            let mut writer = ClassWriter::new(&mut target);
+            let result = writer.write_class(class);
+
+            assert!(result.is_ok(), format!("{:?}", result.err()));
+        }
+        target
+    }
+
+    #[test]
+    fn test_read_array_borrows_from_input() {
+        let bytes = read_test_data("Simple.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+        assert!(class.constant_pool.constants.iter().all(|c| match c { &Constant::Utf8(Cow::Owned(_)) => false, _ => true }));
+        assert_eq!(ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap(), class);
+        assert_eq!(bytes, write_to_vec(&class));
+    }
+
+    #[test]
+    fn test_raw_attribute_borrows_from_input() {
+        let bytes = class_with_method_attribute("Custom", vec![ 1, 2, 3 ]);
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+        match class.methods[0].attributes[0] {
+            Attribute::RawAttribute { info: Cow::Borrowed(info), .. } => assert_eq!(&bytes[bytes.len() - 5..bytes.len() - 2], info),
+            ref other @ _ => assert!(false, format!("{:?}", other))
+        }
+
+        match class.into_owned().methods[0].attributes[0] {
+            Attribute::RawAttribute { info: Cow::Owned(ref info), .. } => assert_eq!(&vec![ 1, 2, 3 ], info),
+            ref other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_lazy_class_decodes_to_full_class() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Shape$Square.class", "module-info.class" ] {
+            let bytes = read_test_data(name);
+            let lazy = ClassReader::read_lazy(&bytes).ok().unwrap();
+            let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+            assert_eq!(class.fields.len(), lazy.fields.len());
+            assert_eq!(class.methods.iter().map(|m| m.name_index.idx).collect::<Vec<usize>>(), lazy.methods.iter().map(|m| m.name_index.idx).collect::<Vec<usize>>());
+            for (method, lazy_method) in class.methods.iter().zip(lazy.methods.iter()) {
+                assert_eq!(method.attributes, lazy_method.attributes.decode(&lazy.constant_pool).ok().unwrap());
+            }
+
+            assert!(class == lazy.decode().ok().unwrap(), format!("{} decoded differently", name));
+        }
+    }
+
+    #[test]
+    fn test_lazy_class_resolves_its_name() {
+        let bytes = read_test_data("Shape$Circle.class");
+        let lazy = ClassReader::read_lazy(&bytes).ok().unwrap();
+
+        assert_eq!(Some(String::from("Shape$Circle")), lazy.class_name());
+        assert_eq!(&bytes[..], lazy.bytes);
+    }
+
+    #[test]
+    fn test_lazy_class_defers_attribute_errors() {
+        // max_stack 1, max_locals 1, one byte of code holding the undefined opcode 0xFF
+        let bytes = class_with_method_attribute("Code", vec![ 0, 1, 0, 1, 0, 0, 0, 1, 0xFF, 0, 0, 0, 0 ]);
+
+        assert!(ClassReader::read_array(&bytes).is_err());
+
+        let lazy = ClassReader::read_lazy(&bytes).ok().unwrap();
+
+        match lazy.methods[0].attributes.decode(&lazy.constant_pool) {
+            Err(ClassReadError { kind: ClassReadErrorKind::BadTag(0xFF), ref section, .. }) => assert_eq!(&vec![ ClassSection::Method(0), ClassSection::Attribute("Code".to_string()) ], section),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_lazy_read_checks_attribute_bounds() {
+        let bytes = read_test_data("Simple.class");
+
+        match ClassReader::read_lazy(&bytes[..bytes.len() - 1]) {
+            Err(ClassReadError { kind: ClassReadErrorKind::Truncated, .. }) => (),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_read_module_info_constants() {
+        let bytes = read_test_data("module-info.class");
+        let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        assert!(!class.constant_pool.constants.iter().any(|c| match c { &Constant::Unknown(_) => true, _ => false }));
+
+        match class.constant_pool.resolve_index(&ConstantPoolIndex::new(6)) {
+            Some(&Constant::Module(ref idx)) => assert_eq!(Some(String::from("jvmti.test")), class.constant_pool.get_utf8_string(idx.idx as u16)),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+
+        match class.constant_pool.resolve_index(&ConstantPoolIndex::new(13)) {
+            Some(&Constant::Package(ref idx)) => assert_eq!(Some(String::from("jvmti/test/api")), class.constant_pool.get_utf8_string(idx.idx as u16)),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_module_info_roundtrip() {
+        let bytes = read_test_data("module-info.class");
+        let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        assert_eq!(bytes, write_to_vec(&class));
+    }
+
+    #[test]
+    fn test_modern_classes_roundtrip() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Switch.class", "Varying.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+            assert_eq!(61, class.version.major_version);
+            assert!(bytes == write_to_vec(&class), format!("{} was not written back identically", name));
+        }
+    }
+
+    #[test]
+    fn test_read_module_attributes() {
+        let bytes = read_test_data("packaged/module-info.class");
+        let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        assert!(!class.attributes.iter().any(|a| match a { &Attribute::RawAttribute { .. } => true, _ => false }));
+
+        for attribute in &class.attributes {
+            match attribute {
+                &Attribute::Module { ref requires, ref exports, ref opens, ref uses, ref provides, .. } => {
+                    assert_eq!(2, requires.len());
+                    assert_eq!(1, exports.len());
+                    assert_eq!(1, opens.len());
+                    assert_eq!(1, uses.len());
+                    assert_eq!(1, provides.len());
+                    assert_eq!(1, provides[0].provides_with.len());
+                    assert!(requires[0].requires_flags.has_flag(RequiresAccessFlags::Mandated as u16));
+                },
+                &Attribute::ModulePackages(ref packages) => assert_eq!(2, packages.len()),
+                &Attribute::ModuleMainClass(ref idx) => assert!(idx.idx > 0),
+                _ => ()
+            }
+        }
+
+        assert_eq!(bytes, write_to_vec(&class));
+    }
+
+    #[test]
+    fn test_module_attribute_can_be_extended() {
+        let bytes = read_test_data("packaged/module-info.class");
+        let mut class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        for attribute in class.attributes.iter_mut() {
+            match attribute {
+                &mut Attribute::Module { ref mut opens, .. } => opens.push(ModuleOpens {
+                    opens_index: ConstantPoolIndex::new(9),
+                    opens_flags: AccessFlags::new(),
+                    opens_to: vec![ ConstantPoolIndex::new(5) ],
+                }),
+                _ => ()
+            }
+        }
+
+        let written = write_to_vec(&class);
+        assert_eq!(bytes.len() + 8, written.len());
+
+        let read_class = ClassReader::read_class(&mut Cursor::new(&written)).ok().unwrap();
+        assert_eq!(class, read_class);
+
+        let lines = printer::ClassfilePrinter::render_lines(&read_class);
+        assert!(lines.iter().any(|line| line.trim() == "opens jvmti/test/api flags: 0x0000 to jvmti.test"), format!("{:#?}", lines));
+        assert!(lines.iter().any(|line| line.trim() == "ModuleMainClass: jvmti/test/internal/Main"), format!("{:#?}", lines));
+    }
+
+    #[test]
+    fn test_read_nest_and_record_attributes() {
+        let host = ClassReader::read_class(&mut Cursor::new(&read_test_data("Shape.class"))).ok().unwrap();
+
+        assert!(host.attributes.iter().any(|a| match a { &Attribute::NestMembers(ref members) => members.len() == 2, _ => false }));
+        assert!(host.attributes.iter().any(|a| match a { &Attribute::PermittedSubclasses(ref subclasses) => subclasses.len() == 2, _ => false }));
+
+        let member = ClassReader::read_class(&mut Cursor::new(&read_test_data("Shape$Square.class"))).ok().unwrap();
+
+        assert!(member.attributes.iter().any(|a| match a { &Attribute::NestHost(ref idx) => idx.idx > 0, _ => false }));
+
+        match member.attributes.iter().find(|a| match a { &&Attribute::Record(_) => true, _ => false }) {
+            Some(&Attribute::Record(ref components)) => {
+                assert_eq!(2, components.len());
+                assert_eq!(Some(String::from("side")), member.constant_pool.get_utf8_string(components[0].name_index.idx as u16));
+                assert_eq!(Some(String::from("D")), member.constant_pool.get_utf8_string(components[0].descriptor_index.idx as u16));
+                assert!(components[0].attributes.is_empty());
+                assert!(components[1].attributes.iter().any(|a| match a { &Attribute::Signature(_) => true, _ => false }), format!("{:?}", components[1].attributes));
+            },
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+
+        for name in &[ "Shape.class", "Shape$Circle.class", "Shape$Square.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+            assert!(!class.attributes.iter().any(|a| match a { &Attribute::RawAttribute { .. } => true, _ => false }), format!("{}: {:?}", name, class.attributes));
+            assert!(bytes == write_to_vec(&class), format!("{} was not written back identically", name));
+        }
+    }
+
+    #[test]
+    fn test_record_component_errors_are_not_written() {
+        let bytes = read_test_data("Shape$Square.class");
+        let mut member = ClassReader::read_array(&bytes).ok().unwrap();
+
+        for attribute in member.attributes.iter_mut() {
+            if let &mut Attribute::Record(ref mut components) = attribute {
+                components[1].attributes = vec![ Attribute::Signature(ConstantPoolIndex::new(0x10000)) ];
+            }
+        }
+
+        let mut target: Vec<u8> = vec![];
+        assert!(ClassWriter::new(&mut target).write_class(&member).is_err());
+    }
+
+    #[test]
+    fn test_nest_members_can_be_extended() {
+        let bytes = read_test_data("Shape.class");
+        let mut class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        let name_index = ConstantPoolIndex::new(class.constant_pool.constants.len());
+        class.constant_pool.constants.push(Constant::Utf8("Shape$Triangle".to_string().into_bytes().into()));
+        let class_index = ConstantPoolIndex::new(class.constant_pool.constants.len());
+        class.constant_pool.constants.push(Constant::Class(name_index));
+
+        for attribute in class.attributes.iter_mut() {
+            match attribute {
+                &mut Attribute::NestMembers(ref mut members) => members.push(ConstantPoolIndex::new(class_index.idx)),
+                _ => ()
+            }
+        }
+
+        let written = write_to_vec(&class);
+        let read_class = ClassReader::read_class(&mut Cursor::new(&written)).ok().unwrap();
+        assert_eq!(class, read_class);
+
+        let lines = printer::ClassfilePrinter::render_lines(&read_class);
+        assert!(lines.iter().any(|line| line.trim() == "Shape$Triangle"), format!("{:#?}", lines));
+        assert!(lines.iter().any(|line| line.trim() == "PermittedSubclasses"), format!("{:#?}", lines));
+    }
+
+    #[test]
+    fn test_dynamic_constant_roundtrip() {
+        let mut class = Classfile::new();
+
+        class.version = ClassfileVersion::new(55, 0);
+        class.constant_pool = ConstantPool::new(vec![
+            Constant::Placeholder,
+            Constant::Utf8("value".to_string().into_bytes().into()),
+            Constant::Utf8("I".to_string().into_bytes().into()),
+            Constant::NameAndType { name_index: ConstantPoolIndex::new(1), descriptor_index: ConstantPoolIndex::new(2) },
+            Constant::Dynamic { bootstrap_method_attr_index: ConstantPoolIndex::new(0), name_and_type_index: ConstantPoolIndex::new(3) },
+            Constant::Long(42),
+            Constant::Placeholder,
+            Constant::Module(ConstantPoolIndex::new(1)),
+            Constant::Package(ConstantPoolIndex::new(2)),
+            Constant::Class(ConstantPoolIndex::new(1))
+        ]);
+        class.this_class = ConstantPoolIndex::new(9);
+
+        let bytes = write_to_vec(&class);
+        let read_class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+
+        assert_eq!(class.constant_pool, read_class.constant_pool);
+        assert_eq!(bytes, write_to_vec(&read_class));
+    }
+
+    #[test]
+    fn test_javac_classes_roundtrip() {
+        for name in &[ "Condy.class", "module-info.class", "packaged/module-info.class", "Shape.class", "Shape$Square.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_class(&mut Cursor::new(&bytes)).ok().unwrap();
+            let written = write_to_vec(&class);
+            let read_class = ClassReader::read_class(&mut Cursor::new(&written)).ok().unwrap();
+
+            assert!(class == read_class, format!("{} read back differently", name));
+            assert!(bytes == written, format!("{} was written differently", name));
+        }
+    }
+
+    #[test]
+    fn test_read_javac_dynamic_constant() {
+        let class = ClassReader::read_class(&mut Cursor::new(&read_test_data("Condy.class"))).ok().unwrap();
+
+        let (bootstrap, name_and_type) = class.constant_pool.constants.iter().filter_map(|constant| match constant {
+            &Constant::Dynamic { ref bootstrap_method_attr_index, ref name_and_type_index } => Some((bootstrap_method_attr_index.idx, name_and_type_index.idx)),
+            _ => None
+        }).next().unwrap();
+
+        assert_eq!(Some((String::from("answer"), String::from("I"))), class.constant_pool.get_name_and_type(name_and_type as u16));
+
+        match class.attributes.iter().find(|a| match a { &&Attribute::BootstrapMethods(_) => true, _ => false }) {
+            Some(&Attribute::BootstrapMethods(ref methods)) => {
+                assert_eq!(1, methods[bootstrap].bootstrap_arguments.len());
+                assert_eq!(Some(&Constant::Integer(41)), class.constant_pool.resolve_index(&methods[bootstrap].bootstrap_arguments[0]));
+            },
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    /// A minimal class with a single method carrying the given attribute
+    fn class_with_method_attribute(attribute_name: &str, info: Vec<u8>) -> Vec<u8> {
+        let mut class = Classfile::new();
+
+        class.version = ClassfileVersion::new(52, 0);
+        class.constant_pool = ConstantPool::new(vec![
+            Constant::Placeholder,
+            Constant::Utf8(attribute_name.to_string().into_bytes().into()),
+            Constant::Utf8("LFoo;".to_string().into_bytes().into()),
+            Constant::Utf8("value".to_string().into_bytes().into()),
+            Constant::Integer(1),
+            Constant::Utf8("Foo".to_string().into_bytes().into()),
+            Constant::Class(ConstantPoolIndex::new(5))
+        ]);
+        class.this_class = ConstantPoolIndex::new(6);
+        class.methods = vec![
+            Method { access_flags: AccessFlags::new(), name_index: ConstantPoolIndex::new(3), descriptor_index: ConstantPoolIndex::new(2), attributes: vec![
+                Attribute::RawAttribute { name_index: ConstantPoolIndex::new(1), info: info.into() }
+            ] }
+        ];
+
+        write_to_vec(&class)
+    }
+
+    #[test]
+    fn test_truncated_class_is_rejected() {
+        let bytes = read_test_data("Simple.class");
+
+        for len in 0..bytes.len() {
+            match ClassReader::read_class(&mut Cursor::new(&bytes[..len])) {
+                Err(ClassReadError { kind: ClassReadErrorKind::Truncated, offset, .. }) => assert!(offset <= len, format!("{} > {}", offset, len)),
+                other @ _ => assert!(false, format!("prefix of {} bytes: {:?}", len, other))
+            }
+        }
+    }
+
+    #[test]
+    fn test_bad_magic_is_rejected() {
+        let mut bytes = read_test_data("Simple.class");
+        bytes[0] = 0xBE;
+
+        let err = ClassReader::read_class(&mut Cursor::new(&bytes)).err().unwrap();
+
+        assert_eq!(ClassReadError::new(0, vec![ ClassSection::Header ], ClassReadErrorKind::BadMagic(0xBEFEBABE)), err);
+    }
+
+    #[test]
+    fn test_bad_constant_tag_is_rejected() {
+        let mut bytes = read_test_data("Simple.class");
+        // the tag of the first constant follows magic, version and constant_pool_count
+        bytes[10] = 2;
+
+        let err = ClassReader::read_class(&mut Cursor::new(&bytes)).err().unwrap();
+
+        assert_eq!(ClassReadError::new(10, vec![ ClassSection::ConstantPool(1) ], ClassReadErrorKind::BadTag(2)), err);
+        assert_eq!("invalid tag 2 at offset 10 (constant pool entry #1)", err.to_string());
+    }
+
+    #[test]
+    fn test_bad_element_value_tag_is_rejected() {
+        // one annotation of type #2 with a single element #3 whose value has the undefined tag 'Q'
+        let info = vec![ 0, 1, 0, 2, 0, 1, 0, 3, b'Q', 0, 4 ];
+        let bytes = class_with_method_attribute("RuntimeVisibleAnnotations", info);
+
+        let err = ClassReader::read_class(&mut Cursor::new(&bytes)).err().unwrap();
+
+        // the attribute is followed by the empty class attribute table only
+        let tag_offset = bytes.len() - 2 - 3;
+        assert_eq!(ClassReadError::new(tag_offset, vec![ ClassSection::Method(0), ClassSection::Attribute("RuntimeVisibleAnnotations".to_string()) ], ClassReadErrorKind::BadTag(b'Q' as u32)), err);
+    }
+
+    #[test]
+    fn test_attribute_length_mismatch_is_rejected() {
+        let bytes = class_with_method_attribute("Signature", vec![ 0, 2, 0, 0 ]);
+
+        match ClassReader::read_class(&mut Cursor::new(&bytes)) {
+            Err(ClassReadError { kind: ClassReadErrorKind::BadLength { expected: 4, actual: 2 }, ref section, .. }) => assert_eq!(&vec![ ClassSection::Method(0), ClassSection::Attribute("Signature".to_string()) ], section),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_attribute_name_must_be_utf8() {
+        let mut bytes = class_with_method_attribute("Signature", vec![ 0, 2 ]);
+        // point the attribute name at the Integer constant instead
+        let name_offset = bytes.len() - 2 - 2 - 4 - 2;
+        bytes[name_offset + 1] = 4;
+
+        let err = ClassReader::read_class(&mut Cursor::new(&bytes)).err().unwrap();
+
+        assert_eq!(ClassReadError::new(name_offset, vec![ ClassSection::Method(0) ], ClassReadErrorKind::BadIndex(4)), err);
+    }
+
+    #[test]
+    fn test_cross_references_must_point_to_the_right_constants() {
+        let read_error = |class: &Classfile| match ClassReader::read_array(&write_to_vec(class)) {
+            Err(err) => (err.section, err.kind),
+            other @ _ => panic!("{:?}", other)
+        };
+
+        // a String whose contents are a Class instead of a Utf8
+        let mut string = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        string.constant_pool.constants.push(Constant::String(ConstantPoolIndex::new(2)));
+        assert_eq!((vec![ ClassSection::ConstantPool(6) ], ClassReadErrorKind::BadIndex(2)), read_error(&string));
+
+        let mut this_class = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        this_class.this_class = ConstantPoolIndex::new(1);
+        assert_eq!((vec![ ClassSection::Header ], ClassReadErrorKind::BadIndex(1)), read_error(&this_class));
+
+        let mut interfaces = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        interfaces.interfaces = vec![ ConstantPoolIndex::new(2), ConstantPoolIndex::new(42) ];
+        assert_eq!((vec![ ClassSection::Interfaces ], ClassReadErrorKind::BadIndex(42)), read_error(&interfaces));
+
+        let mut constant_value = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        constant_value.constant_pool.constants.push(Constant::Utf8(Cow::Borrowed(b"ConstantValue")));
+        constant_value.fields = vec![
+            Field { access_flags: AccessFlags::of(FieldAccessFlags::Static as u16), name_index: ConstantPoolIndex::new(3), descriptor_index: ConstantPoolIndex::new(4), attributes: vec![ Attribute::ConstantValue(ConstantPoolIndex::new(1)) ] }
+        ];
+        assert_eq!((vec![ ClassSection::Field(0), ClassSection::Attribute("ConstantValue".to_string()) ], ClassReadErrorKind::BadIndex(1)), read_error(&constant_value));
+
+        let mut catch_type = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        if let Attribute::Code { ref mut exception_table, .. } = catch_type.methods[0].attributes[0] {
+            exception_table.push(ExceptionHandler { start_pc: 0, end_pc: 1, handler_pc: 0, catch_type: ConstantPoolIndex::new(1) });
+        }
+        assert_eq!((vec![ ClassSection::Method(0), ClassSection::Attribute("Code".to_string()) ], ClassReadErrorKind::BadIndex(1)), read_error(&catch_type));
+    }
+
+    #[test]
+    fn test_member_names_must_point_to_utf8_constants() {
+        let read_error = |class: &Classfile| match ClassReader::read_array(&write_to_vec(class)) {
+            Err(err) => (err.section, err.kind),
+            other @ _ => panic!("{:?}", other)
+        };
+
+        let mut method_name = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        method_name.methods[0].name_index = ConstantPoolIndex::new(2);
+        assert_eq!((vec![ ClassSection::Method(0) ], ClassReadErrorKind::BadIndex(2)), read_error(&method_name));
+
+        let mut field_descriptor = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        field_descriptor.fields = vec![
+            Field { access_flags: AccessFlags::new(), name_index: ConstantPoolIndex::new(3), descriptor_index: ConstantPoolIndex::new(9999), attributes: vec![] }
+        ];
+        assert_eq!((vec![ ClassSection::Field(0) ], ClassReadErrorKind::BadIndex(9999)), read_error(&field_descriptor));
+
+        // members are checked even when their attributes are skipped
+        match ClassReader::read_lazy(&write_to_vec(&method_name)) {
+            Err(err) => assert_eq!((vec![ ClassSection::Method(0) ], ClassReadErrorKind::BadIndex(2)), (err.section, err.kind)),
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_instruction_operands_must_point_to_the_right_constants() {
+        let read_error = |code: Vec<Instruction>| match ClassReader::read_array(&write_to_vec(&class_with_code("()V", 1, code))) {
+            Err(err) => (err.section, err.kind),
+            other @ _ => panic!("{:?}", other)
+        };
+        let in_code = vec![ ClassSection::Method(0), ClassSection::Attribute("Code".to_string()) ];
+
+        assert_eq!((in_code.clone(), ClassReadErrorKind::BadIndex(9000)), read_error(vec![ Instruction::ALOAD_0, Instruction::INVOKEVIRTUAL(9000), Instruction::RETURN ]));
+        assert_eq!((in_code.clone(), ClassReadErrorKind::BadIndex(1)), read_error(vec![ Instruction::NEW(1), Instruction::RETURN ]));
+        assert_eq!((in_code.clone(), ClassReadErrorKind::BadIndex(2)), read_error(vec![ Instruction::GETSTATIC(2), Instruction::RETURN ]));
+        assert_eq!((in_code.clone(), ClassReadErrorKind::BadIndex(3)), read_error(vec![ Instruction::LDC(3), Instruction::RETURN ]));
+        assert_eq!((in_code.clone(), ClassReadErrorKind::BadIndex(2)), read_error(vec![ Instruction::LDC2_W(2), Instruction::RETURN ]));
+    }
+
+    #[test]
+    fn test_simple_class_with_dangling_references_is_rejected() {
+        let bytes = read_test_data("Simple.class");
+
+        let mut method_name = ClassReader::read_array(&bytes).ok().unwrap();
+        method_name.methods[0].name_index = ConstantPoolIndex::new(9999);
+
+        match ClassReader::read_array(&write_to_vec(&method_name)) {
+            Err(err) => assert_eq!((vec![ ClassSection::Method(0) ], ClassReadErrorKind::BadIndex(9999)), (err.section, err.kind)),
+            other @ _ => panic!("{:?}", other)
+        }
+
+        // swap the super constructor call of <init> for a call through a constant that doesn't exist
+        let mut call = ClassReader::read_array(&bytes).ok().unwrap();
+        let position = call.methods.iter().position(|method| method.attributes.iter().any(|attribute| match attribute {
+            &Attribute::Code { ref code, .. } => code.iter().any(|instruction| matches!(instruction, &Instruction::INVOKESPECIAL(_))),
+            _ => false
+        })).unwrap();
+
+        for attribute in call.methods[position].attributes.iter_mut() {
+            if let &mut Attribute::Code { ref mut code, .. } = attribute {
+                for instruction in code.iter_mut().filter(|instruction| matches!(instruction, &&mut Instruction::INVOKESPECIAL(_))) {
+                    *instruction = Instruction::INVOKEVIRTUAL(9000);
+                }
+            }
+        }
+
+        match ClassReader::read_array(&write_to_vec(&call)) {
+            Err(err) => assert_eq!((vec![ ClassSection::Method(position as u16), ClassSection::Attribute("Code".to_string()) ], ClassReadErrorKind::BadIndex(9000)), (err.section, err.kind)),
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_attribute_references_must_point_to_the_right_constants() {
+        // #6 names the attribute under test, #7 is an Integer
+        let read_error = |name: &str, attribute: Attribute<'static>| {
+            let mut class = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+            class.constant_pool.constants.push(Constant::Utf8(name.to_string().into_bytes().into()));
+            class.constant_pool.constants.push(Constant::Integer(7));
+            class.attributes = vec![ attribute ];
+
+            match ClassReader::read_array(&write_to_vec(&class)) {
+                Err(err) => (err.section, err.kind),
+                other @ _ => panic!("{:?}", other)
+            }
+        };
+        let within = |name: &str| vec![ ClassSection::Attribute(name.to_string()) ];
+
+        assert_eq!((within("SourceFile"), ClassReadErrorKind::BadIndex(2)), read_error("SourceFile", Attribute::SourceFile(ConstantPoolIndex::new(2))));
+        assert_eq!((within("Signature"), ClassReadErrorKind::BadIndex(7)), read_error("Signature", Attribute::Signature(ConstantPoolIndex::new(7))));
+        assert_eq!((within("NestHost"), ClassReadErrorKind::BadIndex(1)), read_error("NestHost", Attribute::NestHost(ConstantPoolIndex::new(1))));
+        assert_eq!((within("InnerClasses"), ClassReadErrorKind::BadIndex(7)), read_error("InnerClasses", Attribute::InnerClasses(vec![
+            InnerClass { inner_class_info_index: ConstantPoolIndex::new(2), outer_class_info_index: ConstantPoolIndex::new(0), inner_name_index: ConstantPoolIndex::new(7), access_flags: AccessFlags::new() }
+        ])));
+        assert_eq!((within("BootstrapMethods"), ClassReadErrorKind::BadIndex(2)), read_error("BootstrapMethods", Attribute::BootstrapMethods(vec![
+            BootstrapMethod { bootstrap_method_ref: ConstantPoolIndex::new(2), bootstrap_arguments: vec![] }
+        ])));
+        assert_eq!((within("RuntimeVisibleAnnotations"), ClassReadErrorKind::BadIndex(2)), read_error("RuntimeVisibleAnnotations", Attribute::RuntimeVisibleAnnotations(vec![
+            Annotation { type_index: ConstantPoolIndex::new(2), element_value_pairs: vec![] }
+        ])));
+        assert_eq!((within("RuntimeVisibleAnnotations"), ClassReadErrorKind::BadIndex(1)), read_error("RuntimeVisibleAnnotations", Attribute::RuntimeVisibleAnnotations(vec![
+            Annotation { type_index: ConstantPoolIndex::new(1), element_value_pairs: vec![
+                ElementValuePair { element_name_index: ConstantPoolIndex::new(3), value: ElementValue::ConstantValue(b'J', ConstantPoolIndex::new(1)) }
+            ] }
+        ])));
+    }
+
+    #[test]
+    fn test_deeply_nested_element_values_are_rejected() {
+        // one annotation of type #2 with a single element #3 whose value is a 200 levels deep array
+        let mut info = vec![ 0, 1, 0, 2, 0, 1, 0, 3 ];
+
+        for _ in 0..200 {
+            info.extend_from_slice(&[ b'[', 0, 1 ]);
+        }
+
+        info.extend_from_slice(&[ b'I', 0, 4 ]);
+
+        let bytes = class_with_method_attribute("RuntimeVisibleAnnotations", info);
+
+        match ClassReader::read_class(&mut Cursor::new(&bytes)) {
+            Err(ClassReadError { kind: ClassReadErrorKind::Malformed(_), .. }) => (),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_oversized_table_is_not_written() {
+        let mut class = Classfile::new();
+        class.interfaces = (0..0x10000).map(|_| ConstantPoolIndex::new(1)).collect();
+
+        let mut target: Vec<u8> = vec![];
+        let result = ClassWriter::new(&mut target).write_class(&class);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_errors_inside_method_bodies_are_not_written() {
+        let write = |class: &Classfile| {
+            let mut target: Vec<u8> = vec![];
+            ClassWriter::new(&mut target).write_class(class)
+        };
+
+        assert!(write(&class_with_code("()V", 0, vec![ Instruction::WTF(0xFF), Instruction::RETURN ])).is_err());
+
+        let mut unnamed = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        unnamed.constant_pool.constants[5] = Constant::Utf8(Cow::Borrowed(b"Kode"));
+        assert!(write(&unnamed).is_err());
+
+        // only the first entry is out of range, the ones after it are fine
+        let mut exceptions = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        exceptions.constant_pool.constants.push(Constant::Utf8(Cow::Borrowed(b"Exceptions")));
+        exceptions.methods[0].attributes.push(Attribute::Exceptions(vec![ ConstantPoolIndex::new(0x10000), ConstantPoolIndex::new(2) ]));
+        assert!(write(&exceptions).is_err());
+    }
+
+    fn map_code<F>(class: &mut Classfile, f: F) where F: Fn(LabeledCode) -> LabeledCode {
+        for method in class.methods.iter_mut() {
+            method.attributes = method.attributes.drain(..).map(|attribute| match attribute {
+                code @ Attribute::Code { .. } => f(LabeledCode::from_code(code).unwrap()).into_code().unwrap(),
+                other @ _ => other
+            }).collect();
+        }
+    }
+
+    fn lower(items: Vec<CodeItem>) -> Vec<Instruction> {
+        let mut code = LabeledCode::new(0, 0);
+        code.items = items;
+
+        match code.into_code() {
+            Ok(Attribute::Code { code, .. }) => code,
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_labeled_code_roundtrip() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Switch.class", "Varying.class" ] {
+            let bytes = read_test_data(name);
+            let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+
+            map_code(&mut class, |code| code);
+
+            assert!(bytes == write_to_vec(&class), format!("{} was not written back identically", name));
+        }
+    }
+
+    #[test]
+    fn test_inserted_instruction_keeps_targets() {
+        let bytes = read_test_data("Switch.class");
+        let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+
+        map_code(&mut class, |mut code| { code.items.insert(0, CodeItem::Instruction(Instruction::NOP)); code });
+
+        let written = write_to_vec(&class);
+        let original = ClassReader::read_array(&bytes).ok().unwrap();
+        let shifted = ClassReader::read_array(&written).ok().unwrap();
+
+        for (before, after) in original.methods.into_iter().zip(shifted.methods.into_iter()) {
+            for (before, after) in before.attributes.into_iter().zip(after.attributes.into_iter()) {
+                if let (Ok(mut before), Ok(mut after)) = (LabeledCode::from_code(before), LabeledCode::from_code(after)) {
+                    assert_eq!(CodeItem::Instruction(Instruction::NOP), after.items.remove(0));
+                    assert_eq!(before.items, after.items);
+                    assert_eq!(before.exception_table, after.exception_table);
+                    assert_eq!(before.attributes.len(), after.attributes.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_switch_padding_is_recomputed() {
+        let items = |nops: usize| {
+            let mut items: Vec<CodeItem> = (0..nops).map(|_| CodeItem::Instruction(Instruction::NOP)).collect();
+            items.push(CodeItem::TableSwitch { default: Label(0), low: 0, high: 0, targets: vec![ Label(0) ] });
+            items.push(CodeItem::Label(Label(0)));
+            items.push(CodeItem::Instruction(Instruction::RETURN));
+            items
+        };
+
+        assert_eq!(Instruction::TABLESWITCH(20, 0, 0, vec![ 20 ]), lower(items(0))[0]);
+        assert_eq!(Instruction::TABLESWITCH(19, 0, 0, vec![ 19 ]), lower(items(1))[1]);
+        assert_eq!(Instruction::TABLESWITCH(18, 0, 0, vec![ 18 ]), lower(items(2))[2]);
+    }
+
+    #[test]
+    fn test_far_branches_are_widened() {
+        let items = |branch: Branch, nops: usize| {
+            let mut items = vec![ CodeItem::Branch(branch, Label(0)) ];
+            items.extend((0..nops).map(|_| CodeItem::Instruction(Instruction::NOP)));
+            items.push(CodeItem::Label(Label(0)));
+            items.push(CodeItem::Instruction(Instruction::RETURN));
+            items
+        };
+
+        assert_eq!(Instruction::GOTO(32767), lower(items(Branch::GOTO, 32764))[0]);
+        assert_eq!(Instruction::GOTO_W(32770), lower(items(Branch::GOTO, 32765))[0]);
+        assert_eq!(Instruction::JSR_W(40005), lower(items(Branch::JSR, 40000))[0]);
+
+        let code = lower(items(Branch::IFNULL, 40000));
+        assert_eq!(Instruction::IFNONNULL(8), code[0]);
+        assert_eq!(Instruction::GOTO_W(40005), code[1]);
+    }
+
+    #[test]
+    fn test_unplaced_label_is_rejected() {
+        let mut code = LabeledCode::new(0, 0);
+        let label = code.new_label();
+        code.items = vec![ CodeItem::Branch(Branch::GOTO, label) ];
+
+        assert_eq!(Err(CodeError::UndefinedLabel(label)), code.into_code());
+    }
+
+    fn frame_offsets(table: &Vec<StackMapFrame>) -> Vec<usize> {
+        table.iter().scan(None, |previous: &mut Option<usize>, frame| {
+            let delta = match frame {
+                &StackMapFrame::SameFrame { tag } => tag as usize,
+                &StackMapFrame::SameLocals1StackItemFrame { tag, .. } => tag as usize - 64,
+                &StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. } |
+                &StackMapFrame::ChopFrame { offset_delta, .. } |
+                &StackMapFrame::SameFrameExtended { offset_delta } |
+                &StackMapFrame::AppendFrame { offset_delta, .. } |
+                &StackMapFrame::FullFrame { offset_delta, .. } => offset_delta as usize,
+                &StackMapFrame::FutureUse { .. } => 0
+            };
+
+            let offset = previous.map(|previous| previous + delta + 1).unwrap_or(delta);
+            *previous = Some(offset);
+            Some(offset)
+        }).collect()
+    }
+
+    fn stack_map_tables(class: &Classfile) -> Vec<Vec<usize>> {
+        class.methods.iter().flat_map(|method| method.attributes.iter()).map(|attribute| match attribute {
+            &Attribute::Code { ref attributes, .. } => attributes.iter().filter_map(|attribute| match attribute {
+                &Attribute::StackMapTable(ref table) => Some(frame_offsets(table)),
+                _ => None
+            }).next().unwrap_or(vec![]),
+            _ => vec![]
+        }).collect()
+    }
+
+    #[test]
+    fn test_computed_frames_are_placed_like_javac() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Switch.class", "Varying.class", "Shape.class" ] {
+            let bytes = read_test_data(name);
+            let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+            let expected = stack_map_tables(&class);
+
+            for method in class.methods.iter_mut() {
+                for attribute in method.attributes.iter_mut() {
+                    if let &mut Attribute::Code { ref mut attributes, .. } = attribute {
+                        attributes.retain(|attribute| match attribute { &Attribute::StackMapTable(_) => false, _ => true });
+                    }
+                }
+            }
+
+            let result = compute_frames(&mut class, &EmptyHierarchy);
+
+            assert!(result.is_ok(), format!("{}: {:?}", name, result));
+            assert_eq!(expected, stack_map_tables(&class));
+            assert!(ClassReader::read_array(&write_to_vec(&class)).is_ok());
+        }
+    }
+
+    fn class_with_code(descriptor: &str, max_locals: u16, code: Vec<Instruction>) -> Classfile<'static> {
+        let mut class = Classfile::new();
+
+        class.version = ClassfileVersion::new(52, 0);
+        class.constant_pool = ConstantPool::new(vec![
+            Constant::Placeholder,
+            Constant::Utf8("Test".to_string().into_bytes().into()),
+            Constant::Class(ConstantPoolIndex::new(1)),
+            Constant::Utf8("run".to_string().into_bytes().into()),
+            Constant::Utf8(descriptor.to_string().into_bytes().into()),
+            Constant::Utf8("Code".to_string().into_bytes().into())
+        ]);
+        class.this_class = ConstantPoolIndex::new(2);
+        class.methods = vec![
+            Method { access_flags: AccessFlags::of(MethodAccessFlags::Static as u16), name_index: ConstantPoolIndex::new(3), descriptor_index: ConstantPoolIndex::new(4), attributes: vec![
+                Attribute::Code { max_stack: 2, max_locals: max_locals, code: code, exception_table: vec![], attributes: vec![] }
+            ] }
+        ];
+
+        class
+    }
+
+    fn computed_frames(class: &mut Classfile) -> Vec<StackMapFrame> {
+        let result = compute_frames(class, &EmptyHierarchy);
+        assert!(result.is_ok(), format!("{:?}", result));
+
+        match class.methods[0].attributes.pop() {
+            Some(Attribute::Code { mut attributes, .. }) => match attributes.pop() {
+                Some(Attribute::StackMapTable(table)) => table,
+                other @ _ => panic!("{:?}", other)
+            },
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_computed_frames_use_compact_encodings() {
+        let mut class = class_with_code("(I)V", 3, vec![
+            Instruction::ICONST_5,
+            Instruction::ISTORE_1,
+            Instruction::ILOAD_1,
+            Instruction::IFEQ(4),
+            Instruction::RETURN,
+            Instruction::ILOAD_0,
+            Instruction::IFEQ(4),
+            Instruction::RETURN,
+            Instruction::FCONST_0,
+            Instruction::FSTORE_2,
+            Instruction::ILOAD_0,
+            Instruction::IFNE(-3),
+            Instruction::RETURN
+        ]);
+
+        let frames = computed_frames(&mut class);
+
+        assert_eq!(vec![
+            StackMapFrame::AppendFrame { tag: 252, offset_delta: 7, locals: vec![ VerificationType::Integer ] },
+            StackMapFrame::SameFrame { tag: 4 }
+        ], frames);
+        assert!(class.constant_pool.find_ut8_index("StackMapTable").is_some());
+    }
+
+    #[test]
+    fn test_computed_frames_reject_unreachable_code() {
+        let mut class = class_with_code("()V", 0, vec![ Instruction::RETURN, Instruction::NOP, Instruction::RETURN ]);
+
+        match compute_frames(&mut class, &EmptyHierarchy) {
+            Err(FrameError { offset: 1, kind: FrameErrorKind::Unreachable, .. }) => (),
+            other @ _ => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    struct TestHierarchy;
+
+    impl ClassHierarchy for TestHierarchy {
+        fn class_info(&self, name: &str) -> Option<ClassInfo> {
+            let super_class = match name {
+                "java/lang/Integer" | "java/lang/Long" => "java/lang/Number",
+                "java/lang/Number" | "java/lang/String" => "java/lang/Object",
+                _ => return None
+            };
+
+            Some(ClassInfo { name: name.to_string(), super_class: Some(super_class.to_string()), interfaces: vec![], is_interface: false })
+        }
+    }
+
+    #[test]
+    fn test_common_super_class() {
+        assert_eq!("java/lang/Number", TestHierarchy.common_super_class("java/lang/Integer", "java/lang/Long"));
+        assert_eq!("java/lang/Object", TestHierarchy.common_super_class("java/lang/Integer", "java/lang/String"));
+        assert_eq!("[Ljava/lang/Number;", TestHierarchy.common_super_class("[Ljava/lang/Integer;", "[Ljava/lang/Long;"));
+        assert_eq!("[[I", TestHierarchy.common_super_class("[[I", "[[I"));
+        assert_eq!("[Ljava/lang/Object;", TestHierarchy.common_super_class("[[I", "[[J"));
+        assert_eq!("java/lang/Object", TestHierarchy.common_super_class("[I", "[J"));
+        assert_eq!("java/lang/Object", EmptyHierarchy.common_super_class("java/lang/Integer", "java/lang/Long"));
+    }
+
+    #[test]
+    fn test_analyzer_reports_frames_at_every_offset() {
+        let class = asm::assemble(r#"
+            .version 52 0
+            .class public super Pick
+            .super java/lang/Object
+
+            .method public static pick (ZJ)Ljava/lang/Number;
+                .code stack 2 locals 4
+                    iload_0
+                    ifeq Long
+                    iconst_1
+                    invokestatic method java/lang/Integer valueOf (I)Ljava/lang/Integer;
+                    goto Done
+                Long:
+                    lload_1
+                    invokestatic method java/lang/Long valueOf (J)Ljava/lang/Long;
+                Done:
+                    areturn
+                    nop
+                .end code
+            .end method
+        "#).unwrap();
+
+        let analyzer = analyzer::Analyzer::new(&class.constant_pool, "Pick", &TestHierarchy);
+        let frames = analyzer.analyze_method(&class.methods[0]).unwrap().unwrap();
+        let reference = |name: &str| analyzer::Value::Reference(name.to_string());
+
+        assert_eq!(vec![ 0, 1, 4, 5, 8, 11, 12, 15 ], frames.keys().cloned().collect::<Vec<usize>>());
+        assert_eq!(vec![ analyzer::Value::Integer, analyzer::Value::Long, analyzer::Value::Top, analyzer::Value::Top ], frames[&0].locals);
+        assert_eq!(vec![ analyzer::Value::Integer ], frames[&1].stack);
+        assert_eq!(vec![ reference("java/lang/Integer") ], frames[&8].stack);
+        assert_eq!(vec![ analyzer::Value::Long ], frames[&12].stack);
+        assert_eq!(vec![ reference("java/lang/Number") ], frames[&15].stack);
+
+        match analyzer.analyze("broken", "()V", true, 0, &[ Instruction::POP, Instruction::RETURN ], &[]) {
+            Err(FrameError { ref method, offset: 0, kind: FrameErrorKind::StackUnderflow }) => assert_eq!("broken()V", method),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    fn code_maxs(class: &Classfile) -> Vec<(u16, u16)> {
+        class.methods.iter().flat_map(|method| method.attributes.iter()).filter_map(|attribute| match attribute {
+            &Attribute::Code { max_stack, max_locals, .. } => Some((max_stack, max_locals)),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn test_computed_maxs_match_javac() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Switch.class", "Varying.class", "Shape.class" ] {
+            let bytes = read_test_data(name);
+            let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+            let expected = code_maxs(&class);
+
+            let result = compute_maxs(&mut class);
+
+            assert!(result.is_ok(), format!("{}: {:?}", name, result));
+            assert_eq!(expected, code_maxs(&class), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_computed_maxs_count_words() {
+        let class = class_with_code("(JI)D", 0, vec![
+            Instruction::LLOAD_0,
+            Instruction::ILOAD_2,
+            Instruction::I2L,
+            Instruction::LADD,
+            Instruction::L2D,
+            Instruction::DUP2,
+            Instruction::DSTORE(3),
+            Instruction::DRETURN
+        ]);
+
+        let result = method_maxs(&class.constant_pool, &class.methods[0]);
+
+        assert_eq!(Ok(Some((4, 5))), result);
+    }
+
+    #[test]
+    fn test_computed_maxs_reject_inconsistent_stacks() {
+        let class = class_with_code("(I)V", 1, vec![
+            Instruction::ICONST_0,
+            Instruction::ILOAD_0,
+            Instruction::IFEQ(4),
+            Instruction::ICONST_1,
+            Instruction::POP,
+            Instruction::RETURN
+        ]);
+
+        match method_maxs(&class.constant_pool, &class.methods[0]) {
+            Err(err) => assert_eq!((6, FrameErrorKind::InconsistentStack), (err.offset, err.kind)),
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_computed_maxs_count_unreachable_code() {
+        let class = class_with_code("()V", 0, vec![
+            Instruction::RETURN,
+            Instruction::ICONST_1,
+            Instruction::ICONST_2,
+            Instruction::ICONST_3,
+            Instruction::ICONST_4,
+            Instruction::ICONST_5
+        ]);
+
+        assert_eq!(Ok(Some((5, 0))), method_maxs(&class.constant_pool, &class.methods[0]));
+    }
+
+    #[test]
+    fn test_computed_maxs_size_subroutines() {
+        let class = class_with_code("()V", 0, vec![
+            Instruction::JSR(4),
+            Instruction::RETURN,
+            Instruction::ASTORE_0,
+            Instruction::RET(0)
+        ]);
+
+        assert_eq!(Ok(Some((1, 1))), method_maxs(&class.constant_pool, &class.methods[0]));
+    }
+
+    #[test]
+    fn test_writer_keeps_maxs_it_cannot_compute() {
+        let class = class_with_code("()V", 3, vec![ Instruction::GETSTATIC(99), Instruction::RETURN ]);
+
+        let mut target: Vec<u8> = vec![];
+        let result = ClassWriter::new(&mut target).compute_maxs(true).write_class(&class);
+        assert!(result.is_ok(), format!("{:?}", result.err()));
+        assert_eq!(write_to_vec(&class), target);
+
+        let mut target: Vec<u8> = vec![];
+        let result = ClassWriter::new(&mut target).compute_maxs_of(vec![]).write_class(&class);
+        assert!(result.is_ok(), format!("{:?}", result.err()));
+        assert_eq!(write_to_vec(&class), target);
+
+        let mut target: Vec<u8> = vec![];
+        assert!(ClassWriter::new(&mut target).compute_maxs_of(vec![ 0 ]).write_class(&class).is_err());
+    }
+
+    #[test]
+    fn test_writer_computes_maxs() {
+        let bytes = read_test_data("Varying.class");
+        let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+        let expected = code_maxs(&class);
+
+        for method in class.methods.iter_mut() {
+            for attribute in method.attributes.iter_mut() {
+                if let &mut Attribute::Code { ref mut max_stack, ref mut max_locals, .. } = attribute {
+                    *max_stack = 0;
+                    *max_locals = 0;
+                }
+            }
+        }
+
+        let mut target: Vec<u8> = vec![];
+        let result = ClassWriter::new(&mut target).compute_maxs(true).write_class(&class);
+        assert!(result.is_ok(), format!("{:?}", result.err()));
+
+        let written = ClassReader::read_array(&target).ok().unwrap();
+        assert_eq!(expected, code_maxs(&written));
+        assert_eq!(vec![ (0, 0); expected.len() ], code_maxs(&ClassReader::read_array(&write_to_vec(&class)).ok().unwrap()));
+    }
+
+    #[test]
+    fn test_verify_accepts_javac_output() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Switch.class", "Varying.class", "Shape.class", "Shape$Circle.class", "Test$1.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_array(&bytes).ok().unwrap();
+            let result = verify(&class, &EmptyHierarchy);
+
+            assert!(result.is_ok(), format!("{}: {:?}", name, result));
+        }
+    }
+
+    #[test]
+    fn test_verify_reports_operand_types() {
+        let class = class_with_code("()I", 0, vec![ Instruction::FCONST_0, Instruction::IRETURN ]);
+
+        match verify(&class, &EmptyHierarchy) {
+            Err(err) => {
+                assert_eq!("run()I", err.method);
+                assert_eq!(1, err.offset);
+                assert_eq!(VerifyErrorKind::TypeMismatch { expected: String::from("int"), actual: String::from("float") }, err.kind);
+            },
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_verify_requires_frames() {
+        let mut class = class_with_code("(I)V", 1, vec![ Instruction::ILOAD_0, Instruction::IFEQ(3), Instruction::RETURN ]);
+
+        match verify(&class, &EmptyHierarchy) {
+            Err(err) => assert_eq!((1, VerifyErrorKind::MissingFrame), (err.offset, err.kind)),
+            other @ _ => panic!("{:?}", other)
+        }
+
+        assert!(compute_frames(&mut class, &EmptyHierarchy).is_ok());
+        assert_eq!(Ok(()), verify(&class, &EmptyHierarchy));
+    }
+
+    #[test]
+    fn test_verify_checks_class_hierarchy() {
+        let valid = class_with_code("(Ljava/lang/Integer;)Ljava/lang/Number;", 1, vec![ Instruction::ALOAD_0, Instruction::ARETURN ]);
+        let invalid = class_with_code("(Ljava/lang/Integer;)Ljava/lang/String;", 1, vec![ Instruction::ALOAD_0, Instruction::ARETURN ]);
+
+        assert_eq!(Ok(()), verify(&valid, &TestHierarchy));
+        assert_eq!(Ok(()), verify(&invalid, &EmptyHierarchy));
+
+        match verify(&invalid, &TestHierarchy) {
+            Err(err) => assert_eq!(VerifyErrorKind::TypeMismatch { expected: String::from("java/lang/String"), actual: String::from("java/lang/Integer") }, err.kind),
+            other @ _ => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_compact_constant_pool_removes_unused_and_duplicate_constants() {
+        let bytes = read_test_data("Varying.class");
+        let mut class = ClassReader::read_array(&bytes).ok().unwrap();
+        let name_index = match class.constant_pool.resolve_index(&class.this_class) {
+            Some(&Constant::Class(ref name_index)) => name_index.clone(),
+            other @ _ => panic!("{:?}", other)
+        };
+
+        let name = class.constant_pool.get_utf8(name_index.idx as u16).unwrap().to_vec();
+        let duplicate_name = class.constant_pool.constants.len();
+
+        class.constant_pool.constants.push(Constant::Utf8(name.into()));
+        class.constant_pool.constants.push(Constant::Class(ConstantPoolIndex::new(duplicate_name)));
+        class.constant_pool.constants.push(Constant::Long(42));
+        class.constant_pool.constants.push(Constant::Placeholder);
+        class.constant_pool.constants.push(Constant::Utf8(Cow::Borrowed(b"Unused")));
+        class.this_class = ConstantPoolIndex::new(duplicate_name + 1);
+
+        assert_eq!(Ok(()), compact_constant_pool(&mut class));
+        assert_eq!(bytes, write_to_vec(&class));
+    }
+
+    #[test]
+    fn test_compact_constant_pool_handles_wide_constants() {
+        let mut class = class_with_code("()J", 0, vec![ Instruction::LDC2_W(8), Instruction::POP2, Instruction::LDC2_W(10), Instruction::LRETURN ]);
+
+        class.constant_pool.constants.push(Constant::Long(1));
+        class.constant_pool.constants.push(Constant::Placeholder);
+        class.constant_pool.constants.push(Constant::Long(2));
+        class.constant_pool.constants.push(Constant::Placeholder);
+        class.constant_pool.constants.push(Constant::Long(3));
+        class.constant_pool.constants.push(Constant::Placeholder);
+
+        assert_eq!(Ok(()), compact_constant_pool(&mut class));
+        assert_eq!(Some(&Constant::Long(2)), class.constant_pool.resolve_index(&ConstantPoolIndex::new(6)));
+        assert_eq!(Constant::Placeholder, class.constant_pool.constants[7]);
+        assert_eq!(Some(&Constant::Long(3)), class.constant_pool.resolve_index(&ConstantPoolIndex::new(8)));
+        assert_eq!(10, class.constant_pool.constants.len());
+
+        match class.methods[0].attributes[0] {
+            Attribute::Code { ref code, .. } => assert_eq!(&vec![ Instruction::LDC2_W(6), Instruction::POP2, Instruction::LDC2_W(8), Instruction::LRETURN ], code),
+            ref other => panic!("{:?}", other)
+        }
+
+        assert!(ClassReader::read_array(&write_to_vec(&class)).is_ok());
+    }
+
+    #[test]
+    fn test_compact_constant_pool_rejects_dangling_references() {
+        let mut class = class_with_code("()V", 0, vec![ Instruction::GETSTATIC(42), Instruction::RETURN ]);
+
+        assert_eq!(Err(CompactError::InvalidIndex(42)), compact_constant_pool(&mut class));
+    }
+
+    #[test]
+    fn test_compact_constant_pool_refuses_raw_attributes() {
+        let mut class = class_with_code("()V", 0, vec![ Instruction::RETURN ]);
+        class.constant_pool.constants.push(Constant::Utf8(Cow::Borrowed(b"VendorInfo")));
+        class.constant_pool.constants.push(Constant::Utf8(Cow::Borrowed(b"Referenced")));
+
+        // a nested attribute whose body holds the index of the last constant
+        if let Attribute::Code { ref mut attributes, .. } = class.methods[0].attributes[0] {
+            attributes.push(Attribute::RawAttribute { name_index: ConstantPoolIndex::new(6), info: vec![ 0, 7 ].into() });
+        }
+
+        assert_eq!(Err(CompactError::RawAttribute(String::from("VendorInfo"))), compact_constant_pool(&mut class));
+        assert_eq!(8, class.constant_pool.constants.len());
+    }
+
+    #[test]
+    fn test_constant_pool_indices_account_for_wide_constants() {
+        let mut cp = ConstantPool::new(vec![]);
+
+        assert_eq!(Ok(ConstantPoolIndex::new(1)), cp.add_constant(Constant::Long(42)));
+        assert_eq!(Ok(ConstantPoolIndex::new(3)), cp.add_constant(Constant::Utf8(Cow::Borrowed(b"Test"))));
+        assert_eq!(Ok(ConstantPoolIndex::new(4)), cp.add_constant(Constant::Class(ConstantPoolIndex::new(3))));
+        assert_eq!(5, cp.cp_len());
+
+        assert_eq!(Some(ConstantPoolIndex::new(1)), cp.get_constant_index(&Constant::Long(42)));
+        assert_eq!(Some(ConstantPoolIndex::new(4)), cp.get_constant_index(&Constant::Class(ConstantPoolIndex::new(3))));
+        assert_eq!(None, cp.get_constant_index(&Constant::Placeholder));
+        assert_eq!(Some("Test".to_string()), cp.get_class_name(4));
+    }
+
+    #[test]
+    fn test_constant_pool_refuses_to_grow_past_its_limit() {
+        let mut cp = ConstantPool::new(vec![]);
+
+        for value in 1..0xFFFE {
+            cp.add_constant(Constant::Integer(value)).unwrap();
+        }
+
+        assert_eq!(0xFFFE, cp.cp_len());
+        assert_eq!(Err(ConstantPoolError::PoolFull), cp.add_constant(Constant::Long(42)));
+        assert_eq!(0xFFFE, cp.cp_len());
+
+        assert_eq!(Ok(Utf8Index(0xFFFE)), cp.utf8("Test"));
+        assert_eq!(Err(ConstantPoolError::PoolFull), cp.int(-1));
+        assert_eq!(Err(ConstantPoolError::PoolFull), cp.class("Test"));
+        assert_eq!(Ok(ConstantPoolIndex::new(1)), cp.int(1));
+        assert_eq!(0xFFFF, cp.cp_len());
+
+        assert_eq!(Ok(Utf8Index(0xFFFF)), Utf8Index::try_from(&ConstantPoolIndex::new(0xFFFF)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(0x10000)), Utf8Index::try_from(&ConstantPoolIndex::new(0x10000)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(0x10001)), ClassIndex::try_from(&ConstantPoolIndex::new(0x10001)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(0x10002)), NameAndTypeIndex::try_from(&ConstantPoolIndex::new(0x10002)));
+    }
+
+    #[test]
+    fn test_constant_pool_handles_check_their_targets() {
+        let mut cp = ConstantPool::new(vec![]);
+
+        cp.add_constant(Constant::Double(1.0f64.to_bits())).unwrap();
+        cp.add_constant(Constant::Utf8(Cow::Borrowed(b"run"))).unwrap();
+        cp.add_constant(Constant::Utf8(Cow::Borrowed(b"()V"))).unwrap();
+        cp.add_constant(Constant::NameAndType { name_index: ConstantPoolIndex::new(3), descriptor_index: ConstantPoolIndex::new(4) }).unwrap();
+
+        assert_eq!(Ok((Utf8Index(3), Utf8Index(4))), cp.resolve_name_and_type(NameAndTypeIndex(5)));
+        assert_eq!(Ok(&b"run"[..]), cp.resolve_utf8(Utf8Index(3)));
+        assert_eq!(Err(ConstantPoolError::UnexpectedConstant { idx: 5, expected: "Class" }), cp.resolve_class(ClassIndex(5)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(0)), cp.resolve_utf8(Utf8Index(0)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(2)), cp.resolve_utf8(Utf8Index(2)));
+        assert_eq!(Err(ConstantPoolError::InvalidIndex(6)), cp.resolve_utf8(Utf8Index(6)));
+        assert_eq!(None, cp.resolve_index(&ConstantPoolIndex::new(2)));
+    }
+
+    #[test]
+    fn test_constant_pool_builder_deduplicates() {
+        let mut cp = ConstantPool::new(vec![]);
+
+        let println = cp.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V").unwrap();
+        let out = cp.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;").unwrap();
+        let big = cp.long(1 << 40).unwrap();
+        let greeting = cp.string("Hello").unwrap();
+        let len = cp.cp_len();
+
+        assert_eq!(Ok(println.clone()), cp.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V"));
+        assert_eq!(Ok(out.clone()), cp.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;"));
+        assert_eq!(Ok(big.clone()), cp.long(1 << 40));
+        assert_eq!(Ok(greeting), cp.string("Hello"));
+        assert_eq!(len, cp.cp_len());
+
+        assert_eq!(Some(("java/io/PrintStream".to_string(), "println".to_string(), "(Ljava/lang/String;)V".to_string())), cp.get_member_ref(println.idx as u16));
+        assert_eq!(Some(("java/lang/System".to_string(), "out".to_string(), "Ljava/io/PrintStream;".to_string())), cp.get_member_ref(out.idx as u16));
+        assert_eq!(Some(&Constant::Placeholder), cp.constants.get(big.idx + 1));
+
+        let handle = cp.method_handle(ReferenceKind::InvokeStatic, println.clone());
+        let indy = cp.invoke_dynamic(0, "run", "()Ljava/lang/Runnable;");
+
+        assert_eq!(handle, cp.method_handle(ReferenceKind::InvokeStatic, println));
+        assert_eq!(indy, cp.invoke_dynamic(0, "run", "()Ljava/lang/Runnable;"));
+        let system: ConstantPoolIndex = cp.class("java/lang/System").unwrap().into();
+
+        match cp.get(&out) {
+            Ok(&Constant::FieldRef { ref class_index, .. }) => assert_eq!(system, *class_index),
+            other => assert!(false, format!("{:?}", other))
+        }
+    }
+
+    #[test]
+    fn test_descriptors_are_parsed_and_rendered() {
+        let descriptor = MethodDescriptor::parse("(IJ[[Ljava/lang/String;D)Ljava/lang/Object;").unwrap();
+
+        assert_eq!(vec![
+            FieldType::Int,
+            FieldType::Long,
+            FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Object(String::from("java/lang/String")))))),
+            FieldType::Double
+        ], descriptor.params);
+        assert_eq!(Some(FieldType::Object(String::from("java/lang/Object"))), descriptor.ret);
+        assert_eq!("(IJ[[Ljava/lang/String;D)Ljava/lang/Object;", descriptor.to_string());
+
+        assert_eq!(6, descriptor.param_slots());
+        assert_eq!(6, descriptor.argument_size(true));
+        assert_eq!(7, descriptor.argument_size(false));
+        assert_eq!(1, descriptor.return_slots());
+
+        let void = MethodDescriptor::parse("()V").unwrap();
+
+        assert_eq!(None, void.ret);
+        assert_eq!(0, void.return_slots());
+        assert_eq!("()V", void.descriptor());
+
+        assert_eq!(Ok(FieldType::Array(Box::new(FieldType::Boolean))), FieldType::parse("[Z"));
+        assert_eq!(2, FieldType::parse("D").unwrap().slots());
+    }
+
+    #[test]
+    fn test_invalid_descriptors_are_rejected() {
+        assert_eq!(Err(DescriptorError { descriptor: String::from("V"), offset: 0 }), FieldType::parse("V"));
+        assert_eq!(Err(DescriptorError { descriptor: String::from("II"), offset: 1 }), FieldType::parse("II"));
+        assert_eq!(Err(DescriptorError { descriptor: String::from("Ljava/lang/String"), offset: 17 }), FieldType::parse("Ljava/lang/String"));
+        assert_eq!(Err(DescriptorError { descriptor: String::from("Ljava.lang.String;"), offset: 5 }), FieldType::parse("Ljava.lang.String;"));
+        assert_eq!(Err(DescriptorError { descriptor: String::from("(V)V"), offset: 1 }), MethodDescriptor::parse("(V)V"));
+        assert_eq!(Err(DescriptorError { descriptor: String::from("(I"), offset: 2 }), MethodDescriptor::parse("(I"));
+        assert!(FieldType::parse(&format!("{}I", "[".repeat(256))).is_err());
+        assert!(FieldType::parse(&format!("{}I", "[".repeat(255))).is_ok());
+    }
+
+    #[test]
+    fn test_generic_signatures_are_parsed() {
+        let class = GenericClassSignature::parse("<K:Ljava/lang/Object;V::Ljava/lang/Comparable<-TV;>;>Ljava/util/AbstractMap<TK;TV;>;Ljava/io/Serializable;").unwrap();
+
+        assert_eq!(2, class.type_parameters.len());
+        assert_eq!(None, class.type_parameters[1].class_bound);
+        assert_eq!("java/util/AbstractMap", class.super_class.internal_name());
+        assert_eq!("<K, V extends java.lang.Comparable<? super V>> extends java.util.AbstractMap<K, V> implements java.io.Serializable", class.to_string());
+        assert_eq!("<K, V extends Comparable<? super V>> extends AbstractMap<K, V> implements Serializable", format!("{:#}", class));
+
+        let method = GenericMethodSignature::parse("<T:Ljava/lang/Exception;>(Ljava/util/List<+Ljava/lang/Number;>;[I)V^TT;^Ljava/io/IOException;").unwrap();
+
+        assert_eq!(None, method.ret);
+        assert_eq!(vec![
+            TypeSignature::TypeVariable(String::from("T")),
+            TypeSignature::Class(ClassTypeSignature { package: String::from("java/io"), classes: vec![ SimpleClassTypeSignature { name: String::from("IOException"), type_arguments: vec![] } ] })
+        ], method.throws);
+        assert_eq!("<T extends java.lang.Exception> void run(java.util.List<? extends java.lang.Number>, int[]) throws T, java.io.IOException", method.declaration("run"));
+
+        let field = TypeSignature::parse("Ljava/util/Map<TK;TV;>.Entry<*Ljava/lang/String;>;").unwrap();
+
+        match field {
+            TypeSignature::Class(ref class) => assert_eq!("java/util/Map$Entry", class.internal_name()),
+            ref other => assert!(false, format!("{:?}", other))
+        }
+
+        assert_eq!("Map<K, V>.Entry<?, String>", format!("{:#}", field));
+    }
+
+    #[test]
+    fn test_invalid_generic_signatures_are_rejected() {
+        assert_eq!(Err(SignatureError { signature: String::from("I"), offset: 0 }), TypeSignature::parse("I"));
+        assert_eq!(Err(SignatureError { signature: String::from("Ljava/util/List<>;"), offset: 16 }), TypeSignature::parse("Ljava/util/List<>;"));
+        assert_eq!(Err(SignatureError { signature: String::from("<>Ljava/lang/Object;"), offset: 1 }), GenericClassSignature::parse("<>Ljava/lang/Object;"));
+        assert_eq!(Err(SignatureError { signature: String::from("()V^I"), offset: 4 }), GenericMethodSignature::parse("()V^I"));
+    }
+
+    #[test]
+    fn test_modified_utf8_strings_roundtrip() {
+        let bytes = read_test_data("Strings.class");
+        let class = ClassReader::read_array(&bytes).unwrap();
+
+        let strings: Vec<Option<String>> = class.constant_pool.constants.iter().filter_map(|constant| match constant {
+            &Constant::String(ref idx) => Some(class.constant_pool.get_utf8_string(idx.idx as u16)),
+            _ => None
+        }).collect();
+
+        assert!(strings.contains(&Some(String::from("a\u{0}b"))), format!("{:?}", strings));
+        assert!(strings.contains(&Some(String::from("caf\u{e9} \u{1F600}"))), format!("{:?}", strings));
+        assert!(strings.contains(&None), format!("{:?}", strings));
+
+        let mut cp = ConstantPool::new(class.constant_pool.constants.clone());
+        let len = cp.cp_len();
+
+        cp.utf8("a\u{0}b").unwrap();
+        cp.utf8("caf\u{e9} \u{1F600}").unwrap();
+
+        assert_eq!(len, cp.cp_len());
+        assert_eq!(bytes, write_to_vec(&class));
+    }
+
+    #[test]
+    fn test_modified_utf8_encoding() {
+        assert_eq!(&[ 0x61, 0xC0, 0x80 ][..], &mutf8::encode("a\u{0}")[..]);
+        assert_eq!(&[ 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80 ][..], &mutf8::encode("\u{1F600}")[..]);
+        assert_eq!("caf\u{e9}".as_bytes(), &mutf8::encode("caf\u{e9}")[..]);
+
+        assert_eq!(Ok(String::from("a\u{0}\u{1F600}")), mutf8::decode(&[ 0x61, 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80 ]).map(|string| string.into_owned()));
+        assert_eq!(Err(mutf8::Mutf8Error::InvalidByte(1)), mutf8::decode(&[ 0x61, 0x00 ]));
+        assert_eq!(Err(mutf8::Mutf8Error::InvalidByte(0)), mutf8::decode("\u{1F600}".as_bytes()));
+        assert_eq!(Err(mutf8::Mutf8Error::UnexpectedEnd), mutf8::decode(&[ 0xE2, 0x82 ]));
+        assert_eq!(Err(mutf8::Mutf8Error::UnpairedSurrogate(1)), mutf8::decode(&[ 0x61, 0xED, 0xA0, 0x80 ]));
+        assert_eq!("a\u{FFFD}", mutf8::decode_lossy(&[ 0x61, 0xED, 0xA0, 0x80 ]));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Strings.class", "Shape.class", "module-info.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+            for text in &[ json::to_json(&class).unwrap(), json::to_json_resolved(&class).unwrap() ] {
+                let read_class = json::from_json(text).unwrap();
+
+                assert_eq!(class, read_class);
+                assert_eq!(bytes, write_to_vec(&read_class), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_resolved_form_inlines_constants() {
+        let bytes = read_test_data("Simple.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+        let plain = json::to_json(&class).unwrap();
+        assert!(plain.contains(r#"{"Class":4},{"NameAndType":{"name_index":5,"descriptor_index":6}},{"Utf8":"java/lang/Object"}"#), "{}", plain);
+
+        let resolved = json::to_json_resolved(&class).unwrap();
+        assert!(resolved.contains("\"Class\": {\n          \"idx\": 4,\n          \"value\": \"java/lang/Object\"\n        }"), "{}", resolved);
+
+        // Strings that aren't valid in Rust are kept as bytes
+        let bytes = read_test_data("Strings.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+
+        assert!(json::to_json(&class).unwrap().contains(r#"{"Utf8":[237,160,128]}"#));
+    }
+
+    #[test]
+    fn test_javap_rendering_matches_javap() {
+        let bytes = read_test_data("Varying.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+        let lines = printer::ClassfilePrinter::render_javap(&class, false);
+
+        assert_eq!(&[
+            "  Compiled from \"Varying.java\"",
+            "public class Varying",
+            "  minor version: 0",
+            "  major version: 61",
+            "  flags: (0x0021) ACC_PUBLIC, ACC_SUPER",
+            "  this_class: #8                          // Varying",
+            "  super_class: #2                         // java/lang/Object",
+            "  interfaces: 0, fields: 0, methods: 5, attributes: 1",
+            "Constant pool:",
+            "   #1 = Methodref          #2.#3          // java/lang/Object.\"<init>\":()V",
+        ][..], &lines[..10]);
+
+        let start = lines.iter().position(|line| line == "  public int function();").unwrap();
+
+        assert_eq!(&[
+            "  public int function();",
+            "    descriptor: ()I",
+            "    flags: (0x0001) ACC_PUBLIC",
+            "    Code:",
+            "      stack=1, locals=4, args_size=1",
+            "         0: iconst_2",
+            "         1: istore_1",
+            "         2: iinc          1, 1",
+        ][..], &lines[start..start + 8]);
+
+        assert_eq!(&[
+            "        10: goto          21",
+        ][..], &lines[start + 11..start + 12]);
+
+        assert_eq!(&[
+            "      Exception table:",
+            "         from    to  target type",
+            "             0     5    13   any",
+        ][..], &lines[start + 20..start + 23]);
+
+        assert_eq!(&[
+            "      LocalVariableTable:",
+            "        Start  Length  Slot  Name   Signature",
+            "            2       3     1     a   I",
+            "            7       3     1     a   I",
+            "           16       3     3     a   I",
+            "            0      23     0  this   LVarying;",
+            "      StackMapTable: number_of_entries = 2",
+            "        frame_type = 77 /* same_locals_1_stack_item */",
+            "          stack = [ class java/lang/Throwable ]",
+            "        frame_type = 7 /* same */",
+            "",
+        ][..], &lines[start + 33..start + 44]);
+    }
+
+    #[test]
+    fn test_javap_rendering_hides_private_members() {
+        let bytes = read_test_data("LambdaTest.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+        let public = printer::ClassfilePrinter::render_javap(&class, false);
+        let private = printer::ClassfilePrinter::render_javap(&class, true);
+
+        let lambda = "  private static boolean lambda$doStreamProcessing$0(java.lang.String);";
+
+        assert!(!public.iter().any(|line| line == lambda), "{:#?}", public);
+        assert!(private.iter().any(|line| line == lambda), "{:#?}", private);
+        assert!(public.iter().any(|line| line == "      #106 (Ljava/lang/Object;)Z"), "{:#?}", public);
+        assert!(public.iter().any(|line| line == "  #106 = MethodType         #23           //  (Ljava/lang/Object;)Z"), "{:#?}", public);
+    }
+
+    #[test]
+    fn test_asm_roundtrip() {
+        for name in &[ "Simple.class", "Test.class", "LambdaTest.class", "Strings.class", "Shape.class", "Varying.class", "module-info.class" ] {
+            let bytes = read_test_data(name);
+            let class = ClassReader::read_array(&bytes).ok().unwrap();
+            let text = asm::disassemble(&class).unwrap();
+
+            let assembled = asm::assemble(&text).unwrap();
+            assert_eq!(text, asm::disassemble(&assembled).unwrap(), "{}", name);
+
+            let written = write_to_vec(&assembled);
+            let read_class = ClassReader::read_array(&written).ok().unwrap();
+            assert_eq!(text, asm::disassemble(&read_class).unwrap(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_assemble_builds_constant_pool_and_resolves_labels() {
+        let source = r#"
+            .version 52 0
+            .class public super Counter
+            .super java/lang/Object
+
+            .method public static count (I)I
+                .code stack 2 locals 2
+                    iconst_0
+                    istore_1
+                Loop:
+                    iload_0
+                    ifle Done          ; counts down to zero
+                    iinc 1 1
+                    iinc 0 -1
+                    goto Loop
+                Done:
+                    ldc string "counted"
+                    pop
+                    iload_1
+                    ireturn
+                .end code
+            .end method
+        "#;
+
+        let class = asm::assemble(source).unwrap();
+        let cp = &class.constant_pool;
+
+        assert_eq!(Some(String::from("Counter")), cp.get_class_name(class.this_class.idx as u16));
+        assert_eq!(Some(String::from("java/lang/Object")), cp.get_class_name(class.super_class.idx as u16));
+        assert!(cp.find_ut8_index("Code").is_some());
+
+        match class.methods[0].attributes[0] {
+            Attribute::Code { ref code, .. } => {
+                assert_eq!(Instruction::IFLE(12), code[3]);
+                assert_eq!(Instruction::GOTO(-10), code[6]);
+
+                match code[7] {
+                    Instruction::LDC(idx) => assert_eq!(Some(&Constant::String(cp.get_constant_index(&Constant::Utf8(Cow::Borrowed(b"counted"))).unwrap())), cp.resolve_index(&ConstantPoolIndex::new(idx as usize))),
+                    ref instruction => panic!("{:?}", instruction)
+                }
+            },
+            ref attribute => panic!("{:?}", attribute)
+        }
+
+        let written = write_to_vec(&class);
+        assert_eq!(class, ClassReader::read_array(&written).ok().unwrap());
+
+        assert_eq!(Err(asm::AsmError::Syntax { line: 4, message: String::from("undefined label `Nowhere`") }), asm::assemble(".class A\n.method m ()V\n.code stack 0 locals 1\ngoto Nowhere\n.end code\n.end method"));
+    }
+
+    #[test]
+    fn test_diff_ignores_constant_pool_layout() {
+        let bytes = read_test_data("Varying.class");
+        let class = ClassReader::read_array(&bytes).ok().unwrap();
+        let reassembled = asm::assemble(&asm::disassemble(&class).unwrap()).unwrap();
+
+        assert!(class.constant_pool != reassembled.constant_pool);
+        assert!(diff::diff(&class, &reassembled).is_empty(), "{:?}", diff::diff(&class, &reassembled));
+        assert_eq!(Vec::<String>::new(), diff::diff(&class, &reassembled).render_unified());
+    }
+
+    #[test]
+    fn test_diff_reports_member_and_instruction_changes() {
+        let source = |flags: &str, start: &str, extra: &str| format!(r#"
+            .version 52 0
+            .class public super Counter
+            .super java/lang/Object
+
+            .field private total I
+            .end field
+
+            .method public static {} count (I)I
+                .code stack 2 locals 2
+                    {}
+                    istore_1
+                Loop:
+                    iload_0
+                    ifle Done
+                    iinc 1 1
+                    iinc 0 -1
+                    goto Loop
+                Done:
+                    iload_1
+                    ireturn
+                .end code
+            .end method
+            {}
+        "#, flags, start, extra);
+
+        let old = asm::assemble(&source("", "iconst_0", "")).unwrap();
+        let new = asm::assemble(&source("synchronized", "iconst_1", ".method public reset ()V\n.code stack 0 locals 1\nreturn\n.end code\n.end method")).unwrap();
+        let changes = diff::diff(&old, &new);
+
+        assert!(changes.changes.is_empty(), "{:?}", changes.changes);
+        assert!(changes.fields.is_empty(), "{:?}", changes.fields);
+        assert_eq!(diff::MemberDiff::Added(String::from("reset ()V")), changes.methods[1]);
+
+        match changes.methods[0] {
+            diff::MemberDiff::Changed { ref member, ref changes } => {
+                assert_eq!("count (I)I", member);
+                assert_eq!(diff::Change::Property { name: "flags", old: String::from("public static"), new: String::from("public static synchronized") }, changes[0]);
+
+                match changes[1] {
+                    diff::Change::AttributeChanged { ref name, ref lines } => {
+                        assert_eq!("Code", name);
+                        assert_eq!(2, lines.iter().filter(|line| match line { &&diff::DiffLine::Same(_) => false, _ => true }).count(), "{:?}", lines);
+                    },
+                    ref change => panic!("{:?}", change)
+                }
+            },
+            ref member => panic!("{:?}", member)
+        }
+
+        let rendered = changes.render_unified().join("\n");
+
+        assert!(rendered.starts_with("--- Counter\n+++ Counter\n@@ method count (I)I @@\n-flags public static\n+flags public static synchronized\n"), "{}", rendered);
+        assert!(rendered.contains("\n .code stack 2 locals 2\n-    iconst_0\n+    iconst_1\n     istore_1\n"), "{}", rendered);
+        assert!(rendered.ends_with("\n+method reset ()V"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_cfg_blocks_dominators_and_loops() {
+        let class = asm::assemble(r#"
+            .version 52 0
+            .class public super Flow
+            .super java/lang/Object
+
+            .method public static run (I)I
+                .code stack 2 locals 2
+                    iconst_0
+                    istore_1
+                Loop:
+                    iload_0
+                    ifle Done
+                    iload_0
+                    tableswitch 0
+                        One
+                        Two
+                        default Next
+                One:
+                    iinc 1 1
+                    goto Next
+                Two:
+                    iinc 1 2
+                Next:
+                    iinc 0 -1
+                    goto Loop
+                Done:
+                    iload_1
+                    ireturn
+                Handler:
+                    astore_1
+                    iconst_m1
+                    ireturn
+                    .catch java/lang/RuntimeException Loop Done Handler
+                .end code
+            .end method
+        "#).unwrap();
+
+        let graph = cfg::ControlFlowGraph::from_code(&class.methods[0].attributes[0]).unwrap();
+        let starts: Vec<usize> = graph.blocks.iter().map(|block| block.start).collect();
+        let edges = |block: usize| graph.blocks[block].successors.iter().map(|edge| (edge.target, edge.kind.clone())).collect::<Vec<(usize, cfg::EdgeKind)>>();
+        let caught = match class.methods[0].attributes[0] {
+            Attribute::Code { ref exception_table, .. } => cfg::EdgeKind::Exception(exception_table[0].catch_type.clone()),
+            ref attribute => panic!("{:?}", attribute)
+        };
+
+        assert_eq!(vec![ 0, 2, 6, 28, 34, 37, 43, 45, 48 ], starts);
+        assert_eq!(8, graph.exit());
+        assert_eq!(Some(3), graph.block_at(30));
+        assert_eq!(None, graph.block_at(48));
+        assert_eq!(vec![ (1, cfg::EdgeKind::Fallthrough) ], edges(0));
+        assert_eq!(vec![ (6, cfg::EdgeKind::Branch), (2, cfg::EdgeKind::Fallthrough), (7, caught.clone()) ], edges(1));
+        assert_eq!(vec![ (5, cfg::EdgeKind::Switch), (3, cfg::EdgeKind::Switch), (4, cfg::EdgeKind::Switch), (7, caught.clone()) ], edges(2));
+        assert_eq!(vec![ (1, cfg::EdgeKind::Jump), (7, caught) ], edges(5));
+        assert_eq!(vec![ (8, cfg::EdgeKind::Return) ], edges(6));
+        assert_eq!(vec![ 6, 7 ], graph.blocks[8].predecessors);
+
+        let dominators = graph.dominators();
+        assert_eq!(None, dominators.immediate_dominator(0));
+        assert_eq!(Some(2), dominators.immediate_dominator(5));
+        assert_eq!(Some(1), dominators.immediate_dominator(7));
+        assert!(dominators.dominates(1, 4));
+        assert!(!dominators.dominates(3, 5));
+
+        let loops = graph.loops();
+        assert_eq!(1, loops.len());
+        assert_eq!(1, loops[0].header);
+        assert_eq!(vec![ 5 ], loops[0].latches);
+        assert_eq!(vec![ 1, 2, 3, 4, 5 ], loops[0].blocks.iter().cloned().collect::<Vec<usize>>());
+
+        match class.methods[0].attributes[0] {
+            Attribute::Code { ref code, .. } => {
+                let dot = graph.to_dot(code);
+                assert!(dot.starts_with("digraph cfg {\n"), "{}", dot);
+                assert!(dot.contains("    B1 [label=\"B1\\l2: iload_0\\l3: ifle\\l\"];\n"), "{}", dot);
+                assert!(dot.contains("    B1 -> B6 [label=\"branch\"];\n"), "{}", dot);
+            },
+            ref attribute => panic!("{:?}", attribute)
+        }
+    }
+
+    #[test]
+    fn test_cfg_links_subroutine_returns() {
+        let class = asm::assemble(r#"
+            .version 49 0
+            .class public super Subroutines
+            .super java/lang/Object
+
+            .method public static run ()V
+                .code stack 1 locals 1
+                    jsr Finally
+                    jsr Finally
+                    return
+                Finally:
+                    astore_0
+                    ret 0
+                .end code
+            .end method
+        "#).unwrap();
+
+        let graph = cfg::ControlFlowGraph::from_code(&class.methods[0].attributes[0]).unwrap();
+        let edges = |block: usize| graph.blocks[block].successors.iter().map(|edge| (edge.target, edge.kind.clone())).collect::<Vec<(usize, cfg::EdgeKind)>>();
+
+        assert_eq!(vec![ (3, cfg::EdgeKind::Subroutine) ], edges(0));
+        assert_eq!(vec![ (3, cfg::EdgeKind::Subroutine) ], edges(1));
+        assert_eq!(vec![ (1, cfg::EdgeKind::SubroutineReturn), (2, cfg::EdgeKind::SubroutineReturn) ], edges(3));
+        assert_eq!(vec![ 0, 1 ], graph.blocks[3].predecessors);
+        assert_eq!(Some(3), graph.dominators().immediate_dominator(2));
+
+        assert_eq!(Err(CodeError::NotCode), cfg::ControlFlowGraph::from_code(&Attribute::Deprecated));
+    }
+
+    #[test]
+    fn test_class_path_hierarchy_reads_directories_and_jars() {
+        let jar = ::std::env::temp_dir().join(format!("jvmti-hierarchy-{}.jar", ::std::process::id()));
+
+        {
+            let mut writer = ::zip::ZipWriter::new(File::create(&jar).unwrap());
+            writer.start_file("Simple.class", ::zip::write::FileOptions::default()).unwrap();
+            writer.write_all(&read_test_data("Simple.class")).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let hierarchy = ClassPathHierarchy::new(&[ jar.clone(), ::std::path::PathBuf::from("test-data") ]).unwrap();
+        ::std::fs::remove_file(&jar).unwrap();
+
+        let circle = hierarchy.class_info("Shape$Circle").unwrap();
+        assert_eq!(Some(String::from("java/lang/Record")), circle.super_class);
+        assert_eq!(vec![ String::from("Shape") ], circle.interfaces);
+        assert!(hierarchy.is_interface("Shape"));
+
+        // Read from the JAR before it was removed, and answered from the cache since
+        assert_eq!(Some(String::from("java/lang/Object")), hierarchy.class_info("Simple").unwrap().super_class);
+        assert_eq!(None, hierarchy.class_info("java/lang/Object"));
+        assert_eq!("java/lang/Record", hierarchy.common_super_class("Shape$Circle", "Shape$Square"));
+
+        assert!(ClassPathHierarchy::new(&[ "test-data/Missing.jar" ]).is_err());
+    }
+
+    #[test]
+    fn test_loaded_class_hierarchy_remembers_added_classes() {
+        let hierarchy = LoadedClassHierarchy::new();
+        assert!(hierarchy.is_empty());
+
+        for name in &[ "Shape.class", "Shape$Circle.class" ] {
+            let bytes = read_test_data(name);
+            hierarchy.add_class(0, &ClassReader::read_lazy(&bytes).ok().unwrap());
+        }
+
+        assert_eq!(2, hierarchy.len());
+        assert_eq!(Some(ClassInfo::from_class(&ClassReader::read_array(&read_test_data("Shape$Circle.class")).ok().unwrap())), hierarchy.class_info("Shape$Circle"));
+        assert_eq!(vec![ String::from("Shape$Circle"), String::from("java/lang/Record") ], hierarchy.super_classes("Shape$Circle"));
+        assert!(hierarchy.is_interface("Shape"));
+        assert_eq!(None, hierarchy.class_info("Shape$Square"));
+    }
+
+    #[test]
+    fn test_loaded_class_hierarchy_forgets_unloaded_classes() {
+        let hierarchy = LoadedClassHierarchy::new();
+        let circle = read_test_data("Shape$Circle.class");
+        let square = read_test_data("Shape$Square.class");
+
+        hierarchy.add_class(1, &ClassReader::read_lazy(&circle).ok().unwrap());
+        hierarchy.add_class(2, &ClassReader::read_lazy(&circle).ok().unwrap());
+        hierarchy.add_class(2, &ClassReader::read_lazy(&square).ok().unwrap());
+        assert_eq!(3, hierarchy.len());
+
+        // the same loader defining a class again replaces it
+        hierarchy.add_class(2, &ClassReader::read_lazy(&square).ok().unwrap());
+        assert_eq!(3, hierarchy.len());
+
+        hierarchy.unload(2);
+        assert_eq!(1, hierarchy.len());
+        assert!(hierarchy.class_info("Shape$Circle").is_some());
+        assert_eq!(None, hierarchy.class_info("Shape$Square"));
+
+        hierarchy.unload(1);
+        assert!(hierarchy.is_empty());
+    }
+
     #[test]
     fn test_cursor_read_usage() {
         let mut cursor = Cursor::new(vec![ 1, 2, 3, 4 as u8 ]);
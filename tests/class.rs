@@ -3,49 +3,60 @@ extern crate jvmti;
 #[cfg(test)]
 mod tests {
 
-    use jvmti::class::{Class, ClassId, JavaType};
+    use jvmti::class::{Class, ClassId, ClassSignature};
     use std::ptr;
 
+    fn name(signature: &str) -> (String, String) {
+        let signature = ClassSignature::parse(signature, None).ok().unwrap();
+
+        (signature.package, signature.name)
+    }
+
     #[test]
-    fn primitive_types_are_parsed_correctly() {
-        assert_eq!(Some(JavaType::Void), JavaType::parse("V"));
-        assert_eq!(Some(JavaType::Int), JavaType::parse("I"));
-        assert_eq!(Some(JavaType::Boolean), JavaType::parse("Z"));
-        assert_eq!(Some(JavaType::Short), JavaType::parse("S"));
-        assert_eq!(Some(JavaType::Long), JavaType::parse("J"));
+    fn primitive_classes_are_parsed_correctly() {
+        assert_eq!((String::new(), "void".to_string()), name("V"));
+        assert_eq!((String::new(), "int".to_string()), name("I"));
+        assert_eq!((String::new(), "boolean".to_string()), name("Z"));
+        assert_eq!((String::new(), "short".to_string()), name("S"));
+        assert_eq!((String::new(), "long".to_string()), name("J"));
     }
 
     #[test]
     fn arrays_are_parsed_correctly() {
-        assert_eq!(Some(JavaType::Array(Box::new(JavaType::Int))), JavaType::parse("[I"));
-        assert_eq!(Some(JavaType::Array(Box::new(JavaType::Boolean))), JavaType::parse("[Z"));
-        assert_eq!(Some(JavaType::Array(Box::new(JavaType::Array(Box::new(JavaType::Int))))), JavaType::parse("[[I"));
+        assert_eq!((String::new(), "int[]".to_string()), name("[I"));
+        assert_eq!((String::new(), "boolean[]".to_string()), name("[Z"));
+        assert_eq!((String::new(), "short[][]".to_string()), name("[[S"));
     }
 
     #[test]
     fn classes_are_parsed_correctly() {
-        assert_eq!(Some(JavaType::Class("Lso/blacklight/Test;")), JavaType::parse("Lso/blacklight/Test;"));
-        assert_eq!(Some(JavaType::Class("LTest;")), JavaType::parse("LTest;"));
+        assert_eq!(("so.blacklight".to_string(), "Test".to_string()), name("Lso/blacklight/Test;"));
+        assert_eq!((String::new(), "Test".to_string()), name("LTest;"));
     }
 
     #[test]
     fn arrays_of_classes_are_parsed() {
-        assert_eq!(Some(JavaType::Array(Box::new(JavaType::Class("Lso/blacklight/Test;")))), JavaType::parse("[Lso/blacklight/Test;"));
+        assert_eq!(("so.blacklight".to_string(), "Test[]".to_string()), name("[Lso/blacklight/Test;"));
     }
 
     #[test]
-    fn java_types_are_stringified() {
-        assert_eq!("void", JavaType::to_string(&JavaType::Void));
-        assert_eq!("int", JavaType::to_string(&JavaType::Int));
-        assert_eq!("int[]", JavaType::to_string(&JavaType::Array(Box::new(JavaType::Int))));
-        assert_eq!("so.blacklight.Test", JavaType::to_string(&JavaType::Class("Lso/blacklight/Test;")));
-        assert_eq!("so.blacklight.Test[]", JavaType::to_string(&JavaType::Array(Box::new(JavaType::Class("Lso/blacklight/Test;")))));
-        assert_eq!("short[][]", JavaType::to_string(&JavaType::Array(Box::new(JavaType::Array(Box::new(JavaType::Short))))));
+    fn invalid_signatures_are_rejected() {
+        assert!(ClassSignature::parse("", None).is_err());
+        assert!(ClassSignature::parse("Lso/blacklight/Test", None).is_err());
+        assert!(ClassSignature::parse("[V", None).is_err());
+    }
+
+    #[test]
+    fn generic_signatures_are_parsed() {
+        let signature = ClassSignature::parse("Ljava/util/List;", Some("<E:Ljava/lang/Object;>Ljava/lang/Object;Ljava/util/Collection<TE;>;")).ok().unwrap();
+
+        assert!(signature.generic.is_some());
+        assert!(ClassSignature::parse("Ljava/util/List;", Some("<>")).ok().unwrap().generic.is_none());
     }
 
     #[test]
     fn class_to_string_returns_the_fully_qualified_class_name() {
-        assert_eq!("so.blacklight.Test", Class::new(ClassId { native_id: ptr::null_mut() }, JavaType::Class("Lso/blacklight/Test;")).to_string());
-        assert_eq!("so.blacklight.Test$1", Class::new(ClassId { native_id: ptr::null_mut() }, JavaType::Class("Lso/blacklight/Test$1;")).to_string());
+        assert_eq!("so.blacklight.Test", Class::new(ClassId { native_id: ptr::null_mut() }, ClassSignature::parse("Lso/blacklight/Test;", None).ok().unwrap()).to_string());
+        assert_eq!("so.blacklight.Test$1", Class::new(ClassId { native_id: ptr::null_mut() }, ClassSignature::parse("Lso/blacklight/Test$1;", None).ok().unwrap()).to_string());
     }
 }
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::fs;
+    use std::process::{self, Command, Output};
+
+    fn run(args: &[&str]) -> Output {
+        match Command::new(env!("CARGO_BIN_EXE_jvmti")).args(args).output() {
+            Ok(output) => output,
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    fn stdout(output: &Output) -> String {
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn roundtrip_reports_identical_classes() {
+        let output = run(&[ "roundtrip", "test-data" ]);
+
+        assert!(output.status.success(), "{}", stdout(&output));
+        assert!(stdout(&output).ends_with("13 of 13 classes are identical after rewriting\n"), "{}", stdout(&output));
+    }
+
+    #[test]
+    fn verify_and_stats_read_single_classes() {
+        let verified = run(&[ "verify", "test-data/Varying.class" ]);
+
+        assert!(verified.status.success(), "{}", stdout(&verified));
+        assert_eq!("1 of 1 classes verified\n", stdout(&verified));
+
+        let stats = run(&[ "stats", "test-data/Varying.class" ]);
+
+        assert!(stats.status.success(), "{}", stdout(&stats));
+        assert!(stdout(&stats).starts_with("classes: 1\n"), "{}", stdout(&stats));
+        assert!(stdout(&stats).contains("    Code                        "), "{}", stdout(&stats));
+    }
+
+    #[test]
+    fn diff_reports_changed_lines() {
+        let same = run(&[ "diff", "test-data/Simple.class", "test-data/Simple.class" ]);
+
+        assert!(same.status.success(), "{}", stdout(&same));
+        assert_eq!("", stdout(&same));
+
+        let different = run(&[ "diff", "test-data/Simple.class", "test-data/Varying.class" ]);
+
+        assert_eq!(Some(1), different.status.code());
+        assert!(stdout(&different).contains("\n-class Simple\n+class Varying\n"), "{}", stdout(&different));
+    }
+
+    #[test]
+    fn javap_prints_like_javap() {
+        let output = run(&[ "javap", "test-data/Simple.class" ]);
+
+        assert!(output.status.success(), "{}", stdout(&output));
+        assert!(stdout(&output).starts_with("  Compiled from \"Simple.java\"\npublic class Simple\n  minor version: 0\n"), "{}", stdout(&output));
+        assert!(stdout(&output).ends_with("}\nSourceFile: \"Simple.java\"\n"), "{}", stdout(&output));
+    }
+
+    #[test]
+    fn disassembled_classes_assemble_back() {
+        let disassembled = run(&[ "disassemble", "test-data/Varying.class" ]);
+
+        assert!(disassembled.status.success(), "{}", stdout(&disassembled));
+        assert!(stdout(&disassembled).starts_with(".version 61 0\n.class public super Varying\n"), "{}", stdout(&disassembled));
+
+        let source = env::temp_dir().join(format!("jvmti-cli-{}-Varying.j", process::id()));
+        fs::write(&source, stdout(&disassembled)).unwrap();
+
+        let assembled = run(&[ "assemble", source.to_str().unwrap() ]);
+        assert!(assembled.status.success(), "{}", String::from_utf8_lossy(&assembled.stderr));
+
+        let verified = run(&[ "verify", source.with_extension("class").to_str().unwrap() ]);
+        assert_eq!("1 of 1 classes verified\n", stdout(&verified));
+
+        fs::remove_file(source.with_extension("class")).unwrap();
+        fs::remove_file(source).unwrap();
+    }
+
+    #[test]
+    fn unknown_commands_print_usage() {
+        let output = run(&[ "frobnicate" ]);
+
+        assert_eq!(Some(2), output.status.code());
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage: jvmti"));
+    }
+}
@@ -0,0 +1,109 @@
+extern crate jvmti;
+
+#[cfg(test)]
+mod tests {
+    use jvmti::bytecode::hierarchy::{ClassHierarchy, ClassInfo};
+    use jvmti::emulator::{EmulatedClass, JVMEmulator};
+    use jvmti::environment::hierarchy::{JVMClasses, JVMHierarchy};
+
+    fn emulator(can_tag_objects: bool) -> JVMEmulator {
+        let mut emulator = JVMEmulator::new();
+        emulator.capabilities.can_tag_objects = can_tag_objects;
+
+        emulator.load_class(EmulatedClass::new("Ljava/lang/Object;", None, &[], false));
+        emulator.load_class(EmulatedClass::new("Ljava/lang/Runnable;", None, &[], true));
+        emulator.load_class(EmulatedClass::new("Lcom/example/Task;", Some("Ljava/lang/Object;"), &[ "Ljava/lang/Runnable;" ], false));
+        emulator.load_class(EmulatedClass::new("[Lcom/example/Task;", Some("Ljava/lang/Object;"), &[], false));
+
+        emulator
+    }
+
+    #[test]
+    fn loaded_classes_are_described() {
+        let emulator = emulator(true);
+        let classes = JVMClasses::new();
+        let hierarchy = JVMHierarchy::new(&emulator, &classes);
+
+        assert_eq!(Some(ClassInfo { name: "com/example/Task".to_string(), super_class: Some("java/lang/Object".to_string()), interfaces: vec![ "java/lang/Runnable".to_string() ], is_interface: false }),
+                   hierarchy.class_info("com/example/Task"));
+        assert_eq!(Some(ClassInfo { name: "java/lang/Runnable".to_string(), super_class: Some("java/lang/Object".to_string()), interfaces: vec![], is_interface: true }),
+                   hierarchy.class_info("java/lang/Runnable"));
+        assert_eq!(None, hierarchy.class_info("com/example/Missing"));
+    }
+
+    #[test]
+    fn described_classes_are_signed_once_across_hierarchies() {
+        let emulator = emulator(true);
+        let classes = JVMClasses::new();
+
+        assert!(JVMHierarchy::new(&emulator, &classes).class_info("com/example/Task").is_some());
+        let signed = emulator.signatures.get();
+
+        assert_eq!(None, JVMHierarchy::new(&emulator, &classes).class_info("com/example/Missing"));
+        assert!(JVMHierarchy::new(&emulator, &classes).class_info("java/lang/Runnable").is_some());
+        assert_eq!(signed, emulator.signatures.get());
+
+        // only the class loaded since is signed, along with its superclass
+        emulator.load_class(EmulatedClass::new("Lcom/example/Job;", Some("Ljava/lang/Object;"), &[], false));
+
+        assert!(JVMHierarchy::new(&emulator, &classes).class_info("com/example/Job").is_some());
+        assert_eq!(signed + 2, emulator.signatures.get());
+    }
+
+    #[test]
+    fn classes_are_found_without_tags() {
+        let emulator = emulator(false);
+        let classes = JVMClasses::new();
+        let hierarchy = JVMHierarchy::new(&emulator, &classes);
+
+        assert!(hierarchy.class_info("com/example/Task").is_some());
+        assert!(emulator.tags.borrow().is_empty());
+    }
+
+    #[test]
+    fn classes_loaded_after_an_unload_are_found() {
+        let emulator = emulator(true);
+        let classes = JVMClasses::new();
+        let hierarchy = JVMHierarchy::new(&emulator, &classes);
+
+        assert!(hierarchy.class_info("com/example/Task").is_some());
+
+        // the number of loaded classes stays the same
+        let task = emulator.find_class("Lcom/example/Task;").unwrap();
+        emulator.unload_class(task);
+        emulator.load_class(EmulatedClass::new("Lcom/example/Job;", Some("Ljava/lang/Object;"), &[], false));
+
+        assert!(hierarchy.class_info("com/example/Job").is_some());
+    }
+
+    #[test]
+    fn classes_that_could_not_be_described_are_retried() {
+        let emulator = emulator(true);
+        let classes = JVMClasses::new();
+        let hierarchy = JVMHierarchy::new(&emulator, &classes);
+
+        let mut job = EmulatedClass::new("Lcom/example/Job;", Some("Ljava/lang/Object;"), &[], false);
+        job.prepared = false;
+        let job = emulator.load_class(job);
+
+        assert_eq!(None, hierarchy.class_info("com/example/Job"));
+
+        if let Some(&mut Some(ref mut class)) = emulator.classes.borrow_mut().get_mut(job - 1) {
+            class.prepared = true;
+        }
+
+        assert!(hierarchy.class_info("com/example/Job").is_some());
+    }
+
+    #[test]
+    fn tags_set_by_others_are_kept() {
+        let emulator = emulator(true);
+        let classes = JVMClasses::new();
+        let task = emulator.find_class("Lcom/example/Task;").unwrap();
+
+        emulator.tags.borrow_mut().insert(task, 4711);
+
+        assert!(JVMHierarchy::new(&emulator, &classes).class_info("com/example/Task").is_some());
+        assert_eq!(Some(&4711), emulator.tags.borrow().get(&task));
+    }
+}
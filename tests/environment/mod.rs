@@ -1,2 +1,3 @@
+mod hierarchy;
 mod jvm;
 mod jvmti;
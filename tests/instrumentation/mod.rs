@@ -3,7 +3,9 @@ extern crate jvmti;
 
 #[cfg(test)]
 mod tests {
+    use jvmti::bytecode::*;
     use jvmti::instrumentation::JavaClass;
+    use jvmti::instrumentation::asm::transformer::Transformer;
 
     #[test]
     fn can_create_empty_class() {
@@ -13,4 +15,32 @@ mod tests {
 
         assert_eq!(classfile, classfile);
     }
+
+    #[test]
+    fn transformer_reuses_existing_constants() {
+        let mut class = Classfile::new();
+
+        let first = Transformer::new(&mut class).constant_pool().utf8("Test");
+        let second = Transformer::new(&mut class).constant_pool().utf8("Test");
+
+        assert_eq!(first, second);
+        assert_eq!(2, class.constant_pool.cp_len());
+    }
+
+    #[test]
+    fn transformer_reports_only_real_changes() {
+        let mut class = Classfile::new();
+
+        {
+            let mut transformer = Transformer::new(&mut class);
+            assert!(!transformer.is_modified());
+
+            let _ = transformer.constant_pool().utf8("Test");
+            assert!(transformer.is_modified());
+        }
+
+        let mut transformer = Transformer::new(&mut class);
+        let _ = transformer.constant_pool().utf8("Test");
+        assert!(!transformer.is_modified());
+    }
 }
@@ -1,7 +1,9 @@
 extern crate jvmti;
 extern crate libc;
+extern crate zip;
 
 mod bytecode;
+mod cli;
 mod environment;
 mod instrumentation;
 